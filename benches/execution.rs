@@ -0,0 +1,22 @@
+use criterion::*;
+use ion_shell::Shell;
+
+const LOOPS: &str = include_str!("loops.ion");
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("loop_execution");
+
+    group.throughput(Throughput::Bytes(LOOPS.len() as u64));
+    group.bench_function("execute_script", |b| {
+        b.iter_batched(
+            Shell::new,
+            |mut shell| shell.execute_script(LOOPS).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+use criterion::*;
+use ion_shell::{expansion::Expander, Shell};
+use std::{env, fs};
+
+const WORDS: &str = include_str!("expansion.ion");
+
+fn shell() -> Shell<'static> {
+    let mut shell = Shell::new();
+    shell
+        .execute_script(
+            "let name = value\nlet array = [one two three four five]\nlet map:hmap[str] = \
+             [key=value other=word]\nlet x = 21\n",
+        )
+        .unwrap();
+    shell
+}
+
+fn words(c: &mut Criterion) {
+    let mut shell = shell();
+    let mut group = c.benchmark_group("word_expansion");
+
+    group.throughput(Throughput::Bytes(WORDS.len() as u64));
+    group.bench_function("expand_string", |b| {
+        b.iter(|| {
+            for word in WORDS.lines() {
+                black_box(shell.expand_string(word).unwrap());
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn globs(c: &mut Criterion) {
+    let dir = env::temp_dir().join(format!("ion-bench-globs-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..500 {
+        let extension = if i % 2 == 0 { "ion" } else { "txt" };
+        fs::write(dir.join(format!("file{}.{}", i, extension)), "").unwrap();
+    }
+
+    let mut shell = shell();
+    let mut group = c.benchmark_group("glob_expansion");
+
+    // The directory is left out of the ids, which must be the same on every run for the
+    // benchmarks to be compared with a baseline
+    for pattern in &["*.ion", "file1??.*", "file[0-4]*.txt"] {
+        group.bench_with_input(BenchmarkId::new("expand_string", pattern), pattern, |b, p| {
+            let path = format!("{}/{}", dir.display(), p);
+            b.iter(|| black_box(shell.expand_string(&path).unwrap()))
+        });
+    }
+
+    group.finish();
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+criterion_group!(benches, words, globs);
+criterion_main!(benches);
@@ -40,9 +40,9 @@ impl Index {
     /// ```
     pub fn new(input: isize) -> Index {
         if input < 0 {
-            Index::Backward((input.abs() as usize) - 1)
+            Index::Backward(input.unsigned_abs() - 1)
         } else {
-            Index::Forward(input.abs() as usize)
+            Index::Forward(input.unsigned_abs())
         }
     }
 }
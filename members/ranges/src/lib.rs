@@ -12,11 +12,39 @@ mod tests {
     #[test]
     fn ranges() {
         let range1 = Range::exclusive(Index::new(1), Index::new(5));
-        assert_eq!(Some((1, 4)), range1.bounds(42));
-        assert_eq!(Some((1, 4)), range1.bounds(7));
+        assert_eq!((1, 4), range1.span(42));
+        assert_eq!((1, 4), range1.span(7));
         let range2 = Range::inclusive(Index::new(2), Index::new(-4));
-        assert_eq!(Some((2, 5)), range2.bounds(10));
-        assert_eq!(None, range2.bounds(3));
+        assert_eq!((2, 5), range2.span(10));
+        assert_eq!((0, 0), range2.span(3));
+    }
+
+    #[test]
+    fn clamped_ranges() {
+        let indices = |range: Range, length| range.indices(length).collect::<Vec<_>>();
+        assert_eq!(indices(Range::exclusive(Index::new(2), Index::new(100)), 4), vec![2, 3]);
+        assert_eq!(indices(Range::from(Index::new(-100)), 3), vec![0, 1, 2]);
+        assert_eq!(indices(Range::from(Index::new(7)), 3), Vec::<usize>::new());
+        assert_eq!(indices(Range::to(Index::new(-2)), 0), Vec::<usize>::new());
+        assert_eq!(indices(Range::from(Index::new(isize::MIN)), 2), vec![0, 1]);
+    }
+
+    #[test]
+    fn stepped_ranges() {
+        let indices = |range: &str, length| {
+            parse_index_range(range).unwrap().indices(length).collect::<Vec<_>>()
+        };
+        assert_eq!(indices("0..2..10", 20), vec![0, 2, 4, 6, 8]);
+        assert_eq!(indices("0..2...10", 20), vec![0, 2, 4, 6, 8, 10]);
+        assert_eq!(indices("1..3..", 8), vec![1, 4, 7]);
+        assert_eq!(indices("..-3..", 8), vec![7, 4, 1]);
+        assert_eq!(indices("5..-1..1", 8), vec![5, 4, 3, 2]);
+        assert_eq!(indices("-1..-2...-5", 8), vec![7, 5, 3]);
+        assert_eq!(parse_index_range("0..0..5"), None);
+
+        let selected: Vec<u8> =
+            (0..10).select(&Select::<()>::Range(parse_index_range("..-4..").unwrap()), 10);
+        assert_eq!(selected, vec![9, 5, 1]);
     }
 
     #[test]
@@ -107,4 +135,18 @@ mod tests {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn range_edges() {
+        let expand = |range: &str| parse_range::<String>(range).map(|range| range.collect());
+        assert_eq!(expand("5...5"), Some(vec!["5".to_string()]));
+        assert_eq!(expand("05..=05"), Some(vec!["05".to_string()]));
+        assert_eq!(expand("5..5"), Some(vec![]));
+        assert_eq!(expand("a..a"), Some(vec![]));
+        assert_eq!(expand("a...a"), Some(vec!["a".to_string()]));
+        assert_eq!(expand("a..2...f"), Some(vec!["a".into(), "c".into(), "e".into()]));
+        assert_eq!(expand("a..-2..f"), None);
+        assert_eq!(expand("f..-2..a"), Some(vec!["f".into(), "d".into(), "b".into()]));
+        assert_eq!(expand("01..3..10"), Some(vec!["01".into(), "04".into(), "07".into()]));
+    }
 }
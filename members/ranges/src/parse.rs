@@ -1,5 +1,5 @@
 use super::{Index, Range};
-use std::{cmp::Ordering, u8};
+use std::{cmp::Ordering, iter, u8};
 
 fn numeric_range<'a, K: From<String>>(
     start: isize,
@@ -8,12 +8,16 @@ fn numeric_range<'a, K: From<String>>(
     inclusive: bool,
     nb_digits: usize,
 ) -> Option<Box<dyn Iterator<Item = K> + 'a>> {
-    let end = if start < end && inclusive {
-        end + 1
-    } else if start > end && inclusive {
-        end - 1
-    } else {
-        end
+    let end = match start.cmp(&end) {
+        Ordering::Less if inclusive => end.checked_add(1)?,
+        Ordering::Greater if inclusive => end.checked_sub(1)?,
+        // An inclusive range from a number to itself holds that number only
+        Ordering::Equal if inclusive => {
+            return Some(Box::new(iter::once(
+                format!("{:0width$}", start, width = nb_digits).into(),
+            )))
+        }
+        _ => end,
     };
 
     if step == 0 || (start < end && step < 0) || (start > end && step > 0) {
@@ -28,7 +32,7 @@ fn numeric_range<'a, K: From<String>>(
         let iter = (x..y).scan(start, move |index, _| {
             if end.cmp(index) == ordering {
                 let index_holder = *index;
-                *index += step; // This step adds
+                *index = index.saturating_add(step); // This step adds
                 Some(format!("{:0width$}", index_holder, width = nb_digits).into())
             } else {
                 None
@@ -48,6 +52,13 @@ fn char_range<'a, K: From<String>>(
     if !start.is_ascii_alphabetic() || !end.is_ascii_alphabetic() || step == 0 {
         return None;
     }
+    // As with numbers, the step must go from the start towards the end
+    if (start < end && step < 0) || (start > end && step > 0) {
+        return None;
+    }
+    if start == end && !inclusive {
+        return Some(Box::new(iter::empty()));
+    }
 
     if (start < end && inclusive) || (start > end && !inclusive) {
         end += 1;
@@ -79,13 +90,15 @@ fn finish<K: From<String>>(
     step: isize,
 ) -> Option<Box<dyn Iterator<Item = K>>> {
     if let (Ok(start), Ok(end)) = (start_str.parse::<isize>(), end_str.parse::<isize>()) {
-        let step = if step == 1 && start >= end { -step } else { step };
+        let step = if step == 1 && start > end { -step } else { step };
         let nb_digits = usize::max(count_minimum_digits(start_str), count_minimum_digits(end_str));
         numeric_range(start, end, step, inclusive, nb_digits)
     } else if start_str.len() != 1 || end_str.len() != 1 {
         None
     } else {
-        char_range(start_str.as_bytes()[0], end_str.as_bytes()[0], step, inclusive)
+        let (start, end) = (start_str.as_bytes()[0], end_str.as_bytes()[0]);
+        let step = if step == 1 && start > end { -step } else { step };
+        char_range(start, end, step, inclusive)
     }
 }
 
@@ -115,27 +128,34 @@ pub fn parse_range<K: From<String>>(input: &str) -> Option<Box<dyn Iterator<Item
     }
 }
 
+// In a slice we allow the same syntax as in ranges, where negative indices count from the end and
+// either index may be left out:
+//      Exclusive nonstepped: [start..end]
+//      Inclusive nonstepped: [start...end]
+//      Exclusive stepped: [start..step..end]
+//      Inclusive stepped: [start..step...end]
 pub fn parse_index_range(input: &str) -> Option<Range> {
-    let mut parts = input.splitn(2, "..");
-    let first = parts.next()?;
-    let mut end = parts.next()?;
-
-    if first.is_empty() && !end.is_empty() {
-        end.parse::<isize>().map(|end| Range::to(Index::new(end))).ok()
-    } else if !end.is_empty() {
-        let inclusive = end.starts_with('.') || end.starts_with('=');
-        if inclusive {
-            end = &end[1..];
-        }
-
-        let start = first.parse::<isize>().ok()?;
-        let end = end.parse::<isize>().ok()?;
-        if inclusive {
-            Some(Range::inclusive(Index::new(start), Index::new(end)))
-        } else {
-            Some(Range::exclusive(Index::new(start), Index::new(end)))
-        }
-    } else {
-        first.parse::<isize>().map(|start| Range::from(Index::new(start))).ok()
+    let mut parts = input.split("..").collect::<Vec<_>>();
+    let mut end = parts.pop()?;
+    let inclusive = end.starts_with(|c| c == '.' || c == '=');
+    if inclusive {
+        end = &end[1..];
     }
+    let (start, step) = match parts[..] {
+        [start] => (start, 1),
+        [start, step] => (start, step.parse::<isize>().ok().filter(|&step| step != 0)?),
+        _ => return None,
+    };
+
+    let index = |index: &str| index.parse::<isize>().map(Index::new).ok();
+    // The range goes from the first to the last element, or the other way for negative steps
+    let (first, last) = if step > 0 { (0, -1) } else { (-1, 0) };
+    let start = if start.is_empty() { Index::new(first) } else { index(start)? };
+    let range = match end {
+        "" if inclusive => return None,
+        "" => Range::inclusive(start, Index::new(last)),
+        end if inclusive => Range::inclusive(start, index(end)?),
+        end => Range::exclusive(start, index(end)?),
+    };
+    Some(range.with_step(step))
 }
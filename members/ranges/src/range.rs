@@ -12,6 +12,9 @@ pub struct Range {
     /// Is this range inclusive? If false, this object represents a half-open
     /// range of [start, end), otherwise [start, end]
     inclusive: bool,
+    /// The distance between the selected elements, which are selected in reverse, from the start
+    /// down to the end, if it is negative
+    step:      isize,
 }
 
 impl Display for Range {
@@ -21,34 +24,72 @@ impl Display for Range {
 }
 
 impl Range {
-    /// Returns the bounds of this range as a tuple containing:
-    /// - The starting point of the range
-    /// - The length of the range
+    /// Returns the first index selected by this range, and the number of selected indices, in a
+    /// vector-like object of the given length. The indices beyond its bounds are left out, so
+    /// that the selection may be empty.
     /// ```ignore,rust
-    /// let vec = vec![0, 1, 2, 3, 4, 5, 6, 7, 8];
-    /// let range = Range::exclusive(Index::new(1), Index::new(5));
-    /// let (start, size) = range.bounds(vec.len()).unwrap();
-    /// let expected = vec![1, 2, 3, 4];
-    /// let selection = vec.iter().skip(start).take(size).collect::<Vec<_>>();
-    /// assert_eq!(expected, selection);
+    /// let range = Range::exclusive(Index::new(1), Index::new(20)).with_step(2);
+    /// assert_eq!((1, 4), range.span(9));
     /// ```
-    pub fn bounds(&self, vector_length: usize) -> Option<(usize, usize)> {
-        let start = self.start.resolve(vector_length)?;
-        let end = self.end.resolve(vector_length)?;
-        if end < start {
-            None
-        } else if self.inclusive {
-            Some((start, end - start + 1))
+    pub(crate) fn span(&self, vector_length: usize) -> (usize, usize) {
+        let length = vector_length as isize;
+        let resolve = |index: Index| match index {
+            Index::Forward(n) => n as isize,
+            Index::Backward(n) => length.saturating_sub(1).saturating_sub(n as isize),
+        };
+        let (start, end) = (resolve(self.start), resolve(self.end));
+        let (first, last) = if self.step > 0 {
+            let last = if self.inclusive { end } else { end.saturating_sub(1) };
+            (start.max(0), last.min(length - 1))
         } else {
-            Some((start, end - start))
+            let last = if self.inclusive { end } else { end.saturating_add(1) };
+            (start.min(length - 1), last.max(0))
+        };
+        let distance = if self.step > 0 { last - first } else { first - last };
+        if distance < 0 {
+            (0, 0)
+        } else {
+            (first as usize, (distance / self.step.abs()) as usize + 1)
         }
     }
 
-    pub fn exclusive(start: Index, end: Index) -> Range { Range { start, end, inclusive: false } }
+    /// The indices selected by this range in a vector-like object of the given length, in the
+    /// order of the step
+    /// ```ignore,rust
+    /// let range = Range::inclusive(Index::new(-1), Index::new(0)).with_step(-3);
+    /// assert_eq!(vec![7, 4, 1], range.indices(8).collect::<Vec<_>>());
+    /// ```
+    pub fn indices(&self, vector_length: usize) -> impl Iterator<Item = usize> {
+        let (first, count) = self.span(vector_length);
+        let step = self.step;
+        (0..count as isize).map(move |n| (first as isize + n * step) as usize)
+    }
 
-    pub fn inclusive(start: Index, end: Index) -> Range { Range { start, end, inclusive: true } }
+    /// Whether the range starts after the end of a vector-like object of the given length, rather
+    /// than selecting a part of it, which may be empty
+    pub fn starts_past(&self, vector_length: usize) -> bool {
+        self.step > 0 && matches!(self.start, Index::Forward(n) if n >= vector_length)
+    }
+
+    /// The distance between the selected elements
+    pub const fn step(&self) -> isize { self.step }
 
-    pub fn from(start: Index) -> Range { Range { start, end: Index::new(-1), inclusive: true } }
+    /// Select every `step` element of this range, starting from its end if the step is negative
+    pub fn with_step(self, step: isize) -> Range { Range { step, ..self } }
 
-    pub fn to(end: Index) -> Range { Range { start: Index::new(0), end, inclusive: false } }
+    pub fn exclusive(start: Index, end: Index) -> Range {
+        Range { start, end, inclusive: false, step: 1 }
+    }
+
+    pub fn inclusive(start: Index, end: Index) -> Range {
+        Range { start, end, inclusive: true, step: 1 }
+    }
+
+    pub fn from(start: Index) -> Range {
+        Range { start, end: Index::new(-1), inclusive: true, step: 1 }
+    }
+
+    pub fn to(end: Index) -> Range {
+        Range { start: Index::new(0), end, inclusive: false, step: 1 }
+    }
 }
@@ -39,10 +39,17 @@ where
             Select::All => self.collect(),
             Select::Index(Index::Forward(idx)) => self.nth(*idx).into_iter().collect(),
             Select::Index(Index::Backward(idx)) => self.rev().nth(*idx).into_iter().collect(),
-            Select::Range(range) => range
-                .bounds(size)
-                .map(|(start, length)| self.skip(start).take(length).collect())
-                .unwrap_or_else(|| empty().collect()),
+            Select::Range(range) => {
+                let (first, count) = range.span(size);
+                let step = range.step().unsigned_abs();
+                if count == 0 {
+                    empty().collect()
+                } else if range.step() > 0 {
+                    self.skip(first).step_by(step).take(count).collect()
+                } else {
+                    self.rev().skip(size - 1 - first).step_by(step).take(count).collect()
+                }
+            }
         }
     }
 }
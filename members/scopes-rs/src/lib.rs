@@ -97,6 +97,13 @@ impl<K: Hash + Eq, V: Clone> Scopes<K, V> {
         self.scopes[self.current].insert(name.into(), value.into())
     }
 
+    /// Set a variable in the innermost namespace, or in the global scope outside of namespaces
+    pub fn set_namespace<T: Into<K>, S: Into<V>>(&mut self, name: T, value: S) -> Option<V> {
+        let index =
+            self.scopes[..=self.current].iter().rposition(|scope| scope.namespace).unwrap_or(0);
+        self.scopes[index].insert(name.into(), value.into())
+    }
+
     pub fn set_global<T: Into<K>, S: Into<V>>(&mut self, name: T, value: S) -> Option<V> {
         self.scopes[0].insert(name.into(), value.into())
     }
@@ -1,4 +1,4 @@
-use ion_shell::{builtin, builtins::Status, types::Str, Shell, Signal};
+use ion_shell::{builtin, builtins::Status, types::Str, Shell, Signal, Trap};
 use nix::{sys::signal, unistd::Pid};
 use std::{os::unix::process::CommandExt, process::Command};
 
@@ -52,6 +52,10 @@ DESCRIPTION
 pub fn exit(args: &[Str], shell: &mut Shell<'_>) -> Status {
     // Kill all active background tasks before exiting the shell.
     shell.background_send(Signal::SIGTERM).expect("Could not terminate background jobs");
+    shell.run_deferred();
+    shell.run_trap(Trap::Exit);
+    shell.terminate_coprocesses();
+    shell.close_substitutions();
     let exit_code = args
         .get(1)
         .and_then(|status| status.parse::<i32>().ok())
@@ -63,17 +67,23 @@ pub fn exit(args: &[Str], shell: &mut Shell<'_>) -> Status {
     desc = "replace the shell with the given command",
     man = "
 SYNOPSIS
-    exec [-ch] [--help] [command [arguments ...]]
+    exec [-ch] [--help] [command [arguments ...]] [REDIRECTIONS...]
 
 DESCRIPTION
     Execute <command>, replacing the shell with the specified program.
     The <arguments> following the command become the arguments to
-    <command>.
+    <command>, and the redirections apply to it.
+
+    Without a command, the redirections are applied to the shell itself
+    for the remainder of the script, rather than to a single command:
+    exec > log writes the output of the following commands to log, and
+    exec 3< file keeps file open for reading on the descriptor 3. Within
+    a function, they last until the function returns.
 
 OPTIONS
     -c  Execute command with an empty environment."
 )]
-pub fn exec(args: &[Str], _shell: &mut Shell<'_>) -> Status {
+pub fn exec(args: &[Str], shell: &mut Shell<'_>) -> Status {
     let mut clear_env = false;
     let mut idx = 1;
     for arg in args.iter().skip(1) {
@@ -94,6 +104,9 @@ pub fn exec(args: &[Str], _shell: &mut Shell<'_>) -> Status {
             }
             Status::error(format!("ion: exec: {}", command.exec().to_string()))
         }
-        None => Status::error("ion: exec: no command provided"),
+        None => {
+            shell.keep_redirections();
+            Status::SUCCESS
+        }
     }
 }
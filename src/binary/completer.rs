@@ -1,16 +1,23 @@
+use super::{keymacros::KeyMacros, quickhelp, snippets::Snippets, vcs::Repository};
 use auto_enums::auto_enum;
 use glob::{glob_with, MatchOptions};
-use ion_shell::{expansion::Expander, Shell};
+use ion_shell::{expansion::Expander, Shell, Value};
 use liner::{Completer, CursorPosition, Event, EventKind};
 use std::{
+    cell::RefCell,
     env, iter,
     path::{Path, PathBuf},
     str,
 };
+use termion::event::Key;
 
 pub struct IonCompleter<'a, 'b> {
     shell:      &'b Shell<'a>,
     completion: CompletionType,
+    macros:     Option<&'b RefCell<KeyMacros>>,
+    snippets:   &'b RefCell<Snippets>,
+    vcs:        &'b RefCell<Repository>,
+    quick_help: bool,
 }
 
 /// Unescape filenames for the completer so that special characters will be properly shown.
@@ -57,12 +64,35 @@ fn escape(input: &str) -> String {
 enum CompletionType {
     Nothing,
     Command,
-    VariableAndFiles,
+    /// The command and the position of the argument completed, if known
+    VariableAndFiles(Option<(String, usize)>),
+}
+
+/// The command of the pipeline segment in which the word at the index is, and the position of
+/// the word among its arguments
+fn argument_of(words: &[String], index: usize) -> Option<(String, usize)> {
+    let start = words[..index.min(words.len())]
+        .iter()
+        .rposition(|word| word.ends_with('|') || word.ends_with('&') || word.ends_with(';'))
+        .map_or(0, |i| i + 1);
+    Some((words.get(start)?.clone(), index.checked_sub(start + 1)?))
 }
 
 impl<'a, 'b> IonCompleter<'a, 'b> {
-    pub fn new(shell: &'b Shell<'a>) -> Self {
-        IonCompleter { shell, completion: CompletionType::Nothing }
+    pub fn new(
+        shell: &'b Shell<'a>,
+        macros: Option<&'b RefCell<KeyMacros>>,
+        snippets: &'b RefCell<Snippets>,
+        vcs: &'b RefCell<Repository>,
+    ) -> Self {
+        IonCompleter {
+            shell,
+            completion: CompletionType::Nothing,
+            macros,
+            snippets,
+            vcs,
+            quick_help: false,
+        }
     }
 }
 
@@ -72,7 +102,20 @@ impl<'a, 'b> Completer for IonCompleter<'a, 'b> {
         let vars = self.shell.variables();
 
         match self.completion {
-            CompletionType::VariableAndFiles => {
+            CompletionType::VariableAndFiles(ref argument) => {
+                // The values listed by the type of a function argument are completed first
+                if let Some((command, index)) = argument {
+                    if let Some(Value::Function(function)) = vars.get(command) {
+                        let choices = function.choices(*index);
+                        completions.splice(
+                            0..0,
+                            choices
+                                .into_iter()
+                                .filter(|choice| choice.starts_with(start))
+                                .map(String::from),
+                        );
+                    }
+                }
                 // Initialize a new completer from the definitions collected.
                 // Creates a list of definitions from the shell environment that
                 // will be used
@@ -97,6 +140,10 @@ impl<'a, 'b> Completer for IonCompleter<'a, 'b> {
                             .map(|(s, _)| format!("@{}", &s)),
                     );
                 }
+                // Files with uncommitted changes are the most likely arguments
+                let vcs = self.vcs.borrow();
+                completions
+                    .sort_by_key(|completion| !vcs.is_changed(Path::new(&unescape(completion))));
             }
             CompletionType::Command => {
                 // Initialize a new completer from the definitions collected.
@@ -145,8 +192,36 @@ impl<'a, 'b> Completer for IonCompleter<'a, 'b> {
     }
 
     fn on_event<W: std::io::Write>(&mut self, event: Event<'_, '_, W>) {
+        match event.kind {
+            // Any key dismisses the quick help panel
+            EventKind::BeforeKey(_) if self.quick_help => {
+                quickhelp::dismiss();
+                self.quick_help = false;
+            }
+            EventKind::AfterKey(Key::Alt('h')) => {
+                quickhelp::show(self.shell, event.editor);
+                self.quick_help = true;
+            }
+            EventKind::AfterKey(Key::Alt('s')) => {
+                if let Err(err) = self.snippets.borrow_mut().on_key(event.editor) {
+                    eprintln!("ion: snippet: {}", err);
+                }
+            }
+            _ => (),
+        }
+
         if let EventKind::BeforeComplete = event.kind {
             let (words, pos) = event.editor.get_words_and_cursor_position();
+            let buffer = event.editor.current_buffer();
+            let texts: Vec<String> =
+                words.iter().map(|&(start, end)| buffer.range(start, end)).collect();
+            let argument = match pos {
+                CursorPosition::InWord(index) | CursorPosition::OnWordRightEdge(index) => {
+                    argument_of(&texts, index)
+                }
+                CursorPosition::InSpace(Some(index), _) => argument_of(&texts, index + 1),
+                _ => None,
+            };
             self.completion = match pos {
                 _ if words.is_empty() => CompletionType::Nothing,
                 CursorPosition::InWord(0) => CompletionType::Command,
@@ -154,10 +229,8 @@ impl<'a, 'b> Completer for IonCompleter<'a, 'b> {
                     if index == 0 {
                         CompletionType::Command
                     } else {
-                        let is_pipe = words
-                            .into_iter()
-                            .nth(index - 1)
-                            .map(|(start, end)| event.editor.current_buffer().range(start, end))
+                        let is_pipe = texts
+                            .get(index - 1)
                             .filter(|filename| {
                                 filename.ends_with('|')
                                     || filename.ends_with('&')
@@ -167,12 +240,14 @@ impl<'a, 'b> Completer for IonCompleter<'a, 'b> {
                         if is_pipe {
                             CompletionType::Command
                         } else {
-                            CompletionType::VariableAndFiles
+                            CompletionType::VariableAndFiles(argument)
                         }
                     }
                 }
-                _ => CompletionType::VariableAndFiles,
+                _ => CompletionType::VariableAndFiles(argument),
             };
+        } else if let Some(macros) = self.macros {
+            macros.borrow_mut().on_event(event);
         }
     }
 }
@@ -0,0 +1,310 @@
+//! `ion doctor`: diagnose the common causes of a misconfigured installation.
+use atty::Stream;
+use ion_shell::Shell;
+use std::{
+    collections::HashSet,
+    env,
+    ffi::OsStr,
+    fmt, fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+use xdg::BaseDirectories;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// The result of a check, with the way to fix it when something is wrong
+#[derive(Debug, PartialEq, Eq)]
+struct Finding {
+    level:   Level,
+    subject: &'static str,
+    message: String,
+    fix:     Option<String>,
+}
+
+impl Finding {
+    fn ok(subject: &'static str, message: impl Into<String>) -> Self {
+        Self { level: Level::Ok, subject, message: message.into(), fix: None }
+    }
+
+    fn warning(subject: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { level: Level::Warning, subject, message: message.into(), fix: Some(fix.into()) }
+    }
+
+    fn error(subject: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self { level: Level::Error, subject, message: message.into(), fix: Some(fix.into()) }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Ok => "ok",
+            Level::Warning => "warn",
+            Level::Error => "error",
+        };
+        write!(f, "{:<6}{}: {}", level, self.subject, self.message)?;
+        if let Some(ref fix) = self.fix {
+            write!(f, "\n      fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the checks and print their findings, returning the exit status: 1 if any error was found
+pub fn run() -> i32 {
+    let dirs = BaseDirectories::with_prefix("ion");
+    let mut findings = Vec::new();
+    match dirs {
+        Ok(ref dirs) => {
+            findings.extend(check_config(dirs));
+            findings.extend(check_history(dirs));
+        }
+        Err(ref why) => findings.push(Finding::error(
+            "config",
+            format!("could not locate the configuration directories: {}", why),
+            "set HOME, or XDG_CONFIG_HOME and XDG_DATA_HOME, to writable directories",
+        )),
+    }
+    findings.extend(check_path(env::var_os("PATH").as_deref()));
+    findings.extend(check_locale(|name| env::var(name).ok()));
+    findings.extend(check_terminal(env::var("TERM").ok().as_deref(), atty::is(Stream::Stdout)));
+
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    if findings.iter().any(|finding| finding.level == Level::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Check the syntax of the initrc and of the profile and container scripts
+fn check_config(dirs: &BaseDirectories) -> Vec<Finding> {
+    let mut scripts: Vec<PathBuf> = dirs.find_config_file("initrc").into_iter().collect();
+    for directory in &["profiles", "containers"] {
+        if let Some(directory) = dirs.find_config_file(directory) {
+            if let Ok(entries) = fs::read_dir(directory) {
+                scripts.extend(entries.filter_map(Result::ok).map(|entry| entry.path()));
+            }
+        }
+    }
+    if scripts.is_empty() {
+        return vec![Finding::ok("config", "no initrc, the defaults are used")];
+    }
+
+    scripts
+        .into_iter()
+        .filter(|script| script.is_file())
+        .map(|script| check_script(&script))
+        .collect()
+}
+
+/// Check the syntax of a script without running it, stopping at its first syntax error
+fn check_script(script: &Path) -> Finding {
+    let mut shell = Shell::new();
+    shell.opts_mut().no_exec = true;
+    shell.opts_mut().err_exit_on_parse = true;
+    let result = fs::File::open(script).map_err(|why| why.to_string()).and_then(|file| {
+        shell.execute_command(BufReader::new(file)).map_err(|why| why.to_string())
+    });
+    match result {
+        Ok(_) => Finding::ok("config", script.display().to_string()),
+        Err(why) => Finding::error(
+            "config",
+            format!("{}: {}", script.display(), why),
+            format!("correct the syntax, checked with `ion -n {}`", script.display()),
+        ),
+    }
+}
+
+/// Check for missing, relative and duplicated directories in the PATH
+fn check_path(path: Option<&OsStr>) -> Vec<Finding> {
+    let path = match path {
+        Some(path) if !path.is_empty() => path,
+        _ => {
+            return vec![Finding::error(
+                "PATH",
+                "PATH is not set, so no command can be found",
+                "set PATH in the environment of ion, such as `/usr/local/bin:/usr/bin:/bin`",
+            )]
+        }
+    };
+
+    let mut findings = Vec::new();
+    let mut seen = HashSet::new();
+    for directory in env::split_paths(path) {
+        if directory.as_os_str().is_empty() || directory.is_relative() {
+            findings.push(Finding::warning(
+                "PATH",
+                format!("'{}' is relative to the working directory", directory.display()),
+                "remove it, or replace it with an absolute path",
+            ));
+        } else if !seen.insert(directory.clone()) {
+            findings.push(Finding::warning(
+                "PATH",
+                format!("{} is listed more than once", directory.display()),
+                "remove the duplicate entry",
+            ));
+        } else if !directory.is_dir() {
+            findings.push(Finding::warning(
+                "PATH",
+                format!("{} is not a directory", directory.display()),
+                "remove it from PATH",
+            ));
+        }
+    }
+    if findings.is_empty() {
+        findings.push(Finding::ok("PATH", format!("{} directories", seen.len())));
+    }
+    findings
+}
+
+/// Check that the locale uses UTF-8, which ion assumes to edit and display lines
+fn check_locale<F: Fn(&str) -> Option<String>>(var: F) -> Vec<Finding> {
+    // The first set of these variables determines the character encoding
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|name| var(*name).filter(|value| !value.is_empty()).map(|value| (name, value)));
+    let finding = match locale {
+        Some((name, value)) => {
+            let lowercase = value.to_lowercase();
+            if lowercase.contains("utf-8") || lowercase.contains("utf8") {
+                Finding::ok("locale", format!("{}={}", name, value))
+            } else {
+                Finding::warning(
+                    "locale",
+                    format!("{}={} does not use UTF-8", name, value),
+                    format!("set {} to a UTF-8 locale, such as `en_US.UTF-8`", name),
+                )
+            }
+        }
+        None => Finding::warning(
+            "locale",
+            "no locale is set",
+            "set LANG to a UTF-8 locale, such as `en_US.UTF-8`",
+        ),
+    };
+    vec![finding]
+}
+
+/// Check that the terminal supports the line editor
+fn check_terminal(term: Option<&str>, is_tty: bool) -> Vec<Finding> {
+    let finding = match term {
+        _ if !is_tty => Finding::ok("terminal", "not a terminal, skipped"),
+        None | Some("") => Finding::warning(
+            "terminal",
+            "TERM is not set",
+            "set TERM to the type of the terminal, such as `xterm-256color`",
+        ),
+        Some("dumb") => Finding::warning(
+            "terminal",
+            "TERM=dumb does not support editing the line nor colors",
+            "set TERM to the type of the terminal, such as `xterm-256color`",
+        ),
+        Some(term) => match termion::terminal_size() {
+            Ok((columns, rows)) => {
+                Finding::ok("terminal", format!("TERM={}, {}x{}", term, columns, rows))
+            }
+            Err(why) => Finding::warning(
+                "terminal",
+                format!("could not get the size of the terminal: {}", why),
+                "run ion in a terminal emulator which reports its size",
+            ),
+        },
+    };
+    vec![finding]
+}
+
+/// Check that the history file can be read and appended to
+fn check_history(dirs: &BaseDirectories) -> Vec<Finding> {
+    let path = match dirs.find_data_file("history") {
+        Some(path) => path,
+        None => return vec![Finding::ok("history", "no history file yet")],
+    };
+    vec![history_finding(&path)]
+}
+
+fn history_finding(path: &Path) -> Finding {
+    let fix = format!("make {} readable and writable by the user", path.display());
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(why) => return Finding::error("history", format!("{}: {}", path.display(), why), fix),
+    };
+    if metadata.permissions().readonly() {
+        return Finding::error("history", format!("{} is read-only", path.display()), fix);
+    }
+    match fs::read(path) {
+        Ok(contents) => match String::from_utf8(contents) {
+            Ok(contents) => Finding::ok(
+                "history",
+                format!("{}, {} entries", path.display(), contents.lines().count()),
+            ),
+            Err(_) => Finding::warning(
+                "history",
+                format!("{} is not valid UTF-8", path.display()),
+                format!("remove the invalid lines, or move {} away", path.display()),
+            ),
+        },
+        Err(why) => Finding::error("history", format!("{}: {}", path.display(), why), fix),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_problems() {
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let bin = temp.as_path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        let missing = temp.as_path().join("missing");
+        let path = env::join_paths(&[bin.clone(), PathBuf::from("."), missing, bin]).unwrap();
+
+        let levels = check_path(Some(&path))
+            .iter()
+            .map(|finding| (finding.level, finding.message.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(levels.len(), 3);
+        assert!(levels.iter().all(|&(level, _)| level == Level::Warning));
+        assert!(levels[0].1.contains("relative"));
+        assert!(levels[1].1.contains("not a directory"));
+        assert!(levels[2].1.contains("more than once"));
+        assert_eq!(check_path(None)[0].level, Level::Error);
+    }
+
+    #[test]
+    fn broken_initrc() {
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let initrc = temp.as_path().join("initrc");
+        fs::write(&initrc, "let greeting = hello\necho $greeting\n").unwrap();
+        assert_eq!(check_script(&initrc).level, Level::Ok);
+
+        fs::write(&initrc, "let greeting =\necho $greeting\n").unwrap();
+        let finding = check_script(&initrc);
+        assert_eq!(finding.level, Level::Error);
+        assert!(finding.message.contains("no values supplied for assignment"));
+    }
+
+    #[test]
+    fn locale_encoding() {
+        let levels = |vars: &[(&str, &str)]| {
+            check_locale(|name| {
+                vars.iter().find(|&&(var, _)| var == name).map(|&(_, value)| value.to_string())
+            })[0]
+                .level
+        };
+        assert_eq!(levels(&[("LANG", "en_US.UTF-8")]), Level::Ok);
+        assert_eq!(levels(&[("LC_ALL", "C"), ("LANG", "en_US.UTF-8")]), Level::Warning);
+        assert_eq!(levels(&[("LC_ALL", ""), ("LC_CTYPE", "fr_FR.utf8")]), Level::Ok);
+        assert_eq!(levels(&[]), Level::Warning);
+    }
+}
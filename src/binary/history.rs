@@ -1,4 +1,4 @@
-use super::InteractiveShell;
+use super::{histstats, InteractiveShell};
 use builtins_proc::builtin_interactive;
 use ion_shell::{
     builtins::{man_pages, Status},
@@ -10,8 +10,9 @@ use liner::Context;
 use regex::Regex;
 use std::{
     cell::RefCell,
+    path::Path,
     rc::Rc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 #[builtin_interactive(
@@ -22,10 +23,15 @@ NAME
 
 SYNOPSIS
     history [option]
+    history stats [--json] [-n COUNT]
 
 DESCRIPTION
     Manipulates or prints the command history. 
     If no option is given then the command history printed instead.
+
+    The stats subcommand prints the most used commands and directories, and percentiles of the
+    duration of commands. Directories and durations are only known for the commands recorded
+    while HISTORY_TIMESTAMP was enabled.
     
 OPTIONS:
     +inc_append: Append each command to history as entered.
@@ -33,7 +39,11 @@ OPTIONS:
     +shared: Share history between shells using the same history file, implies inc_append.
     -shared: Default, do not share shell history.
     +duplicates: Default, allow duplicates in history.
-    -duplicates: Do not allow duplicates in history."
+    -duplicates: Do not allow duplicates in history.
+
+STATS OPTIONS:
+    --json: Print the statistics as a JSON object instead of tables.
+    -n COUNT: Show the COUNT most frequent commands and directories. Defaults to 10."
 )]
 //
 pub fn history(
@@ -65,6 +75,7 @@ pub fn history(
             Some("-duplicates") => {
                 context_bis.borrow_mut().history.load_duplicates = false;
             }
+            Some("stats") => return stats(&context_bis.borrow(), &args[2..]),
             Some(_) => {
                 Status::error(
                     "Invalid history option. Choices are [+|-] inc_append, duplicates and share \
@@ -79,6 +90,31 @@ pub fn history(
     }
 }
 
+fn stats(context: &Context, args: &[types::Str]) -> Status {
+    let mut json = false;
+    let mut top = 10;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "-n" => match args.next().map(|count| count.parse::<usize>()) {
+                Some(Ok(count)) => top = count,
+                _ => return Status::bad_argument("history: -n requires a number"),
+            },
+            _ => return Status::bad_argument(format!("history: invalid stats option: {}", arg)),
+        }
+    }
+
+    let lines = context.history.buffers.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let stats = histstats::Stats::new(&histstats::entries(lines.iter().map(String::as_str)), top);
+    if json {
+        println!("{}", stats.json());
+    } else {
+        print!("{}", stats.table());
+    }
+    Status::SUCCESS
+}
+
 #[derive(Debug, Default)]
 pub struct IgnoreSetting {
     // Macro definition fails if last flag has a comment at the end of the line.
@@ -134,26 +170,31 @@ impl<'a> InteractiveShell<'a> {
         }
     }
 
-    /// Saves a command in the history, depending on @HISTORY_IGNORE. Should be called
-    /// immediately after `on_command()`
-    pub fn save_command_in_history(&self, command: &str) {
+    /// Saves a command in the history, depending on @HISTORY_IGNORE, with its secrets redacted.
+    /// Should be called immediately after `on_command()`
+    pub fn save_command_in_history(&self, command: &str, elapsed: Duration, directory: &Path) {
         if self.should_save_command(command) {
+            let command = self.shell.borrow().redact(command).into_owned();
             if self.shell.borrow().variables().get_str("HISTORY_TIMESTAMP").unwrap_or_default()
                 == "1"
             {
                 // Get current time stamp
                 let since_unix_epoch =
                     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-                let cur_time_sys = ["#", &since_unix_epoch.to_owned().to_string()].concat();
+                let cur_time_sys = histstats::metadata(
+                    since_unix_epoch,
+                    elapsed.as_millis() as u64,
+                    &directory.to_string_lossy(),
+                );
 
-                // Push current time to history
+                // Push current time, duration and directory to history
                 if let Err(err) = self.context.borrow_mut().history.push(cur_time_sys.into()) {
                     eprintln!("ion: {}", err)
                 }
             }
 
             // Push command itself to history
-            if let Err(err) = self.context.borrow_mut().history.push(command.into()) {
+            if let Err(err) = self.context.borrow_mut().history.push(command.as_str().into()) {
                 eprintln!("ion: {}", err);
             }
         }
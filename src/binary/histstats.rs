@@ -0,0 +1,229 @@
+//! Command frequency statistics computed from the history, as printed by `history stats`.
+//!
+//! When `HISTORY_TIMESTAMP` is enabled, every command in the history is preceded by a metadata
+//! line of the form `#<seconds since epoch> <duration in ms> <working directory>`. Older entries
+//! may only carry the timestamp, and entries recorded without timestamps carry nothing; those
+//! only count towards the command statistics.
+use itertools::Itertools;
+use std::{collections::HashMap, fmt::Write};
+
+/// A command of the history along with its recorded metadata
+#[derive(Debug, PartialEq)]
+pub struct Entry<'a> {
+    command:   &'a str,
+    duration:  Option<u64>,
+    directory: Option<&'a str>,
+}
+
+/// Format the metadata line recorded before a command in the history
+pub fn metadata(timestamp: u64, duration: u64, directory: &str) -> String {
+    format!("#{} {} {}", timestamp, duration, directory)
+}
+
+/// Pair each command of the history with the metadata line preceding it
+pub fn entries<'a, I: IntoIterator<Item = &'a str>>(lines: I) -> Vec<Entry<'a>> {
+    let mut entries = Vec::new();
+    let mut pending = (None, None);
+    for line in lines {
+        if let Some(meta) = line.strip_prefix('#') {
+            let mut fields = meta.splitn(3, ' ');
+            // Anything else starting with a `#`, such as `#summary#` lines, is not metadata
+            if fields.next().map_or(false, |time| time.parse::<u64>().is_ok()) {
+                pending = (fields.next().and_then(|ms| ms.parse().ok()), fields.next());
+            }
+        } else if !line.trim().is_empty() {
+            let (duration, directory) = std::mem::take(&mut pending);
+            entries.push(Entry { command: line, duration, directory });
+        }
+    }
+    entries
+}
+
+/// Duration percentiles, in milliseconds
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Percentiles {
+    p50: u64,
+    p90: u64,
+    p99: u64,
+    max: u64,
+}
+
+impl Percentiles {
+    fn new(mut durations: Vec<u64>) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+        durations.sort_unstable();
+        // Nearest-rank percentile
+        let rank = |p: usize| durations[(p * durations.len() + 99) / 100 - 1];
+        Some(Self {
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: durations[durations.len() - 1],
+        })
+    }
+
+    fn fields(self) -> [(&'static str, u64); 4] {
+        [("p50", self.p50), ("p90", self.p90), ("p99", self.p99), ("max", self.max)]
+    }
+}
+
+/// Usage statistics of the history
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    commands:    Vec<(String, usize)>,
+    directories: Vec<(String, usize)>,
+    durations:   Option<Percentiles>,
+}
+
+impl Stats {
+    /// Compute the statistics, keeping the `top` most frequent commands and directories
+    pub fn new(entries: &[Entry<'_>], top: usize) -> Self {
+        Self {
+            commands:    most_frequent(
+                entries.iter().filter_map(|entry| entry.command.split_whitespace().next()),
+                top,
+            ),
+            directories: most_frequent(entries.iter().filter_map(|entry| entry.directory), top),
+            durations:   Percentiles::new(
+                entries.iter().filter_map(|entry| entry.duration).collect(),
+            ),
+        }
+    }
+
+    /// Render the statistics as aligned tables
+    pub fn table(&self) -> String {
+        let mut out = String::new();
+        table(&mut out, ["COMMAND", "COUNT"], &self.commands);
+        if !self.directories.is_empty() {
+            out.push('\n');
+            table(&mut out, ["DIRECTORY", "COUNT"], &self.directories);
+        }
+        if let Some(durations) = self.durations {
+            let durations =
+                durations.fields().iter().map(|&(name, ms)| (name.into(), ms)).collect::<Vec<_>>();
+            out.push('\n');
+            table(&mut out, ["DURATION", "MS"], &durations);
+        }
+        out
+    }
+
+    /// Render the statistics as a JSON object
+    pub fn json(&self) -> String {
+        let counts = |key: &str, entries: &[(String, usize)]| {
+            entries
+                .iter()
+                .map(|(name, count)| format!("{{\"{}\":{},\"count\":{}}}", key, quote(name), count))
+                .join(",")
+        };
+        let durations = self.durations.map_or_else(
+            || "null".into(),
+            |durations| {
+                let fields = durations.fields();
+                format!(
+                    "{{{}}}",
+                    fields.iter().map(|(name, ms)| format!("\"{}\":{}", name, ms)).join(",")
+                )
+            },
+        );
+        format!(
+            "{{\"commands\":[{}],\"directories\":[{}],\"durations_ms\":{}}}",
+            counts("command", &self.commands),
+            counts("directory", &self.directories),
+            durations
+        )
+    }
+}
+
+/// Count the occurences of each value, most frequent first
+fn most_frequent<'a, I: Iterator<Item = &'a str>>(values: I, top: usize) -> Vec<(String, usize)> {
+    let mut counts = HashMap::new();
+    for value in values {
+        *counts.entry(value).or_insert(0) += 1;
+    }
+    counts
+        .into_iter()
+        .sorted_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)))
+        .take(top)
+        .map(|(value, count)| (value.to_string(), count))
+        .collect()
+}
+
+fn table<T: std::fmt::Display>(out: &mut String, headers: [&str; 2], rows: &[(String, T)]) {
+    let width =
+        rows.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0).max(headers[0].len());
+    let _ = writeln!(out, "{:width$}  {}", headers[0], headers[1], width = width);
+    for (name, value) in rows {
+        let _ = writeln!(out, "{:width$}  {}", name, value, width = width);
+    }
+}
+
+/// Quote a string for JSON
+fn quote(string: &str) -> String {
+    let mut out = String::with_capacity(string.len() + 2);
+    out.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HISTORY: &[&str] = &[
+        "#1600000000 12 /home/ion",
+        "git status",
+        "#1600000001 30 /home/ion/src",
+        "git commit",
+        "#summary# elapsed real time: 0.1 seconds",
+        "#1600000002",
+        "ls -l",
+        "cargo build",
+    ];
+
+    #[test]
+    fn parse_entries() {
+        let entries = entries(HISTORY.iter().copied());
+        assert_eq!(entries.len(), 4);
+        assert_eq!(
+            entries[1],
+            Entry {
+                command:   "git commit",
+                duration:  Some(30),
+                directory: Some("/home/ion/src"),
+            }
+        );
+        assert_eq!(entries[2], Entry { command: "ls -l", duration: None, directory: None });
+        assert_eq!(entries[3], Entry { command: "cargo build", duration: None, directory: None });
+    }
+
+    #[test]
+    fn statistics() {
+        let stats = Stats::new(&entries(HISTORY.iter().copied()), 2);
+        assert_eq!(stats.commands, vec![("git".into(), 2), ("cargo".into(), 1)]);
+        assert_eq!(stats.durations, Some(Percentiles { p50: 12, p90: 30, p99: 30, max: 30 }));
+        assert_eq!(
+            stats.json(),
+            concat!(
+                r#"{"commands":[{"command":"git","count":2},{"command":"cargo","count":1}],"#,
+                r#""directories":[{"directory":"/home/ion","count":1},"#,
+                r#"{"directory":"/home/ion/src","count":1}],"#,
+                r#""durations_ms":{"p50":12,"p90":30,"p99":30,"max":30}}"#,
+            )
+        );
+        assert!(stats.table().starts_with("COMMAND  COUNT\ngit      2\ncargo    1\n\nDIRECTORY"));
+    }
+}
@@ -0,0 +1,225 @@
+use builtins_proc::builtin_interactive;
+use ion_shell::{
+    builtins::{man_pages, Status},
+    types, Shell,
+};
+use itertools::Itertools;
+use liner::{Editor, Event, EventKind};
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+use termion::event::Key;
+
+/// Keyboard macros recorded from the line editor.
+///
+/// A recording is started with `Ctrl-X (` and stopped with `Ctrl-X )`. The last recorded macro
+/// is replayed on the current line with `Ctrl-X e`. Only editing keys are recorded: history
+/// navigation and completion are left out, as their effect is not reproducible.
+#[derive(Debug, Default)]
+pub struct KeyMacros {
+    /// The keys recorded so far, if a recording is in progress
+    recording: Option<Vec<Key>>,
+    /// The last complete macro
+    last:      Vec<Key>,
+    /// Whether the previous key was the `Ctrl-X` prefix
+    prefix:    bool,
+    /// Where the macro is persisted, if anywhere
+    file:      Option<PathBuf>,
+}
+
+impl KeyMacros {
+    /// Load the macro persisted at the given path, and remember it for later saves
+    pub fn load(path: PathBuf) -> Self {
+        let last = fs::read_to_string(&path)
+            .map(|content| content.lines().filter_map(parse_key).collect())
+            .unwrap_or_default();
+        KeyMacros { last, file: Some(path), ..KeyMacros::default() }
+    }
+
+    /// Write the last macro to the macro file
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.file.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no file to save the macro to")
+        })?;
+        let mut file = fs::File::create(path)?;
+        for key in &self.last {
+            writeln!(file, "{}", format_key(*key))?;
+        }
+        Ok(())
+    }
+
+    /// Track the keys entered in the editor, and replay the macro on request
+    pub fn on_event<W: Write>(&mut self, event: Event<'_, '_, W>) {
+        let key = match event.kind {
+            EventKind::AfterKey(key) => key,
+            _ => return,
+        };
+
+        if self.prefix {
+            self.prefix = false;
+            // The key following the prefix was inserted in the buffer, remove it
+            let result = match key {
+                Key::Char('(') => {
+                    self.recording = Some(Vec::new());
+                    event.editor.delete_before_cursor()
+                }
+                Key::Char(')') => {
+                    if let Some(keys) = self.recording.take() {
+                        self.last = keys;
+                    }
+                    event.editor.delete_before_cursor()
+                }
+                Key::Char('e') if self.recording.is_none() => {
+                    event.editor.delete_before_cursor().and_then(|_| self.replay(event.editor))
+                }
+                _ => {
+                    self.record(key);
+                    Ok(())
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("ion: keyboard macro: {}", err);
+            }
+        } else if key == Key::Ctrl('x') {
+            self.prefix = true;
+        } else {
+            self.record(key);
+        }
+    }
+
+    fn record(&mut self, key: Key) {
+        if let Some(ref mut keys) = self.recording {
+            if format_key(key).is_empty() {
+                return;
+            }
+            keys.push(key);
+        }
+    }
+
+    fn replay<W: Write>(&self, editor: &mut Editor<'_, W>) -> io::Result<()> {
+        for key in &self.last {
+            match *key {
+                Key::Char(c) => editor.insert_after_cursor(c)?,
+                Key::Backspace => editor.delete_before_cursor()?,
+                Key::Delete => editor.delete_after_cursor()?,
+                Key::Left => editor.move_cursor_left(1)?,
+                Key::Right => editor.move_cursor_right(1)?,
+                Key::Home => editor.move_cursor_to_start_of_line()?,
+                Key::End => editor.move_cursor_to_end_of_line()?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The textual form of a key in the macro file. Keys that can't be replayed are empty.
+fn format_key(key: Key) -> String {
+    match key {
+        Key::Char(c) => format!("char {}", c.escape_default()),
+        Key::Backspace => "backspace".into(),
+        Key::Delete => "delete".into(),
+        Key::Left => "left".into(),
+        Key::Right => "right".into(),
+        Key::Home => "home".into(),
+        Key::End => "end".into(),
+        _ => String::new(),
+    }
+}
+
+fn parse_key(line: &str) -> Option<Key> {
+    match line {
+        "backspace" => Some(Key::Backspace),
+        "delete" => Some(Key::Delete),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "home" => Some(Key::Home),
+        "end" => Some(Key::End),
+        _ if line.starts_with("char ") => match &line[5..] {
+            "\\t" => Some(Key::Char('\t')),
+            "\\n" => Some(Key::Char('\n')),
+            "\\\\" => Some(Key::Char('\\')),
+            "\\'" => Some(Key::Char('\'')),
+            "\\\"" => Some(Key::Char('"')),
+            c if c.starts_with("\\u{") => u32::from_str_radix(&c[3..c.len() - 1], 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .map(Key::Char),
+            c => c.chars().next().map(Key::Char),
+        },
+        _ => None,
+    }
+}
+
+#[builtin_interactive(
+    desc = "inspect or persist the keyboard macro",
+    man = "
+SYNOPSIS
+    keymacro [show|clear|save]
+
+DESCRIPTION
+    Keyboard macros are recorded in the line editor with Ctrl-X ( and Ctrl-X ), and replayed
+    with Ctrl-X e. Only the last recorded macro is kept. Recording is only available with the
+    emacs keybindings.
+
+    The macro saved with `keymacro save` is loaded again when the next session starts.
+
+OPTIONS:
+    show: print the keys of the last macro (default)
+    clear: forget the last macro
+    save: persist the last macro in the ion data directory"
+)]
+pub fn keymacro(
+    macros: Rc<RefCell<KeyMacros>>,
+) -> impl Fn(&[types::Str], &mut Shell<'_>) -> Status {
+    move |args: &[types::Str], _shell: &mut Shell<'_>| -> Status {
+        if man_pages::check_help(args, HELP_PAGE) {
+            return Status::SUCCESS;
+        }
+        match args.get(1).map(|s| s.as_str()) {
+            Some("show") | None => {
+                println!(
+                    "{}",
+                    macros.borrow().last.iter().map(|key| format_key(*key)).format("\n")
+                );
+                Status::SUCCESS
+            }
+            Some("clear") => {
+                macros.borrow_mut().last.clear();
+                Status::SUCCESS
+            }
+            Some("save") => match macros.borrow().save() {
+                Ok(()) => Status::SUCCESS,
+                Err(err) => Status::error(format!("ion: keymacro: {}", err)),
+            },
+            Some(_) => Status::bad_argument("keymacro: choices are show, clear and save"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_file_round_trip() {
+        let keys = [
+            Key::Char('a'),
+            Key::Char('\\'),
+            Key::Char('\t'),
+            Key::Char('é'),
+            Key::Backspace,
+            Key::Left,
+            Key::End,
+        ];
+        for key in keys.iter() {
+            assert_eq!(parse_key(&format_key(*key)), Some(*key));
+        }
+        assert_eq!(format_key(Key::Up), "");
+        assert_eq!(parse_key("up"), None);
+    }
+}
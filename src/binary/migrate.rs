@@ -0,0 +1,325 @@
+//! `ion migrate`: translate the common constructs of bash and zsh configuration files to an
+//! initrc.
+use std::fs;
+
+/// Builtins of bash and zsh which have no equivalent in ion
+const UNSUPPORTED: &[&str] = &[
+    "bind", "complete", "compdef", "compinit", "declare", "eval", "setopt", "shopt", "trap",
+    "typeset", "unalias", "unset", "unsetopt", "zle", "zstyle", "[[",
+];
+
+/// The keywords which open a block, and the keywords which close them
+const BLOCKS: &[(&str, &str)] =
+    &[("if", "fi"), ("for", "done"), ("while", "done"), ("until", "done"), ("case", "esac")];
+
+/// Variables which configure the prompt of bash and zsh
+const PROMPTS: &[&str] = &["PS1", "PS2", "PROMPT", "RPROMPT", "PROMPT_COMMAND"];
+
+/// The translation of a configuration file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Migration {
+    /// The translated lines
+    pub initrc:  String,
+    /// The line number and text of the constructs which could not be translated, with the
+    /// reason
+    pub skipped: Vec<(usize, String, &'static str)>,
+}
+
+/// Translate the files, printing the initrc to the standard output and the untranslated
+/// constructs to the standard error.
+pub fn run(paths: &[String]) -> i32 {
+    if paths.is_empty() {
+        eprintln!("ion migrate: usage: ion migrate FILE...");
+        return 2;
+    }
+    let mut status = 0;
+    for path in paths {
+        match fs::read_to_string(path) {
+            Ok(source) => {
+                let migration = translate(&source);
+                println!("# Migrated from {}", path);
+                print!("{}", migration.initrc);
+                for (line, text, reason) in migration.skipped {
+                    eprintln!("ion migrate: {}:{}: {}: {}", path, line, reason, text);
+                }
+            }
+            Err(why) => {
+                eprintln!("ion migrate: could not read {}: {}", path, why);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+/// Translate the source of a bash or zsh configuration file
+pub fn translate(source: &str) -> Migration {
+    let mut migration = Migration::default();
+    let mut lines = source.lines().enumerate().map(|(index, line)| (index + 1, line));
+    while let Some((number, line)) = lines.next() {
+        let trimmed = line.trim();
+        let first = trimmed.split_whitespace().next().unwrap_or_default();
+
+        if let Some(&(_, end)) = BLOCKS.iter().find(|&&(start, _)| start == first) {
+            skip_block(&mut lines, trimmed, end);
+            migration.skipped.push((number, trimmed.into(), "control flow is not translated"));
+        } else if let Some(name) = function_name(trimmed) {
+            let mut body = Vec::new();
+            match trimmed.find('{').map(|brace| trimmed[brace + 1..].trim()) {
+                // A function defined on a single line
+                Some(rest) if rest.ends_with('}') => {
+                    body.extend(rest.trim_end_matches('}').split(';').map(str::trim));
+                }
+                Some(rest) => {
+                    body.push(rest);
+                    for (_, line) in &mut lines {
+                        if line.trim() == "}" {
+                            break;
+                        }
+                        body.push(line.trim());
+                    }
+                }
+                None => {}
+            }
+            match function(name, &body) {
+                Ok(function) => migration.initrc.push_str(&function),
+                Err(reason) => migration.skipped.push((number, trimmed.into(), reason)),
+            }
+        } else {
+            match statement(trimmed) {
+                Ok(statement) => {
+                    migration.initrc.push_str(&statement);
+                    migration.initrc.push('\n');
+                }
+                Err(reason) => migration.skipped.push((number, trimmed.into(), reason)),
+            }
+        }
+    }
+    migration
+}
+
+/// Skip the lines of a block, up to its closing keyword
+fn skip_block<'a, I: Iterator<Item = (usize, &'a str)>>(lines: &mut I, first: &str, end: &str) {
+    let closes =
+        |line: &str| line.split(|c: char| c.is_whitespace() || c == ';').any(|word| word == end);
+    if closes(first) {
+        return;
+    }
+    let mut depth = 1;
+    for (_, line) in lines {
+        let first = line.split_whitespace().next().unwrap_or_default();
+        if BLOCKS.iter().any(|&(start, _)| start == first) && !closes(line) {
+            depth += 1;
+        } else if closes(line) {
+            depth -= 1;
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+}
+
+/// The name of the function defined by `name() {` or `function name {`
+fn function_name(line: &str) -> Option<&str> {
+    let name = match line.strip_prefix("function ") {
+        Some(rest) => rest.split(|c: char| c == '(' || c == '{' || c.is_whitespace()).next()?,
+        None => line.split("()").next().filter(|_| line.contains("()"))?.trim(),
+    };
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-') {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Translate a function whose body is made of simple commands. The positional parameters
+/// become named arguments, so the function takes exactly as many arguments as it uses.
+fn function(name: &str, body: &[&str]) -> Result<String, &'static str> {
+    let body: Vec<&str> = body.iter().copied().filter(|line| !line.is_empty()).collect();
+    if body.iter().any(|line| line.contains("$@") || line.contains("$*") || line.contains("$#")) {
+        return Err("functions taking any number of arguments are not translated");
+    }
+    let arity = (1..10).rev().find(|n| body.iter().any(|line| line.contains(&format!("${}", n))));
+
+    let mut function = format!("fn {}", name);
+    for n in 1..=arity.unwrap_or(0) {
+        function.push_str(&format!(" arg{}", n));
+    }
+    function.push('\n');
+    for line in body {
+        let mut line = statement(line)?;
+        for n in 1..=arity.unwrap_or(0) {
+            line = line.replace(&format!("${}", n), &format!("$arg{}", n));
+        }
+        function.push_str("    ");
+        function.push_str(&line);
+        function.push('\n');
+    }
+    function.push_str("end\n");
+    Ok(function)
+}
+
+/// Translate a single statement
+fn statement(line: &str) -> Result<String, &'static str> {
+    let mut words = line.splitn(2, char::is_whitespace);
+    let command = words.next().unwrap_or_default();
+    let rest = words.next().unwrap_or_default().trim();
+
+    match command {
+        "" => Ok(String::new()),
+        _ if command.starts_with('#') => Ok(line.into()),
+        "alias" => {
+            let (name, value) = assignment(rest).ok_or("unrecognized alias")?;
+            let value = match unquote(value) {
+                (value, '"') => format!("\"{}\"", value),
+                (value, _) => format!("'{}'", value),
+            };
+            Ok(format!("alias {} = {}", name, value))
+        }
+        "export" => match assignment(rest) {
+            Some((name, value)) => Ok(format!("export {} = {}", name, self::value(value)?)),
+            None if is_name(rest) => Ok(format!("export {}", rest)),
+            None => Err("unrecognized export"),
+        },
+        "local" => match assignment(rest) {
+            Some((name, value)) => Ok(format!("local {} = {}", name, self::value(value)?)),
+            None => Err("local variables must be given a value"),
+        },
+        "source" | "." => Err("sourced files are not translated"),
+        "set" if rest == "-o vi" => Ok("keybindings vi".into()),
+        "set" if rest == "-o emacs" => Ok("keybindings emacs".into()),
+        "bindkey" if rest == "-v" => Ok("keybindings vi".into()),
+        "bindkey" if rest == "-e" => Ok("keybindings emacs".into()),
+        "set" | "bindkey" => Err("shell options are not translated"),
+        _ if UNSUPPORTED.contains(&command) => Err("this builtin has no equivalent in ion"),
+        _ => match assignment(line) {
+            Some((name, _)) if PROMPTS.contains(&name) => {
+                Err("the prompt is drawn by the PROMPT function in ion")
+            }
+            Some(("path", value)) => path_edit(value, false),
+            Some(("path+", value)) => path_edit(value, true),
+            Some((name, value)) => Ok(format!("let {} = {}", name, self::value(value)?)),
+            None if line.contains('=') && line.split('=').next().map_or(false, is_name) => {
+                Err("per-command variables are not supported")
+            }
+            None if line.contains('`') => {
+                Err("command substitutions with backquotes are not translated")
+            }
+            None => Ok(line.into()),
+        },
+    }
+}
+
+/// Split `NAME=VALUE`, if the value is a single word
+fn assignment(text: &str) -> Option<(&str, &str)> {
+    let mut parts = text.splitn(2, '=');
+    let name = parts.next()?;
+    let value = parts.next()?;
+    if (is_name(name) || name == "path+") && single_word(value) {
+        Some((name, value))
+    } else {
+        None
+    }
+}
+
+/// Translate the value of an assignment, which is kept as is unless it uses backquotes
+fn value(value: &str) -> Result<String, &'static str> {
+    if value.contains('`') {
+        Err("command substitutions with backquotes are not translated")
+    } else if value.contains("${") && value.contains(":-") {
+        Err("default values of parameters are not translated")
+    } else if value.is_empty() {
+        Ok("\"\"".into())
+    } else {
+        Ok(value.into())
+    }
+}
+
+/// Translate `path=(...)` and `path+=(...)` from zsh to an export of PATH
+fn path_edit(value: &str, append: bool) -> Result<String, &'static str> {
+    let directories = value
+        .strip_prefix('(')
+        .and_then(|value| value.strip_suffix(')'))
+        .ok_or("unrecognized path edit")?
+        .split_whitespace()
+        .map(|directory| match directory {
+            "$path" => "$PATH".to_string(),
+            _ => match directory.strip_prefix('~') {
+                Some(rest) => format!("$HOME{}", rest),
+                None => directory.to_string(),
+            },
+        })
+        .collect::<Vec<_>>();
+    let path = directories.join(":");
+    Ok(if append {
+        format!("export PATH = \"$PATH:{}\"", path)
+    } else {
+        format!("export PATH = \"{}\"", path)
+    })
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether the text is a single shell word, which quotes may contain whitespaces in
+fn single_word(text: &str) -> bool {
+    let mut quote = None;
+    let mut depth = 0;
+    for c in text.chars() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, _) if c.is_whitespace() && depth == 0 => return false,
+            _ => (),
+        }
+    }
+    quote.is_none()
+}
+
+/// Remove the quotes around a value, returning the quote which was used
+fn unquote(value: &str) -> (&str, char) {
+    for &quote in &['\'', '"'] {
+        if value.len() > 1 && value.starts_with(quote) && value.ends_with(quote) {
+            return (&value[1..value.len() - 1], quote);
+        }
+    }
+    (value, ' ')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bashrc() {
+        let migration = translate(
+            "# aliases\nalias ll='ls -l'\nexport EDITOR=vim\nexport \
+             PATH=\"$HOME/bin:$PATH\"\nHISTSIZE=1000\nmkcd() { mkdir -p \"$1\" && cd \"$1\"; \
+             }\nif [ -f ~/.local ]; then\n  . ~/.local\nfi\nPS1='\\u@\\h '\nshopt -s \
+             histappend\npath+=(~/go/bin)\nset -o vi\n",
+        );
+        assert_eq!(
+            migration.initrc,
+            "# aliases\nalias ll = 'ls -l'\nexport EDITOR = vim\nexport PATH = \
+             \"$HOME/bin:$PATH\"\nlet HISTSIZE = 1000\nfn mkcd arg1\n    mkdir -p \"$arg1\" && cd \
+             \"$arg1\"\nend\nexport PATH = \"$PATH:$HOME/go/bin\"\nkeybindings vi\n"
+        );
+        let skipped = migration.skipped.iter().map(|&(line, ..)| line).collect::<Vec<_>>();
+        assert_eq!(skipped, vec![7, 10, 11]);
+    }
+
+    #[test]
+    fn multiline_functions() {
+        let migration = translate(
+            "function greet {\n    echo hello\n    echo `date`\n}\nup() {\n    cd ..\n}\n",
+        );
+        assert_eq!(migration.initrc, "fn up\n    cd ..\nend\n");
+        assert_eq!(migration.skipped.len(), 1);
+        assert_eq!(migration.skipped[0].0, 1);
+    }
+}
@@ -2,30 +2,49 @@
 pub mod builtins;
 mod completer;
 mod designators;
+pub mod doctor;
 mod history;
+mod histstats;
 mod huponexit;
 mod keybindings;
+mod keymacros;
 mod lexer;
+pub mod migrate;
+mod profiles;
 mod prompt;
+mod quickhelp;
 mod readln;
+mod snippets;
+mod sudo;
+pub mod tutor;
+mod vcs;
 
 use ion_shell::{
-    builtins::{BuiltinFunction, Status},
+    builtins::Status,
     expansion::Expander,
     parser::Terminator,
     types::{self, array},
-    IonError, PipelineError, Shell, Signal, Value,
+    BackgroundEvent, IonError, PipelineError, Shell, Signal, Trap, Value,
 };
 use itertools::Itertools;
+use keymacros::KeyMacros;
 use liner::{Buffer, Context, KeyBindings};
+use nix::unistd::Pid;
+use profiles::Profiles;
+use snippets::Snippets;
 use std::{
     cell::{Cell, RefCell},
+    env,
     fs::{self, OpenOptions},
     io::{self, Write},
     os::unix::io::{AsRawFd, IntoRawFd},
     path::Path,
     rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+use sudo::SudoCredentials;
+use vcs::Repository;
 use xdg::BaseDirectories;
 
 pub const MAN_ION: &str = r#"ion 1.0.0-alpha
@@ -34,6 +53,9 @@ the currently available shells. It is not POSIX compliant.
 
 USAGE:
     ion [FLAGS] [OPTIONS] [args]...
+    ion doctor
+    ion migrate <files>...
+    ion tutor [lesson]
 
 FLAGS:
     -f, --fake-interactive    Use a fake interactive mode, where errors don't exit the shell
@@ -49,13 +71,26 @@ OPTIONS:
 
 ARGS:
     <args>...    Script arguments (@args). If the -c option is not specified, the first parameter is taken as a
-                 filename to execute"#;
+                 filename to execute
+
+SUBCOMMANDS:
+    doctor       Check the configuration, PATH, locale, terminal and history file, and suggest fixes
+    migrate      Translate the aliases, exports, simple functions and PATH edits of bash or zsh
+                 configuration files to an initrc
+    tutor        Interactive lessons on ion's variables, arrays, methods and match statements, with
+                 checked exercises. The lessons are variables, methods and match"#;
 
 pub struct InteractiveShell<'a> {
-    context:    Rc<RefCell<Context>>,
-    shell:      RefCell<Shell<'a>>,
-    terminated: Cell<bool>,
-    huponexit:  Rc<Cell<bool>>,
+    context:       Rc<RefCell<Context>>,
+    shell:         RefCell<Shell<'a>>,
+    terminated:    Cell<bool>,
+    macros:        Rc<RefCell<KeyMacros>>,
+    snippets:      Rc<RefCell<Snippets>>,
+    sudo:          RefCell<SudoCredentials>,
+    profiles:      Profiles,
+    vcs:           RefCell<Repository>,
+    /// The changes of state of the background jobs, printed before the next prompt
+    notifications: Arc<Mutex<Vec<String>>>,
 }
 
 impl<'a> InteractiveShell<'a> {
@@ -65,15 +100,21 @@ impl<'a> InteractiveShell<'a> {
         let mut context = Context::new();
         context.word_divider_fn = Box::new(word_divide);
         InteractiveShell {
-            context:    Rc::new(RefCell::new(context)),
-            shell:      RefCell::new(shell),
-            terminated: Cell::new(true),
-            huponexit:  Rc::new(Cell::new(false)),
+            context:       Rc::new(RefCell::new(context)),
+            shell:         RefCell::new(shell),
+            terminated:    Cell::new(true),
+            macros:        Rc::new(RefCell::new(KeyMacros::default())),
+            snippets:      Rc::new(RefCell::new(Snippets::default())),
+            sudo:          RefCell::new(SudoCredentials::default()),
+            profiles:      Profiles::default(),
+            vcs:           RefCell::new(Repository::default()),
+            notifications: Arc::default(),
         }
     }
 
-    /// Handles commands given by the REPL, and saves them to history.
-    pub fn save_command(&self, cmd: &str) {
+    /// Handles commands given by the REPL, and saves them to history along with how long they
+    /// took and the directory they were run in.
+    pub fn save_command(&self, cmd: &str, elapsed: Duration, directory: &Path) {
         if !cmd.ends_with('/')
             && self
                 .shell
@@ -82,9 +123,9 @@ impl<'a> InteractiveShell<'a> {
                 .ok()
                 .map_or(false, |path| Path::new(&path.as_str()).is_dir())
         {
-            self.save_command_in_history(&[cmd, "/"].concat());
+            self.save_command_in_history(&[cmd, "/"].concat(), elapsed, directory);
         } else {
-            self.save_command_in_history(cmd);
+            self.save_command_in_history(cmd, elapsed, directory);
         }
     }
 
@@ -103,6 +144,18 @@ impl<'a> InteractiveShell<'a> {
                 });
             }
         })));
+
+        // The jobs which stop or exit while a line is edited are reported before the next prompt,
+        // rather than over the line
+        let notifications = self.notifications.clone();
+        self.shell.borrow_mut().set_background_event(Some(Arc::new(move |njob, pid, event| {
+            let notification = job_notification(njob, pid, &event);
+            if event == BackgroundEvent::Added {
+                eprintln!("{}", notification);
+            } else {
+                notifications.lock().unwrap().push(notification);
+            }
+        })));
     }
 
     fn create_config_file(base_dirs: &BaseDirectories) -> Result<(), io::Error> {
@@ -114,14 +167,17 @@ impl<'a> InteractiveShell<'a> {
     /// Liner.
     pub fn execute_interactive(self) -> ! {
         let context_bis = self.context.clone();
-        let huponexit = self.huponexit.clone();
         let prep_for_exit = &move |shell: &mut Shell<'_>| {
             // context will be sent a signal to commit all changes to the history file,
             // and waiting for the history thread in the background to finish.
-            if huponexit.get() {
+            shell.run_deferred();
+            shell.run_trap(Trap::Exit);
+            if shell.opts().hup_on_exit {
                 shell.resume_stopped();
                 shell.background_send(Signal::SIGHUP).expect("Failed to prepare for exit");
             }
+            shell.terminate_coprocesses();
+            shell.close_substitutions();
             context_bis.borrow_mut().history.commit_to_file();
         };
 
@@ -133,37 +189,74 @@ impl<'a> InteractiveShell<'a> {
 
         let exec = self.shell.borrow().builtins().get("exec").unwrap();
         let exec = &|args: &[types::Str], shell: &mut Shell<'_>| -> Status {
-            prep_for_exit(shell);
+            // Without a command, exec only applies its redirections to the shell
+            if args[1..].iter().any(|arg| arg.as_str() != "-c") {
+                prep_for_exit(shell);
+            }
             exec(args, shell)
         };
 
         let history = &history::builtin_history(self.context.clone());
         let keybindings = &keybindings::builtin_keybindings(self.context.clone());
-
-        let huponexit = self.huponexit.clone();
-        let set_huponexit: BuiltinFunction = &huponexit::builtin_huponexit(huponexit);
+        let keymacro = &keymacros::builtin_keymacro(self.macros.clone());
+        let snippet = &snippets::builtin_snippet(self.snippets.clone());
 
         // change the lifetime to allow adding local builtins
-        let InteractiveShell { context, shell, terminated, huponexit } = self;
+        let InteractiveShell {
+            context,
+            shell,
+            terminated,
+            macros,
+            snippets,
+            sudo,
+            profiles,
+            vcs,
+            notifications,
+        } = self;
         let mut shell = shell.into_inner();
         shell
             .builtins_mut()
             .add("history", history, "Prints or manipulates the command history")
             .add("keybindings", keybindings, "Change the keybindings")
+            .add("keymacro", keymacro, "Inspect or persist the keyboard macro")
+            .add("snippet", snippet, "Manage the snippets inserted with Alt-s")
             .add("exit", exit, "Exits the current session")
             .add("exec", exec, "Replace the shell with the given command.")
-            .add("huponexit", set_huponexit, "Hangup the shell's background jobs on exit");
+            .add(
+                "huponexit",
+                &huponexit::builtin_huponexit,
+                "Hangup the shell's background jobs on exit",
+            );
 
         match BaseDirectories::with_prefix("ion") {
             Ok(project_dir) => {
                 Self::exec_init_file(&project_dir, &mut shell);
+                Self::exec_container_file(&project_dir, &mut shell);
                 Self::load_history(&project_dir, &mut shell, &mut context.borrow_mut());
+                match project_dir.place_data_file("keymacro") {
+                    Ok(path) => *macros.borrow_mut() = KeyMacros::load(path),
+                    Err(err) => eprintln!("ion: could not locate keyboard macro file: {}", err),
+                }
+                match project_dir.place_data_file("snippets") {
+                    Ok(path) => *snippets.borrow_mut() = Snippets::load(path),
+                    Err(err) => eprintln!("ion: could not locate snippets file: {}", err),
+                }
             }
             Err(err) => eprintln!("ion: unable to get xdg base directory: {:?}", err),
         }
 
-        InteractiveShell { context, shell: RefCell::new(shell), terminated, huponexit }
-            .exec(prep_for_exit)
+        InteractiveShell {
+            context,
+            shell: RefCell::new(shell),
+            terminated,
+            macros,
+            snippets,
+            sudo,
+            profiles,
+            vcs,
+            notifications,
+        }
+        .exec(prep_for_exit)
     }
 
     fn load_history(project_dir: &BaseDirectories, shell: &mut Shell, context: &mut Context) {
@@ -192,9 +285,10 @@ impl<'a> InteractiveShell<'a> {
 
     fn exec_init_file(project_dir: &BaseDirectories, shell: &mut Shell) {
         let initrc = project_dir.find_config_file(Self::CONFIG_FILE_NAME);
-        match initrc.and_then(|initrc| fs::File::open(&initrc).ok()) {
+        // The whole initrc is read, so that the files it sources are read ahead as it runs
+        match initrc.and_then(|initrc| fs::read_to_string(&initrc).ok()) {
             Some(script) => {
-                if let Err(err) = shell.execute_command(std::io::BufReader::new(script)) {
+                if let Err(err) = shell.execute_script(&script) {
                     eprintln!("ion: could not exec initrc: {}", err);
                 }
             }
@@ -210,8 +304,14 @@ impl<'a> InteractiveShell<'a> {
         let cmd: &str =
             &designators::expand_designators(&self.context.borrow(), command.trim_end());
         self.terminated.set(true);
+        let directory = env::current_dir().unwrap_or_default();
+        let start = Instant::now();
+        let runs_sudo = sudo::runs_sudo(cmd);
         {
             let mut shell = self.shell.borrow_mut();
+            if runs_sudo {
+                self.warn_sudo_password(&mut shell, cmd);
+            }
             match shell.on_command(cmd.bytes(), true) {
                 Ok(_) => (),
                 Err(IonError::PipelineExecutionError(PipelineError::CommandNotFound(command))) => {
@@ -242,16 +342,36 @@ impl<'a> InteractiveShell<'a> {
                     }
                 }
                 Err(err) => {
-                    eprintln!("ion: {}", err);
+                    print_error(&err);
                     shell.reset_flow();
                 }
             }
         }
-        self.save_command(&cmd);
+        if runs_sudo {
+            self.sudo.borrow_mut().invalidate();
+        }
+        self.save_command(&cmd, start.elapsed(), &directory);
+    }
+
+    /// Call the `SUDO_PASSWORD` function before a command which will ask for the password of
+    /// sudo, because no credentials are cached.
+    fn warn_sudo_password(&self, shell: &mut Shell<'_>, command: &str) {
+        if let Some(Value::Function(func)) = shell.variables().get("SUDO_PASSWORD").cloned() {
+            let interval = sudo::probe_interval(shell).unwrap_or_default();
+            if !self.sudo.borrow_mut().cached(interval) {
+                if let Err(why) = shell.execute_function(&func, &["ion", command]) {
+                    eprintln!("ion: sudo password handler: {}", why);
+                }
+            }
+        }
     }
 
     fn exec<T: Fn(&mut Shell<'_>)>(mut self, prep_for_exit: &T) -> ! {
         loop {
+            self.profiles.update(self.shell.get_mut());
+            for notification in self.notifications.lock().unwrap().drain(..) {
+                eprintln!("{}", notification);
+            }
             if let Err(err) = io::stdout().flush() {
                 eprintln!("ion: failed to flush stdio: {}", err);
             }
@@ -273,6 +393,23 @@ impl<'a> InteractiveShell<'a> {
         }
     }
 
+    /// Run the script of the `containers` configuration directory named after the container the
+    /// shell runs in, such as `containers/toolbox`, to adjust the PATH or the prompt to it
+    fn exec_container_file(project_dir: &BaseDirectories, shell: &mut Shell<'_>) {
+        let container = match ion_shell::sys::container::detect() {
+            Some(container) => container,
+            None => return,
+        };
+        let script = project_dir
+            .find_config_file(Path::new("containers").join(&container))
+            .and_then(|path| fs::File::open(path).ok());
+        if let Some(script) = script {
+            if let Err(err) = shell.execute_command(std::io::BufReader::new(script)) {
+                eprintln!("ion: could not exec the {} container file: {}", container, err);
+            }
+        }
+    }
+
     /// Try to cd if the command failed
     fn try_cd(dir: &str, shell: &mut Shell<'_>) -> nix::Result<Status> {
         // Gag the cd output
@@ -297,6 +434,27 @@ impl<'a> InteractiveShell<'a> {
     }
 }
 
+/// Describe a change of state of a background job
+fn job_notification(njob: usize, pid: Pid, event: &BackgroundEvent) -> String {
+    match event {
+        BackgroundEvent::Added => format!("ion: bg [{}] {}", njob, pid),
+        BackgroundEvent::Stopped => format!("ion: ([{}] {}) Stopped", njob, pid),
+        BackgroundEvent::Resumed => format!("ion: ([{}] {}) Running", njob, pid),
+        BackgroundEvent::Exited(status) => {
+            format!("ion: ([{}] {}) exited with {}", njob, pid, status)
+        }
+        BackgroundEvent::Errored(error) => format!("ion: ([{}] {}) errored: {}", njob, pid, error),
+    }
+}
+
+/// Print an error to stderr, pointing at the offending token for syntax errors
+pub fn print_error(err: &IonError) {
+    match err {
+        IonError::InvalidSyntax(diagnostic) => eprintln!("ion: {}", diagnostic.render()),
+        _ => eprintln!("ion: {}", err),
+    }
+}
+
 #[derive(Debug)]
 struct WordDivide<I>
 where
@@ -0,0 +1,183 @@
+use ion_shell::{expansion::Expander, flow_control::Function, types, Shell, Value};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+use xdg::BaseDirectories;
+
+/// The file which names the profile of a directory and of its subdirectories
+const MARKER: &str = ".ion-profile";
+
+/// Profiles selected by the working directory.
+///
+/// A directory selects a profile when it contains a `.ion-profile` file, whose first line is
+/// the name of the profile, or when it is a git root listed in the `PROFILE_ROOTS` map. The
+/// profile is a script in the `profiles` directory of the ion configuration, which is run when
+/// entering the directory. The aliases, functions and variables it defines are restored to
+/// their previous values when leaving it.
+#[derive(Debug, Default)]
+pub struct Profiles {
+    directory: Option<PathBuf>,
+    active:    Option<Profile>,
+}
+
+#[derive(Debug)]
+struct Profile {
+    name:     String,
+    /// The previous values of the definitions of the profile, if they had one
+    replaced: Vec<(types::Str, Option<Value<Rc<Function>>>)>,
+}
+
+impl Profiles {
+    /// Switch to the profile of the working directory, if the directory changed since the last
+    /// call
+    pub fn update(&mut self, shell: &mut Shell<'_>) {
+        let directory = match env::current_dir() {
+            Ok(directory) => directory,
+            Err(_) => return,
+        };
+        if self.directory.as_ref() == Some(&directory) {
+            return;
+        }
+        let name = profile_of(&directory, shell);
+        self.directory = Some(directory);
+        if self.active.as_ref().map(|profile| &profile.name) == name.as_ref() {
+            return;
+        }
+        if let Some(profile) = self.active.take() {
+            profile.leave(shell);
+        }
+        self.active = name.and_then(|name| Profile::enter(name, shell));
+    }
+}
+
+impl Profile {
+    fn enter(name: String, shell: &mut Shell<'_>) -> Option<Self> {
+        let script = BaseDirectories::with_prefix("ion")
+            .ok()
+            .and_then(|dirs| dirs.find_config_file(Path::new("profiles").join(&name)))
+            .and_then(|path| fs::File::open(path).ok());
+        match script {
+            Some(script) => Some(Self::run(name, BufReader::new(script), shell)),
+            None => {
+                eprintln!("ion: profile {}: no such profile", name);
+                None
+            }
+        }
+    }
+
+    /// Run the script of the profile, remembering the definitions it replaced
+    fn run<R: Read>(name: String, script: R, shell: &mut Shell<'_>) -> Self {
+        let before: HashMap<_, _> = shell
+            .variables()
+            .definitions()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        if let Err(err) = shell.execute_command(script) {
+            eprintln!("ion: profile {}: {}", name, err);
+        }
+        let replaced = shell
+            .variables()
+            .definitions()
+            .filter(|&(name, value)| before.get(name) != Some(value))
+            .map(|(name, _)| (name.clone(), before.get(name).cloned()))
+            .collect();
+
+        shell.variables_mut().set("PROFILE", name.as_str());
+        Self { name, replaced }
+    }
+
+    fn leave(self, shell: &mut Shell<'_>) {
+        let variables = shell.variables_mut();
+        for (name, value) in self.replaced {
+            match value {
+                Some(value) => variables.set(&name, value),
+                None => {
+                    variables.remove(&name);
+                }
+            }
+        }
+        variables.remove("PROFILE");
+    }
+}
+
+/// The name of the profile selected by the nearest marked or listed parent of the directory
+fn profile_of(directory: &Path, shell: &Shell<'_>) -> Option<String> {
+    let roots = listed_roots(shell);
+    directory.ancestors().find_map(|parent| {
+        let name = match fs::read_to_string(parent.join(MARKER)) {
+            Ok(marker) => marker.lines().next().unwrap_or_default().trim().to_string(),
+            Err(_) if parent.join(".git").exists() => {
+                roots.iter().find(|(root, _)| root == parent)?.1.clone()
+            }
+            Err(_) => return None,
+        };
+        // Profiles may only be taken from the profiles directory
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            None
+        } else {
+            Some(name)
+        }
+    })
+}
+
+/// The git roots and their profiles, from the `PROFILE_ROOTS` map
+fn listed_roots(shell: &Shell<'_>) -> Vec<(PathBuf, String)> {
+    let entries: Vec<(types::Str, String)> = match shell.variables().get("PROFILE_ROOTS") {
+        Some(Value::HashMap(map)) => {
+            map.iter().map(|(root, name)| (root.clone(), name.to_string())).collect()
+        }
+        Some(Value::BTreeMap(map)) => {
+            map.iter().map(|(root, name)| (root.clone(), name.to_string())).collect()
+        }
+        _ => return Vec::new(),
+    };
+    entries
+        .into_iter()
+        .map(|(root, name)| {
+            let root = shell.tilde(&root).unwrap_or(root);
+            (PathBuf::from(root.as_str()), name)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch_profiles() {
+        let mut shell = Shell::default();
+        shell
+            .execute_command(
+                "alias ll = 'ls -l'\nfn PROMPT\n    echo '$ '\nend\nlet level = 1\n".as_bytes(),
+            )
+            .unwrap();
+        let definitions = |shell: &Shell<'_>| {
+            let mut definitions: Vec<_> = shell
+                .variables()
+                .definitions()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            definitions.sort_by(|(a, _), (b, _)| a.cmp(b));
+            definitions
+        };
+        let initial = definitions(&shell);
+
+        let work = "alias ll = 'ls -la'\nfn PROMPT\n    echo 'work $ '\nend\nlet level = 2\n";
+        let profile = Profile::run("work".into(), work.as_bytes(), &mut shell);
+        assert_eq!(shell.variables().get_str("PROFILE").unwrap().as_str(), "work");
+        assert_eq!(shell.variables().get_str("level").unwrap().as_str(), "2");
+        assert_ne!(definitions(&shell), initial);
+
+        let home = "fn greet\n    echo hello\nend\nlet level = 3\n";
+        profile.leave(&mut shell);
+        let profile = Profile::run("home".into(), home.as_bytes(), &mut shell);
+        assert!(matches!(shell.variables().get("greet"), Some(Value::Function(_))));
+        profile.leave(&mut shell);
+        assert_eq!(definitions(&shell), initial);
+    }
+}
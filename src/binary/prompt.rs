@@ -1,4 +1,4 @@
-use super::InteractiveShell;
+use super::{sudo, InteractiveShell};
 use ion_shell::{
     expansion::{self, Expander},
     IonError, PipelineError, Shell,
@@ -13,6 +13,11 @@ impl<'a> InteractiveShell<'a> {
         let blocks = if self.terminated.get() { shell.block_len() } else { shell.block_len() + 1 };
 
         if blocks == 0 {
+            if let Some(interval) = sudo::probe_interval(&shell) {
+                let cached = self.sudo.borrow_mut().cached(interval);
+                shell.variables_mut().set("SUDO_CACHED", if cached { "1" } else { "0" });
+            }
+            self.vcs.borrow_mut().update(&mut shell);
             let out =
                 shell.command("PROMPT", false).map(|res| res.to_string()).unwrap_or_else(|err| {
                     if let expansion::Error::Subprocess(err) = err {
@@ -0,0 +1,96 @@
+//! The quick help panel shown below the edit line with `Alt-h`.
+use ion_shell::{Shell, Value};
+use liner::{CursorPosition, Editor};
+use std::{
+    io::{self, Write},
+    process::{Command, Stdio},
+};
+use termion::{clear, cursor};
+
+/// The maximum number of lines the panel may take
+const MAX_LINES: usize = 6;
+
+/// Find the command word the cursor is in: the first word of the pipeline segment.
+fn command_word<W: Write>(editor: &Editor<'_, W>) -> Option<String> {
+    let (words, pos) = editor.get_words_and_cursor_position();
+    let index = match pos {
+        CursorPosition::InWord(i)
+        | CursorPosition::OnWordLeftEdge(i)
+        | CursorPosition::OnWordRightEdge(i)
+        | CursorPosition::InSpace(Some(i), _) => i,
+        CursorPosition::InSpace(None, _) => 0,
+    };
+    let buffer = editor.current_buffer();
+    let words: Vec<String> =
+        words.into_iter().take(index + 1).map(|(start, end)| buffer.range(start, end)).collect();
+    let start = words
+        .iter()
+        .rposition(|word| word.ends_with('|') || word.ends_with('&') || word.ends_with(';'))
+        .map_or(0, |i| i + 1);
+    words.into_iter().nth(start)
+}
+
+/// Collect the help for a command: the function description, the builtin help, or the
+/// beginning of its man page, in that order.
+fn help_text(shell: &Shell<'_>, command: &str) -> Option<Vec<String>> {
+    if let Some(Value::Function(function)) = shell.variables().get(command) {
+        let description = function.description().map_or("no description", |desc| desc.as_str());
+        return Some(vec![format!("{}: function - {}", command, description)]);
+    }
+    if let Some(help) = shell.builtins().get_help(command) {
+        return Some(help.lines().map(|line| format!("{}: {}", command, line.trim())).collect());
+    }
+    let output = Command::new("man")
+        .arg(command)
+        .env("MANPAGER", "cat")
+        .env("MANWIDTH", "80")
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let page = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<String> = page
+        .lines()
+        .skip_while(|line| line.trim() != "NAME")
+        .skip(1)
+        .take_while(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_string())
+        .collect();
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines)
+    }
+}
+
+/// Render the help for the command under the cursor below the edit line.
+pub fn show<W: Write>(shell: &Shell<'_>, editor: &Editor<'_, W>) {
+    let lines = command_word(editor)
+        .and_then(|command| help_text(shell, &command))
+        .unwrap_or_else(|| vec!["no help available".into()]);
+    let lines = &lines[..lines.len().min(MAX_LINES)];
+
+    // The terminal is in raw mode, so line feeds keep the column. Scroll first to make room for
+    // the panel, so that the saved cursor position stays valid.
+    let mut out = io::stdout();
+    let _ = write!(
+        out,
+        "{}{}{}",
+        "\n".repeat(lines.len()),
+        cursor::Up(lines.len() as u16),
+        cursor::Save
+    );
+    for line in lines {
+        let _ = write!(out, "\r\n{}{}", clear::CurrentLine, line);
+    }
+    let _ = write!(out, "{}", cursor::Restore);
+    let _ = out.flush();
+}
+
+/// Remove the help panel from below the edit line
+pub fn dismiss() {
+    let mut out = io::stdout();
+    let _ = write!(out, "{}\r\n{}{}", cursor::Save, clear::AfterCursor, cursor::Restore);
+    let _ = out.flush();
+}
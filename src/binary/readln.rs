@@ -1,5 +1,6 @@
 use super::{completer::IonCompleter, InteractiveShell};
 use ion_shell::Shell;
+use liner::KeyBindings;
 use nix::fcntl::{fcntl, FcntlArg, OFlag};
 use std::io::ErrorKind;
 
@@ -16,10 +17,15 @@ impl<'a> InteractiveShell<'a> {
         Self::change_blocking(1);
         Self::change_blocking(2);
         let prompt = self.prompt();
+        // Keyboard macros rely on the inserted keys, which only holds for the emacs bindings
+        let macros = match self.context.borrow().key_bindings {
+            KeyBindings::Emacs => Some(&*self.macros),
+            KeyBindings::Vi => None,
+        };
         let line = self.context.borrow_mut().read_line(
             prompt,
             None,
-            &mut IonCompleter::new(&self.shell.borrow()),
+            &mut IonCompleter::new(&self.shell.borrow(), macros, &self.snippets, &self.vcs),
         );
 
         match line {
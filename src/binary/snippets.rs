@@ -0,0 +1,224 @@
+use builtins_proc::builtin_interactive;
+use ion_shell::{
+    builtins::{man_pages, Status},
+    types, Shell,
+};
+use liner::Editor;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+
+/// Named snippets of text which can be inserted in the line editor.
+///
+/// Typing the name of a snippet and pressing `Alt-s` replaces the name with the snippet. The
+/// snippet text may contain `$1` to `$9` placeholders: the cursor is moved to the first one on
+/// insertion, and every following `Alt-s` jumps to the next one. `$0` marks where the cursor
+/// ends up after the last placeholder.
+#[derive(Debug, Default)]
+pub struct Snippets {
+    snippets: BTreeMap<String, String>,
+    /// The placeholders of the last inserted snippet which have yet to be visited
+    stops:    VecDeque<usize>,
+    /// The placeholder the cursor was last moved to
+    at:       usize,
+    /// The length of the buffer when the cursor was last moved to a placeholder
+    len:      usize,
+    /// Where the snippets are persisted, if anywhere
+    file:     Option<PathBuf>,
+}
+
+impl Snippets {
+    /// Load the snippets persisted at the given path, and remember it for later saves
+    pub fn load(path: PathBuf) -> Self {
+        let snippets = fs::read_to_string(&path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter_map(|line| {
+                        let mut fields = line.splitn(2, '\t');
+                        Some((fields.next()?.to_string(), unescape(fields.next()?)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        Snippets { snippets, file: Some(path), ..Snippets::default() }
+    }
+
+    fn save(&self) -> io::Result<()> {
+        let path = match self.file {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut file = fs::File::create(path)?;
+        for (name, text) in &self.snippets {
+            writeln!(file, "{}\t{}", name, text.escape_default())?;
+        }
+        Ok(())
+    }
+
+    /// Expand the snippet named by the word before the cursor, or move to the next placeholder
+    pub fn on_key<W: Write>(&mut self, editor: &mut Editor<'_, W>) -> io::Result<()> {
+        let before = editor.current_buffer().range(0, editor.cursor());
+        let word = before.rsplit(char::is_whitespace).next().unwrap_or_default();
+        if let Some(snippet) = self.snippets.get(word) {
+            for _ in 0..word.chars().count() {
+                editor.delete_before_cursor()?;
+            }
+            let start = editor.cursor();
+            let (text, stops) = placeholders(snippet);
+            editor.insert_str_after_cursor(&text)?;
+            self.stops = stops.into_iter().map(|stop| start + stop).collect();
+            self.at = start;
+            self.len = editor.current_buffer().num_chars();
+        }
+        self.next_stop(editor)
+    }
+
+    fn next_stop<W: Write>(&mut self, editor: &mut Editor<'_, W>) -> io::Result<()> {
+        // Text typed at the current placeholder shifts the placeholders after it
+        let len = editor.current_buffer().num_chars();
+        let shift = len as isize - self.len as isize;
+        let at = self.at;
+        for stop in self.stops.iter_mut().filter(|stop| **stop > at) {
+            *stop = (*stop as isize + shift).max(at as isize) as usize;
+        }
+        self.len = len;
+
+        match self.stops.pop_front() {
+            Some(stop) => {
+                self.at = stop;
+                editor.move_cursor_to(stop.min(len))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Remove the placeholders from a snippet, returning the text and the character offsets of the
+/// placeholders in the order they are visited.
+fn placeholders(snippet: &str) -> (String, Vec<usize>) {
+    let mut text = String::with_capacity(snippet.len());
+    let mut stops = Vec::new();
+    let mut length = 0;
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek().and_then(|next| next.to_digit(10)) {
+            Some(number) if c == '$' => {
+                let _ = chars.next();
+                // `$0` is visited last
+                stops.push((if number == 0 { 10 } else { number }, length));
+            }
+            _ => {
+                text.push(c);
+                length += 1;
+            }
+        }
+    }
+    stops.sort_by_key(|&(number, _)| number);
+    (text, stops.into_iter().map(|(_, offset)| offset).collect())
+}
+
+fn unescape(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => output.push('\n'),
+            Some('t') => output.push('\t'),
+            Some('r') => output.push('\r'),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                if let Some(c) = u32::from_str_radix(&code, 16).ok().and_then(std::char::from_u32) {
+                    output.push(c);
+                }
+            }
+            Some(c) => output.push(c),
+            None => output.push('\\'),
+        }
+    }
+    output
+}
+
+#[builtin_interactive(
+    desc = "manage the snippets inserted with Alt-s",
+    man = "
+SYNOPSIS
+    snippet [list]
+    snippet add NAME TEXT
+    snippet rm NAME
+
+DESCRIPTION
+    Snippets are inserted in the line editor by typing their name and pressing Alt-s. The
+    text may contain the $1 to $9 placeholders: the cursor is moved to $1 on insertion, and
+    Alt-s moves it on to the next placeholder. $0 marks the final position of the cursor.
+
+    Snippets are kept in the ion data directory, and are available in later sessions.
+
+OPTIONS:
+    list: print the snippets (default)
+    add: define the snippet NAME, replacing any previous definition
+    rm: remove the snippet NAME"
+)]
+pub fn snippet(
+    snippets: Rc<RefCell<Snippets>>,
+) -> impl Fn(&[types::Str], &mut Shell<'_>) -> Status {
+    move |args: &[types::Str], _shell: &mut Shell<'_>| -> Status {
+        if man_pages::check_help(args, HELP_PAGE) {
+            return Status::SUCCESS;
+        }
+        let mut snippets = snippets.borrow_mut();
+        match (args.get(1).map(|s| s.as_str()), args.get(2..).unwrap_or_default()) {
+            (Some("list"), []) | (None, _) => {
+                for (name, text) in &snippets.snippets {
+                    println!("{}\t{}", name, text.escape_default());
+                }
+                return Status::SUCCESS;
+            }
+            (Some("add"), [name, text]) => {
+                snippets.snippets.insert(name.to_string(), text.to_string());
+            }
+            (Some("rm"), [name]) => {
+                if snippets.snippets.remove(name.as_str()).is_none() {
+                    return Status::error(format!("ion: snippet: no snippet named {}", name));
+                }
+            }
+            _ => {
+                return Status::bad_argument(
+                    "snippet: expected `add NAME TEXT`, `rm NAME` or `list`",
+                )
+            }
+        }
+        match snippets.save() {
+            Ok(()) => Status::SUCCESS,
+            Err(err) => Status::error(format!("ion: snippet: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholder_offsets() {
+        let (text, stops) = placeholders("for $1 in $2$0\n  $3\nend");
+        assert_eq!(text, "for  in \n  \nend");
+        assert_eq!(stops, vec![4, 8, 11, 8]);
+        assert_eq!(placeholders("echo $HOME $"), ("echo $HOME $".into(), vec![]));
+    }
+
+    #[test]
+    fn escaped_text_round_trip() {
+        let text = "match $1\n\tcase \"é\"\\";
+        assert_eq!(unescape(&text.escape_default().to_string()), text);
+    }
+}
@@ -0,0 +1,78 @@
+use ion_shell::Shell;
+use std::{
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// Whether `sudo` can run without asking for a password, as of the last probe.
+///
+/// Probing runs `sudo -n true`, which fails instead of asking for a password when no credentials
+/// are cached. The result is reused until the probe interval elapsed, so that redrawing the
+/// prompt does not spawn `sudo` each time.
+#[derive(Debug, Default)]
+pub struct SudoCredentials {
+    checked: Option<Instant>,
+    cached:  bool,
+}
+
+impl SudoCredentials {
+    /// Whether sudo credentials are cached, probing sudo again if the last probe is older than
+    /// the interval
+    pub fn cached(&mut self, interval: Duration) -> bool {
+        if self.checked.map_or(true, |checked| checked.elapsed() >= interval) {
+            self.cached = probe();
+            self.checked = Some(Instant::now());
+        }
+        self.cached
+    }
+
+    /// Forget the last probe, after a command which may have changed the credentials
+    pub fn invalidate(&mut self) { self.checked = None; }
+}
+
+fn probe() -> bool {
+    Command::new("sudo")
+        .args(&["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |status| status.success())
+}
+
+/// The interval between two probes of the prompt, if `SUDO_PROBE_INTERVAL` enables them
+pub fn probe_interval(shell: &Shell<'_>) -> Option<Duration> {
+    shell
+        .variables()
+        .get_str("SUDO_PROBE_INTERVAL")
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+}
+
+/// Whether a command of the line runs `sudo` in a way that may ask for a password
+pub fn runs_sudo(line: &str) -> bool {
+    line.split(|c| c == ';' || c == '|' || c == '&' || c == '\n').any(|command| {
+        let mut words = command.split_whitespace();
+        words.next() == Some("sudo")
+            && !words
+                .take_while(|word| word.starts_with('-'))
+                .any(|option| option == "-n" || option == "--non-interactive")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sudo_commands() {
+        assert!(runs_sudo("sudo apt update"));
+        assert!(runs_sudo("cd /etc && sudo -u root vim hosts"));
+        assert!(runs_sudo("echo 1 | sudo tee /proc/sys/vm/drop_caches"));
+        assert!(!runs_sudo("sudo -n true"));
+        assert!(!runs_sudo("echo sudo; pseudo ls"));
+    }
+}
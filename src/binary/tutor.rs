@@ -0,0 +1,277 @@
+//! `ion tutor`: interactive lessons on the syntax which sets ion apart from POSIX shells.
+//!
+//! Each lesson is an ion script shipped in the binary. Its commands prepare the shell of the
+//! lesson, and its comments hold the lesson itself:
+//!
+//! - `#:` lines are explanations printed as they are met,
+//! - `#?` lines are the task of an exercise, which the user answers with ion commands,
+//! - `#!` is the hint of the exercise, printed on request,
+//! - `#=` is the command which checks the answer, by its exit status.
+use ion_shell::{BlockError, IonError, Shell};
+use std::io::{self, BufRead, Write};
+
+/// The lessons, in the order in which they are taught
+const LESSONS: &[(&str, &str)] = &[
+    ("variables", include_str!("lessons/01-variables.ion")),
+    ("methods", include_str!("lessons/02-methods.ion")),
+    ("match", include_str!("lessons/03-match.ion")),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+enum Step<'a> {
+    /// Lines of explanation
+    Say(Vec<&'a str>),
+    /// Commands preparing the shell of the lesson
+    Run(String),
+    Exercise(Exercise<'a>),
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Exercise<'a> {
+    task:  Vec<&'a str>,
+    hint:  Option<&'a str>,
+    check: &'a str,
+}
+
+/// Whether the user went through the lesson, or asked to stop
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Completed,
+    Quit,
+}
+
+/// Teach the lessons, starting with the named one if given
+pub fn run(args: &[String]) -> i32 {
+    let start = match args {
+        [] => 0,
+        [name] => match LESSONS.iter().position(|&(lesson, _)| lesson == name) {
+            Some(start) => start,
+            None => {
+                let names: Vec<_> = LESSONS.iter().map(|&(lesson, _)| lesson).collect();
+                eprintln!(
+                    "ion tutor: no lesson named {}, choose one of: {}",
+                    name,
+                    names.join(", ")
+                );
+                return 2;
+            }
+        },
+        _ => {
+            eprintln!("ion tutor: usage: ion tutor [LESSON]");
+            return 2;
+        }
+    };
+
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout();
+    println!("Type the commands asked by each exercise. Type `hint` for help, `skip` to move to");
+    println!("the next exercise, or `quit` to stop.");
+    for (number, &(name, script)) in LESSONS.iter().enumerate().skip(start) {
+        println!("\n== Lesson {} of {}: {} ==\n", number + 1, LESSONS.len(), name);
+        match teach(&parse(script), &mut input, &mut output) {
+            Ok(Outcome::Completed) => (),
+            Ok(Outcome::Quit) => {
+                println!("Continue later with `ion tutor {}`.", name);
+                return 0;
+            }
+            Err(why) => {
+                eprintln!("ion tutor: {}", why);
+                return 1;
+            }
+        }
+    }
+    println!("\nThat was the last lesson. The manual covers the rest of ion.");
+    0
+}
+
+/// Split a lesson into its steps
+fn parse(script: &str) -> Vec<Step<'_>> {
+    let mut steps = Vec::new();
+    for line in script.lines() {
+        let (marker, text) = match line.get(..2) {
+            Some(marker) if ["#:", "#?", "#!", "#="].contains(&marker) => {
+                (marker, line[2..].strip_prefix(' ').unwrap_or(&line[2..]))
+            }
+            _ => ("", line),
+        };
+        match (marker, steps.last_mut()) {
+            ("#:", Some(Step::Say(lines))) => lines.push(text),
+            ("#:", _) => steps.push(Step::Say(vec![text])),
+            ("#?", Some(Step::Exercise(exercise))) if exercise.check.is_empty() => {
+                exercise.task.push(text)
+            }
+            ("#?", _) => {
+                steps.push(Step::Exercise(Exercise { task: vec![text], ..Exercise::default() }))
+            }
+            ("#!", Some(Step::Exercise(exercise))) => exercise.hint = Some(text),
+            ("#=", Some(Step::Exercise(exercise))) => exercise.check = text,
+            ("#!", _) | ("#=", _) => (),
+            (..) if text.trim().is_empty() || text.starts_with('#') => (),
+            (_, Some(Step::Run(commands))) => {
+                commands.push('\n');
+                commands.push_str(text);
+            }
+            (..) => steps.push(Step::Run(text.into())),
+        }
+    }
+    steps
+}
+
+/// Go through the steps of a lesson, reading the answers of the user from the input
+fn teach<R: BufRead, W: Write>(
+    steps: &[Step<'_>],
+    input: &mut R,
+    output: &mut W,
+) -> io::Result<Outcome> {
+    let mut shell = Shell::new();
+    for step in steps {
+        match step {
+            Step::Say(lines) => {
+                for line in lines {
+                    writeln!(output, "{}", line)?;
+                }
+            }
+            Step::Run(commands) => {
+                if let Err(why) = shell.execute_command(commands.as_bytes()) {
+                    writeln!(output, "ion tutor: the lesson failed to start: {}", why)?;
+                }
+            }
+            Step::Exercise(exercise) => {
+                if exercise.ask(&mut shell, input, output)? == Outcome::Quit {
+                    return Ok(Outcome::Quit);
+                }
+            }
+        }
+    }
+    Ok(Outcome::Completed)
+}
+
+impl<'a> Exercise<'a> {
+    /// Read and run answers until one of them passes the check, or the exercise is skipped
+    fn ask<R: BufRead, W: Write>(
+        &self,
+        shell: &mut Shell<'_>,
+        input: &mut R,
+        output: &mut W,
+    ) -> io::Result<Outcome> {
+        writeln!(output)?;
+        for line in &self.task {
+            writeln!(output, "> {}", line)?;
+        }
+        loop {
+            let answer = match read(input, output, "tutor> ")? {
+                Some(answer) => answer,
+                None => return Ok(Outcome::Quit),
+            };
+            match answer.trim() {
+                "" => continue,
+                "quit" => return Ok(Outcome::Quit),
+                "skip" => return Ok(Outcome::Completed),
+                "hint" => {
+                    writeln!(output, "{}", self.hint.unwrap_or("no hint for this exercise"))?;
+                    continue;
+                }
+                _ => (),
+            }
+
+            let mut result = shell.execute_command(answer.as_bytes());
+            // Blocks are answered over several lines
+            while let Err(IonError::StatementFlowError(BlockError::UnclosedBlock(_))) = result {
+                match read(input, output, "    > ")? {
+                    Some(line) => result = shell.execute_command(line.as_bytes()),
+                    None => return Ok(Outcome::Quit),
+                }
+            }
+            if let Err(why) = result {
+                writeln!(output, "ion: {}", why)?;
+            } else if self.passes(shell) {
+                writeln!(output, "Correct!")?;
+                return Ok(Outcome::Completed);
+            }
+            writeln!(output, "Not quite. Type `hint` for help, or `skip` to move on.")?;
+        }
+    }
+
+    fn passes(&self, shell: &mut Shell<'_>) -> bool {
+        shell.execute_command(self.check.as_bytes()).map_or(false, |status| status.is_success())
+    }
+}
+
+/// Read a line of input after printing the prompt, or `None` at the end of the input
+fn read<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    prompt: &str,
+) -> io::Result<Option<String>> {
+    write!(output, "{}", prompt)?;
+    output.flush()?;
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        writeln!(output)?;
+        Ok(None)
+    } else {
+        Ok(Some(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lessons_parse() {
+        for &(name, script) in LESSONS {
+            let steps = parse(script);
+            let exercises = steps
+                .iter()
+                .filter_map(|step| match step {
+                    Step::Exercise(exercise) => Some(exercise),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert!(!exercises.is_empty(), "{} has no exercise", name);
+            assert!(
+                exercises
+                    .iter()
+                    .all(|exercise| !exercise.check.is_empty() && exercise.hint.is_some()),
+                "{} has an exercise without a check or a hint",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn hints_pass_their_checks() {
+        for &(name, script) in LESSONS {
+            let steps = parse(script);
+            let answers = steps
+                .iter()
+                .filter_map(|step| match step {
+                    Step::Exercise(exercise) => exercise.hint,
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut output = Vec::new();
+            let outcome = teach(&steps, &mut answers.as_bytes(), &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            assert_eq!(outcome, Outcome::Completed, "{}: {}", name, output);
+            assert!(!output.contains("Not quite"), "{}: {}", name, output);
+        }
+    }
+
+    #[test]
+    fn wrong_answers_and_skips() {
+        let steps = parse("#? Set x to 1\n#! let x = 1\n#= is $x 1\n#? Set y\n#= exists -s y\n");
+        let mut output = Vec::new();
+        let outcome =
+            teach(&steps, &mut "let x = 2\nhint\nlet x = 1\nskip\n".as_bytes(), &mut output)
+                .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(outcome, Outcome::Completed);
+        assert_eq!(output.matches("Not quite").count(), 1);
+        assert!(output.contains("let x = 1\n"));
+        assert!(output.contains("Correct!"));
+    }
+}
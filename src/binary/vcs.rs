@@ -0,0 +1,233 @@
+use ion_shell::Shell;
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+/// A version control system which can describe the state of a repository.
+pub trait VcsStatus {
+    /// The name of the system, exposed to the prompt as `$VCS`
+    fn name(&self) -> &'static str;
+
+    /// The directory which marks the root of a repository
+    fn marker(&self) -> &'static str;
+
+    /// The current branch, channel or bookmark of the repository
+    fn branch(&self, root: &Path) -> Option<String>;
+
+    /// The paths, relative to the root, of the files with uncommitted changes
+    fn changes(&self, root: &Path) -> Vec<String>;
+}
+
+pub struct Git;
+
+impl VcsStatus for Git {
+    fn name(&self) -> &'static str { "git" }
+
+    fn marker(&self) -> &'static str { ".git" }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        run(root, "git", &["symbolic-ref", "--short", "HEAD"])
+            .or_else(|| run(root, "git", &["rev-parse", "--short", "HEAD"]))
+            .map(|branch| branch.trim().to_string())
+    }
+
+    fn changes(&self, root: &Path) -> Vec<String> {
+        run(root, "git", &["status", "--porcelain", "-z", "--no-renames"])
+            .map(|status| {
+                status.split('\0').filter_map(|entry| entry.get(3..)).map(String::from).collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub struct Mercurial;
+
+impl VcsStatus for Mercurial {
+    fn name(&self) -> &'static str { "hg" }
+
+    fn marker(&self) -> &'static str { ".hg" }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        run(root, "hg", &["branch"]).map(|branch| branch.trim().to_string())
+    }
+
+    fn changes(&self, root: &Path) -> Vec<String> {
+        run(root, "hg", &["status", "--print0"])
+            .map(|status| {
+                status.split('\0').filter_map(|entry| entry.get(2..)).map(String::from).collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+pub struct Pijul;
+
+impl VcsStatus for Pijul {
+    fn name(&self) -> &'static str { "pijul" }
+
+    fn marker(&self) -> &'static str { ".pijul" }
+
+    fn branch(&self, root: &Path) -> Option<String> {
+        run(root, "pijul", &["channel"])?
+            .lines()
+            .find_map(|line| line.strip_prefix("* "))
+            .map(|channel| channel.trim().to_string())
+    }
+
+    fn changes(&self, root: &Path) -> Vec<String> {
+        run(root, "pijul", &["diff", "--short", "--untracked"])
+            .map(|diff| {
+                diff.lines()
+                    .filter_map(|line| line.split_once(char::is_whitespace))
+                    .map(|(_, path)| path.trim().to_string())
+                    .filter(|path| !path.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// The supported systems, in the order in which their markers are looked for
+const PROVIDERS: [&dyn VcsStatus; 3] = [&Git, &Mercurial, &Pijul];
+
+/// The variables describing the repository to the prompt
+const VARIABLES: [&str; 3] = ["VCS", "VCS_BRANCH", "VCS_CHANGES"];
+
+/// The state of the repository of the working directory, as of the last probe.
+///
+/// Probing runs the status and branch commands of the system, which may take a while in a large
+/// repository. The result is reused for the same directory until the probe interval elapsed, so
+/// that redrawing the prompt does not run them each time.
+#[derive(Default)]
+pub struct Repository {
+    checked: Option<(PathBuf, Instant)>,
+    changes: HashSet<PathBuf>,
+}
+
+impl Repository {
+    /// Inspect the repository of the working directory if `VCS_PROBE_INTERVAL` enables it, and
+    /// set the `VCS`, `VCS_BRANCH` and `VCS_CHANGES` variables for the prompt, or remove them
+    /// outside of a repository. The variables are left alone while probing is disabled.
+    pub fn update(&mut self, shell: &mut Shell<'_>) {
+        let interval = match probe_interval(shell) {
+            Some(interval) => interval,
+            None => {
+                // Remove what the last probe set, once probing is disabled
+                if self.checked.take().is_some() {
+                    self.clear(shell);
+                }
+                return;
+            }
+        };
+        let directory = match env::current_dir() {
+            Ok(directory) => directory,
+            Err(_) => {
+                self.checked = None;
+                return self.clear(shell);
+            }
+        };
+        if self
+            .checked
+            .as_ref()
+            .map_or(false, |(checked, time)| *checked == directory && time.elapsed() < interval)
+        {
+            return;
+        }
+        let detected = detect(&directory);
+        self.checked = Some((directory, Instant::now()));
+        match detected {
+            Some((provider, root)) => {
+                self.changes =
+                    provider.changes(&root).into_iter().map(|path| root.join(path)).collect();
+                let variables = shell.variables_mut();
+                variables.set("VCS", provider.name());
+                variables.set("VCS_BRANCH", provider.branch(&root).unwrap_or_default());
+                variables.set("VCS_CHANGES", self.changes.len().to_string());
+            }
+            None => self.clear(shell),
+        }
+    }
+
+    fn clear(&mut self, shell: &mut Shell<'_>) {
+        self.changes.clear();
+        let variables = shell.variables_mut();
+        for name in &VARIABLES {
+            variables.remove(name);
+        }
+    }
+
+    /// Whether the file, relative to the working directory, has uncommitted changes
+    pub fn is_changed(&self, path: &Path) -> bool {
+        !self.changes.is_empty()
+            && env::current_dir()
+                .map_or(false, |directory| self.changes.contains(&directory.join(path)))
+    }
+}
+
+/// The system and root of the nearest repository containing the directory
+fn detect(directory: &Path) -> Option<(&'static dyn VcsStatus, PathBuf)> {
+    directory.ancestors().find_map(|parent| {
+        PROVIDERS
+            .iter()
+            .find(|provider| parent.join(provider.marker()).exists())
+            .map(|&provider| (provider, parent.to_path_buf()))
+    })
+}
+
+/// The interval between two probes of the same directory, if `VCS_PROBE_INTERVAL` enables them
+fn probe_interval(shell: &Shell<'_>) -> Option<Duration> {
+    shell
+        .variables()
+        .get_str("VCS_PROBE_INTERVAL")
+        .ok()?
+        .parse()
+        .ok()
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+}
+
+fn run(root: &Path, program: &str, args: &[&str]) -> Option<String> {
+    Command::new(program)
+        .args(args)
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn nearest_repository() {
+        let temp = mktemp::Temp::new_dir().unwrap();
+        let root = temp.as_path().to_path_buf();
+        let nested = root.join("vendor/lib");
+        fs::create_dir_all(root.join(".hg")).unwrap();
+        fs::create_dir_all(nested.join(".pijul")).unwrap();
+
+        let (provider, found) = detect(&root.join("src")).unwrap();
+        assert_eq!((provider.name(), found), ("hg", root));
+        let (provider, found) = detect(&nested.join("src")).unwrap();
+        assert_eq!((provider.name(), found), ("pijul", nested));
+    }
+
+    #[test]
+    fn disabled_probe() {
+        let mut shell = Shell::default();
+        shell.variables_mut().set("VCS", "user value");
+        let mut repository = Repository::default();
+        repository.update(&mut shell);
+        assert!(repository.checked.is_none());
+        assert_eq!(shell.variables().get_str("VCS").unwrap().as_str(), "user value");
+    }
+}
@@ -1,10 +1,13 @@
 use crate::{
     expansion::{self, Expander},
-    parser::lexers::assignments::{Primitive, TypeError},
+    parser::lexers::{
+        assignments::{Primitive, TypeError},
+        ArgumentSplitter,
+    },
     shell::variables::Value,
     types,
 };
-use std::rc::Rc;
+use std::{fs, rc::Rc};
 
 /// Determines if the supplied value is either an array or a string.
 ///
@@ -42,51 +45,218 @@ pub fn is_boolean(value: &mut types::Str) -> bool {
     }
 }
 
+/// The number of seconds of a duration made of numbers followed by a unit, such as `5m30s`. A
+/// number without a unit is a number of seconds.
+fn duration_seconds(value: &str) -> Option<f64> {
+    let mut seconds = 0.;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        let number = rest[..end].parse::<f64>().ok()?;
+        rest = &rest[end..];
+        let unit_end = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let unit = match &rest[..unit_end] {
+            "ms" => 0.001,
+            "" | "s" => 1.,
+            "m" => 60.,
+            "h" => 60. * 60.,
+            "d" => 24. * 60. * 60.,
+            "w" => 7. * 24. * 60. * 60.,
+            _ => return None,
+        };
+        rest = &rest[unit_end..];
+        seconds += number * unit;
+    }
+    Some(seconds).filter(|seconds| seconds.is_finite() && !value.is_empty())
+}
+
+/// The number of bytes of a size, made of a number and a decimal unit such as `KB`, or a binary
+/// one such as `KiB` or `K`
+fn size_bytes(value: &str) -> Option<u64> {
+    let end = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let number = value[..end].parse::<f64>().ok()?;
+    let unit = value[end..].trim_start().to_ascii_lowercase();
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("ib").filter(|p| !p.is_empty()) {
+        (prefix, 1024.)
+    } else if let Some(prefix) = unit.strip_suffix('b').filter(|p| !p.is_empty()) {
+        (prefix, 1000.)
+    } else {
+        (unit.strip_suffix('b').unwrap_or(&unit), 1024.)
+    };
+    let power = match prefix {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        _ => return None,
+    };
+    let bytes = (number * f64::powi(base, power)).round();
+    // 2^64, as u64::MAX is not exactly a float
+    Some(bytes as u64).filter(|_| bytes < 18_446_744_073_709_551_616.)
+}
+
+/// The number as a string, without a fractional part if it is whole
+fn number_string(number: f64) -> types::Str {
+    if number.fract() == 0. && number.abs() < 1e15 {
+        format!("{}", number as i64).into()
+    } else {
+        number.to_string().into()
+    }
+}
+
+/// The value of a plain type, normalized, or `None` if it is not a value of the type
+fn normalize<E: Expander>(
+    shell: &E,
+    mut value: types::Str,
+    kind: &Primitive,
+) -> Option<types::Str> {
+    match kind {
+        Primitive::Str => Some(value),
+        Primitive::Boolean if is_boolean(&mut value) => Some(value),
+        Primitive::Integer if value.parse::<i64>().is_ok() => Some(value),
+        Primitive::Float if value.parse::<f64>().is_ok() => Some(value),
+        Primitive::Path { canonical } => {
+            let path = if value.starts_with('~') { shell.tilde(&value).ok()? } else { value };
+            if *canonical {
+                Some(fs::canonicalize(path.as_str()).ok()?.to_str()?.into())
+            } else {
+                Some(path).filter(|path| !path.is_empty())
+            }
+        }
+        Primitive::Duration => duration_seconds(&value).map(number_string),
+        Primitive::Size => size_bytes(&value).map(|bytes| bytes.to_string().into()),
+        Primitive::Enum(choices) if choices.iter().any(|choice| choice == value.as_str()) => {
+            Some(value)
+        }
+        Primitive::Optional(_) if value.is_empty() => Some(value),
+        Primitive::Optional(inner) => normalize(shell, value, inner),
+        _ => None,
+    }
+}
+
+/// The unexpanded elements of an array literal
+fn literal_elements(literal: &str) -> ArgumentSplitter<'_> {
+    ArgumentSplitter::new(&literal[1..literal.len() - 1])
+}
+
+/// Whether an element of the array literal is itself an array literal
+fn is_nested(literal: &str) -> bool { literal_elements(literal).any(is_array) }
+
+/// Whether values of the type are collections
+const fn is_collection(kind: &Primitive) -> bool {
+    matches!(kind, Primitive::Array(_) | Primitive::HashMap(_) | Primitive::BTreeMap(_))
+}
+
+/// Check each element of an array literal against the type of the elements, keeping nested
+/// literals and array variables as nested arrays.
+fn get_nested_array<E: Expander>(
+    shell: &mut E,
+    literal: &str,
+    inner_kind: &Primitive,
+) -> expansion::Result<Value<Rc<types::Function>>, E::Error> {
+    let mut array = Vec::new();
+    for element in literal_elements(literal) {
+        match inner_kind {
+            _ if is_array(element) => array.push(value_check(shell, element, inner_kind)?),
+            Primitive::Array(ref kind) if element.starts_with('@') => {
+                let extracted = shell.get_array(element)?;
+                array.push(
+                    extracted
+                        .iter()
+                        .map(|item| value_check(shell, item, kind))
+                        .collect::<Result<_, _>>()?,
+                );
+            }
+            Primitive::HashMap(_) | Primitive::BTreeMap(_) if element.starts_with('@') => {
+                array.push(value_check(shell, element, inner_kind)?)
+            }
+            _ if is_collection(inner_kind) => {
+                return Err(TypeError::BadValue(inner_kind.clone()).into())
+            }
+            _ => {
+                for item in shell.get_array(element)?.iter() {
+                    array.push(value_check(shell, item, inner_kind)?);
+                }
+            }
+        }
+    }
+    Ok(Value::Array(array))
+}
+
 fn get_map_of<E: Expander>(
     primitive_type: &Primitive,
     shell: &mut E,
     expression: &str,
 ) -> expansion::Result<Value<Rc<types::Function>>, E::Error> {
-    let array = shell.expand_string(expression)?;
-
     let inner_kind = match primitive_type {
         Primitive::HashMap(ref inner) | Primitive::BTreeMap(ref inner) => inner,
         _ => unreachable!(),
     };
 
-    let size = array.len();
-
-    let iter = array.into_iter().map(|string| {
-        let mut parts = string.splitn(2, '=');
+    let mut entries = Vec::new();
+    for element in literal_elements(expression) {
+        // Nested literals are checked before being expanded, so that they are not flattened
+        let mut parts = element.splitn(2, '=');
         if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-            value_check(shell, value, inner_kind).and_then(|val| match val {
-                Value::Str(_) | Value::Array(_) | Value::HashMap(_) | Value::BTreeMap(_) => {
-                    Ok(((*key).into(), val))
-                }
-                _ => Err(TypeError::BadValue((**inner_kind).clone()).into()),
-            })
-        } else {
-            Err(TypeError::BadValue(*inner_kind.clone()).into())
-        }
-    });
-
-    match primitive_type {
-        Primitive::HashMap(_) => {
-            let mut hmap = types::HashMap::with_capacity(size);
-            for item in iter {
-                let (key, value) = item?;
-                hmap.insert(key, value);
+            if is_array(value) {
+                entries.push((shell.get_string(key)?, value_check(shell, value, inner_kind)?));
+                continue;
             }
-            Ok(Value::HashMap(hmap))
         }
-        Primitive::BTreeMap(_) => {
-            let mut bmap = types::BTreeMap::new();
-            for item in iter {
-                let (key, value) = item?;
-                bmap.insert(key, value);
+
+        for string in shell.get_array(element)?.iter() {
+            let mut parts = string.splitn(2, '=');
+            if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                match value_check(shell, value, inner_kind)? {
+                    val @ Value::Str(_)
+                    | val @ Value::Array(_)
+                    | val @ Value::HashMap(_)
+                    | val @ Value::BTreeMap(_) => entries.push((key.into(), val)),
+                    _ => return Err(TypeError::BadValue((**inner_kind).clone()).into()),
+                }
+            } else {
+                return Err(TypeError::BadValue((**inner_kind).clone()).into());
             }
-            Ok(Value::BTreeMap(bmap))
         }
+    }
+
+    Ok(collect_map(primitive_type, entries))
+}
+
+/// Get the entries of a map variable, coercing its values to the type of the map. The values
+/// are not expanded again, so only maps of plain values can be checked.
+fn get_map_of_variable<E: Expander>(
+    primitive_type: &Primitive,
+    shell: &mut E,
+    name: &str,
+) -> expansion::Result<Value<Rc<types::Function>>, E::Error> {
+    let inner_kind = match primitive_type {
+        Primitive::HashMap(ref inner) | Primitive::BTreeMap(ref inner) => inner,
+        _ => unreachable!(),
+    };
+
+    let entries = shell
+        .map_keys(name)?
+        .into_iter()
+        .zip(shell.map_values(name)?)
+        .map(|(key, value)| match normalize(shell, value, inner_kind) {
+            Some(value) => Ok((key, Value::Str(value))),
+            None => Err(TypeError::BadValue((**inner_kind).clone()).into()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(collect_map(primitive_type, entries))
+}
+
+fn collect_map(
+    primitive_type: &Primitive,
+    entries: Vec<(types::Str, Value<Rc<types::Function>>)>,
+) -> Value<Rc<types::Function>> {
+    match primitive_type {
+        Primitive::HashMap(_) => Value::HashMap(entries.into_iter().collect()),
+        Primitive::BTreeMap(_) => Value::BTreeMap(entries.into_iter().collect()),
         _ => unreachable!(),
     }
 }
@@ -96,14 +266,43 @@ pub fn value_check<E: Expander>(
     value: &str,
     expected: &Primitive,
 ) -> expansion::Result<Value<Rc<types::Function>>, E::Error> {
+    // An optional value which is missing or empty is nothing, rather than a bad value
+    if let Primitive::Optional(ref inner) = expected {
+        if value.is_empty() {
+            return Ok(Value::None);
+        } else if is_array(value) || is_collection(inner) {
+            return value_check(shell, value, inner);
+        }
+        let extracted = shell.get_string(value)?;
+        return match normalize(shell, extracted, expected) {
+            Some(value) if value.is_empty() => Ok(Value::None),
+            Some(value) => Ok(Value::Str(value)),
+            None => Err(TypeError::BadValue(expected.clone()).into()),
+        };
+    }
+
+    // Map variables are passed whole to map arguments
+    if let Primitive::HashMap(_) | Primitive::BTreeMap(_) = expected {
+        if let Some(name) = value.strip_prefix('@') {
+            if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return get_map_of_variable(expected, shell, name);
+            }
+        }
+    }
+
     if is_array(value) {
-        let extracted = shell.get_array(value)?;
         match expected {
-            Primitive::Str => extracted
+            Primitive::Str if is_nested(value) => get_nested_array(shell, value, expected),
+            Primitive::Array(ref inner) if is_nested(value) || is_collection(inner) => {
+                get_nested_array(shell, value, inner)
+            }
+            Primitive::Str => shell
+                .get_array(value)?
                 .iter()
                 .map(|item| value_check(shell, item, &Primitive::Str))
                 .collect::<Result<_, _>>(),
-            Primitive::Array(ref inner) => extracted
+            Primitive::Array(ref inner) => shell
+                .get_array(value)?
                 .iter()
                 .map(|item| value_check(shell, item, inner))
                 .collect::<Result<_, _>>(),
@@ -112,20 +311,13 @@ pub fn value_check<E: Expander>(
             _ => Err(TypeError::BadValue(expected.clone()).into()),
         }
     } else {
-        let mut extracted = shell.get_string(value)?;
-        match expected {
-            Primitive::Str => Ok(Value::Str(extracted)),
-            Primitive::Boolean => {
-                if is_boolean(&mut extracted) {
-                    Ok(Value::Str(extracted))
-                } else {
-                    Err(TypeError::BadValue(expected.clone()).into())
-                }
-            }
-            Primitive::Integer if extracted.parse::<i64>().is_ok() => Ok(Value::Str(extracted)),
-            Primitive::Float if extracted.parse::<f64>().is_ok() => Ok(Value::Str(extracted)),
-            Primitive::Indexed(_, ref kind) => value_check(shell, value, kind),
-            _ => Err(TypeError::BadValue(expected.clone()).into()),
+        if let Primitive::Indexed(_, ref kind) = expected {
+            return value_check(shell, value, kind);
+        }
+        let extracted = shell.get_string(value)?;
+        match normalize(shell, extracted, expected) {
+            Some(value) => Ok(Value::Str(value)),
+            None => Err(TypeError::BadValue(expected.clone()).into()),
         }
     }
 }
@@ -170,6 +362,45 @@ mod test {
         assert_eq!(test, "other");
     }
 
+    #[test]
+    fn units_() {
+        assert_eq!(duration_seconds("5m30s"), Some(330.));
+        assert_eq!(duration_seconds("1.5h"), Some(5400.));
+        assert_eq!(duration_seconds("90"), Some(90.));
+        assert_eq!(duration_seconds("250ms"), Some(0.25));
+        assert_eq!(duration_seconds("5 minutes"), None);
+        assert_eq!(duration_seconds(""), None);
+        assert_eq!(number_string(330.), "330");
+        assert_eq!(number_string(0.25), "0.25");
+
+        assert_eq!(size_bytes("10GiB"), Some(10 * 1024 * 1024 * 1024));
+        assert_eq!(size_bytes("10GB"), Some(10_000_000_000));
+        assert_eq!(size_bytes("1.5k"), Some(1536));
+        assert_eq!(size_bytes("512"), Some(512));
+        assert_eq!(size_bytes("512B"), Some(512));
+        assert_eq!(size_bytes("3 MiB"), Some(3 * 1024 * 1024));
+        assert_eq!(size_bytes("10XB"), None);
+        assert_eq!(size_bytes("GiB"), None);
+    }
+
+    #[test]
+    fn is_enum_() {
+        let env = Primitive::Enum(vec!["dev".into(), "prod".into()]);
+        assert_eq!(
+            value_check(&mut DummyExpander, "prod", &env).unwrap(),
+            Value::Str("prod".into())
+        );
+        assert!(value_check(&mut DummyExpander, "test", &env).is_err());
+    }
+
+    #[test]
+    fn is_optional_() {
+        let kind = Primitive::Optional(Box::new(Primitive::Integer));
+        assert_eq!(value_check(&mut DummyExpander, "", &kind).unwrap(), Value::None);
+        assert_eq!(value_check(&mut DummyExpander, "42", &kind).unwrap(), Value::Str("42".into()));
+        assert!(value_check(&mut DummyExpander, "forty", &kind).is_err());
+    }
+
     #[test]
     fn is_integer_array_() {
         assert_eq!(
@@ -192,4 +423,56 @@ mod test {
         )
         .is_err());
     }
+
+    #[test]
+    fn is_map_() {
+        let bmap = |entries: &[(&str, &str)]| {
+            Value::BTreeMap(
+                entries
+                    .iter()
+                    .map(|&(key, value)| (key.into(), Value::Str(value.into())))
+                    .collect(),
+            )
+        };
+        let booleans = Primitive::BTreeMap(Box::new(Primitive::Boolean));
+        assert_eq!(
+            value_check(&mut DummyExpander, "[a=1 b=n]", &booleans).unwrap(),
+            bmap(&[("a", "true"), ("b", "false")])
+        );
+        assert!(value_check(&mut DummyExpander, "[a=1 b]", &booleans).is_err());
+
+        let integers = Primitive::BTreeMap(Box::new(Primitive::Integer));
+        assert_eq!(
+            value_check(&mut DummyExpander, "@MAP", &integers).unwrap(),
+            bmap(&[("one", "1"), ("two", "2")])
+        );
+        assert!(value_check(&mut DummyExpander, "@MAP", &booleans).is_err());
+        assert!(value_check(&mut DummyExpander, "@ARRAY", &integers).is_err());
+    }
+
+    #[test]
+    fn is_nested_array_() {
+        let array = |items: &[&str]| {
+            Value::Array(items.iter().map(|&item| Value::Str(item.into())).collect())
+        };
+        let table = Primitive::Array(Box::new(Primitive::Array(Box::new(Primitive::Integer))));
+        assert_eq!(
+            value_check(&mut DummyExpander, "[[1 2] [3 4]]", &table).unwrap(),
+            Value::Array(vec![array(&["1", "2"]), array(&["3", "4"])])
+        );
+        assert!(value_check(&mut DummyExpander, "[[1 2] [3 four]]", &table).is_err());
+        assert!(value_check(&mut DummyExpander, "[[1 2] 3]", &table).is_err());
+
+        assert_eq!(
+            value_check(&mut DummyExpander, "[[a b] c]", &Primitive::Str).unwrap(),
+            Value::Array(vec![array(&["a", "b"]), Value::Str("c".into())])
+        );
+
+        let map = Primitive::BTreeMap(Box::new(Primitive::Array(Box::new(Primitive::Integer))));
+        assert_eq!(
+            value_check(&mut DummyExpander, "[k=[1 2]]", &map).unwrap(),
+            Value::BTreeMap(std::iter::once(("k".into(), array(&["1", "2"]))).collect())
+        );
+        assert!(value_check(&mut DummyExpander, "[k=[1 b]]", &map).is_err());
+    }
 }
@@ -0,0 +1,376 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{Shell, Value},
+    types,
+};
+use builtins_proc::builtin;
+use std::fmt::Write;
+
+/// The kind of value an option takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Flag,
+    Str,
+    Int,
+    Float,
+}
+
+impl Kind {
+    /// The placeholder of the value in the help
+    fn placeholder(self) -> &'static str {
+        match self {
+            Kind::Flag => "",
+            Kind::Str => " STR",
+            Kind::Int => " INT",
+            Kind::Float => " FLOAT",
+        }
+    }
+}
+
+/// An option declared by a spec, as in `o/output=str:out.txt  the file to write`
+#[derive(Debug, PartialEq)]
+struct Spec<'a> {
+    short:   Option<char>,
+    long:    &'a str,
+    kind:    Kind,
+    default: Option<&'a str>,
+    help:    &'a str,
+}
+
+impl<'a> Spec<'a> {
+    fn parse(spec: &'a str) -> Result<Self, String> {
+        let (definition, help) = match spec.split_once(char::is_whitespace) {
+            Some((definition, help)) => (definition, help.trim()),
+            None => (spec, ""),
+        };
+        let (definition, default) = match definition.split_once(':') {
+            Some((definition, default)) => (definition, Some(default)),
+            None => (definition, None),
+        };
+        let (names, kind) = match definition.split_once('=') {
+            Some((names, "")) | Some((names, "str")) => (names, Kind::Str),
+            Some((names, "int")) => (names, Kind::Int),
+            Some((names, "float")) => (names, Kind::Float),
+            Some((_, kind)) => return Err(format!("unknown type '{}' in '{}'", kind, spec)),
+            None if default.is_some() => (definition, Kind::Str),
+            None => (definition, Kind::Flag),
+        };
+        let (short, long) = match names.split_once('/') {
+            Some((short, long)) => {
+                let mut chars = short.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(short), None) if short.is_alphanumeric() => (Some(short), long),
+                    _ => return Err(format!("invalid short option '{}' in '{}'", short, spec)),
+                }
+            }
+            None => (None, names),
+        };
+        let valid = |c: char| c.is_alphanumeric() || c == '-' || c == '_';
+        if long.is_empty() || long.starts_with('-') || !long.chars().all(valid) {
+            return Err(format!("invalid option name '{}' in '{}'", long, spec));
+        }
+
+        let spec = Spec { short, long, kind, default, help };
+        if let Some(default) = default {
+            spec.check(default)?;
+        }
+        Ok(spec)
+    }
+
+    /// The variable given the value of the option, its long name with dashes turned into
+    /// underscores
+    fn variable(&self) -> String { self.long.replace('-', "_") }
+
+    /// Whether the value has the kind of the option
+    fn check(&self, value: &str) -> Result<(), String> {
+        let (valid, expected) = match self.kind {
+            Kind::Int => (value.parse::<i64>().is_ok(), "an integer"),
+            Kind::Float => (value.parse::<f64>().is_ok(), "a number"),
+            Kind::Flag | Kind::Str => (true, ""),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("option '--{}' expects {}, not '{}'", self.long, expected, value))
+        }
+    }
+}
+
+/// The arguments, sorted by `parse_arguments`
+#[derive(Debug, Default, PartialEq)]
+struct Parsed {
+    /// The value of each option, in the order of the specs
+    values:      Vec<types::Str>,
+    positionals: Vec<types::Str>,
+    help:        bool,
+}
+
+fn parse_arguments(specs: &[Spec<'_>], args: &[types::Str]) -> Result<Parsed, String> {
+    let mut given: Vec<Option<types::Str>> = specs.iter().map(|_| None).collect();
+    let mut positionals = Vec::new();
+    let own_help = specs.iter().any(|spec| spec.long == "help");
+    let own_h = specs.iter().any(|spec| spec.short == Some('h'));
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg.as_str() == "--" {
+            positionals.extend(args.by_ref().cloned());
+            break;
+        } else if let Some(option) = arg.strip_prefix("--") {
+            let (name, value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (option, None),
+            };
+            if name == "help" && !own_help {
+                return Ok(Parsed { help: true, ..Parsed::default() });
+            }
+            let index = specs
+                .iter()
+                .position(|spec| spec.long == name)
+                .ok_or(format!("unknown option '--{}'", name))?;
+            let spec = &specs[index];
+            given[index] = Some(match (spec.kind, value) {
+                (Kind::Flag, None) => "true".into(),
+                (Kind::Flag, Some(_)) => return Err(format!("option '--{}' takes no value", name)),
+                (_, Some(value)) => value.into(),
+                (_, None) => {
+                    args.next().cloned().ok_or(format!("option '--{}' requires a value", name))?
+                }
+            });
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            for (position, short) in arg[1..].char_indices() {
+                if short == 'h' && !own_h {
+                    return Ok(Parsed { help: true, ..Parsed::default() });
+                }
+                let index = specs
+                    .iter()
+                    .position(|spec| spec.short == Some(short))
+                    .ok_or(format!("unknown option '-{}'", short))?;
+                if specs[index].kind == Kind::Flag {
+                    given[index] = Some("true".into());
+                    continue;
+                }
+                // The value is the rest of the argument, as in -ofile, or the next one
+                let rest = &arg[1 + position + short.len_utf8()..];
+                given[index] = Some(if rest.is_empty() {
+                    args.next().cloned().ok_or(format!("option '-{}' requires a value", short))?
+                } else {
+                    rest.into()
+                });
+                break;
+            }
+        } else {
+            positionals.push(arg.clone());
+        }
+    }
+
+    let mut values = Vec::with_capacity(specs.len());
+    for (spec, value) in specs.iter().zip(given) {
+        if let Some(value) = &value {
+            spec.check(value)?;
+        }
+        values.push(value.unwrap_or_else(|| match (spec.kind, spec.default) {
+            (_, Some(default)) => default.into(),
+            (Kind::Flag, None) => "false".into(),
+            (_, None) => types::Str::new(),
+        }));
+    }
+    Ok(Parsed { values, positionals, help: false })
+}
+
+/// The help of the script, generated from the specs
+fn help(name: &str, description: Option<&str>, specs: &[Spec<'_>]) -> String {
+    let mut rows: Vec<(String, String)> = specs
+        .iter()
+        .map(|spec| {
+            let short = spec.short.map_or_else(|| "    ".into(), |short| format!("-{}, ", short));
+            let option = format!("{}--{}{}", short, spec.long, spec.kind.placeholder());
+            let help = match spec.default {
+                Some(default) if spec.help.is_empty() => format!("(default: {})", default),
+                Some(default) => format!("{} (default: {})", spec.help, default),
+                None => spec.help.into(),
+            };
+            (option, help)
+        })
+        .collect();
+    if !specs.iter().any(|spec| spec.long == "help") {
+        let short = if specs.iter().any(|spec| spec.short == Some('h')) { "    " } else { "-h, " };
+        rows.push((format!("{}--help", short), "print this help".into()));
+    }
+
+    let mut help = format!("Usage: {} [OPTIONS] [ARGUMENTS...]\n", name);
+    if let Some(description) = description {
+        let _ = write!(help, "\n{}\n", description);
+    }
+    help.push_str("\nOptions:\n");
+    let width = rows.iter().map(|(option, _)| option.len()).max().unwrap_or(0);
+    for (option, text) in rows {
+        let _ = writeln!(help, "  {:width$}  {}", option, text, width = width);
+    }
+    help
+}
+
+#[builtin(
+    desc = "parse the options given to a script or a function",
+    man = "
+SYNOPSIS
+    argparse [-n NAME] [-d DESCRIPTION] SPEC... -- ARGUMENTS...
+
+DESCRIPTION
+    Parses the arguments after --, usually @args[1..], with the options declared by the specs,
+    and stores the value of each option in the variable named after it, with its dashes turned
+    into underscores. The arguments which are not options are stored in the array rest.
+
+    Options are written as --output VALUE, --output=VALUE, -o VALUE or -oVALUE, and flags may
+    be grouped, as in -vq. A given flag is true, and a missing one false. A missing option is
+    given its default value, or is empty. The arguments after a lone -- are never options.
+
+    Unless a spec declares them, -h and --help print the help of the script, generated from the
+    specs, to the standard output.
+
+    Returns 0 when the arguments were parsed, 1 after printing the help, and 2 when they are
+    invalid, so that argparse ... || exit stops the script in both cases.
+
+SPECS
+    A spec is written as [SHORT/]LONG[=TYPE][:DEFAULT], followed by the help text of the option
+    after a space. Without a type or a default, the option is a flag. The types are:
+
+    str
+        any text, the type of the options without a type but with a default
+    int
+        an integer
+    float
+        a number
+
+OPTIONS
+    -n, --name NAME
+        the name of the script in the help, @args[0] by default
+    -d, --description DESCRIPTION
+        a description of the script, printed in the help
+
+EXAMPLES
+    argparse -n backup -d 'Archives the given directories' \\
+        'v/verbose  print each archived file' \\
+        'o/output=str:backup.tar  the archive to write' \\
+        'l/level=int:6  the level of compression' -- @args[1..] || exit
+    echo $verbose $output $level @rest"
+)]
+pub fn argparse(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut name = None;
+    let mut description = None;
+    let mut args = &args[1..];
+    while let Some((arg, rest)) = args.split_first() {
+        let target = match arg.as_str() {
+            "-n" | "--name" => &mut name,
+            "-d" | "--description" => &mut description,
+            _ => break,
+        };
+        match rest.first() {
+            Some(value) => *target = Some(value.as_str()),
+            None => {
+                return Status::bad_argument(format!(
+                    "ion: argparse: option '{}' requires a value",
+                    arg
+                ))
+            }
+        }
+        args = &rest[1..];
+    }
+    let separator = match args.iter().position(|arg| arg.as_str() == "--") {
+        Some(separator) => separator,
+        None => return Status::bad_argument("ion: argparse: no '--' before the arguments"),
+    };
+
+    let mut specs = Vec::with_capacity(separator);
+    for spec in &args[..separator] {
+        match Spec::parse(spec) {
+            Ok(spec) => specs.push(spec),
+            Err(why) => return Status::bad_argument(format!("ion: argparse: {}", why)),
+        }
+    }
+    let script = match (name, shell.variables().get("args")) {
+        (Some(name), _) => name.into(),
+        (None, Some(Value::Array(array))) if !array.is_empty() => array[0].to_string(),
+        _ => "script".into(),
+    };
+
+    match parse_arguments(&specs, &args[separator + 1..]) {
+        Ok(parsed) if parsed.help => {
+            print!("{}", help(&script, description, &specs));
+            Status::FALSE
+        }
+        Ok(parsed) => {
+            for (spec, value) in specs.iter().zip(parsed.values) {
+                shell.variables_mut().set(&spec.variable(), value);
+            }
+            let rest: types::Array<_> = parsed.positionals.into_iter().map(Value::Str).collect();
+            shell.variables_mut().set("rest", Value::Array(rest));
+            Status::SUCCESS
+        }
+        Err(why) => Status::bad_argument(format!(
+            "{}: {}\nTry '{} --help' for more information.",
+            script, why, script
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<types::Str> { args.iter().map(|&arg| arg.into()).collect() }
+
+    #[test]
+    fn argparse_specs() {
+        assert_eq!(
+            Spec::parse("o/output=str:out.txt  the file to write").unwrap(),
+            Spec {
+                short:   Some('o'),
+                long:    "output",
+                kind:    Kind::Str,
+                default: Some("out.txt"),
+                help:    "the file to write",
+            }
+        );
+        assert_eq!(Spec::parse("dry-run").unwrap().kind, Kind::Flag);
+        assert_eq!(Spec::parse("dry-run").unwrap().variable(), "dry_run");
+        assert_eq!(Spec::parse("level:6").unwrap().kind, Kind::Str);
+        assert!(Spec::parse("level=int:six").is_err());
+        assert!(Spec::parse("level=list").is_err());
+        assert!(Spec::parse("lv/level").is_err());
+    }
+
+    #[test]
+    fn argparse_arguments() {
+        let specs: Vec<_> = ["v/verbose", "q/quiet", "o/output=str:out.txt", "l/level=int"]
+            .iter()
+            .map(|spec| Spec::parse(spec).unwrap())
+            .collect();
+        let parsed = parse_arguments(&specs, &args(&["-vl3", "a", "--", "-q"])).unwrap();
+        assert_eq!(parsed.values, args(&["true", "false", "out.txt", "3"]));
+        assert_eq!(parsed.positionals, args(&["a", "-q"]));
+
+        let parsed = parse_arguments(&specs, &args(&["--output=x", "-", "--level", "-2"])).unwrap();
+        assert_eq!(parsed.values, args(&["false", "false", "x", "-2"]));
+        assert_eq!(parsed.positionals, args(&["-"]));
+
+        assert!(parse_arguments(&specs, &args(&["-a", "--help"])).is_err());
+        assert!(parse_arguments(&specs, &args(&["--help", "-a"])).unwrap().help);
+        assert!(parse_arguments(&specs, &args(&["--level", "high"])).is_err());
+        assert!(parse_arguments(&specs, &args(&["--verbose=yes"])).is_err());
+        assert!(parse_arguments(&specs, &args(&["-o"])).is_err());
+    }
+
+    #[test]
+    fn argparse_help() {
+        let specs: Vec<_> = ["v/verbose  print more", "level=int:6"]
+            .iter()
+            .map(|spec| Spec::parse(spec).unwrap())
+            .collect();
+        assert_eq!(
+            help("backup", None, &specs),
+            "Usage: backup [OPTIONS] [ARGUMENTS...]\n\nOptions:\n  -v, --verbose    print more\n      \
+             --level INT  (default: 6)\n  -h, --help       print this help\n"
+        );
+    }
+}
@@ -1,15 +1,51 @@
 use super::Status;
 use crate as ion_shell;
 use crate::{
-    shell::{Shell, Value},
+    shell::{executables, Shell, Value},
     types,
 };
 use builtins_proc::builtin;
 
-use std::{borrow::Cow, env};
+use std::path::PathBuf;
+
+/// What a command name may resolve to, in the order the shell looks them up
+#[derive(Debug, PartialEq)]
+enum Candidate {
+    Alias(types::Str),
+    Function,
+    Builtin,
+    File(PathBuf),
+}
+
+impl Candidate {
+    /// The kind of the candidate, as printed by `type -t`
+    fn kind(&self) -> &'static str {
+        match self {
+            Candidate::Alias(_) => "alias",
+            Candidate::Function => "function",
+            Candidate::Builtin => "builtin",
+            Candidate::File(_) => "file",
+        }
+    }
+}
+
+/// Everything the name resolves to, the first of which is what the shell runs. Aliases and
+/// functions share their names with variables, so at most one of them is found.
+fn candidates(command: &str, shell: &Shell<'_>) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    match shell.variables().get(command) {
+        Some(Value::Alias(alias)) => candidates.push(Candidate::Alias((**alias).clone())),
+        Some(Value::Function(_)) => candidates.push(Candidate::Function),
+        _ => (),
+    }
+    if shell.builtins().contains(command) {
+        candidates.push(Candidate::Builtin);
+    }
+    candidates.extend(executables(command).into_iter().map(Candidate::File));
+    candidates
+}
 
 #[builtin(
-    names = "which, type",
     desc = "locate a program file in the current user's path",
     man = "
 SYNOPSIS
@@ -26,37 +62,102 @@ pub fn which(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
 
     let mut result = Status::SUCCESS;
     for command in &args[1..] {
-        match get_command_info(command, shell) {
-            Ok(c_type) => match c_type.as_ref() {
-                "alias" => {
-                    if let Some(Value::Alias(ref alias)) = shell.variables().get(&**command) {
-                        println!("{}: alias to {}", command, &**alias);
-                    }
-                }
-                "function" => println!("{}: function", command),
-                "builtin" => println!("{}: built-in shell command", command),
-                path => println!("{}", path),
-            },
-            Err(_) => result = Status::from_exit_code(1),
+        match candidates(command, shell).first() {
+            Some(Candidate::Alias(alias)) => println!("{}: alias to {}", command, alias),
+            Some(Candidate::Function) => println!("{}: function", command),
+            Some(Candidate::Builtin) => println!("{}: built-in shell command", command),
+            Some(Candidate::File(path)) => println!("{}", path.display()),
+            None => result = Status::from_exit_code(1),
         }
     }
     result
 }
 
-fn get_command_info<'a>(command: &str, shell: &mut Shell<'_>) -> Result<Cow<'a, str>, ()> {
-    match shell.variables().get(command) {
-        Some(Value::Alias(_)) => Ok("alias".into()),
-        Some(Value::Function(_)) => Ok("function".into()),
-        _ if shell.builtins().contains(command) => Ok("builtin".into()),
-        _ => {
-            let paths = env::var_os("PATH").unwrap_or_else(|| "/bin".into());
-            for path in env::split_paths(&paths) {
-                let executable = path.join(command);
-                if executable.is_file() {
-                    return Ok(executable.display().to_string().into());
-                }
+#[builtin(
+    names = "type",
+    desc = "describe what command names resolve to",
+    man = "
+SYNOPSIS
+    type [-a] [-t] NAME...
+
+DESCRIPTION
+    Prints whether each name, used as a command, runs an alias, a function, a builtin or an
+    executable file, and which file it is, which helps to find out why a name does not run the
+    command it was expected to. Names are looked up in the order the shell does: aliases and
+    functions first, then builtins, then the directories of PATH.
+
+    Returns 1 if one of the names resolves to nothing, which is reported on standard error.
+
+OPTIONS
+    -a, --all
+        prints everything the name resolves to, the first of which is what runs, so that a
+        builtin or function shadowing a program shows up with the program
+    -t, --type
+        prints only the kind of each match: alias, function, builtin or file
+
+EXAMPLES
+    type -a echo
+    test $(type -t ll) = alias && echo \"ll is an alias\""
+)]
+pub fn type_(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut all = false;
+    let mut kind_only = false;
+    let mut names = &args[1..];
+    while let Some((arg, rest)) = names.split_first() {
+        match arg.as_str() {
+            "-a" | "--all" => all = true,
+            "-t" | "--type" => kind_only = true,
+            "-at" | "-ta" => {
+                all = true;
+                kind_only = true;
+            }
+            "--" => {
+                names = rest;
+                break;
+            }
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Status::bad_argument(format!("ion: type: unknown option '{}'", arg))
             }
-            Err(())
+            _ => break,
         }
+        names = rest;
+    }
+    if names.is_empty() {
+        return Status::bad_argument("ion: type: no name given");
+    }
+
+    let mut result = Status::SUCCESS;
+    for name in names {
+        let candidates = candidates(name, shell);
+        if candidates.is_empty() {
+            result = Status::error(format!("ion: type: {}: not found", name));
+        }
+        for candidate in candidates.iter().take(if all { candidates.len() } else { 1 }) {
+            match candidate {
+                _ if kind_only => println!("{}", candidate.kind()),
+                Candidate::Alias(alias) => println!("{} is aliased to '{}'", name, alias),
+                Candidate::Function => println!("{} is a function", name),
+                Candidate::Builtin => println!("{} is a shell builtin", name),
+                Candidate::File(path) => println!("{} is {}", name, path.display()),
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_candidates() {
+        let shell = Shell::new();
+        assert_eq!(candidates("echo", &shell).first(), Some(&Candidate::Builtin));
+        assert_eq!(candidates("ion-no-such-command", &shell), Vec::new());
+        assert_eq!(
+            candidates("testing/executable_file", &shell),
+            vec![Candidate::File("testing/executable_file".into())]
+        );
+        assert_eq!(candidates("testing/empty_file", &shell), Vec::new());
     }
 }
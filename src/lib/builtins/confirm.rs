@@ -0,0 +1,149 @@
+use super::{
+    read::{read_record, Record},
+    Status,
+};
+use crate as ion_shell;
+use crate::{shell::Shell, types};
+use builtins_proc::builtin;
+use itertools::Itertools;
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// How the question is asked by `confirm`
+#[derive(Debug, Default)]
+struct Options {
+    default: Option<bool>,
+    timeout: Option<Duration>,
+}
+
+/// Whether the answer agrees, if it is one
+fn answer(text: &str) -> Option<bool> {
+    match text.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(true),
+        "n" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_options(args: &[types::Str]) -> Result<(Options, String), String> {
+    let mut options = Options::default();
+    let mut question = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("option '{}' requires a value", arg));
+        match arg.as_str() {
+            "-d" | "--default" => {
+                let default = value()?;
+                options.default =
+                    Some(answer(default).ok_or(format!("invalid default answer '{}'", default))?);
+            }
+            "-t" | "--timeout" => {
+                let secs = value()?;
+                options.timeout = secs
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.)
+                    .map(Duration::from_secs_f64);
+                if options.timeout.is_none() {
+                    return Err(format!("invalid timeout '{}'", secs));
+                }
+            }
+            _ => question.push(arg),
+        }
+    }
+    Ok((options, question.into_iter().join(" ")))
+}
+
+#[builtin(
+    desc = "ask a question answered with yes or no",
+    man = "
+SYNOPSIS
+    confirm [--default yes|no] [--timeout TIMEOUT] QUESTION...
+
+DESCRIPTION
+    Asks the question on standard error, followed by the answers it takes, then reads the answer
+    from standard input until it is yes or no, or one of their initials, in any case. Returns 0
+    when the answer is yes, and 1 when it is no.
+
+    When the standard input is not a terminal, the question is not answered but takes the
+    default answer, or no if there is none, which is printed after it.
+
+OPTIONS
+    -d, --default ANSWER
+        the answer taken when an empty line is read, when the input ends, or once the timeout
+        expires
+    -t, --timeout TIMEOUT
+        take the default answer if the question is not answered within TIMEOUT seconds, or
+        return 124 if there is none
+
+EXAMPLES
+    confirm 'Delete everything?' --default no --timeout 30 && rm -r *"
+)]
+pub fn confirm(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let (options, question) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: confirm: {}", why)),
+    };
+    let answers = match options.default {
+        Some(true) => "[Y/n]",
+        Some(false) => "[y/N]",
+        None => "[y/n]",
+    };
+    let prompt = if question.is_empty() {
+        format!("{} ", answers)
+    } else {
+        format!("{} {} ", question, answers)
+    };
+
+    let default = options.default.unwrap_or(false);
+    if !atty::is(atty::Stream::Stdin) {
+        eprintln!("{}{}", prompt, if default { "yes" } else { "no" });
+        return Status::from(default);
+    }
+
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        eprint!("{}", prompt);
+        let _ = io::stderr().flush();
+        match read_record(b'\n', None, deadline) {
+            Ok(Record::Read(text)) => match (answer(&text), options.default) {
+                (Some(agrees), _) => return Status::from(agrees),
+                (None, Some(default)) if text.trim().is_empty() => return Status::from(default),
+                (None, _) => (),
+            },
+            Ok(Record::End) => {
+                eprintln!();
+                return Status::from(default);
+            }
+            Ok(Record::TimedOut) => {
+                eprintln!();
+                return options.default.map_or(Status::TIMED_OUT, Status::from);
+            }
+            Err(why) => return Status::error(format!("ion: confirm: {}", why)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn confirm_options() {
+        let args: Vec<types::Str> = vec!["Delete", "--default", "No", "everything?", "-t", "30"]
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let (options, question) = parse_options(&args).unwrap();
+        assert_eq!(options.default, Some(false));
+        assert_eq!(options.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(question, "Delete everything?");
+
+        let args: Vec<types::Str> = vec!["--default".into(), "maybe".into()];
+        assert!(parse_options(&args).is_err());
+        assert_eq!(answer(" Y\n"), Some(true));
+        assert_eq!(answer("nope"), None);
+    }
+}
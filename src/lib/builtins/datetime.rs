@@ -0,0 +1,320 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{shell::Shell, types};
+use builtins_proc::builtin;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The format of the times printed when none is given: ISO 8601, in UTC
+pub(crate) const ISO_8601: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+const WEEKDAYS: [&str; 7] =
+    ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+/// The number of seconds since the epoch
+pub(crate) fn now() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    }
+}
+
+/// The days since the epoch of a date, with months and days counted from 1
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Count the years from March, so that the leap day is the last of a year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of a number of days since the epoch
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Parse a number of exactly the given digits
+fn digits(text: &str, count: usize) -> Option<i64> {
+    if text.len() == count && text.bytes().all(|byte| byte.is_ascii_digit()) {
+        text.parse().ok()
+    } else {
+        None
+    }
+}
+
+/// Parse a time: `now`, a number of seconds since the epoch preceded by `@`, or a date in the ISO
+/// 8601 format, as in `2024-03-01`, `2024-03-01T12:30` or `2024-03-01 12:30:15+02:00`. The times
+/// without an offset are in UTC.
+pub(crate) fn parse_time(time: &str) -> Option<i64> {
+    if time == "now" {
+        return Some(now());
+    }
+    if let Some(seconds) = time.strip_prefix('@') {
+        return seconds.parse().ok();
+    }
+    let (date, clock) = match time.find(|c| c == 'T' || c == ' ') {
+        Some(split) => (&time[..split], &time[split + 1..]),
+        None => (time, ""),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year = digits(parts.next()?, 4)?;
+    let month = digits(parts.next()?, 2).filter(|month| (1..=12).contains(month))?;
+    let day = digits(parts.next()?, 2).filter(|day| (1..=31).contains(day))?;
+
+    let (clock, offset) = match clock.find(|c| c == 'Z' || c == '+' || c == '-') {
+        Some(split) => clock.split_at(split),
+        None => (clock, ""),
+    };
+    let mut seconds = 0;
+    if !clock.is_empty() {
+        let mut parts = clock.splitn(3, ':');
+        let hours = digits(parts.next()?, 2).filter(|&hours| hours < 24)?;
+        let minutes = digits(parts.next()?, 2).filter(|&minutes| minutes < 60)?;
+        let secs = parts.next().map_or(Some(0), |secs| digits(secs, 2)).filter(|&s| s < 61)?;
+        seconds = hours * 3600 + minutes * 60 + secs;
+    }
+    let offset = match offset {
+        "" | "Z" => 0,
+        _ => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "00"));
+            sign * (digits(hours, 2)? * 3600 + digits(minutes, 2)? * 60)
+        }
+    };
+    Some(days_from_civil(year, month, day) * 86_400 + seconds - offset)
+}
+
+/// Parse a signed duration made of numbers followed by their unit: `s`, `m`, `h`, `d` or `w`, as
+/// in `90s`, `-2h` or `1d12h`
+pub(crate) fn parse_offset(duration: &str) -> Option<i64> {
+    let (sign, mut rest) = match duration.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, duration.strip_prefix('+').unwrap_or(duration)),
+    };
+    if rest.is_empty() {
+        return None;
+    }
+    let mut seconds: i64 = 0;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !c.is_ascii_digit()).filter(|&end| end > 0)?;
+        let number: i64 = rest[..end].parse().ok()?;
+        let unit = match rest[end..].chars().next()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.checked_mul(unit)?)?;
+        rest = &rest[end + 1..];
+    }
+    Some(sign * seconds)
+}
+
+/// Format a time, in UTC, as `strftime` would
+pub(crate) fn format_time(time: i64, format: &str) -> String {
+    let days = time.div_euclid(86_400);
+    let secs = time.rem_euclid(86_400);
+    let (year, month, day) = civil_from_days(days);
+    let weekday = (days + 4).rem_euclid(7) as usize;
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+
+    let mut formatted = String::with_capacity(format.len() + 16);
+    let mut chars = format.chars();
+    while let Some(character) = chars.next() {
+        if character != '%' {
+            formatted.push(character);
+            continue;
+        }
+        let part = match chars.next() {
+            Some('Y') => year.to_string(),
+            Some('y') => format!("{:02}", year.rem_euclid(100)),
+            Some('m') => format!("{:02}", month),
+            Some('d') => format!("{:02}", day),
+            Some('e') => format!("{:2}", day),
+            Some('j') => format!("{:03}", day_of_year),
+            Some('H') => format!("{:02}", secs / 3600),
+            Some('I') => format!("{:02}", (secs / 3600 + 11) % 12 + 1),
+            Some('p') => if secs < 43_200 { "AM" } else { "PM" }.into(),
+            Some('M') => format!("{:02}", secs / 60 % 60),
+            Some('S') => format!("{:02}", secs % 60),
+            Some('s') => time.to_string(),
+            Some('A') => WEEKDAYS[weekday].into(),
+            Some('a') => WEEKDAYS[weekday][..3].into(),
+            Some('u') => (if weekday == 0 { 7 } else { weekday }).to_string(),
+            Some('w') => weekday.to_string(),
+            Some('B') => MONTHS[month as usize - 1].into(),
+            Some('b') => MONTHS[month as usize - 1][..3].into(),
+            Some('F') => format_time(time, "%Y-%m-%d"),
+            Some('T') => format_time(time, "%H:%M:%S"),
+            Some('Z') => "UTC".into(),
+            Some('z') => "+0000".into(),
+            Some('n') => "\n".into(),
+            Some('%') => "%".into(),
+            Some(other) => format!("%{}", other),
+            None => "%".into(),
+        };
+        formatted.push_str(&part);
+    }
+    formatted
+}
+
+#[builtin(
+    desc = "print and compute dates and times",
+    man = "
+SYNOPSIS
+    datetime now [--format FORMAT]
+    datetime show TIME [--format FORMAT]
+    datetime add [TIME] DURATION [--format FORMAT]
+    datetime diff TIME TIME
+
+DESCRIPTION
+    Prints dates and times, in UTC, the same way on every system, unlike the date commands which
+    take different options.
+
+    now prints the current time, show prints the given time, and add prints the time, or the
+    current one, moved by the duration. diff prints the number of seconds from the second time to
+    the first.
+
+    A time is now, a number of seconds since the epoch preceded by @, as in @1700000000, or a date
+    in the ISO 8601 format, as in 2024-03-01, 2024-03-01T12:30 or 2024-03-01T12:30:15+02:00. The
+    times are in UTC unless they have an offset.
+
+    A duration is made of numbers followed by their unit, which is s, m, h, d or w for seconds,
+    minutes, hours, days or weeks, as in 90s or 1d12h. It is negative if it starts with a minus.
+
+OPTIONS
+    -f, --format FORMAT
+        the format of the time printed, in which the following sequences are replaced, and which
+        is %Y-%m-%dT%H:%M:%SZ by default:
+        %Y, %y   the year, and its last two digits
+        %m       the month, from 01 to 12
+        %B, %b   the name of the month, and its first three letters
+        %d, %e   the day of the month, from 01 to 31, or padded with a space
+        %j       the day of the year, from 001 to 366
+        %A, %a   the name of the weekday, and its first three letters
+        %u, %w   the weekday, from 1 for Monday to 7, or from 0 for Sunday to 6
+        %H, %I   the hour, from 00 to 23, or from 01 to 12
+        %p       AM or PM
+        %M, %S   the minutes and the seconds
+        %s       the number of seconds since the epoch
+        %F, %T   the date as %Y-%m-%d, and the time as %H:%M:%S
+        %Z, %z   the time zone, which is always UTC and +0000
+        %n, %%   a newline, and a percent sign
+
+EXAMPLES
+    datetime now --format %Y-%m-%d
+    datetime add 2h
+    datetime add 2024-02-28 1d --format '%A %e %B'
+    let elapsed = $(datetime diff now @$start)"
+)]
+pub fn datetime(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let mut format = ISO_8601;
+    let mut operands = Vec::new();
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--format" => match args.next() {
+                Some(value) => format = value.as_str(),
+                None => return Status::bad_argument("ion: datetime: no format given"),
+            },
+            _ => operands.push(arg.as_str()),
+        }
+    }
+
+    match run(&operands, format) {
+        Ok(output) => {
+            println!("{}", output);
+            Status::SUCCESS
+        }
+        Err(why) => Status::bad_argument(format!("ion: datetime: {}", why)),
+    }
+}
+
+/// The output of the command of `datetime`
+fn run(operands: &[&str], format: &str) -> Result<String, String> {
+    let time = |time: &str| parse_time(time).ok_or(format!("invalid time '{}'", time));
+    let offset =
+        |offset: &str| parse_offset(offset).ok_or(format!("invalid duration '{}'", offset));
+    let out_of_range = || String::from("the time is out of range");
+    match *operands {
+        ["now"] => Ok(format_time(now(), format)),
+        ["show", at] => Ok(format_time(time(at)?, format)),
+        ["add", duration] => {
+            let moved = now().checked_add(offset(duration)?).ok_or_else(out_of_range)?;
+            Ok(format_time(moved, format))
+        }
+        ["add", at, duration] => {
+            let moved = time(at)?.checked_add(offset(duration)?).ok_or_else(out_of_range)?;
+            Ok(format_time(moved, format))
+        }
+        ["diff", first, second] => {
+            Ok(time(first)?.checked_sub(time(second)?).ok_or_else(out_of_range)?.to_string())
+        }
+        [] => Err("no command given".into()),
+        [command, ..] => Err(format!("invalid use of '{}'", command)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_parsing() {
+        assert_eq!(parse_time("@86400"), Some(86_400));
+        assert_eq!(parse_time("1970-01-02"), Some(86_400));
+        assert_eq!(parse_time("2000-02-29T00:00:00Z"), Some(951_782_400));
+        assert_eq!(parse_time("2000-02-29 02:30+02:30"), Some(951_782_400));
+        assert_eq!(parse_time("1969-12-31T23:59:59"), Some(-1));
+        assert_eq!(parse_time("2000-13-01"), None);
+        assert_eq!(parse_time("2000-01-01T25:00"), None);
+        assert_eq!(parse_time("tomorrow"), None);
+    }
+
+    #[test]
+    fn offset_parsing() {
+        assert_eq!(parse_offset("90s"), Some(90));
+        assert_eq!(parse_offset("-2h"), Some(-7200));
+        assert_eq!(parse_offset("1d12h"), Some(129_600));
+        assert_eq!(parse_offset("2"), None);
+        assert_eq!(parse_offset("h"), None);
+        assert_eq!(parse_offset(""), None);
+    }
+
+    #[test]
+    fn time_formatting() {
+        assert_eq!(format_time(0, ISO_8601), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            format_time(951_782_400, "%A %e %B %Y, day %j"),
+            "Tuesday 29 February 2000, day 060"
+        );
+        assert_eq!(format_time(-1, "%F %T %I%p %u %s"), "1969-12-31 23:59:59 11PM 3 -1");
+        assert_eq!(format_time(0, "100%% %q"), "100% %q");
+    }
+}
@@ -189,6 +189,7 @@ fn function_is_defined(function: &str, shell: &Shell<'_>) -> bool {
 mod tests {
     use super::*;
     use crate::{
+        expansion::pipelines::PipeType,
         flow_control::Function,
         parser::lexers::assignments::{KeyBuf, Primitive},
         shell::flow_control::Statement,
@@ -310,7 +311,7 @@ mod tests {
         let mut args = Vec::new();
         args.push(KeyBuf { name: "testy".into(), kind: Primitive::Str });
         let mut statements = Vec::new();
-        statements.push(Statement::End);
+        statements.push(Statement::End(PipeType::Normal));
         let description: types::Str = "description".into();
 
         shell.variables_mut().set(
@@ -319,6 +320,8 @@ mod tests {
                 Some(description),
                 name.clone(),
                 args,
+                false,
+                None,
                 statements,
             ))),
         );
@@ -459,7 +462,7 @@ mod tests {
         let mut args = Vec::new();
         args.push(KeyBuf { name: "testy".into(), kind: Primitive::Str });
         let mut statements = Vec::new();
-        statements.push(Statement::End);
+        statements.push(Statement::End(PipeType::Normal));
         let description: types::Str = "description".into();
 
         shell.variables_mut().set(
@@ -468,6 +471,8 @@ mod tests {
                 Some(description),
                 name.clone(),
                 args,
+                false,
+                None,
                 statements,
             ))),
         );
@@ -0,0 +1,116 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{shell::Shell, types};
+use builtins_proc::builtin;
+
+/// The optional features of the shell, and whether this build has them
+const FEATURES: [(&str, bool); 8] = [
+    ("http", cfg!(feature = "http")),
+    ("json", cfg!(feature = "json")),
+    ("man", cfg!(feature = "man")),
+    ("secrets", cfg!(feature = "secrets")),
+    ("serde", cfg!(feature = "serde")),
+    ("toml", cfg!(feature = "toml")),
+    ("unicode", cfg!(feature = "unicode")),
+    ("yaml", cfg!(feature = "yaml")),
+];
+
+/// Whether the build has the feature, or `None` if there is no such feature
+fn has(feature: &str) -> Option<bool> {
+    FEATURES.iter().find(|(name, _)| *name == feature).map(|&(_, enabled)| enabled)
+}
+
+#[builtin(
+    desc = "list the optional features of the shell",
+    man = "
+SYNOPSIS
+    features [--all]
+
+DESCRIPTION
+    Prints the optional features this build of the shell has, one per line. With --all, prints
+    every feature instead, followed by yes or no.
+
+FEATURES
+    http
+        the fetch builtin, making HTTP requests
+    json
+        the $json() and @json() methods, reading values out of JSON documents
+    man
+        the manual pages of the builtins
+    secrets
+        the encrypted secret store, the secret builtin and the secret namespace
+    serde
+        the serialization of parsed statements, for programs embedding the shell
+    toml
+        the $toml() and @toml() methods, reading values out of TOML documents
+    unicode
+        the Unicode classes in the regular expressions of the shell
+    yaml
+        the $yaml() and @yaml() methods, reading values out of YAML documents"
+)]
+pub fn features(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let all = match args.get(1).map(|arg| arg.as_str()) {
+        None => false,
+        Some("--all") => true,
+        Some(arg) => {
+            return Status::bad_argument(format!("ion: features: unexpected argument '{}'", arg))
+        }
+    };
+    for &(name, enabled) in &FEATURES {
+        if all {
+            println!("{} {}", name, if enabled { "yes" } else { "no" });
+        } else if enabled {
+            println!("{}", name);
+        }
+    }
+    Status::SUCCESS
+}
+
+#[builtin(
+    desc = "test if the shell has an optional feature",
+    man = "
+SYNOPSIS
+    has-feature FEATURE...
+
+DESCRIPTION
+    Returns 0 if this build of the shell has every feature, and 1 otherwise, so that a script can
+    fall back on something else when a feature is missing. The features are listed by features
+    --all. Returns 2 if one of them is not a feature of the shell.
+
+EXAMPLES
+    if has-feature secrets
+        let token = ${secret::TOKEN}
+    else
+        read token
+    end"
+)]
+pub fn has_feature(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    if args.len() < 2 {
+        return Status::bad_argument("ion: has-feature: no feature given");
+    }
+    let mut enabled = true;
+    for feature in &args[1..] {
+        match has(feature) {
+            Some(has) => enabled &= has,
+            None => {
+                return Status::bad_argument(format!(
+                    "ion: has-feature: unknown feature '{}'",
+                    feature
+                ))
+            }
+        }
+    }
+    Status::from(enabled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_features() {
+        assert_eq!(has("secrets"), Some(cfg!(feature = "secrets")));
+        assert_eq!(has("http"), Some(cfg!(feature = "http")));
+        assert_eq!(has("https"), None);
+    }
+}
@@ -0,0 +1,348 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{parse_duration, Shell, Value},
+    types,
+};
+use builtins_proc::builtin;
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    time::Duration,
+};
+
+/// The request made by `fetch`
+#[derive(Debug, Default, PartialEq)]
+struct Request<'a> {
+    method:  Option<&'a str>,
+    headers: Vec<&'a str>,
+    body:    Option<&'a str>,
+    timeout: Option<Duration>,
+}
+
+fn parse_options(args: &[types::Str]) -> Result<(Request<'_>, &str), String> {
+    let mut request = Request::default();
+    let mut url = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(|value| value.as_str())
+                .ok_or(format!("option '{}' requires a value", arg))
+        };
+        match arg.as_str() {
+            "-X" | "--request" => request.method = Some(value()?),
+            "-H" | "--header" => {
+                let header = value()?;
+                if !header.contains(':') {
+                    return Err(format!("invalid header '{}'", header));
+                }
+                request.headers.push(header);
+            }
+            "-d" | "--data" => request.body = Some(value()?),
+            "-m" | "--max-time" => {
+                let timeout = value()?;
+                request.timeout =
+                    Some(parse_duration(timeout).ok_or(format!("invalid time '{}'", timeout))?);
+            }
+            _ if url.is_none() => url = Some(arg.as_str()),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+    Ok((request, url.ok_or("no URL given")?))
+}
+
+/// The parts of an http URL
+#[derive(Debug, PartialEq)]
+struct Url<'a> {
+    /// The host and port, as given in the Host header
+    authority: &'a str,
+    host:      &'a str,
+    port:      u16,
+    /// The path and query
+    path:      &'a str,
+}
+
+fn parse_url(url: &str) -> Result<Url<'_>, String> {
+    let rest = match url.split_once("://") {
+        Some(("http", rest)) => rest,
+        Some(("https", _)) => return Err("https is not supported, use http instead".into()),
+        Some((scheme, _)) => return Err(format!("unsupported scheme '{}'", scheme)),
+        None => url,
+    };
+    let (authority, path) = match rest.find(|c| c == '/' || c == '?') {
+        Some(start) => rest.split_at(start),
+        None => (rest, "/"),
+    };
+    let (host, port) = match authority.rsplit_once(':') {
+        Some((host, port)) if !authority.ends_with(']') => {
+            (host, port.parse().map_err(|_| format!("invalid port '{}'", port))?)
+        }
+        _ => (authority, 80),
+    };
+    if host.is_empty() {
+        return Err(format!("no host in the URL '{}'", url));
+    }
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok(Url { authority, host, port, path })
+}
+
+/// The status and the headers of the response to the request
+#[derive(Debug, PartialEq)]
+struct Response {
+    status:  u16,
+    /// The headers, with lowercase names and the values of repeated headers joined by commas
+    headers: Vec<(String, String)>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str())
+    }
+}
+
+fn invalid(why: &str) -> io::Error { io::Error::new(io::ErrorKind::InvalidData, why) }
+
+/// Read a line ending with CRLF, without its end
+fn read_line<R: BufRead>(reader: &mut R, ended: &str) -> io::Result<String> {
+    let mut line = Vec::new();
+    reader.read_until(b'\n', &mut line)?;
+    if !line.ends_with(b"\r\n") {
+        return Err(invalid(ended));
+    }
+    line.truncate(line.len() - 2);
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// Read the status line and the headers of the response, up to its body
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Response> {
+    let ended = "the response has no end of headers";
+    let status = read_line(reader, ended)?
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| invalid("the response has no status"))?;
+    let mut response = Response { status, headers: Vec::new() };
+    loop {
+        let line = read_line(reader, ended)?;
+        if line.is_empty() {
+            return Ok(response);
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim().to_lowercase(), value.trim()),
+            None => continue,
+        };
+        // A header given several times is a list of values
+        match response.headers.iter_mut().find(|(header, _)| *header == name) {
+            Some((_, values)) => {
+                values.push_str(", ");
+                values.push_str(value);
+            }
+            None => response.headers.push((name, value.to_string())),
+        }
+    }
+}
+
+/// Copy the body of the response as it is received, reading the chunks of a chunked body
+fn copy_body<R: BufRead, W: Write>(
+    reader: &mut R,
+    response: &Response,
+    output: &mut W,
+) -> io::Result<()> {
+    if response.header("transfer-encoding").map_or(false, |encoding| encoding.contains("chunked")) {
+        let ended = "the response ends in the middle of a chunk";
+        loop {
+            let line = read_line(reader, ended)?;
+            // The size may be followed by extensions
+            let size = line.split(';').next().unwrap_or_default().trim();
+            let size = u64::from_str_radix(size, 16)
+                .map_err(|_| invalid(&format!("invalid chunk size '{}'", size)))?;
+            if size == 0 {
+                return Ok(());
+            }
+            if io::copy(&mut reader.by_ref().take(size), output)? < size {
+                return Err(invalid(ended));
+            }
+            read_line(reader, ended)?;
+        }
+    } else if let Some(length) =
+        response.header("content-length").and_then(|length| length.parse().ok())
+    {
+        io::copy(&mut reader.by_ref().take(length), output).map(|_| ())
+    } else {
+        io::copy(reader, output).map(|_| ())
+    }
+}
+
+/// Send the request, returning the connection from which the response is read
+fn send(url: &Url<'_>, request: &Request<'_>) -> io::Result<BufReader<TcpStream>> {
+    let mut stream = match request.timeout {
+        Some(timeout) => {
+            let mut last = io::Error::new(io::ErrorKind::NotFound, "the host has no address");
+            let mut connected = None;
+            for address in (url.host, url.port).to_socket_addrs()? {
+                match TcpStream::connect_timeout(&address, timeout) {
+                    Ok(stream) => {
+                        connected = Some(stream);
+                        break;
+                    }
+                    Err(why) => last = why,
+                }
+            }
+            connected.ok_or(last)?
+        }
+        None => TcpStream::connect((url.host, url.port))?,
+    };
+    stream.set_read_timeout(request.timeout)?;
+    stream.set_write_timeout(request.timeout)?;
+
+    let method = request.method.unwrap_or(if request.body.is_some() { "POST" } else { "GET" });
+    let slash = if url.path.starts_with('/') { "" } else { "/" };
+    let mut head = format!("{} {}{} HTTP/1.1\r\n", method, slash, url.path);
+    head.push_str(&format!("Host: {}\r\n", url.authority));
+    head.push_str(&format!("User-Agent: ion/{}\r\n", env!("CARGO_PKG_VERSION")));
+    head.push_str("Accept: */*\r\nConnection: close\r\n");
+    for header in &request.headers {
+        head.push_str(header);
+        head.push_str("\r\n");
+    }
+    if let Some(body) = request.body {
+        head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(request.body.unwrap_or_default().as_bytes())?;
+
+    Ok(BufReader::new(stream))
+}
+
+#[builtin(
+    desc = "make an HTTP request",
+    man = "
+SYNOPSIS
+    fetch [-X METHOD] [-H HEADER]... [-d BODY] [-m DURATION] URL
+
+DESCRIPTION
+    Sends an HTTP request to the URL, and writes the body of the response to the standard output,
+    so that scripts may download files on systems without curl or wget. The status of the response
+    is stored in the variable FETCH_STATUS, and its headers in the hmap FETCH_HEADERS, under their
+    lowercase names.
+
+    Returns 0 if the status of the response is a success, from 200 to 299, and 1 otherwise. Returns
+    124 if the request timed out. Only http URLs are supported, and redirections are not followed.
+
+OPTIONS
+    -X, --request METHOD
+        the method of the request, GET by default, or POST if a body is given
+    -H, --header HEADER
+        a header of the request, written as NAME: VALUE
+    -d, --data BODY
+        the body of the request
+    -m, --max-time DURATION
+        the longest wait to connect, and then between two reads or writes. The duration is a
+        number of seconds, or of minutes, hours or days with the suffix m, h or d.
+
+EXAMPLES
+    fetch http://example.com/ > index.html
+    fetch -X PUT -H 'Content-Type: application/json' -d '{\"on\": true}' http://10.0.0.2/light
+    echo $FETCH_STATUS $FETCH_HEADERS[content-type]"
+)]
+pub fn fetch(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (request, url) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: fetch: {}", why)),
+    };
+    let url = match parse_url(url) {
+        Ok(url) => url,
+        Err(why) => return Status::bad_argument(format!("ion: fetch: {}", why)),
+    };
+    let failed = |why: io::Error| {
+        if matches!(why.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) {
+            eprintln!("ion: fetch: {}: timed out", url.authority);
+            Status::TIMED_OUT
+        } else {
+            Status::error(format!("ion: fetch: {}: {}", url.authority, why))
+        }
+    };
+    let (mut reader, response) = match send(&url, &request)
+        .and_then(|mut reader| read_head(&mut reader).map(|response| (reader, response)))
+    {
+        Ok(received) => received,
+        Err(why) => return failed(why),
+    };
+
+    let headers: types::HashMap<_> = response
+        .headers
+        .iter()
+        .map(|(name, value)| (name.as_str().into(), Value::Str(value.as_str().into())))
+        .collect();
+    shell.variables_mut().set("FETCH_STATUS", response.status.to_string());
+    shell.variables_mut().set("FETCH_HEADERS", Value::HashMap(headers));
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(why) = copy_body(&mut reader, &response, &mut stdout).and_then(|_| stdout.flush()) {
+        return failed(why);
+    }
+    Status::from((200..300).contains(&response.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetch_options() {
+        let args: Vec<types::Str> =
+            vec!["-X".into(), "PUT".into(), "-H".into(), "Accept: text/plain".into(), "h".into()];
+        let (request, url) = parse_options(&args).unwrap();
+        assert_eq!(request.method, Some("PUT"));
+        assert_eq!(request.headers, vec!["Accept: text/plain"]);
+        assert_eq!(url, "h");
+        assert!(parse_options(&args[..4]).is_err());
+        assert!(parse_options(&["-H".into(), "Accept".into(), "h".into()]).is_err());
+    }
+
+    #[test]
+    fn fetch_urls() {
+        assert_eq!(
+            parse_url("http://example.com:8080/a?b=c").unwrap(),
+            Url {
+                authority: "example.com:8080",
+                host:      "example.com",
+                port:      8080,
+                path:      "/a?b=c",
+            }
+        );
+        assert_eq!(
+            parse_url("[::1]").unwrap(),
+            Url { authority: "[::1]", host: "::1", port: 80, path: "/" }
+        );
+        assert_eq!(parse_url("example.com?q").unwrap().path, "?q");
+        assert!(parse_url("https://example.com").is_err());
+        assert!(parse_url("http://:80/").is_err());
+    }
+
+    fn receive(mut raw: &[u8]) -> io::Result<(Response, Vec<u8>)> {
+        let response = read_head(&mut raw)?;
+        let mut body = Vec::new();
+        copy_body(&mut raw, &response, &mut body)?;
+        Ok((response, body))
+    }
+
+    #[test]
+    fn fetch_responses() {
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nX-A: b\r\nX-A: c\r\n\r\nhi there";
+        let (response, body) = receive(raw).unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.header("x-a"), Some("b, c"));
+        assert_eq!(body, b"hi");
+
+        let raw = b"HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2;x\r\nde\r\n0\r\n\r\n";
+        let (response, body) = receive(raw).unwrap();
+        assert_eq!(response.status, 404);
+        assert_eq!(body, b"abcde");
+        assert!(receive(b"HTTP/1.1 200 OK\r\n").is_err());
+        assert!(receive(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nab").is_err());
+    }
+}
@@ -0,0 +1,78 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{shell::Shell, types};
+use builtins_proc::builtin;
+
+#[builtin(
+    desc = "list or reset the paths of the programs run",
+    man = "
+SYNOPSIS
+    hash [-r] [-d NAME...] [-t NAME...] [NAME...]
+
+DESCRIPTION
+    The shell searches the directories of PATH for a program the first time it runs it, and then
+    remembers its path. Without arguments, hash lists the remembered programs, with the number of
+    times each was looked up. With names, it searches their programs and remembers them.
+
+    The paths are forgotten when PATH changes, and a program which was removed is searched again,
+    so the paths only need to be forgotten when a program is installed in a directory which comes
+    before the one of the remembered program.
+
+    Returns 1 if one of the names has no program.
+
+OPTIONS
+    -r
+        forgets the paths of every program
+    -d NAME...
+        forgets the paths of the programs of the names
+    -t NAME...
+        prints the path of the program of each name
+
+EXAMPLES
+    hash -t git
+    hash -r"
+)]
+pub fn hash(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let cache = shell.command_cache_mut();
+    match args.get(1).map(types::Str::as_str) {
+        None => {
+            let entries = cache.entries();
+            if !entries.is_empty() {
+                println!("hits\tcommand");
+            }
+            for (_, path, hits) in entries {
+                println!("{:4}\t{}", hits, path.display());
+            }
+            Status::SUCCESS
+        }
+        Some("-r") if args.len() == 2 => {
+            cache.clear();
+            Status::SUCCESS
+        }
+        Some("-r") => Status::bad_argument("ion: hash: -r takes no names"),
+        Some("-d") => {
+            let mut status = Status::SUCCESS;
+            for name in &args[2..] {
+                if !cache.forget(name) {
+                    status = Status::error(format!("ion: hash: {}: not found", name));
+                }
+            }
+            status
+        }
+        Some(option) if option.starts_with('-') && option != "-t" => {
+            Status::bad_argument(format!("ion: hash: unknown option '{}'", option))
+        }
+        Some(option) => {
+            let print = option == "-t";
+            let mut status = Status::SUCCESS;
+            for name in &args[if print { 2 } else { 1 }..] {
+                match cache.resolve(name) {
+                    Some(path) if print => println!("{}", path.display()),
+                    Some(_) => (),
+                    None => status = Status::error(format!("ion: hash: {}: not found", name)),
+                }
+            }
+            status
+        }
+    }
+}
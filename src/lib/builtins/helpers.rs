@@ -1,4 +1,5 @@
 use super::{super::types, Value};
+use crate::shell::reporting;
 use std::{fmt::Display, rc::Rc};
 
 /// The exit status of a command
@@ -18,6 +19,8 @@ impl Status {
     pub const SUCCESS: Self = Self(0);
     /// The process was killed
     pub const TERMINATED: Self = Self(143);
+    /// The command did not finish before its timeout, as with `timeout(1)`
+    pub const TIMED_OUT: Self = Self(124);
     /// In builtins that outputs bools, indicates that the result is true
     pub const TRUE: Self = Self(0);
 
@@ -31,7 +34,7 @@ impl Status {
     pub fn error<T: AsRef<str>>(err: T) -> Self {
         let err = err.as_ref();
         if !err.is_empty() {
-            eprintln!("{}", err);
+            reporting::report(err);
         }
         Self(1)
     }
@@ -40,7 +43,7 @@ impl Status {
     pub fn bad_argument<T: AsRef<str>>(err: T) -> Self {
         let err = err.as_ref();
         if !err.is_empty() {
-            eprintln!("{}", err);
+            reporting::report(err);
         }
         Self(2)
     }
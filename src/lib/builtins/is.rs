@@ -1,6 +1,9 @@
 use super::Status;
 use crate as ion_shell;
-use crate::{shell::Shell, types};
+use crate::{
+    shell::{Shell, Value},
+    types,
+};
 use builtins_proc::builtin;
 
 // TODO: Add support for multiple name in builtins man
@@ -9,17 +12,23 @@ use builtins_proc::builtin;
     desc = "checks if two arguments are the same",
     man = "
 SYNOPSIS
-    is [ -h | --help ] [not]
+    is [ -h | --help ] [not] LEFT RIGHT
+    is [not] set VARIABLE
 
 DESCRIPTION
     Returns 0 if the two arguments are equal
 
 OPTIONS
     not
-        returns 0 if the two arguments are not equal."
+        returns 0 if the two arguments are not equal.
+    set
+        returns 0 if the variable is set, which an optional argument of a function is not when it
+        is left out or empty."
 )]
 pub fn is(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     match args.len() {
+        4 if args[1] == "not" && args[2] == "set" => return Status::from(!is_set(&args[3], shell)),
+        3 if args[1] == "set" => return Status::from(is_set(&args[2], shell)),
         4 => {
             if args[1] != "not" {
                 return Status::error(format!("Expected 'not' instead found '{}'", args[1]));
@@ -38,6 +47,12 @@ pub fn is(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     Status::SUCCESS
 }
 
+/// Whether the variable, named with or without its sigil, has a value
+fn is_set(name: &str, shell: &Shell<'_>) -> bool {
+    let name = name.strip_prefix(|c| c == '$' || c == '@').unwrap_or(name);
+    !matches!(shell.variables().get(name), None | Some(Value::None))
+}
+
 fn eval_arg(arg: &str, shell: &mut Shell<'_>) -> types::Str {
     if let Some(value) = get_var_string(arg, shell) {
         value
@@ -81,6 +96,13 @@ fn test_is() {
     assert!(builtin_is(&vec_string(&["is", " ", " "]), &mut shell).is_success());
     assert!(builtin_is(&vec_string(&["is", "$x", "$x"]), &mut shell).is_success());
 
+    // Set variables
+    shell.variables_mut().set("none", Value::None);
+    assert!(builtin_is(&vec_string(&["is", "set", "x"]), &mut shell).is_success());
+    assert!(builtin_is(&vec_string(&["is", "set", "none"]), &mut shell).is_failure());
+    assert!(builtin_is(&vec_string(&["is", "set", "unknown"]), &mut shell).is_failure());
+    assert!(builtin_is(&vec_string(&["is", "not", "set", "none"]), &mut shell).is_success());
+
     // Two arguments
     assert!(builtin_is(&vec_string(&["is", " "]), &mut shell).is_failure());
 
@@ -1,4 +1,4 @@
-//! Contains the `jobs`, `disown`, `bg`, and `fg` commands that manage job
+//! Contains the `jobs`, `disown`, `bg`, `fg`, `kill`, and `wait` commands that manage job
 //! control in the shell.
 
 use super::Status;
@@ -6,7 +6,54 @@ use crate::{
     shell::{BackgroundProcess, Shell},
     types,
 };
+use nix::{
+    sys::signal::{self, Signal},
+    unistd::Pid,
+};
 use smallvec::SmallVec;
+use std::{
+    convert::TryFrom,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// Find the job designated by a job specifier: `N` or `%N` for the job number N, `%+` or `%%`
+/// for the current job, `%-` for the previous job, and `%STRING` for the job whose command
+/// starts with STRING.
+pub fn job_id(shell: &Shell<'_>, spec: &str) -> Result<usize, String> {
+    find_job(shell, spec, BackgroundProcess::exists)
+}
+
+/// Find the job designated by a job specifier among the jobs matching the filter
+fn find_job(
+    shell: &Shell<'_>,
+    spec: &str,
+    filter: fn(&BackgroundProcess) -> bool,
+) -> Result<usize, String> {
+    let jobs = shell.background_jobs();
+    let exists = |njob: &usize| jobs.get(*njob).map_or(false, filter);
+    let spec = spec.strip_prefix('%').unwrap_or(spec);
+    match spec {
+        "+" | "%" | "" => {
+            shell.previous_job().filter(exists).ok_or_else(|| "no current job".to_owned())
+        }
+        "-" => shell.prior_job().filter(exists).ok_or_else(|| "no previous job".to_owned()),
+        _ if spec.bytes().all(|b| b.is_ascii_digit()) => spec
+            .parse::<usize>()
+            .ok()
+            .filter(exists)
+            .ok_or_else(|| format!("job {} does not exist", spec)),
+        _ => {
+            let mut matching =
+                (0..jobs.len()).filter(exists).filter(|&njob| jobs[njob].name().starts_with(spec));
+            match (matching.next(), matching.next()) {
+                (Some(njob), None) => Ok(njob),
+                (Some(_), Some(_)) => Err(format!("ambiguous job spec: '%{}'", spec)),
+                (None, _) => Err(format!("no job matches '%{}'", spec)),
+            }
+        }
+    }
+}
 
 /// Disowns given process job IDs, and optionally marks jobs to not receive SIGHUP signals.
 /// The `-a` flag selects all jobs, `-r` selects all running jobs, and `-h` specifies to mark
@@ -26,18 +73,17 @@ pub fn disown(shell: &mut Shell<'_>, args: &[types::Str]) -> Result<(), String>
             "-a" => all_jobs = true,
             "-h" => no_sighup = true,
             "-r" => run_jobs = true,
-            _ => {
-                let jobspec =
-                    arg.parse::<usize>().map_err(|_| format!("invalid jobspec: '{}'", arg))?;
-                collected_jobs.push(jobspec);
-            }
+            _ => collected_jobs.push(job_id(shell, arg)?),
         }
     }
 
-    if !all_jobs && !run_jobs && collected_jobs.is_empty() {
-        return Err("must provide arguments to select jobs".to_owned());
-    } else if (all_jobs && run_jobs) || (collected_jobs.is_empty() && (all_jobs || run_jobs)) {
+    if (all_jobs || run_jobs) && !collected_jobs.is_empty() {
+        return Err("cannot select jobs together with -a or -r".to_owned());
+    } else if all_jobs && run_jobs {
         return Err("must only provide a single job spec".to_owned());
+    } else if !all_jobs && !run_jobs && collected_jobs.is_empty() {
+        // Without arguments, the current job is disowned
+        collected_jobs.push(job_id(shell, "%+")?);
     }
 
     let action: fn(&mut BackgroundProcess) = if no_sighup {
@@ -99,13 +145,11 @@ pub fn fg(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
         }
     } else {
         for arg in args {
-            match arg.parse::<usize>() {
+            match job_id(shell, arg) {
                 Ok(njob) => {
                     fg_job(shell, njob);
                 }
-                Err(_) => {
-                    return Status::error(format!("ion: fg: {} is not a valid job number", arg))
-                }
+                Err(why) => return Status::error(format!("ion: fg: {}", why)),
             }
         }
         Status::SUCCESS
@@ -135,15 +179,251 @@ pub fn bg(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
         }
     } else {
         for arg in args {
-            if let Ok(njob) = arg.parse::<usize>() {
-                let status = bg_job(shell, njob);
-                if !status.is_success() {
-                    return status;
+            match job_id(shell, arg) {
+                Ok(njob) => {
+                    let status = bg_job(shell, njob);
+                    if !status.is_success() {
+                        return status;
+                    }
                 }
-            } else {
-                return Status::error(format!("ion: bg: {} is not a valid job number", arg));
-            };
+                Err(why) => return Status::error(format!("ion: bg: {}", why)),
+            }
         }
         Status::SUCCESS
     }
 }
+
+/// Sends a signal to jobs, given by job specifiers starting with `%`, and to processes, given by
+/// their ID, or to process groups, given by their negated ID after `--`. The signal is given by
+/// its name or its number, as `-s SIGNAL` or `-SIGNAL`, and the signal 0 only checks that the
+/// targets exist. With `-l`, lists the signals instead.
+pub fn kill(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
+    let (signal, targets) = match args {
+        [flag, names @ ..] if flag == "-l" => return list_signals(names),
+        [flag, name, targets @ ..] if flag == "-s" => (kill_signal(name), targets),
+        [flag, ..] if flag == "--" => (Ok(Some(Signal::SIGTERM)), args),
+        [flag, targets @ ..] if flag.starts_with('-') && flag.len() > 1 => {
+            (kill_signal(&flag[1..]), targets)
+        }
+        targets => (Ok(Some(Signal::SIGTERM)), targets),
+    };
+    let signal = match signal {
+        Ok(signal) => signal,
+        Err(why) => return Status::error(format!("ion: kill: {}", why)),
+    };
+    // The targets which follow `--` may be negative, naming process groups
+    let targets = match targets {
+        [flag, targets @ ..] if flag == "--" => targets,
+        targets => targets,
+    };
+    if targets.is_empty() {
+        return Status::error("ion: kill: must provide jobs or processes to signal");
+    }
+
+    let mut status = Status::SUCCESS;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            job_id(shell, target).and_then(|njob| {
+                // Jobs run in process groups of their own, led by their first process
+                let pgid = shell.background_jobs()[njob].pid();
+                signal::killpg(pgid, signal).map_err(|why| why.to_string())
+            })
+        } else {
+            target
+                .parse::<i32>()
+                .map_err(|_| format!("'{}' is neither a job nor a process ID", target))
+                .and_then(|pid| {
+                    signal::kill(Pid::from_raw(pid), signal).map_err(|why| why.to_string())
+                })
+        };
+        if let Err(why) = result {
+            status = Status::error(format!("ion: kill: {}: {}", target, why));
+        }
+    }
+    status
+}
+
+/// Waits for the jobs given by job specifiers or process IDs, or for all of the running jobs
+/// without any. With `--any`, the wait ends once one of the jobs exits, and with `--timeout`, once
+/// the given number of seconds elapsed. The process ID and the exit status of the job ending the
+/// wait are stored into the variables given to `--pid` and `--status`.
+pub fn wait(shell: &mut Shell<'_>, args: &[types::Str]) -> Status {
+    let mut any = false;
+    let mut timeout = None;
+    let (mut pid_var, mut status_var) = (None, None);
+    let mut targets = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &**arg {
+            "--any" => any = true,
+            "--all" => any = false,
+            "--timeout" => match args.next().and_then(|secs| secs.parse::<f64>().ok()) {
+                Some(secs) if secs.is_finite() && secs >= 0.0 => {
+                    timeout = Some(Duration::from_secs_f64(secs))
+                }
+                _ => return Status::error("ion: wait: --timeout requires a number of seconds"),
+            },
+            "--pid" | "--status" => {
+                let var = match args.next() {
+                    Some(var) => var,
+                    None => {
+                        return Status::error(format!("ion: wait: {} requires a variable", arg))
+                    }
+                };
+                if &**arg == "--pid" {
+                    pid_var = Some(var);
+                } else {
+                    status_var = Some(var);
+                }
+            }
+            _ => targets.push(arg),
+        }
+    }
+
+    let njobs: Vec<usize> = if targets.is_empty() {
+        let jobs = shell.background_jobs();
+        (0..jobs.len()).filter(|&njob| jobs[njob].is_running() || jobs[njob].is_done()).collect()
+    } else {
+        match targets.iter().map(|target| wait_target(shell, target)).collect() {
+            Ok(njobs) => njobs,
+            Err(why) => return Status::error(format!("ion: wait: {}", why)),
+        }
+    };
+    if njobs.is_empty() {
+        return Status::SUCCESS;
+    }
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    match shell.wait_for_jobs(&njobs, any, deadline) {
+        Ok(Some(njob)) => {
+            let (pid, status) = {
+                let jobs = shell.background_jobs();
+                (jobs[njob].pid(), jobs[njob].exit_status())
+            };
+            if let Some(var) = pid_var {
+                shell.variables_mut().set(var, pid.to_string());
+            }
+            if let Some(var) = status_var {
+                shell.variables_mut().set(var, status.to_string());
+            }
+            // The statuses of the jobs which were waited on are not kept any longer
+            let mut jobs = shell.background_jobs_mut();
+            let waited = if any { std::slice::from_ref(&njob) } else { &njobs[..] };
+            for &njob in waited {
+                if jobs[njob].is_done() {
+                    jobs[njob].forget();
+                }
+            }
+            Status::from_exit_code(status)
+        }
+        Ok(None) => Status::TIMED_OUT,
+        Err(why) => Status::error(format!("ion: wait: {}", why)),
+    }
+}
+
+/// The job designated by a job specifier, or the job of the given process ID, including the
+/// jobs which already exited
+fn wait_target(shell: &Shell<'_>, target: &str) -> Result<usize, String> {
+    let waitable = |job: &BackgroundProcess| job.exists() || job.is_done();
+    if target.starts_with('%') {
+        return find_job(shell, target, waitable);
+    }
+    let pid = target
+        .parse::<i32>()
+        .map_err(|_| format!("'{}' is neither a job nor a process ID", target))?;
+    shell
+        .background_jobs()
+        .iter()
+        .position(|job| waitable(job) && job.pid().as_raw() == pid)
+        .ok_or_else(|| format!("no job has the process ID {}", pid))
+}
+
+/// The signal sent by `kill`, or `None` for the signal 0, which is not sent
+fn kill_signal(name: &str) -> Result<Option<Signal>, String> {
+    if name == "0" {
+        Ok(None)
+    } else {
+        parse_signal(name).map(Some)
+    }
+}
+
+/// Print the names of the signals, without the `SIG` prefix, or the name of each signal given by
+/// its number, or the number of each signal given by its name. The number may also be an exit
+/// status of a command killed by the signal.
+fn list_signals(names: &[types::Str]) -> Status {
+    if names.is_empty() {
+        let names: Vec<_> = Signal::iterator().map(|signal| &signal.as_str()[3..]).collect();
+        println!("{}", names.join(" "));
+        return Status::SUCCESS;
+    }
+    let mut status = Status::SUCCESS;
+    for name in names {
+        match name.parse::<i32>() {
+            Ok(number) => {
+                let number = if number > 128 { number - 128 } else { number };
+                match Signal::try_from(number) {
+                    Ok(signal) => println!("{}", &signal.as_str()[3..]),
+                    Err(_) => {
+                        status = Status::error(format!("ion: kill: invalid signal: '{}'", name))
+                    }
+                }
+            }
+            Err(_) => match parse_signal(name) {
+                Ok(signal) => println!("{}", signal as i32),
+                Err(why) => status = Status::error(format!("ion: kill: {}", why)),
+            },
+        }
+    }
+    status
+}
+
+/// A signal given by its number, or by its name with or without the `SIG` prefix
+fn parse_signal(name: &str) -> Result<Signal, String> {
+    let signal = match name.parse::<i32>() {
+        Ok(number) => Signal::try_from(number).ok(),
+        Err(_) => {
+            let name = name.to_ascii_uppercase();
+            if name.starts_with("SIG") {
+                Signal::from_str(&name).ok()
+            } else {
+                Signal::from_str(&format!("SIG{}", name)).ok()
+            }
+        }
+    };
+    signal.ok_or_else(|| format!("invalid signal: '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals() {
+        assert_eq!(parse_signal("9"), Ok(Signal::SIGKILL));
+        assert_eq!(parse_signal("hup"), Ok(Signal::SIGHUP));
+        assert_eq!(parse_signal("SIGUSR1"), Ok(Signal::SIGUSR1));
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert_eq!(kill_signal("0"), Ok(None));
+        assert_eq!(kill_signal("-0"), Err("invalid signal: '-0'".to_owned()));
+    }
+
+    #[test]
+    fn missing_jobs() {
+        let shell = Shell::new();
+        assert_eq!(job_id(&shell, "%+"), Err("no current job".to_owned()));
+        assert_eq!(job_id(&shell, "%-"), Err("no previous job".to_owned()));
+        assert_eq!(job_id(&shell, "%3"), Err("job 3 does not exist".to_owned()));
+        assert_eq!(job_id(&shell, "%sleep"), Err("no job matches '%sleep'".to_owned()));
+    }
+
+    #[test]
+    fn wait_targets() {
+        let shell = Shell::new();
+        assert_eq!(wait_target(&shell, "%1"), Err("job 1 does not exist".to_owned()));
+        assert_eq!(wait_target(&shell, "1"), Err("no job has the process ID 1".to_owned()));
+        assert_eq!(
+            wait_target(&shell, "one"),
+            Err("'one' is neither a job nor a process ID".to_owned())
+        );
+    }
+}
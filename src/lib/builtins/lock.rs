@@ -0,0 +1,136 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    expansion::{
+        pipelines::{PipeItem, PipeType, Pipeline, RedirectFrom},
+        quoted,
+    },
+    shell::{flow_control::Statement, Job, Shell},
+    types,
+};
+use builtins_proc::builtin;
+use nix::{
+    errno::Errno,
+    fcntl::{flock, FlockArg},
+};
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::io::AsRawFd,
+    path::PathBuf,
+};
+
+/// Open the file, creating it if needed, and lock it. Returns `None` if the lock is held by
+/// another process and it should not be waited for.
+fn acquire(path: &str, wait: bool) -> Result<Option<File>, String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|why| format!("could not open '{}': {}", path, why))?;
+    let arg = if wait { FlockArg::LockExclusive } else { FlockArg::LockExclusiveNonblock };
+    match flock(file.as_raw_fd(), arg) {
+        Ok(()) => Ok(Some(file)),
+        Err(Errno::EWOULDBLOCK) if !wait => Ok(None),
+        Err(why) => Err(format!("could not lock '{}': {}", path, why)),
+    }
+}
+
+#[builtin(
+    desc = "hold a lock on a file, to run commands one at a time",
+    man = "
+SYNOPSIS
+    lock [--try] FILE [-- COMMAND...]
+
+DESCRIPTION
+    Waits until no other process holds the lock on the file, which is created if it does not
+    exist, then takes it. The lock is released once the command given after -- is done, in which
+    case its status is returned. Without a command, the lock is held until the function or the
+    script calling lock ends, or until it is released with unlock.
+
+    The lock is advisory, and taken with flock: it excludes the processes locking the same file,
+    and is released if the shell exits.
+
+OPTIONS
+    -t, --try
+        return 1 at once if the lock is held by another process, rather than waiting for it
+
+EXAMPLES
+    lock /run/backup.lock -- rsync -a /home /mnt/backup
+    lock --try /run/report.lock || exit"
+)]
+pub fn lock(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let mut args = args[1..].iter();
+    let mut wait = true;
+    let mut path = None;
+    for arg in &mut args {
+        match arg.as_str() {
+            "-t" | "--try" => wait = false,
+            "--" => break,
+            _ if path.is_none() => path = Some(arg),
+            _ => return Status::bad_argument(format!("ion: lock: unexpected argument '{}'", arg)),
+        }
+    }
+    let path = match path {
+        Some(path) => path,
+        None => return Status::bad_argument("ion: lock: no file given"),
+    };
+    // A second lock on the file from this process would never be granted
+    if shell.locks_mut().contains_key(&PathBuf::from(path.as_str())) {
+        return Status::error(format!("ion: lock: '{}' is already locked by this shell", path));
+    }
+    let file = match acquire(path, wait) {
+        Ok(Some(file)) => file,
+        Ok(None) => return Status::FALSE,
+        Err(why) => return Status::error(format!("ion: lock: {}", why)),
+    };
+
+    let command: types::Args = args.cloned().collect();
+    if !command.is_empty() {
+        let status = shell
+            .execute_args(command)
+            .unwrap_or_else(|why| Status::error(format!("ion: lock: {}", why)));
+        drop(file);
+        return status;
+    }
+    shell.locks_mut().insert(PathBuf::from(path.as_str()), file);
+    let unlock = Job::new(args!["unlock", quoted(path)], RedirectFrom::None);
+    let item = PipeItem::new(unlock, Vec::new(), Vec::new(), Vec::new());
+    shell.defer(Statement::Pipeline(Pipeline { items: vec![item], pipe: PipeType::Normal }));
+    Status::SUCCESS
+}
+
+#[builtin(
+    desc = "release a lock taken by lock",
+    man = "
+SYNOPSIS
+    unlock FILE
+
+DESCRIPTION
+    Releases the lock on the file taken by lock without a command, before the function or the
+    script which took it ends. Returns 1 if the shell does not hold it."
+)]
+pub fn unlock(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    match args.get(1) {
+        Some(path) => {
+            Status::from(shell.locks_mut().remove(&PathBuf::from(path.as_str())).is_some())
+        }
+        None => Status::bad_argument("ion: unlock: no file given"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_excludes_other_descriptions() {
+        let path = std::env::temp_dir().join(format!("ion-lock-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        let held = acquire(path, false).unwrap();
+        assert!(held.is_some());
+        assert!(acquire(path, false).unwrap().is_none());
+        drop(held);
+        assert!(acquire(path, false).unwrap().is_some());
+        let _ = std::fs::remove_file(path);
+    }
+}
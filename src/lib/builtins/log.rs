@@ -0,0 +1,190 @@
+use super::{
+    datetime::{format_time, ISO_8601},
+    Status,
+};
+use crate as ion_shell;
+use crate::{
+    shell::{Shell, Value},
+    types,
+};
+use builtins_proc::builtin;
+use itertools::Itertools;
+use std::{
+    env,
+    io::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How important a message logged by `log` is
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Level {
+    Info,
+    Success,
+    Warn,
+    Error,
+}
+
+impl Level {
+    fn parse(level: &str) -> Option<Self> {
+        match level {
+            "info" => Some(Self::Info),
+            "success" | "ok" => Some(Self::Success),
+            "warn" | "warning" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    const fn tag(self) -> &'static str {
+        match self {
+            Self::Info => "info",
+            Self::Success => "success",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    /// The ANSI color of the tag
+    const fn color(self) -> &'static str {
+        match self {
+            Self::Info => "\x1B[34m",
+            Self::Success => "\x1B[32m",
+            Self::Warn => "\x1B[33m",
+            Self::Error => "\x1B[31m",
+        }
+    }
+
+    /// Whether messages of the level are written to the standard error
+    const fn is_diagnostic(self) -> bool { matches!(self, Self::Warn | Self::Error) }
+}
+
+/// What is written along with a message by `log`
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    timestamp: bool,
+    script:    bool,
+    color:     Option<bool>,
+}
+
+fn parse_options(args: &[types::Str]) -> Result<(Options, Level, &[types::Str]), String> {
+    let mut options = Options::default();
+    let mut args = args;
+    while let Some((arg, rest)) = args.split_first() {
+        match arg.as_str() {
+            "-t" | "--timestamp" => options.timestamp = true,
+            "-s" | "--script" => options.script = true,
+            "--color" => options.color = Some(true),
+            "--no-color" => options.color = Some(false),
+            level => {
+                let level = Level::parse(level).ok_or(format!("unknown level '{}'", level))?;
+                return Ok((options, level, rest));
+            }
+        }
+        args = rest;
+    }
+    Err("no level given".into())
+}
+
+/// The UTC date and time, in the ISO 8601 format, of a number of seconds since the epoch
+fn timestamp(secs: u64) -> String { format_time(secs as i64, ISO_8601) }
+
+/// The name of the script being run, if any
+fn script_name(shell: &Shell<'_>) -> Option<types::Str> {
+    match shell.variables().get("args")? {
+        Value::Array(args) => match args.first()? {
+            Value::Str(name) => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[builtin(
+    desc = "print a message with its level",
+    man = "
+SYNOPSIS
+    log [--timestamp] [--script] [--color | --no-color] LEVEL MESSAGE...
+
+DESCRIPTION
+    Prints the message prefixed with its level, which is info, success, warn or error. Warnings
+    and errors are written to the standard error, and the other messages to the standard output.
+    The level is colored if the output is a terminal and the NO_COLOR variable is not set.
+
+OPTIONS
+    -t, --timestamp
+        prefix the message with the UTC date and time
+    -s, --script
+        prefix the message with the name of the script being run
+    --color, --no-color
+        color the level, or not, whether the output is a terminal or not
+
+EXAMPLES
+    log info copying $len(@files) files
+    log --timestamp warn disk almost full
+    log error cannot reach $host"
+)]
+pub fn log(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (options, level, message) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: log: {}", why)),
+    };
+
+    let color = options.color.unwrap_or_else(|| {
+        let stream =
+            if level.is_diagnostic() { atty::Stream::Stderr } else { atty::Stream::Stdout };
+        atty::is(stream) && env::var_os("NO_COLOR").is_none()
+    });
+    let mut line = String::new();
+    if options.timestamp {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        line.push_str(&timestamp(now.as_secs()));
+        line.push(' ');
+    }
+    if options.script {
+        if let Some(name) = script_name(shell) {
+            line.push_str(&name);
+            line.push_str(": ");
+        }
+    }
+    if color {
+        line.push_str(&format!("{}[{}]\x1B[0m ", level.color(), level.tag()));
+    } else {
+        line.push_str(&format!("[{}] ", level.tag()));
+    }
+    line.push_str(&message.iter().join(" "));
+    line.push('\n');
+
+    let written = if level.is_diagnostic() {
+        io::stderr().write_all(line.as_bytes())
+    } else {
+        io::stdout().write_all(line.as_bytes())
+    };
+    match written {
+        Ok(()) => Status::SUCCESS,
+        Err(why) => Status::error(format!("ion: log: {}", why)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_options() {
+        let args: Vec<types::Str> =
+            vec!["-t".into(), "--no-color".into(), "warn".into(), "disk".into(), "full".into()];
+        let (options, level, message) = parse_options(&args).unwrap();
+        assert_eq!(options, Options { timestamp: true, script: false, color: Some(false) });
+        assert_eq!(level, Level::Warn);
+        assert_eq!(message, &args[3..]);
+        assert!(parse_options(&args[..2]).is_err());
+        assert!(parse_options(&["debug".into()]).is_err());
+    }
+
+    #[test]
+    fn log_timestamp() {
+        assert_eq!(timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(timestamp(1_790_000_000), "2026-09-21T14:13:20Z");
+    }
+}
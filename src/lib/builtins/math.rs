@@ -30,6 +30,302 @@ fn calc_or_polish_calc(args: &str) -> Result<Value, CalcError> {
     eval_with_env(args, &mut env).or_else(|_| eval_polish_with_env(args, &mut env))
 }
 
+/// How a result is rounded to its precision
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Rounding {
+    /// To the nearest value, and away from zero halfway
+    Nearest,
+    /// To the nearest value, and to the even one halfway
+    Even,
+    /// Towards positive infinity
+    Up,
+    /// Towards negative infinity
+    Down,
+    /// Towards zero
+    Zero,
+}
+
+impl Rounding {
+    fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "nearest" => Some(Self::Nearest),
+            "even" => Some(Self::Even),
+            "up" | "ceil" => Some(Self::Up),
+            "down" | "floor" => Some(Self::Down),
+            "zero" | "trunc" => Some(Self::Zero),
+            _ => None,
+        }
+    }
+
+    fn apply(self, value: f64) -> f64 {
+        match self {
+            Self::Nearest => value.round(),
+            Self::Even => {
+                let rounded = value.round();
+                if (value - value.trunc()).abs() == 0.5 && rounded % 2. != 0. {
+                    rounded - value.signum()
+                } else {
+                    rounded
+                }
+            }
+            Self::Up => value.ceil(),
+            Self::Down => value.floor(),
+            Self::Zero => value.trunc(),
+        }
+    }
+}
+
+/// The largest precision, beyond which the decimals of a double are no longer significant
+pub(crate) const MAX_PRECISION: usize = 17;
+
+/// A number of decimals, from 0 to `MAX_PRECISION`
+pub(crate) fn parse_precision(text: &str) -> Option<usize> {
+    text.parse().ok().filter(|&precision| precision <= MAX_PRECISION)
+}
+
+/// The number rounded to a number of decimals, and written with all of them
+pub(crate) fn with_precision(value: f64, precision: usize, rounding: Rounding) -> String {
+    let scale = 10f64.powi(precision as i32);
+    let rounded = rounding.apply(value * scale) / scale;
+    // Avoid printing -0
+    let rounded = if rounded == 0. { 0. } else { rounded };
+    format!("{:.*}", precision, rounded)
+}
+
+/// A token of an expression read by `evaluate`
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.char_indices().peekable();
+    while let Some(&(start, character)) = chars.peek() {
+        if character.is_whitespace() {
+            chars.next();
+        } else if character.is_ascii_digit() || character == '.' {
+            let mut end = start;
+            while let Some(&(index, character)) = chars.peek() {
+                let exponent = matches!(character, 'e' | 'E');
+                let signed = matches!(character, '+' | '-')
+                    && matches!(expression[..index].chars().last(), Some('e') | Some('E'));
+                if !(character.is_ascii_digit() || character == '.' || exponent || signed) {
+                    break;
+                }
+                end = index + character.len_utf8();
+                chars.next();
+            }
+            let number = &expression[start..end];
+            tokens.push(Token::Number(
+                number.parse().map_err(|_| format!("invalid number '{}'", number))?,
+            ));
+        } else if character.is_alphabetic() || character == '_' {
+            let mut name = String::new();
+            while let Some(&(_, character)) = chars.peek() {
+                if !(character.is_alphanumeric() || character == '_') {
+                    break;
+                }
+                name.push(character);
+                chars.next();
+            }
+            tokens.push(Token::Name(name));
+        } else {
+            chars.next();
+            tokens.push(match character {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                ',' => Token::Comma,
+                '*' if chars.peek().map(|&(_, next)| next) == Some('*') => {
+                    chars.next();
+                    Token::Operator('^')
+                }
+                '+' | '-' | '*' | '/' | '%' | '^' => Token::Operator(character),
+                _ => return Err(format!("unexpected character '{}'", character)),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser evaluating an expression as it reads it
+struct Evaluator {
+    tokens:   Vec<Token>,
+    position: usize,
+}
+
+impl Evaluator {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.position) }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// A sum or a difference of terms
+    fn expression(&mut self) -> Result<f64, String> {
+        let mut value = self.term()?;
+        while let Some(&Token::Operator(operator @ ('+' | '-'))) = self.peek() {
+            self.next();
+            let term = self.term()?;
+            value = if operator == '+' { value + term } else { value - term };
+        }
+        Ok(value)
+    }
+
+    /// A product, quotient or remainder of factors
+    fn term(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        while let Some(&Token::Operator(operator @ ('*' | '/' | '%'))) = self.peek() {
+            self.next();
+            let factor = self.unary()?;
+            value = match operator {
+                '*' => value * factor,
+                '/' => value / factor,
+                _ => value % factor,
+            };
+        }
+        Ok(value)
+    }
+
+    /// A signed power, so that `-2^2` is -4
+    fn unary(&mut self) -> Result<f64, String> {
+        match self.peek() {
+            Some(Token::Operator('-')) => {
+                self.next();
+                Ok(-self.unary()?)
+            }
+            Some(Token::Operator('+')) => {
+                self.next();
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    /// A power, whose exponent is raised first, as in `2^3^2`
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.primary()?;
+        if self.peek() == Some(&Token::Operator('^')) {
+            self.next();
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn primary(&mut self) -> Result<f64, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Open) => {
+                let value = self.expression()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(value),
+                    _ => Err("missing closing parenthesis".into()),
+                }
+            }
+            Some(Token::Name(name)) if self.peek() == Some(&Token::Open) => {
+                self.next();
+                let mut args = Vec::new();
+                if self.peek() == Some(&Token::Close) {
+                    self.next();
+                } else {
+                    loop {
+                        args.push(self.expression()?);
+                        match self.next() {
+                            Some(Token::Comma) => (),
+                            Some(Token::Close) => break,
+                            _ => return Err("missing closing parenthesis".into()),
+                        }
+                    }
+                }
+                call(&name, &args)
+            }
+            Some(Token::Name(name)) => match name.as_str() {
+                "pi" => Ok(std::f64::consts::PI),
+                "tau" => Ok(std::f64::consts::TAU),
+                "e" => Ok(std::f64::consts::E),
+                "inf" => Ok(f64::INFINITY),
+                _ => Err(format!("unknown constant '{}'", name)),
+            },
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of expression".into()),
+        }
+    }
+}
+
+/// Call a function of the calculator
+fn call(name: &str, args: &[f64]) -> Result<f64, String> {
+    let unary: Option<fn(f64) -> f64> = match name {
+        "sin" => Some(f64::sin),
+        "cos" => Some(f64::cos),
+        "tan" => Some(f64::tan),
+        "asin" => Some(f64::asin),
+        "acos" => Some(f64::acos),
+        "atan" => Some(f64::atan),
+        "sinh" => Some(f64::sinh),
+        "cosh" => Some(f64::cosh),
+        "tanh" => Some(f64::tanh),
+        "sqrt" => Some(f64::sqrt),
+        "cbrt" => Some(f64::cbrt),
+        "exp" => Some(f64::exp),
+        "ln" => Some(f64::ln),
+        "log2" => Some(f64::log2),
+        "log10" => Some(f64::log10),
+        "abs" => Some(f64::abs),
+        "floor" => Some(f64::floor),
+        "ceil" => Some(f64::ceil),
+        "round" => Some(f64::round),
+        "trunc" => Some(f64::trunc),
+        "sign" => Some(f64::signum),
+        _ => None,
+    };
+    match (unary, args) {
+        (Some(function), &[value]) => Ok(function(value)),
+        (Some(_), _) => Err(format!("{} takes one argument", name)),
+        (None, _) => match (name, args) {
+            ("log", &[value]) => Ok(value.log10()),
+            ("log", &[value, base]) => Ok(value.log(base)),
+            ("pow", &[base, exponent]) => Ok(base.powf(exponent)),
+            ("atan2", &[y, x]) => Ok(y.atan2(x)),
+            ("hypot", &[x, y]) => Ok(x.hypot(y)),
+            ("min", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |min, &x| min.min(x))),
+            ("max", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |max, &x| max.max(x))),
+            ("log" | "pow" | "atan2" | "hypot" | "min" | "max", _) => {
+                Err(format!("wrong number of arguments for {}", name))
+            }
+            _ => Err(format!("unknown function '{}'", name)),
+        },
+    }
+}
+
+/// Evaluate an infix expression of floating-point numbers, with functions such as `sin(x)` or
+/// `log(x, base)`, and the constants `pi`, `tau` and `e`
+pub(crate) fn evaluate(expression: &str) -> Result<f64, String> {
+    let mut evaluator = Evaluator { tokens: tokenize(expression)?, position: 0 };
+    let value = evaluator.expression()?;
+    match evaluator.peek() {
+        None => Ok(value),
+        Some(token) => Err(format!("unexpected {:?}", token)),
+    }
+}
+
+/// The value of an expression: the calculator's, or `evaluate`'s for the expressions calling
+/// functions, which it does not support
+pub(crate) fn calculate(expression: &str) -> Result<String, String> {
+    match calc_or_polish_calc(expression) {
+        Ok(value) => Ok(value.to_string()),
+        Err(why) => {
+            evaluate(expression).map(|value| value.to_string()).map_err(|_| why.to_string())
+        }
+    }
+}
+
 fn calc_or_polish_calc_with_env(
     args: &str,
     env: &mut impl calc::parse::Environment,
@@ -37,14 +333,83 @@ fn calc_or_polish_calc_with_env(
     eval_with_env(args, env).or_else(|_| eval_polish_with_env(args, env))
 }
 
+/// How `math` prints its results
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    precision: Option<usize>,
+    rounding:  Option<Rounding>,
+}
+
+impl Options {
+    fn print(&self, expression: &str) -> Status {
+        let value = match calculate(expression) {
+            Ok(value) => value,
+            Err(why) => return Status::error(why),
+        };
+        if self.precision.is_none() && self.rounding.is_none() {
+            println!("{}", value);
+            return Status::SUCCESS;
+        }
+        match value.parse::<f64>() {
+            Ok(number) => {
+                let precision = self.precision.unwrap_or(0);
+                println!(
+                    "{}",
+                    with_precision(number, precision, self.rounding.unwrap_or(Rounding::Nearest))
+                );
+                Status::SUCCESS
+            }
+            Err(_) => Status::error(format!("ion: math: cannot round '{}'", value)),
+        }
+    }
+}
+
+fn parse_options(args: &[crate::types::Str]) -> Result<(Options, &[crate::types::Str]), String> {
+    let mut options = Options::default();
+    let mut args = args;
+    while let Some((arg, rest)) = args.split_first() {
+        let value = || rest.first().ok_or(format!("option '{}' requires a value", arg));
+        match arg.as_str() {
+            "-p" | "--precision" => {
+                let precision = value()?;
+                options.precision = Some(parse_precision(precision).ok_or(format!(
+                    "invalid precision '{}': expected 0 to {}",
+                    precision, MAX_PRECISION
+                ))?);
+            }
+            "-r" | "--round" => {
+                let mode = value()?;
+                options.rounding =
+                    Some(Rounding::parse(mode).ok_or(format!("invalid rounding mode '{}'", mode))?);
+            }
+            "--" => return Ok((options, rest)),
+            _ => return Ok((options, args)),
+        }
+        args = &rest[1..];
+    }
+    Ok((options, args))
+}
+
 #[builtin(
     desc = "Floating-point calculator",
     man = "
 SYNOPSIS
-    math [EXPRESSION]
+    math [--precision DIGITS] [--round MODE] [--] [EXPRESSION]
 
 DESCRIPTION
-    Evaluates arithmetic expressions
+    Evaluates arithmetic expressions, read from the standard input if none is given. Besides the
+    operators, the expressions in infix notation may call the functions sin, cos, tan, asin, acos,
+    atan, atan2(y, x), sinh, cosh, tanh, sqrt, cbrt, exp, ln, log (in base 10, or log(x, base)),
+    log2, log10, pow(x, y), hypot(x, y), abs, sign, floor, ceil, round, trunc, min and max, and
+    use the constants pi, tau and e.
+
+OPTIONS
+    -p, --precision DIGITS
+        print the result rounded to this number of decimals, from 0 to 17, and with all of them
+    -r, --round MODE
+        how the result is rounded: nearest, the default, rounds halfway away from zero, even
+        rounds halfway to the even digit, and up, down or zero round towards positive infinity,
+        negative infinity or zero. Without a precision, the result is rounded to an integer.
 
 SPECIAL EXPRESSIONS
     help (only in interactive mode)
@@ -70,19 +435,19 @@ EXAMPLES
     Add two plus two in polish notation
         math + 2 2
 
+    Print the sine of a quarter turn with three decimals
+        math -p 3 'sin(pi / 4)'
+
 AUTHOR
     Written by Hunter Goldstein."
 )]
 pub fn math(args: &[crate::types::Str], _: &mut crate::Shell<'_>) -> Status {
-    if args.get(1).is_some() {
-        let result = calc_or_polish_calc(&args[1..].join(" "));
-        match result {
-            Ok(v) => {
-                println!("{}", v);
-                Status::SUCCESS
-            }
-            Err(e) => Status::error(format!("{}", e)),
-        }
+    let (options, args) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: math: {}", why)),
+    };
+    if !args.is_empty() {
+        options.print(&args.join(" "))
     } else if atty::is(atty::Stream::Stdin) {
         println!("{}", REPL_GUIDE);
         let mut context = Context::new();
@@ -116,14 +481,41 @@ pub fn math(args: &[crate::types::Str], _: &mut crate::Shell<'_>) -> Status {
     } else {
         let mut input = String::with_capacity(1024);
         io::stdin().read_to_string(&mut input).unwrap();
+        options.print(&input)
+    }
+}
 
-        let result = calc_or_polish_calc(&input);
-        match result {
-            Ok(v) => {
-                println!("{}", v);
-                Status::SUCCESS
-            }
-            Err(e) => Status::error(format!("{}", e)),
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluation() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7.));
+        assert_eq!(evaluate("-2 ^ 2"), Ok(-4.));
+        assert_eq!(evaluate("2 ** 3 ^ 2"), Ok(512.));
+        assert_eq!(evaluate("min(4, max(1, 2), 3) % 2"), Ok(0.));
+        assert_eq!(evaluate("log(8, 2) + 1.5e1"), Ok(18.));
+        assert_eq!(evaluate("cos(pi)"), Ok(-1.));
+        assert!(evaluate("sin(1, 2)").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("foo").is_err());
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(with_precision(2.5, 0, Rounding::Even), "2");
+        assert_eq!(with_precision(-3.5, 0, Rounding::Even), "-4");
+        assert_eq!(with_precision(2.345, 2, Rounding::Down), "2.34");
+        assert_eq!(with_precision(-0.01, 1, Rounding::Nearest), "0.0");
+        assert_eq!(with_precision(1., 3, Rounding::Up), "1.000");
+    }
+
+    #[test]
+    fn precision() {
+        assert_eq!(parse_precision("0"), Some(0));
+        assert_eq!(parse_precision("17"), Some(17));
+        assert_eq!(parse_precision("18"), None);
+        assert_eq!(parse_precision("-1"), None);
     }
 }
@@ -2,36 +2,68 @@
 /// helpers for creating help
 pub mod man_pages;
 
+mod argparse;
 mod command_info;
 mod conditionals;
+mod confirm;
+pub(crate) mod datetime;
 mod echo;
 mod exists;
+mod features;
+#[cfg(feature = "http")]
+mod fetch;
 mod functions;
+mod hash;
 mod helpers;
 mod is;
 mod job_control;
-mod math;
-mod random;
+mod lock;
+mod log;
+pub(crate) mod math;
+mod printf;
+pub(crate) mod random;
+mod read;
+pub(crate) mod resources;
+mod retry;
+#[cfg(feature = "secrets")]
+mod secret;
 mod set;
 mod source;
 mod status;
 mod test;
+mod trap;
 mod variables;
 
+#[cfg(feature = "http")]
+pub use self::fetch::builtin_fetch;
+#[cfg(feature = "secrets")]
+pub use self::secret::builtin_secret;
 pub use self::{
-    command_info::builtin_which,
+    argparse::builtin_argparse,
+    command_info::{builtin_type_, builtin_which},
     conditionals::{builtin_contains, builtin_ends_with, builtin_starts_with},
+    confirm::builtin_confirm,
+    datetime::builtin_datetime,
     echo::builtin_echo,
     exists::builtin_exists,
+    features::{builtin_features, builtin_has_feature},
     functions::builtin_fn_,
+    hash::builtin_hash,
     helpers::Status,
     is::builtin_is,
+    lock::{builtin_lock, builtin_unlock},
+    log::builtin_log,
     man_pages::check_help,
     math::builtin_math,
+    printf::builtin_printf,
+    read::builtin_read,
+    resources::{builtin_ionice, builtin_nice, builtin_ulimit},
+    retry::builtin_retry,
     set::builtin_set,
-    source::builtin_source,
+    source::{builtin_import, builtin_source},
     status::builtin_status,
     test::builtin_test,
+    trap::builtin_trap,
     variables::{builtin_alias, builtin_drop, builtin_unalias},
 };
 use crate as ion_shell;
@@ -41,13 +73,12 @@ use crate::{
 };
 use builtins_proc::builtin;
 use itertools::Itertools;
-use liner::{Completer, Context, Prompt};
 use mktemp::Temp;
 use std::{
     borrow::Cow,
     collections::HashMap,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{BufRead, BufReader},
     path::{Path, PathBuf},
     process::{Command, Stdio},
 };
@@ -160,29 +191,43 @@ impl<'a> BuiltinMap<'a> {
 
     /// Create and control variables
     ///
-    /// Contains `fn`, `alias`, `unalias`, `drop`, `read`
+    /// Contains `fn`, `alias`, `unalias`, `drop`, `read`, `confirm`, and `secret` with the
+    /// `secrets` feature
     pub fn with_variables(&mut self) -> &mut Self {
         self.add("fn", &builtin_fn_, "Print list of functions")
             .add("alias", &builtin_alias, "View, set or unset aliases")
             .add("unalias", &builtin_unalias, "Delete an alias")
             .add("drop", &builtin_drop, "Delete a variable")
             .add("read", &builtin_read, "Read some variables\n    read <variable>")
+            .add("confirm", &builtin_confirm, "Ask a question answered with yes or no");
+        #[cfg(feature = "secrets")]
+        self.add("secret", &builtin_secret, "Manage the encrypted secret store");
+        self
     }
 
     /// Control subrpocesses states
     ///
-    /// Contains `disown`, `bg`, `fg`, `wait`, `isatty`, `jobs`
+    /// Contains `disown`, `bg`, `fg`, `kill`, `wait`, `trap`, `isatty`, `jobs`, `retry`, `lock`,
+    /// `unlock`, `ulimit`, `nice`, `ionice`
     pub fn with_process_control(&mut self) -> &mut Self {
         self.add("disown", &builtin_disown, DISOWN_DESC)
             .add("bg", &builtin_bg, "Resumes a stopped background process")
             .add("fg", &builtin_fg, "Resumes and sets a background process as the active process")
+            .add("kill", &builtin_kill, "Sends a signal to jobs and processes")
             .add(
                 "wait",
                 &builtin_wait,
                 "Waits until all running background processes have completed",
             )
+            .add("trap", &builtin_trap, "Runs commands on signals, on exit and on failures")
             .add("isatty", &builtin_isatty, "Returns 0 exit status if the supplied FD is a tty")
             .add("jobs", &builtin_jobs, "Displays all jobs that are attached to the background")
+            .add("retry", &builtin_retry, "Runs a command again until it succeeds")
+            .add("lock", &builtin_lock, "Holds a lock on a file while running commands")
+            .add("unlock", &builtin_unlock, "Releases a lock taken by lock")
+            .add("ulimit", &builtin_ulimit, "Get and set the limits of the resources of processes")
+            .add("nice", &builtin_nice, "Run a program with a lower or higher priority")
+            .add("ionice", &builtin_ionice, "Run a program with another I/O priority")
     }
 
     /// Utilities concerning the filesystem
@@ -230,14 +275,35 @@ impl<'a> BuiltinMap<'a> {
 
     /// Basic utilities for any ion embedded library
     ///
-    /// Contains `help`, `source`, `status`, `echo`, `type`, `which`
+    /// Contains `help`, `source`, `import`, `status`, `echo`, `printf`, `log`, `type`, `which`,
+    /// `features`, `has-feature`, `datetime`, `argparse`, `hash`
     pub fn with_basic(&mut self) -> &mut Self {
         self.add("help", &builtin_help, HELP_DESC)
             .add("source", &builtin_source, SOURCE_DESC)
+            .add(
+                "import",
+                &builtin_import,
+                "Evaluate a file, defining its functions and variables in a namespace",
+            )
             .add("status", &builtin_status, "Evaluates the current runtime status")
             .add("echo", &builtin_echo, "Display a line of text")
+            .add("printf", &builtin_printf, "Print arguments through a format")
+            .add("log", &builtin_log, "Print a message with its level")
             .add("which", &builtin_which, "indicates what would be called for a given command")
-            .add("type", &builtin_which, "indicates what would be called for a given command")
+            .add("type", &builtin_type_, "Describe what command names resolve to")
+            .add("features", &builtin_features, "List the optional features of the shell")
+            .add("has-feature", &builtin_has_feature, "Test if the shell has an optional feature")
+            .add("datetime", &builtin_datetime, "Print and compute dates and times")
+            .add("argparse", &builtin_argparse, "Parse the options given to a script")
+            .add("hash", &builtin_hash, "List or reset the paths of the programs run")
+    }
+
+    /// Network utilities, which reach other hosts. Not included by default
+    ///
+    /// Contains `fetch`
+    #[cfg(feature = "http")]
+    pub fn with_network(&mut self) -> &mut Self {
+        self.add("fetch", &builtin_fetch, "Make an HTTP request")
     }
 
     /// Utilities that may be a security risk. Not included by default
@@ -342,15 +408,38 @@ pub fn dir_depth(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "Change directory.",
     man = "
 SYNOPSIS
-    cd DIRECTORY
+    cd [DIRECTORY | - | +N | -N]
 
 DESCRIPTION
     Without arguments cd changes the working directory to your home directory.
     With arguments cd changes the working directory to the directory you provided.
+
+    The current directory is the top of the directory stack, which cd replaces. With -, cd
+    changes to the previous directory, swapping the two top directories of the stack when it is
+    the one under the top. With +N or -N, cd changes to the Nth directory of the stack, counted
+    from the top or from the bottom starting at 0, and moves it to the top. The stack is listed
+    by dirs, and is the array @dirstack.
 "
 )]
 pub fn cd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     let err = match args.get(1) {
+        Some(arg) if parse_numeric_arg(arg).is_some() => {
+            let len = shell.dir_stack().dirs().len();
+            let index = match parse_numeric_arg(arg) {
+                Some((true, num)) => Some(num),
+                Some((false, num)) => (len - 1).checked_sub(num),
+                None => None,
+            };
+            match index {
+                Some(index) => shell.dir_stack_mut().move_to_top(index),
+                None => {
+                    return Status::error(format!(
+                        "ion: cd: {}: directory stack index out of range",
+                        arg
+                    ))
+                }
+            }
+        }
         Some(dir) => {
             let dir = dir.as_str();
             if let Some(Value::Array(cdpath)) = shell.variables().get("CDPATH").cloned() {
@@ -416,10 +505,23 @@ pub fn bool(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "prints the directory stack",
     man = "
 SYNOPSIS
-    dirs
+    dirs [-c] [-l] [-p] [-v] [+N | -N]
 
 DESCRIPTION
-    dirs prints the current directory stack.
+    dirs prints the current directory stack, the current directory first. The stack is also the
+    array @dirstack.
+
+OPTIONS
+    -c
+        clears the stack but for the current directory
+    -l
+        prints absolute paths
+    -p
+        prints one directory per line
+    -v
+        prints one directory per line, after its index in the stack
+    +N, -N
+        prints only the Nth directory, counted from the top or from the bottom starting at 0
 "
 )]
 pub fn dirs(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
@@ -489,10 +591,17 @@ pub fn dirs(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "push a directory to the directory stack",
     man = "
 SYNOPSIS
-    pushd DIRECTORY
+    pushd [-n] [DIRECTORY | +N | -N]
 
 DESCRIPTION
-    pushd pushes a directory to the directory stack.
+    pushd pushes a directory to the directory stack, and changes to it. Without arguments, pushd
+    swaps the two top directories of the stack. With +N or -N, pushd rotates the stack so that
+    its Nth directory, counted from the top or from the bottom starting at 0, is on the top.
+    The stack is then printed.
+
+OPTIONS
+    -n
+        keeps the current directory, pushing the directory under the top
 "
 )]
 pub fn pushd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
@@ -566,12 +675,17 @@ pub fn pushd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "shift through the directory stack",
     man = "
 SYNOPSIS
-    popd
+    popd [-n] [+N | -N]
 
 DESCRIPTION
-    popd removes the top directory from the directory stack and changes the working directory to \
-           the new top directory.
+    popd removes the top directory from the directory stack and changes the working directory to
+    the new top directory. With +N or -N, popd removes the Nth directory of the stack instead,
+    counted from the top or from the bottom starting at 0. The stack is then printed.
     pushd adds directories to the stack.
+
+OPTIONS
+    -n
+        keeps the current directory, removing the directory under the top
 "
 )]
 pub fn popd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
@@ -624,60 +738,39 @@ pub fn popd(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     }
 }
 
-struct EmptyCompleter;
-
-impl Completer for EmptyCompleter {
-    fn completions(&mut self, _start: &str) -> Vec<String> { Vec::new() }
-}
-
-#[builtin(
-    desc = "read a line of input into some variables",
-    man = "
-SYNOPSIS
-    read VARIABLES...
-
-DESCRIPTION
-    For each variable reads from standard input and stores the results in the variable.
-"
-)]
-pub fn read(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    if atty::is(atty::Stream::Stdin) {
-        let mut con = Context::new();
-        for arg in args.iter().skip(1) {
-            match con.read_line(Prompt::from(format!("{}=", arg.trim())), None, &mut EmptyCompleter)
-            {
-                Ok(buffer) => {
-                    shell.variables_mut().set(arg.as_ref(), buffer.trim());
-                }
-                Err(_) => return Status::FALSE,
-            }
-        }
-    } else {
-        let stdin = io::stdin();
-        let handle = stdin.lock();
-        let mut lines = handle.lines();
-        for arg in args.iter().skip(1) {
-            if let Some(Ok(line)) = lines.next() {
-                shell.variables_mut().set(arg.as_ref(), line.trim());
-            }
-        }
-    }
-    Status::SUCCESS
-}
-
 #[builtin(
     desc = "evaluates the specified commands",
     man = "
 SYNOPSIS
-    eval COMMANDS...
+    eval [--isolated] COMMANDS...
 
 DESCRIPTION
     eval evaluates the given arguments as a command. If more than one argument is given,
-    all arguments are joined using a space as a separator."
+    all arguments are joined using a space as a separator. The commands are parsed and run as
+    those of a script, and the status of the last one is returned.
+
+OPTIONS
+    --isolated
+        Run the commands in a scope of their own, as the body of a function. The variables and
+        functions they define or assign are discarded once they ran, while those defined
+        before remain readable. The variables set with global or export still outlive them."
 )]
 pub fn eval(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    shell.execute_command(args[1..].join(" ").as_bytes()).unwrap_or_else(|_| {
-        Status::error("ion: supplied eval expression was not terminated".to_string())
+    let (isolated, commands) = match args.get(1) {
+        Some(arg) if arg.as_str() == "--isolated" => (true, &args[2..]),
+        _ => (false, &args[1..]),
+    };
+    if isolated {
+        shell.variables_mut().new_scope(true);
+    }
+    let result = shell.execute_command(commands.join(" ").as_bytes());
+    if isolated {
+        shell.variables_mut().pop_scope();
+    }
+    result.unwrap_or_else(|why| {
+        // The blocks left open by the commands must not take the statements which follow
+        shell.reset_flow();
+        Status::error(format!("ion: eval: {}", why))
     })
 }
 
@@ -687,17 +780,37 @@ pub fn eval(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
 SYNOPSIS
     random
     random START END
+    random int MIN MAX
+    random choice VALUES...
+    random bytes COUNT [--hex]
+    random seed SEED
 
 DESCRIPTION
     random generates a pseudo-random integer. IT IS NOT SECURE.
     The range depends on what arguments you pass. If no arguments are given the range is [0, \
            32767].
-    If two arguments are given the range is [START, END]."
+    If two arguments are given the range is [START, END].
+
+    random int prints an integer from MIN to MAX, both included, random choice prints one of the
+    values, and random bytes prints the given number of bytes, or their hexadecimal digits.
+
+    random seed seeds the generator of the shell, which then draws the same numbers on every run,
+    for scripts which must be reproducible. A single argument which is not a subcommand is an
+    error, rather than a seed. The numbers are also drawn by the variables of the
+    rand:: namespace: ${rand::u8}, ${rand::u16}, ${rand::u32}, ${rand::u64}, ${rand::i64},
+    ${rand::float}, which is from 0 to 1, and ${rand::bool}. A command substitution is run by
+    another process, whose draws do not change the next numbers of the shell, so that
+    ${rand::u32} is preferable to $(random) after seeding.
+
+EXAMPLES
+    random seed 42
+    let dice = ${rand::u8}
+    random choice rock paper scissors"
 )]
-pub fn random(args: &[types::Str], _: &mut Shell<'_>) -> Status {
-    match random::random(&args[1..]) {
+pub fn random(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    match random::random(&args[1..], shell.rng_mut()) {
         Ok(()) => Status::SUCCESS,
-        Err(why) => Status::error(why),
+        Err(why) => Status::error(format!("ion: random: {}", why)),
     }
 }
 
@@ -729,17 +842,61 @@ pub fn false_(args: &[types::Str], _: &mut Shell<'_>) -> Status { Status::FALSE
     desc = "wait for a background job",
     man = "
 SYNOPSIS
-    wait
+    wait [--any | --all] [--timeout SECONDS] [--pid VAR] [--status VAR] [JOB | PID...]
 
 DESCRIPTION
-    Wait for the background jobs to finish"
+    Wait for the given background jobs to finish, or for all of the running jobs if none are
+    given. The exit status is the one of the job ending the wait, or 124 if the timeout is reached.
+    The status of a job which already finished is kept until it is waited on.
+
+OPTIONS
+    --all
+        wait for all of the jobs to finish, which is the default
+    --any
+        wait for the first of the jobs to finish
+    --timeout SECONDS
+        stop waiting once SECONDS elapsed
+    --pid VAR
+        store the process ID of the job ending the wait into VAR
+    --status VAR
+        store the exit status of the job ending the wait into VAR
+
+JOB SPECIFIERS
+    %N          the job numbered N, as listed by jobs
+    %+, %%, %   the current job, which was last sent to the background
+    %-          the previous job
+    %STRING     the job whose command starts with STRING
+    PID         the job whose first process has the process ID PID"
 )]
 pub fn wait(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    if let Err(err) = shell.wait_for_background() {
-        Status::error(err.to_string())
-    } else {
-        Status::SUCCESS
-    }
+    job_control::wait(shell, &args[1..])
+}
+
+#[builtin(
+    desc = "send a signal to jobs and processes",
+    man = "
+SYNOPSIS
+    kill [-s SIGNAL | -SIGNAL] [--] TARGET...
+    kill -l [SIGNAL | STATUS...]
+
+DESCRIPTION
+    Sends a signal to each target, SIGTERM by default. A target is either a process ID, a job
+    specifier starting with %, in which case the signal is sent to every process of the job, or
+    after --, the negated ID of a process group. The signal is given by its number, or by its
+    name with or without the SIG prefix. The signal 0 is not sent, but checks that the targets
+    exist and may be signaled.
+
+    With -l, prints the names of the signals, or the name of each signal given by its number or
+    by the exit status of a command it killed, or the number of each signal given by its name.
+
+JOB SPECIFIERS
+    %N          the job numbered N, as listed by jobs
+    %+, %%, %   the current job, which was last sent to the background
+    %-          the previous job
+    %STRING     the job whose command starts with STRING"
+)]
+pub fn kill(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    job_control::kill(shell, &args[1..])
 }
 
 #[builtin(
@@ -760,10 +917,11 @@ pub fn jobs(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "sends jobs to background",
     man = "
 SYNOPSIS
-    bg PID
+    bg [JOB...]
 
 DESCRIPTION
-    bg sends the job to the background resuming it if it has stopped."
+    bg sends the jobs to the background resuming them if they have stopped. Without arguments,
+    the current job is resumed. The jobs are given by job specifiers, as described by wait."
 )]
 pub fn bg(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     job_control::bg(shell, &args[1..])
@@ -773,10 +931,12 @@ pub fn bg(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "bring job to the foreground",
     man = "
 SYNOPSIS
-    fg PID
+    fg [JOB...]
 
 DESCRIPTION
-    fg brings the specified job to foreground resuming it if it has stopped."
+    fg brings the specified job to foreground resuming it if it has stopped. Without arguments,
+    the current job is brought to the foreground. The jobs are given by job specifiers, as
+    described by wait."
 )]
 pub fn fg(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     job_control::fg(shell, &args[1..])
@@ -786,16 +946,19 @@ pub fn fg(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     desc = "disown processes",
     man = "
 SYNOPSIS
-    disown [ --help | -r | -h | -a ][PID...]
+    disown [ --help | -r | -h | -a ][JOB...]
 
 DESCRIPTION
-    Disowning a process removes that process from the shell's background process table.
+    Disowning a process removes that process from the shell's background process table, so that
+    it is neither listed by jobs nor sent SIGHUP when the shell exits with huponexit set. Without
+    arguments, the current job is disowned. The jobs are given by job specifiers, as described
+    by wait.
 
 OPTIONS
     -r  Remove all running jobs from the background process list.
     -h  Specifies that each job supplied will not receive the SIGHUP signal when the shell \
            receives a SIGHUP.
-    -a  If no job IDs were supplied, remove all jobs from the background process list."
+    -a  Remove all jobs from the background process list."
 )]
 pub fn disown(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     match job_control::disown(shell, &args[1..]) {
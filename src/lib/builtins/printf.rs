@@ -0,0 +1,532 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{shell::reporting, types, Shell};
+use builtins_proc::builtin;
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    io::{self, Write},
+    iter::Peekable,
+    slice,
+    str::Chars,
+};
+
+/// The flags, width and precision of a conversion
+#[derive(Debug, Default, Clone, Copy)]
+struct Spec {
+    left:      bool,
+    plus:      bool,
+    space:     bool,
+    zero:      bool,
+    alternate: bool,
+    width:     usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pad the number with its sign or prefix to the width, with zeros after the prefix when
+    /// asked to and allowed
+    fn pad_number(&self, prefix: &str, digits: &str, zeros: bool) -> String {
+        let len = prefix.len() + digits.chars().count();
+        let fill = self.width.saturating_sub(len);
+        if self.left {
+            format!("{}{}{}", prefix, digits, " ".repeat(fill))
+        } else if self.zero && zeros {
+            format!("{}{}{}", prefix, "0".repeat(fill), digits)
+        } else {
+            format!("{}{}{}", " ".repeat(fill), prefix, digits)
+        }
+    }
+
+    /// Pad the text to the width with spaces
+    fn pad(&self, text: &[u8]) -> Vec<u8> {
+        let fill =
+            " ".repeat(self.width.saturating_sub(String::from_utf8_lossy(text).chars().count()));
+        if self.left {
+            [text, fill.as_bytes()].concat()
+        } else {
+            [fill.as_bytes(), text].concat()
+        }
+    }
+
+    /// The sign of a positive or negative number
+    const fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+
+    /// Pad the digits of an integer with zeros up to the precision
+    fn precise(&self, digits: String) -> String {
+        match self.precision {
+            Some(0) if digits == "0" => String::new(),
+            Some(precision) if precision > digits.len() => {
+                format!("{}{}", "0".repeat(precision - digits.len()), digits)
+            }
+            _ => digits,
+        }
+    }
+}
+
+/// The byte given by the digits following the first ones of an escape sequence, up to a number
+/// of them
+fn byte(chars: &mut Peekable<Chars<'_>>, radix: u32, max: usize, mut value: u32) -> u8 {
+    for _ in 0..max {
+        match chars.peek().and_then(|c| c.to_digit(radix)) {
+            Some(digit) => value = value * radix + digit,
+            None => break,
+        }
+        chars.next();
+    }
+    value as u8
+}
+
+/// Interpret the escape sequence following a backslash, or return false for `\c`, after which
+/// nothing more is printed. The octal escapes of `%b` start with a zero, as in `\0NNN`.
+fn escape(chars: &mut Peekable<Chars<'_>>, out: &mut Vec<u8>, zero_octal: bool) -> bool {
+    match chars.next() {
+        Some('a') => out.push(7),
+        Some('b') => out.push(8),
+        Some('c') => return false,
+        Some('e') => out.push(27),
+        Some('f') => out.push(12),
+        Some('n') => out.push(b'\n'),
+        Some('r') => out.push(b'\r'),
+        Some('t') => out.push(b'\t'),
+        Some('v') => out.push(11),
+        Some('x') if chars.peek().map_or(false, char::is_ascii_hexdigit) => {
+            out.push(byte(chars, 16, 2, 0))
+        }
+        Some('0') if zero_octal => out.push(byte(chars, 8, 3, 0)),
+        Some(digit @ '0'..='7') if !zero_octal => {
+            out.push(byte(chars, 8, 2, digit.to_digit(8).unwrap()))
+        }
+        Some(c @ '\\') | Some(c @ '"') | Some(c @ '\'') => out.push(c as u8),
+        Some(c) => out.extend_from_slice(format!("\\{}", c).as_bytes()),
+        None => out.push(b'\\'),
+    }
+    true
+}
+
+/// An integer given in decimal, in octal with a leading zero, in hexadecimal with a leading
+/// `0x`, or as the code of the character following a quote
+fn parse_integer(text: &str) -> Option<i64> {
+    let text = text.trim();
+    if let Some(quoted) = text.strip_prefix('\'').or_else(|| text.strip_prefix('"')) {
+        return Some(quoted.chars().next().map_or(0, |c| c as i64));
+    }
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    if digits.starts_with(|c| c == '+' || c == '-') {
+        return None;
+    }
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if digits.len() > 1 && digits.starts_with('0') {
+        i64::from_str_radix(&digits[1..], 8).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_float(text: &str) -> Option<f64> {
+    text.trim().parse::<f64>().ok().or_else(|| parse_integer(text).map(|value| value as f64))
+}
+
+/// A float in the notation of `%e`, with the exponent given with its sign and two digits at least
+fn exponential(value: f64, precision: usize) -> String {
+    let formatted = format!("{:.*e}", precision, value);
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    format!("{}e{}{:02}", mantissa, if exponent < 0 { '-' } else { '+' }, exponent.abs())
+}
+
+/// Remove the trailing zeros of the fractional part, and the point if none remain
+fn without_trailing_zeros(number: &str) -> String {
+    let (mantissa, exponent) = number.split_at(number.find('e').unwrap_or(number.len()));
+    if !mantissa.contains('.') {
+        return number.into();
+    }
+    format!("{}{}", mantissa.trim_end_matches('0').trim_end_matches('.'), exponent)
+}
+
+/// The digits of a float without its sign, in the notation of the conversion
+fn float_digits(conversion: char, spec: &Spec, value: f64) -> String {
+    if value.is_nan() {
+        return "nan".into();
+    } else if value.is_infinite() {
+        return "inf".into();
+    }
+    let precision = spec.precision.unwrap_or(6);
+    let magnitude = value.abs();
+    let digits = match conversion.to_ascii_lowercase() {
+        'f' => format!("{:.*}", precision, magnitude),
+        'e' => exponential(magnitude, precision),
+        // The notation of %e is taken when the exponent is below -4, or not below the precision
+        _ => {
+            let precision = precision.max(1);
+            let formatted = exponential(magnitude, precision - 1);
+            let exponent: i32 = formatted[formatted.find('e').unwrap() + 1..].parse().unwrap();
+            let number = if exponent < -4 || exponent >= precision as i32 {
+                formatted
+            } else {
+                format!("{:.*}", (precision as i32 - 1 - exponent) as usize, magnitude)
+            };
+            if spec.alternate {
+                number
+            } else {
+                without_trailing_zeros(&number)
+            }
+        }
+    };
+    // The alternate form always has a point
+    match digits.find('e') {
+        _ if !spec.alternate || digits.contains('.') => digits,
+        Some(e) => format!("{}.{}", &digits[..e], &digits[e..]),
+        None => format!("{}.", digits),
+    }
+}
+
+/// An argument as an element of an array, quoted when it would not be read back as one word
+fn element(arg: &str) -> Cow<'_, str> {
+    let special = |c: char| c.is_whitespace() || "'\"\\$@[](){}|&;<>#*?~".contains(c);
+    if !arg.is_empty() && !arg.contains(special) {
+        arg.into()
+    } else if !arg.contains('\'') {
+        format!("'{}'", arg).into()
+    } else {
+        let mut quoted = String::from("\"");
+        for c in arg.chars() {
+            if "\\\"$@".contains(c) {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted.into()
+    }
+}
+
+/// Prints the arguments through a format
+struct Printer<'a> {
+    args:   slice::Iter<'a, types::Str>,
+    errors: Vec<String>,
+}
+
+impl<'a> Printer<'a> {
+    fn next(&mut self) -> &'a str { self.args.next().map_or("", types::Str::as_str) }
+
+    fn integer(&mut self) -> i64 {
+        let arg = self.next();
+        if arg.is_empty() {
+            return 0;
+        }
+        parse_integer(arg).unwrap_or_else(|| {
+            self.errors.push(format!("'{}' is not a valid integer", arg));
+            0
+        })
+    }
+
+    fn float(&mut self) -> f64 {
+        let arg = self.next();
+        if arg.is_empty() {
+            return 0.;
+        }
+        parse_float(arg).unwrap_or_else(|| {
+            self.errors.push(format!("'{}' is not a valid number", arg));
+            0.
+        })
+    }
+
+    /// Print the format once, consuming the arguments its conversions take. Returns false once
+    /// the output is stopped by `\c`.
+    fn print(&mut self, format: &str, out: &mut Vec<u8>) -> Result<bool, String> {
+        let mut chars = format.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    if !escape(&mut chars, out, false) {
+                        return Ok(false);
+                    }
+                }
+                '%' => {
+                    if !self.convert(&mut chars, out)? {
+                        return Ok(false);
+                    }
+                }
+                c => out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read the specification following a `%`, then print the arguments it converts
+    fn convert(
+        &mut self,
+        chars: &mut Peekable<Chars<'_>>,
+        out: &mut Vec<u8>,
+    ) -> Result<bool, String> {
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.peek() {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '0' => spec.zero = true,
+                '#' => spec.alternate = true,
+                _ => break,
+            }
+            chars.next();
+        }
+        if chars.peek() == Some(&'*') {
+            chars.next();
+            let width = self.integer();
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                spec.width = spec.width * 10 + digit as usize;
+                chars.next();
+            }
+        }
+        if chars.peek() == Some(&'.') {
+            chars.next();
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                spec.precision = usize::try_from(self.integer()).ok();
+            } else {
+                let mut precision = 0;
+                while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+                    precision = precision * 10 + digit as usize;
+                    chars.next();
+                }
+                spec.precision = Some(precision);
+            }
+        }
+        // The length modifiers of C are accepted but make no difference
+        while chars.peek().map_or(false, |c| "hlLqjzt".contains(*c)) {
+            chars.next();
+        }
+
+        let conversion = chars.next().ok_or("missing conversion at the end of the format")?;
+        match conversion {
+            '%' => out.push(b'%'),
+            'd' | 'i' => {
+                let value = self.integer();
+                let digits = spec.precise(value.unsigned_abs().to_string());
+                let zeros = spec.precision.is_none();
+                out.extend_from_slice(
+                    spec.pad_number(spec.sign(value < 0), &digits, zeros).as_bytes(),
+                );
+            }
+            'u' | 'o' | 'x' | 'X' => {
+                let value = self.integer() as u64;
+                let (digits, prefix) = match conversion {
+                    'u' => (value.to_string(), ""),
+                    'o' => (format!("{:o}", value), ""),
+                    _ => (
+                        format!("{:x}", value),
+                        if spec.alternate && value != 0 { "0x" } else { "" },
+                    ),
+                };
+                let mut digits = spec.precise(digits);
+                if conversion == 'o' && spec.alternate && !digits.starts_with('0') {
+                    digits.insert(0, '0');
+                }
+                let mut number = spec.pad_number(prefix, &digits, spec.precision.is_none());
+                if conversion == 'X' {
+                    number = number.to_uppercase();
+                }
+                out.extend_from_slice(number.as_bytes());
+            }
+            'f' | 'F' | 'e' | 'E' | 'g' | 'G' => {
+                let value = self.float();
+                let digits = float_digits(conversion, &spec, value);
+                let negative = value.is_sign_negative() && !value.is_nan();
+                let mut number = spec.pad_number(spec.sign(negative), &digits, value.is_finite());
+                if conversion.is_ascii_uppercase() {
+                    number = number.to_uppercase();
+                }
+                out.extend_from_slice(number.as_bytes());
+            }
+            'c' => {
+                let arg = self.next();
+                let c = arg.chars().next().map_or(String::new(), String::from);
+                out.extend(spec.pad(c.as_bytes()));
+            }
+            's' => {
+                let arg = self.next();
+                let text = match spec.precision {
+                    Some(precision) => arg.chars().take(precision).collect(),
+                    None => Cow::Borrowed(arg),
+                };
+                out.extend(spec.pad(text.as_bytes()));
+            }
+            'b' => {
+                let arg = self.next();
+                let mut text = Vec::new();
+                let mut chars = arg.chars().peekable();
+                let mut going = true;
+                while let Some(c) = chars.next() {
+                    if c != '\\' {
+                        text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    } else if !escape(&mut chars, &mut text, true) {
+                        going = false;
+                        break;
+                    }
+                }
+                out.extend(spec.pad(&text));
+                return Ok(going);
+            }
+            'a' => {
+                let elements: Vec<_> = self.args.by_ref().map(|arg| element(arg)).collect();
+                out.extend(spec.pad(format!("[{}]", elements.join(" ")).as_bytes()));
+            }
+            conversion => return Err(format!("invalid conversion '%{}'", conversion)),
+        }
+        Ok(true)
+    }
+}
+
+#[builtin(
+    desc = "print arguments through a format",
+    man = "
+SYNOPSIS
+    printf FORMAT [ARGUMENTS]...
+
+DESCRIPTION
+    Prints the FORMAT on standard output, with each of its conversions replaced by the next of
+    the ARGUMENTS converted as it specifies. The FORMAT is used again as long as arguments
+    remain, and conversions missing arguments take an empty string, or zero.
+
+    Conversions are written as in C, with flags among `-+ 0#`, a width and a precision, either of
+    which may be `*` to take it from the next argument, and one of the following:
+
+        %d, %i  a signed decimal integer
+        %u      an unsigned decimal integer
+        %o      an unsigned octal integer
+        %x, %X  an unsigned hexadecimal integer
+        %f, %F  a decimal float
+        %e, %E  a float in exponential notation
+        %g, %G  a float as %f or %e, whichever is shorter
+        %c      the first character of the argument
+        %s      the argument
+        %b      the argument with its backslash escapes interpreted, as `\\0NNN` for octal bytes
+        %a      all the remaining arguments, as an array
+        %%      a percent sign
+
+    Integers may be given in octal with a leading 0, in hexadecimal with a leading 0x, or as the
+    code of the character following a quote. Floats are always written with a point, whatever
+    the locale.
+
+    The FORMAT interprets the escapes of `echo -e`, along with `\\NNN` for octal bytes and
+    `\\xHH` for hexadecimal ones.
+
+EXAMPLES
+    printf '%-10s|%5.2f\\n' pi 3.14159
+    printf '%s=%d\\n' one 1 two 2
+    printf '%a\\n' @array"
+)]
+pub fn printf(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let format = match args.get(1) {
+        Some(format) => format,
+        None => return Status::bad_argument("ion: printf: no format given"),
+    };
+    let mut printer = Printer { args: args[2..].iter(), errors: Vec::new() };
+    let mut out = Vec::new();
+    let result = loop {
+        let remaining = printer.args.len();
+        match printer.print(format, &mut out) {
+            Ok(true) if printer.args.len() > 0 && printer.args.len() < remaining => (),
+            Ok(_) => break Ok(()),
+            Err(why) => break Err(why),
+        }
+    };
+
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    if let Err(why) = stdout.write_all(&out).and_then(|_| stdout.flush()) {
+        return Status::error(format!("ion: printf: {}", why));
+    }
+    if let Err(why) = result {
+        return Status::bad_argument(format!("ion: printf: {}", why));
+    }
+    for error in &printer.errors {
+        reporting::report(format!("ion: printf: {}", error));
+    }
+    Status::from(printer.errors.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(format: &str, args: &[&str]) -> String {
+        let args: Vec<types::Str> = args.iter().copied().map(Into::into).collect();
+        let mut printer = Printer { args: args.iter(), errors: Vec::new() };
+        let mut out = Vec::new();
+        printer.print(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            printed("%d|%5d|%-5d|%05d|%+d", &["42", "42", "42", "-42", "7"]),
+            "42|   42|42   |-0042|+7"
+        );
+        assert_eq!(
+            printed("%.3d %x %#X %#o %u", &["5", "255", "255", "8", "-1"]),
+            "005 ff 0XFF 010 18446744073709551615"
+        );
+        assert_eq!(printed("%d %d %d", &["0x10", "010", "'A"]), "16 8 65");
+        assert_eq!(printed("%*d|%.*d", &["-4", "1", "3", "2"]), "1   |002");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(
+            printed("%f %.2f %8.3f %-8.1f|", &["1.5", "3.14159", "-2.5", "2"]),
+            "1.500000 3.14   -2.500 2.0     |"
+        );
+        assert_eq!(printed("%e %.2E", &["12345.678", "0.000123"]), "1.234568e+04 1.23E-04");
+        assert_eq!(
+            printed("%g %g %g %G", &["100000", "1000000", "0.0001", "1e-5"]),
+            "100000 1e+06 0.0001 1E-05"
+        );
+        assert_eq!(printed("%#.0f %+.1f %f", &["3", "2", "inf"]), "3. +2.0 inf");
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(
+            printed("%s|%5s|%-5s|%.2s|%c", &["a", "b", "c", "long", "xyz"]),
+            "a|    b|c    |lo|x"
+        );
+        assert_eq!(printed("%b\\t%s\\x41\\101", &["1\\n2\\0101", "\\n"]), "1\n2A\t\\nAA");
+        assert_eq!(printed("100%%", &[]), "100%");
+        assert_eq!(
+            printed("%a", &["one", "two words", "it's", ""]),
+            "[one 'two words' \"it's\" '']"
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let args: Vec<types::Str> = vec!["one".into()];
+        let mut printer = Printer { args: args.iter(), errors: Vec::new() };
+        assert_eq!(printer.print("%d", &mut Vec::new()), Ok(true));
+        assert_eq!(printer.errors.len(), 1);
+        assert!(printer.print("%y", &mut Vec::new()).is_err());
+        assert!(printer.print("%", &mut Vec::new()).is_err());
+    }
+}
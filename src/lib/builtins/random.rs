@@ -1,15 +1,47 @@
 use crate::types;
 use itertools::Itertools;
-use rand::{thread_rng, Rng};
+use rand::{distributions::Uniform, rngs::StdRng, thread_rng, Rng, RngCore, SeedableRng};
+use std::io::{self, Write};
 
 const INVALID: &str = "Invalid argument for random";
 
-fn rand_list(args: &[types::Str]) -> Result<(), types::Str> {
+/// The number of random bytes drawn and written at once
+const CHUNK: usize = 4096;
+
+/// The generator of the random numbers of the shell: the one of the thread, or the one seeded by
+/// `random seed` for draws which are the same on every run
+pub(crate) fn generator(seeded: &mut Option<StdRng>) -> Box<dyn RngCore + '_> {
+    match seeded {
+        Some(rng) => Box::new(rng),
+        None => Box::new(thread_rng()),
+    }
+}
+
+/// The value of the variable of the `rand::` namespace, if there is one of this name
+pub(crate) fn variable(rng: &mut dyn RngCore, name: &str) -> Option<types::Str> {
+    let value = match name {
+        "u8" => rng.gen::<u8>().to_string(),
+        "u16" => rng.gen::<u16>().to_string(),
+        "u32" => rng.gen::<u32>().to_string(),
+        "u64" => rng.gen::<u64>().to_string(),
+        "i64" => rng.gen::<i64>().to_string(),
+        "float" => rng.gen::<f64>().to_string(),
+        "bool" => rng.gen::<bool>().to_string(),
+        _ => return None,
+    };
+    Some(value.into())
+}
+
+fn parse<T: std::str::FromStr>(arg: &str) -> Result<T, types::Str> {
+    arg.parse().map_err(|_| format!("{}: '{}'", INVALID, arg).into())
+}
+
+fn rand_list(args: &[types::Str], rng: &mut dyn RngCore) -> Result<(), types::Str> {
     let num_random = args[0].parse::<usize>().map_err::<types::Str, _>(|_| INVALID.into())?;
     let mut output = Vec::with_capacity(num_random);
     while output.len() < num_random {
         for _ in 0..(num_random - output.len()) {
-            let rand_num = thread_rng().gen_range(1, args.len());
+            let rand_num = rng.gen_range(1, args.len());
             output.push(&*args[rand_num]);
         }
         output.dedup();
@@ -18,29 +50,96 @@ fn rand_list(args: &[types::Str]) -> Result<(), types::Str> {
     Ok(())
 }
 
-pub fn random(args: &[types::Str]) -> Result<(), types::Str> {
+/// Write a number of random bytes, as they are or in hexadecimal, a chunk at a time
+fn write_bytes(
+    mut count: usize,
+    hex: bool,
+    rng: &mut dyn RngCore,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut chunk = [0; CHUNK];
+    while count > 0 {
+        let bytes = &mut chunk[..count.min(CHUNK)];
+        rng.fill_bytes(bytes);
+        if hex {
+            write!(out, "{:02x}", bytes.iter().format(""))?;
+        } else {
+            out.write_all(bytes)?;
+        }
+        count -= bytes.len();
+    }
+    if hex {
+        writeln!(out)?;
+    }
+    out.flush()
+}
+
+fn bytes(args: &[types::Str], rng: &mut dyn RngCore) -> Result<(), types::Str> {
+    let (count, hex) = match args {
+        [count] => (count, false),
+        [count, hex] if hex.as_str() == "--hex" => (count, true),
+        _ => return Err("usage: random bytes COUNT [--hex]".into()),
+    };
+    let stdout = io::stdout();
+    write_bytes(parse(count)?, hex, rng, &mut stdout.lock()).map_err(|why| why.to_string().into())
+}
+
+pub fn random(args: &[types::Str], seeded: &mut Option<StdRng>) -> Result<(), types::Str> {
+    match args.first().map(types::Str::as_str) {
+        Some("seed") => {
+            let seed = match &args[1..] {
+                [seed] => parse(seed)?,
+                _ => return Err("usage: random seed SEED".into()),
+            };
+            *seeded = Some(StdRng::seed_from_u64(seed));
+            return Ok(());
+        }
+        Some("int") => {
+            let (start, end): (i64, i64) = match &args[1..] {
+                [start, end] => (parse(start)?, parse(end)?),
+                _ => return Err("usage: random int MIN MAX".into()),
+            };
+            if end < start {
+                return Err("MAX must not be less than MIN".into());
+            }
+            println!("{}", generator(seeded).sample(Uniform::new_inclusive(start, end)));
+            return Ok(());
+        }
+        Some("choice") => {
+            if args.len() < 2 {
+                return Err("usage: random choice VALUES...".into());
+            }
+            println!("{}", args[generator(seeded).gen_range(1, args.len())]);
+            return Ok(());
+        }
+        Some("bytes") => return bytes(&args[1..], &mut *generator(seeded)),
+        _ => (),
+    }
+    if let [_] = args {
+        return Err("the generator is seeded with `random seed SEED`".into());
+    }
+
+    let mut generator = generator(seeded);
+    let rng = &mut *generator;
     match args.len() {
         0 => {
-            let rand_num = thread_rng().gen_range(0, 32767);
+            let rand_num = rng.gen_range(0, 32767);
             println!("{}", rand_num);
         }
-        1 => {
-            eprintln!("Ion Shell does not currently support changing the seed");
-        }
         2 => {
             let start: u64 = args[0].parse().map_err::<types::Str, _>(|_| INVALID.into())?;
             let end: u64 = args[1].parse().map_err::<types::Str, _>(|_| INVALID.into())?;
             if end <= start {
                 return Err("END must be greater than START".into());
             }
-            let rand_num = thread_rng().gen_range(start, end);
+            let rand_num = rng.gen_range(start, end);
             println!("{}", rand_num);
         }
         3 => {
             let start: u64 = args[0].parse().map_err::<types::Str, _>(|_| INVALID.into())?;
             let step = match args[1].parse::<u64>() {
                 Ok(v) => v,
-                Err(_) => return rand_list(args),
+                Err(_) => return rand_list(args, rng),
             };
             match args[2].parse::<u64>() {
                 Ok(end) => {
@@ -51,14 +150,42 @@ pub fn random(args: &[types::Str]) -> Result<(), types::Str> {
                     if start / step >= end {
                         end += 1;
                     }
-                    let rand_num = thread_rng().gen_range(start / step, end);
+                    let rand_num = rng.gen_range(start / step, end);
                     println!("{}", rand_num * step);
                 }
-                Err(_) => return rand_list(args),
+                Err(_) => return rand_list(args, rng),
             };
         }
-        _ => return rand_list(args),
+        _ => return rand_list(args, rng),
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_draws() {
+        let mut first = Some(StdRng::seed_from_u64(7));
+        let mut second = Some(StdRng::seed_from_u64(7));
+        let draw = |seeded: &mut Option<StdRng>| {
+            (0..4).map(|_| variable(&mut *generator(seeded), "u64").unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(draw(&mut first), draw(&mut second));
+        assert_eq!(variable(&mut *generator(&mut None), "u128"), None);
+    }
+
+    #[test]
+    fn chunked_bytes() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut out = Vec::new();
+        write_bytes(2 * CHUNK + 3, false, &mut rng, &mut out).unwrap();
+        assert_eq!(out.len(), 2 * CHUNK + 3);
+        let mut out = Vec::new();
+        write_bytes(CHUNK + 1, true, &mut rng, &mut out).unwrap();
+        assert_eq!(out.len(), 2 * (CHUNK + 1) + 1);
+        assert!(out[..out.len() - 1].iter().all(u8::is_ascii_hexdigit));
+    }
+}
@@ -0,0 +1,294 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{Shell, Value},
+    types,
+};
+use builtins_proc::builtin;
+use liner::{Completer, Context, Prompt};
+use nix::{
+    poll::{self, PollFd, PollFlags},
+    sys::termios::{self, LocalFlags, SetArg},
+    unistd,
+};
+use std::{
+    convert::TryFrom,
+    io::{self, Write},
+    os::unix::io::RawFd,
+    time::{Duration, Instant},
+};
+
+const STDIN: RawFd = nix::libc::STDIN_FILENO;
+
+struct EmptyCompleter;
+
+impl Completer for EmptyCompleter {
+    fn completions(&mut self, _start: &str) -> Vec<String> { Vec::new() }
+}
+
+/// How the records are read by `read`
+#[derive(Debug, Default)]
+struct Options {
+    prompt:    Option<types::Str>,
+    silent:    bool,
+    array:     Option<types::Str>,
+    delimiter: Option<u8>,
+    chars:     Option<usize>,
+    timeout:   Option<Duration>,
+}
+
+impl Options {
+    /// Whether the line editor may read the records, which is not the case when they are not
+    /// whole lines, when they are not echoed, or when they are read against a timeout
+    const fn edited(&self) -> bool {
+        !self.silent && self.delimiter.is_none() && self.chars.is_none() && self.timeout.is_none()
+    }
+}
+
+/// A record read from the standard input
+#[derive(Debug, PartialEq)]
+pub(super) enum Record {
+    Read(String),
+    End,
+    TimedOut,
+}
+
+/// The number of characters in the bytes read so far. A multibyte character which is not read in
+/// full is not counted yet, while an invalid byte counts as a character of its own.
+fn complete_chars(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.chars().count(),
+        Err(error) => {
+            let (valid, rest) = bytes.split_at(error.valid_up_to());
+            let valid = String::from_utf8_lossy(valid).chars().count();
+            match error.error_len() {
+                Some(invalid) => valid + 1 + complete_chars(&rest[invalid..]),
+                None => valid,
+            }
+        }
+    }
+}
+
+/// Read a record ending with the delimiter, or once the number of characters is read, a byte at
+/// a time so that no more than the record is consumed from the standard input
+pub(super) fn read_record(
+    delimiter: u8,
+    chars: Option<usize>,
+    deadline: Option<Instant>,
+) -> nix::Result<Record> {
+    let mut record = Vec::new();
+    loop {
+        if chars.map_or(false, |chars| complete_chars(&record) >= chars) {
+            break;
+        }
+        if let Some(deadline) = deadline {
+            let left = deadline.saturating_duration_since(Instant::now()).as_millis();
+            let mut fds = [PollFd::new(STDIN, PollFlags::POLLIN)];
+            if poll::poll(&mut fds, i32::try_from(left).unwrap_or(i32::MAX))? == 0 {
+                return Ok(Record::TimedOut);
+            }
+        }
+        let mut byte = [0];
+        match unistd::read(STDIN, &mut byte)? {
+            0 if record.is_empty() => return Ok(Record::End),
+            0 => break,
+            _ if byte[0] == delimiter && chars.is_none() => break,
+            _ => record.push(byte[0]),
+        }
+    }
+    Ok(Record::Read(String::from_utf8_lossy(&record).into_owned()))
+}
+
+/// Run the function with the echo of the terminal disabled, if the standard input is one
+fn without_echo<T>(silent: bool, read: impl FnOnce() -> T) -> T {
+    let attributes = if silent { termios::tcgetattr(STDIN).ok() } else { None };
+    if let Some(ref attributes) = attributes {
+        let mut silenced = attributes.clone();
+        silenced.local_flags.remove(LocalFlags::ECHO);
+        let _ = termios::tcsetattr(STDIN, SetArg::TCSANOW, &silenced);
+    }
+    let result = read();
+    if let Some(ref attributes) = attributes {
+        let _ = termios::tcsetattr(STDIN, SetArg::TCSANOW, attributes);
+        eprintln!();
+    }
+    result
+}
+
+fn parse_options<'a>(
+    args: &mut impl Iterator<Item = &'a types::Str>,
+) -> Result<(Options, Vec<&'a types::Str>), String> {
+    let mut options = Options::default();
+    let mut variables = Vec::new();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("option '{}' requires a value", arg));
+        match arg.as_str() {
+            "-p" => options.prompt = Some(value()?.clone()),
+            "-s" => options.silent = true,
+            "-a" => options.array = Some(value()?.clone()),
+            "-d" => options.delimiter = Some(value()?.bytes().next().unwrap_or(0)),
+            "-n" => {
+                let chars = value()?;
+                options.chars = Some(
+                    chars
+                        .parse()
+                        .map_err(|_| format!("invalid number of characters '{}'", chars))?,
+                );
+            }
+            "-t" => {
+                let secs = value()?;
+                options.timeout = secs
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|secs| secs.is_finite() && *secs >= 0.)
+                    .map(Duration::from_secs_f64);
+                if options.timeout.is_none() {
+                    return Err(format!("invalid timeout '{}'", secs));
+                }
+            }
+            _ => variables.push(arg),
+        }
+    }
+    Ok((options, variables))
+}
+
+#[builtin(
+    desc = "read a line of input into some variables",
+    man = "
+SYNOPSIS
+    read [-p PROMPT] [-s] [-a ARRAY] [-d DELIM] [-n NCHARS] [-t TIMEOUT] VARIABLES...
+
+DESCRIPTION
+    For each variable reads a line from standard input and stores it in the variable, without
+    its surrounding whitespace. Returns 1 once the input has no more lines.
+
+OPTIONS
+    -p PROMPT
+        print PROMPT on standard error before reading
+    -s
+        do not echo the input read from a terminal, as when reading a password
+    -a ARRAY
+        read one more line and store its words in the array variable ARRAY
+    -d DELIM
+        end the lines with the first character of DELIM rather than with a newline, or with
+        a NUL byte if DELIM is empty
+    -n NCHARS
+        read NCHARS characters for each variable rather than lines, keeping any whitespace
+    -t TIMEOUT
+        give up, returning 124, if the input is not read within TIMEOUT seconds
+
+EXAMPLES
+    read -p 'password: ' -s password
+    read -a words <<< 'one two three'
+    read -n 1 -t 5 answer || echo no answer"
+)]
+pub fn read(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (options, variables) = match parse_options(&mut args.iter().skip(1)) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: read: {}", why)),
+    };
+    let array = options.array.as_ref();
+    if let Some(constant) = variables
+        .iter()
+        .copied()
+        .chain(array)
+        .find(|name| shell.assigns_constant(name.as_str(), false))
+    {
+        return Status::error(format!("ion: read: cannot assign to the constant `{}`", constant));
+    }
+    let records = variables.iter().copied().map(Some).chain(array.map(|_| None));
+
+    if options.edited() && atty::is(atty::Stream::Stdin) {
+        let mut con = Context::new();
+        for variable in records {
+            let prompt = match (&options.prompt, variable) {
+                (Some(prompt), _) => prompt.to_string(),
+                (None, Some(variable)) => format!("{}=", variable.trim()),
+                (None, None) => String::new(),
+            };
+            match con.read_line(Prompt::from(prompt), None, &mut EmptyCompleter) {
+                Ok(buffer) => store(shell, variable, array, buffer.trim()),
+                Err(_) => return Status::FALSE,
+            }
+        }
+        return Status::SUCCESS;
+    }
+
+    if let Some(ref prompt) = options.prompt {
+        eprint!("{}", prompt);
+        let _ = io::stderr().flush();
+    }
+    let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+    let delimiter = options.delimiter.unwrap_or(b'\n');
+    let silent = options.silent && atty::is(atty::Stream::Stdin);
+    without_echo(silent, || {
+        for variable in records {
+            match read_record(delimiter, options.chars, deadline) {
+                Ok(Record::Read(record)) => {
+                    let record =
+                        if options.chars.is_some() { record.as_str() } else { record.trim() };
+                    store(shell, variable, array, record);
+                }
+                Ok(Record::End) => return Status::FALSE,
+                Ok(Record::TimedOut) => return Status::TIMED_OUT,
+                Err(why) => return Status::error(format!("ion: read: {}", why)),
+            }
+        }
+        Status::SUCCESS
+    })
+}
+
+/// Store the record in the variable, or split into words in the array when no variable is given
+fn store(
+    shell: &mut Shell<'_>,
+    variable: Option<&types::Str>,
+    array: Option<&types::Str>,
+    record: &str,
+) {
+    match (variable, array) {
+        (Some(variable), _) => shell.variables_mut().set(variable.as_str(), record),
+        (None, Some(array)) => {
+            let words: types::Array<_> =
+                record.split_whitespace().map(|word| Value::Str(word.into())).collect();
+            shell.variables_mut().set(array.as_str(), Value::Array(words));
+        }
+        (None, None) => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_options() {
+        let args: Vec<types::Str> =
+            vec!["-p", "name: ", "-s", "-n", "3", "-d", "", "-t", "1.5", "-a", "words", "first"]
+                .into_iter()
+                .map(Into::into)
+                .collect();
+        let (options, variables) = parse_options(&mut args.iter()).unwrap();
+        assert_eq!(options.prompt.as_ref().map(types::Str::as_str), Some("name: "));
+        assert!(options.silent);
+        assert_eq!(options.chars, Some(3));
+        assert_eq!(options.delimiter, Some(0));
+        assert_eq!(options.timeout, Some(Duration::from_millis(1500)));
+        assert_eq!(options.array.as_ref().map(types::Str::as_str), Some("words"));
+        assert_eq!(
+            variables.iter().map(|variable| variable.as_str()).collect::<Vec<_>>(),
+            ["first"]
+        );
+        assert!(!options.edited());
+
+        let args: Vec<types::Str> = vec!["-t".into(), "soon".into()];
+        assert!(parse_options(&mut args.iter()).is_err());
+    }
+
+    #[test]
+    fn multibyte_chars() {
+        let text = "aé€😀".as_bytes();
+        let counts: Vec<usize> = (0..=text.len()).map(|len| complete_chars(&text[..len])).collect();
+        assert_eq!(counts, [0, 1, 1, 2, 2, 2, 3, 3, 3, 3, 4]);
+        assert_eq!(complete_chars(b"a\xffb\xe2"), 3);
+    }
+}
@@ -0,0 +1,416 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{shell::Shell, types};
+use builtins_proc::builtin;
+use nix::{libc, unistd};
+use std::io;
+
+/// A limit of `ulimit`: its option, its name, its resource, and the unit its values are given in,
+/// in bytes or in the resource's own unit
+type Limit = (char, &'static str, i32, libc::rlim_t);
+
+const LIMITS: [Limit; 10] = [
+    ('c', "core file size (kbytes)", libc::RLIMIT_CORE as i32, 1024),
+    ('d', "data segment size (kbytes)", libc::RLIMIT_DATA as i32, 1024),
+    ('f', "file size (kbytes)", libc::RLIMIT_FSIZE as i32, 1024),
+    ('l', "locked memory (kbytes)", libc::RLIMIT_MEMLOCK as i32, 1024),
+    ('m', "resident set size (kbytes)", libc::RLIMIT_RSS as i32, 1024),
+    ('n', "open files", libc::RLIMIT_NOFILE as i32, 1),
+    ('s', "stack size (kbytes)", libc::RLIMIT_STACK as i32, 1024),
+    ('t', "cpu time (seconds)", libc::RLIMIT_CPU as i32, 1),
+    ('u', "processes", libc::RLIMIT_NPROC as i32, 1),
+    ('v', "virtual memory (kbytes)", libc::RLIMIT_AS as i32, 1024),
+];
+
+fn get_limit(resource: i32) -> io::Result<libc::rlimit> {
+    let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+    if unsafe { libc::getrlimit(resource as _, &mut limit) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(limit)
+}
+
+fn set_limit(resource: i32, limit: &libc::rlimit) -> io::Result<()> {
+    if unsafe { libc::setrlimit(resource as _, limit) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// A limit, in the unit of `ulimit`
+fn format_limit(value: libc::rlim_t, unit: libc::rlim_t) -> String {
+    if value == libc::RLIM_INFINITY {
+        "unlimited".into()
+    } else {
+        (value / unit).to_string()
+    }
+}
+
+/// A limit given to `ulimit`, in bytes or in the resource's own unit
+fn parse_limit(value: &str, unit: libc::rlim_t) -> Option<libc::rlim_t> {
+    if value == "unlimited" {
+        return Some(libc::RLIM_INFINITY);
+    }
+    value.parse::<libc::rlim_t>().ok()?.checked_mul(unit)
+}
+
+/// The soft or hard limit, in the unit of `ulimit`
+fn show_limit((_, name, resource, unit): Limit, hard: bool) -> Result<String, String> {
+    match get_limit(resource) {
+        Ok(limit) => Ok(format_limit(if hard { limit.rlim_max } else { limit.rlim_cur }, unit)),
+        Err(why) => Err(format!("ion: ulimit: {}: {}", name, why)),
+    }
+}
+
+#[builtin(
+    desc = "get and set the limits of the resources of processes",
+    man = "
+SYNOPSIS
+    ulimit [-H | -S] [-a | -c | -d | -f | -l | -m | -n | -s | -t | -u | -v] [LIMIT]
+
+DESCRIPTION
+    Prints the limit of a resource, or sets it to LIMIT, a number or unlimited. The limits are
+    the ones of the shell, which every command it runs from then on inherits, so that a script
+    may cap the memory or the time its commands use. Without an option, the limit is the one of
+    the size of the files written.
+
+    A soft limit may be raised up to the hard limit, which may only be lowered, unless the shell
+    runs as root. Both are set unless -H or -S is given, and the soft limit is printed unless -H
+    is given.
+
+OPTIONS
+    -H
+        the hard limit
+    -S
+        the soft limit
+    -a
+        prints every limit
+    -c
+        the size of the core dumps, in kilobytes
+    -d
+        the size of the data segment of a process, in kilobytes
+    -f
+        the size of the files written, in kilobytes
+    -l
+        the size of the memory locked in RAM, in kilobytes
+    -m
+        the resident set size, in kilobytes
+    -n
+        the number of open file descriptors
+    -s
+        the size of the stack, in kilobytes
+    -t
+        the CPU time of a process, in seconds
+    -u
+        the number of processes of the user
+    -v
+        the size of the virtual memory of a process, in kilobytes
+
+EXAMPLES
+    ulimit -c unlimited
+    ulimit -S -v 1048576
+    ulimit -a"
+)]
+pub fn ulimit(args: &[types::Str], _: &mut Shell<'_>) -> Status {
+    let (mut hard, mut soft, mut all) = (false, false, false);
+    let mut limit = LIMITS[2];
+    let mut value = None;
+    for arg in &args[1..] {
+        match arg.as_str() {
+            "-H" => hard = true,
+            "-S" => soft = true,
+            "-a" => all = true,
+            option if option.starts_with('-') && option.len() == 2 => {
+                let flag = option.chars().nth(1);
+                match LIMITS.iter().find(|&&(letter, ..)| Some(letter) == flag) {
+                    Some(&found) => limit = found,
+                    None => {
+                        return Status::bad_argument(format!(
+                            "ion: ulimit: unknown option '{}'",
+                            option
+                        ))
+                    }
+                }
+            }
+            _ if value.is_none() => value = Some(arg),
+            _ => {
+                return Status::bad_argument(format!("ion: ulimit: unexpected argument '{}'", arg))
+            }
+        }
+    }
+
+    if all {
+        for &limit in &LIMITS {
+            match show_limit(limit, hard) {
+                Ok(value) => println!("-{}: {:<28}{}", limit.0, limit.1, value),
+                Err(why) => return Status::error(why),
+            }
+        }
+        return Status::SUCCESS;
+    }
+
+    let value = match value {
+        Some(value) => value,
+        None => {
+            return match show_limit(limit, hard) {
+                Ok(value) => {
+                    println!("{}", value);
+                    Status::SUCCESS
+                }
+                Err(why) => Status::error(why),
+            }
+        }
+    };
+    let (_, name, resource, unit) = limit;
+    let value = match parse_limit(value, unit) {
+        Some(value) => value,
+        None => return Status::bad_argument(format!("ion: ulimit: invalid limit '{}'", value)),
+    };
+    let mut limit = match get_limit(resource) {
+        Ok(limit) => limit,
+        Err(why) => return Status::error(format!("ion: ulimit: {}: {}", name, why)),
+    };
+    if hard || !soft {
+        limit.rlim_max = value;
+    }
+    if soft || !hard {
+        limit.rlim_cur = value;
+    }
+    match set_limit(resource, &limit) {
+        Ok(()) => Status::SUCCESS,
+        Err(why) => Status::error(format!("ion: ulimit: {}: {}", name, why)),
+    }
+}
+
+/// The classes of the I/O scheduling of Linux
+const IO_CLASSES: [&str; 4] = ["none", "realtime", "best-effort", "idle"];
+
+/// The priority the programs run by the shell are given, by `nice` and `ionice`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Priority {
+    /// The adjustment of the niceness, added to the one of the shell
+    pub nice: Option<i32>,
+    /// The class of the I/O scheduling, an index of `IO_CLASSES`, and the level in the class
+    pub io:   Option<(i32, i32)>,
+}
+
+impl Priority {
+    /// Give the priority to the current process, a child which is about to run a program
+    pub(crate) fn apply(self) -> io::Result<()> {
+        if let Some(adjustment) = self.nice {
+            let niceness = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, niceness + adjustment) } == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some((class, level)) = self.io {
+            set_io_priority(class, level)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+#[cfg(target_os = "linux")]
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+
+#[cfg(target_os = "linux")]
+fn set_io_priority(class: i32, level: i32) -> io::Result<()> {
+    let priority = libc::c_long::from(class << IOPRIO_CLASS_SHIFT | level);
+    if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_priority(_: i32, _: i32) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "I/O priorities are only supported on Linux"))
+}
+
+#[cfg(target_os = "linux")]
+fn get_io_priority() -> io::Result<(i32, i32)> {
+    let priority = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+    if priority == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let priority = priority as i32;
+    Ok((priority >> IOPRIO_CLASS_SHIFT, priority & ((1 << IOPRIO_CLASS_SHIFT) - 1)))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_io_priority() -> io::Result<(i32, i32)> {
+    Err(io::Error::new(io::ErrorKind::Other, "I/O priorities are only supported on Linux"))
+}
+
+/// Run the command with the priority changed, and then restore the one of the shell
+fn run_with(
+    shell: &mut Shell<'_>,
+    priority: Priority,
+    command: &[types::Str],
+    name: &str,
+) -> Status {
+    let previous = std::mem::replace(shell.priority_mut(), priority);
+    let status = shell
+        .execute_args(command.iter().cloned().collect())
+        .unwrap_or_else(|why| Status::error(format!("ion: {}: {}", name, why)));
+    *shell.priority_mut() = previous;
+    status
+}
+
+#[builtin(
+    desc = "run a program with a lower or higher priority",
+    man = "
+SYNOPSIS
+    nice [-n ADJUSTMENT | -ADJUSTMENT] [COMMAND...]
+
+DESCRIPTION
+    Runs the command with its niceness adjusted, 10 more than the one of the shell by default.
+    The higher the niceness, from -20 to 19, the less time the program is given when the CPU is
+    busy. Only root may lower the niceness: the command of another user still runs, with the
+    niceness of the shell, after a warning. The programs run by the command get the adjusted
+    niceness, but not the builtins and functions which run within the shell. Without a command,
+    prints the niceness of the shell.
+
+OPTIONS
+    -n ADJUSTMENT, -ADJUSTMENT
+        the number added to the niceness, so that -19 adds 19 and --5 subtracts 5
+
+EXAMPLES
+    nice make -j8
+    nice -n 19 tar czf backup.tar.gz /home
+    nice -19 tar czf backup.tar.gz /home"
+)]
+pub fn nice(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (adjustment, command) = match &args[1..] {
+        [option, adjustment, command @ ..] if option.as_str() == "-n" => {
+            match adjustment.parse::<i32>() {
+                Ok(adjustment) => (adjustment, command),
+                Err(_) => {
+                    return Status::bad_argument(format!(
+                        "ion: nice: invalid adjustment '{}'",
+                        adjustment
+                    ))
+                }
+            }
+        }
+        [option] if option.as_str() == "-n" => {
+            return Status::bad_argument("ion: nice: option '-n' requires a value")
+        }
+        // The classic form, in which the adjustment follows the dash
+        [option, command @ ..] if option.starts_with('-') && option[1..].parse::<i32>().is_ok() => {
+            (option[1..].parse().unwrap_or_default(), command)
+        }
+        command => (10, command),
+    };
+    if command.is_empty() {
+        let niceness = shell.priority_mut().nice.unwrap_or(0)
+            + unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+        println!("{}", niceness);
+        return Status::SUCCESS;
+    }
+    let priority = *shell.priority_mut();
+    let nice = priority.nice.unwrap_or(0) + adjustment;
+    if nice < 0 && !unistd::geteuid().is_root() {
+        eprintln!("ion: nice: only root may lower the niceness, which is left as it is");
+    }
+    run_with(shell, Priority { nice: Some(nice), ..priority }, command, "nice")
+}
+
+#[builtin(
+    desc = "run a program with another I/O priority",
+    man = "
+SYNOPSIS
+    ionice [-c CLASS] [-n LEVEL] [COMMAND...]
+
+DESCRIPTION
+    Runs the command with its class of I/O scheduling and its level in the class changed, so that
+    a program reading or writing many files does not slow the others down. The programs run by
+    the command get the priority, but not the builtins and functions which run within the shell.
+    Without a command, prints the I/O priority of the shell. Only supported on Linux.
+
+OPTIONS
+    -c CLASS
+        the class, best-effort by default: realtime, which only root may give, best-effort, or
+        idle, in which the program only gets the disk when no other one uses it. The classes may
+        also be given by their numbers, 1, 2 and 3.
+    -n LEVEL
+        the level of the realtime and best-effort classes, from 0, the highest, to 7, 4 by
+        default
+
+EXAMPLES
+    ionice -c idle rsync -a /home /backup
+    ionice -n 7 find / -name core"
+)]
+pub fn ionice(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (mut class, mut level) = (2, 4);
+    let mut args = &args[1..];
+    while let Some((option, rest)) = args.split_first() {
+        let value = match (option.as_str(), rest.first()) {
+            ("-c", Some(value)) | ("-n", Some(value)) => value,
+            ("-c", None) | ("-n", None) => {
+                return Status::bad_argument(format!(
+                    "ion: ionice: option '{}' requires a value",
+                    option
+                ))
+            }
+            _ => break,
+        };
+        let (target, valid) = if option.as_str() == "-c" {
+            let named = IO_CLASSES.iter().position(|&name| name == value.as_str());
+            (
+                &mut class,
+                named
+                    .map(|index| index as i32)
+                    .or_else(|| value.parse().ok())
+                    .filter(|number| (1..=3).contains(number)),
+            )
+        } else {
+            (&mut level, value.parse().ok().filter(|number| (0..=7).contains(number)))
+        };
+        match valid {
+            Some(valid) => *target = valid,
+            None => {
+                return Status::bad_argument(format!(
+                    "ion: ionice: invalid value '{}' for '{}'",
+                    value, option
+                ))
+            }
+        }
+        args = &rest[1..];
+    }
+
+    if args.is_empty() {
+        return match get_io_priority() {
+            Ok((class, level)) => {
+                let name = IO_CLASSES.get(class as usize).unwrap_or(&"unknown");
+                println!("{}: prio {}", name, level);
+                Status::SUCCESS
+            }
+            Err(why) => Status::error(format!("ion: ionice: {}", why)),
+        };
+    }
+    if class == 1 && !unistd::geteuid().is_root() {
+        eprintln!("ion: ionice: only root may give the realtime class, which is not given");
+    }
+    // The idle class has no levels
+    let level = if class == 3 { 0 } else { level };
+    let priority = *shell.priority_mut();
+    run_with(shell, Priority { io: Some((class, level)), ..priority }, args, "ionice")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        assert_eq!(parse_limit("unlimited", 1024), Some(libc::RLIM_INFINITY));
+        assert_eq!(parse_limit("4", 1024), Some(4096));
+        assert_eq!(parse_limit("-4", 1), None);
+        assert_eq!(format_limit(4096, 1024), "4");
+        assert_eq!(format_limit(libc::RLIM_INFINITY, 1), "unlimited");
+        assert!(get_limit(libc::RLIMIT_NOFILE as i32).is_ok());
+    }
+}
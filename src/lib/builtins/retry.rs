@@ -0,0 +1,165 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{parse_duration, Shell},
+    types,
+};
+use builtins_proc::builtin;
+use nix::sys::signal::Signal;
+use rand::{thread_rng, Rng};
+use std::{env, thread::sleep, time::Duration};
+
+/// How a command is retried by `retry`
+#[derive(Debug, PartialEq)]
+struct Options {
+    tries:   u32,
+    backoff: Duration,
+}
+
+impl Default for Options {
+    fn default() -> Self { Self { tries: 3, backoff: Duration::from_secs(1) } }
+}
+
+impl Options {
+    /// The wait after the given failed attempt, counted from 1: the backoff doubled after each
+    /// failure, up to half of which is chosen at random so that retrying jobs spread out
+    fn delay(&self, attempt: u32, jitter: f64) -> Duration {
+        let delay = self.backoff.saturating_mul(2u32.saturating_pow(attempt - 1));
+        delay.mul_f64(1. - jitter / 2.)
+    }
+}
+
+fn parse_options(args: &[types::Str]) -> Result<(Options, &[types::Str]), String> {
+    let mut options = Options::default();
+    let mut args = args;
+    while let Some((arg, rest)) = args.split_first() {
+        let value = || rest.first().ok_or(format!("option '{}' requires a value", arg));
+        match arg.as_str() {
+            "-n" | "--tries" => {
+                let tries = value()?;
+                options.tries = tries
+                    .parse()
+                    .ok()
+                    .filter(|&tries| tries > 0)
+                    .ok_or(format!("invalid number of tries '{}'", tries))?;
+            }
+            "-b" | "--backoff" => {
+                let backoff = value()?;
+                options.backoff =
+                    parse_duration(backoff).ok_or(format!("invalid backoff '{}'", backoff))?;
+            }
+            "--" => return Ok((options, rest)),
+            _ => return Ok((options, args)),
+        }
+        args = &rest[1..];
+    }
+    Ok((options, args))
+}
+
+#[builtin(
+    desc = "run a command again until it succeeds",
+    man = "
+SYNOPSIS
+    retry [--tries TRIES] [--backoff DURATION] [--] COMMAND...
+
+DESCRIPTION
+    Runs the command until it succeeds, or until it failed the given number of times,
+    waiting longer after each failure. Returns the status of the last attempt.
+
+    Only a single command is retried: in `retry cmd | other`, the output of retry itself is
+    piped. To retry a pipeline, define a function running it, and retry the function.
+
+    The number of the attempt, counted from 1, is given to the command in the environment
+    variable RETRY_ATTEMPT. Each failed attempt but the last is reported on standard
+    error. A command interrupted by a signal is not retried.
+
+OPTIONS
+    -n, --tries TRIES
+        the number of attempts, 3 by default
+    -b, --backoff DURATION
+        the wait after the first failure, 1 second by default, which is doubled after
+        each of the next ones. Up to half of each wait is skipped at random, so that the
+        commands retried at the same time spread out. The duration is a number of seconds,
+        or of minutes, hours or days with the suffix m, h or d.
+
+EXAMPLES
+    retry --tries 5 --backoff 2s -- curl -sf example.com/status
+
+    fn fetch_version
+        curl -sf example.com/status | jq -e .version
+    end
+    retry fetch_version"
+)]
+pub fn retry(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (options, command) = match parse_options(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(why) => return Status::bad_argument(format!("ion: retry: {}", why)),
+    };
+    if command.is_empty() {
+        return Status::bad_argument("ion: retry: no command given");
+    }
+
+    let previous = env::var_os("RETRY_ATTEMPT");
+    let mut attempt = 1;
+    let status = loop {
+        env::set_var("RETRY_ATTEMPT", attempt.to_string());
+        let status = match shell.execute_args(command.iter().cloned().collect()) {
+            Ok(status) => status,
+            Err(why) => break Status::error(format!("ion: retry: {}", why)),
+        };
+        let interrupted = status == Status::from_signal(Signal::SIGINT as u8)
+            || status == Status::from_signal(Signal::SIGTERM as u8);
+        if status.is_success() || interrupted || attempt == options.tries {
+            break status;
+        }
+        let delay = options.delay(attempt, thread_rng().gen::<f64>());
+        eprintln!(
+            "ion: retry: attempt {} of {} failed with status {}, retrying in {:.1}s",
+            attempt,
+            options.tries,
+            status,
+            delay.as_secs_f64()
+        );
+        sleep(delay);
+        attempt += 1;
+    };
+    match previous {
+        Some(previous) => env::set_var("RETRY_ATTEMPT", previous),
+        None => env::remove_var("RETRY_ATTEMPT"),
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_options() {
+        let args: Vec<types::Str> = vec!["--tries", "5", "-b", "2s", "--", "curl", "-s"]
+            .into_iter()
+            .map(Into::into)
+            .collect();
+        let (options, command) = parse_options(&args).unwrap();
+        assert_eq!(options, Options { tries: 5, backoff: Duration::from_secs(2) });
+        assert_eq!(command, &args[5..]);
+
+        // The command starts at the first argument which is not an option
+        let args: Vec<types::Str> =
+            vec!["-n", "2", "echo", "-n"].into_iter().map(Into::into).collect();
+        let (options, command) = parse_options(&args).unwrap();
+        assert_eq!(options.tries, 2);
+        assert_eq!(command, &args[2..]);
+
+        let args: Vec<types::Str> = vec!["--tries".into(), "0".into()];
+        assert!(parse_options(&args).is_err());
+    }
+
+    #[test]
+    fn backoff() {
+        let options = Options { tries: 5, backoff: Duration::from_secs(2) };
+        assert_eq!(options.delay(1, 0.), Duration::from_secs(2));
+        assert_eq!(options.delay(3, 0.), Duration::from_secs(8));
+        assert_eq!(options.delay(3, 1.), Duration::from_secs(4));
+    }
+}
@@ -0,0 +1,71 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{secrets, Shell},
+    types,
+};
+use age::secrecy::ExposeSecret;
+use builtins_proc::builtin;
+use std::io::{self, BufRead};
+
+/// Read the value of a secret, without echoing it if it is typed in the terminal
+fn read_value(name: &str) -> io::Result<String> {
+    if atty::is(atty::Stream::Stdin) {
+        secrets::prompt(&format!("{}: ", name)).map(|value| value.expose_secret().clone())
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        Ok(line.trim_end_matches(&['\r', '\n'][..]).into())
+    }
+}
+
+#[builtin(
+    desc = "manage the secrets of the encrypted secret store",
+    man = "
+SYNOPSIS
+    secret [list]
+    secret set NAME
+    secret get NAME
+    secret rm NAME
+
+DESCRIPTION
+    Secrets are kept in a file of the ion data directory, encrypted with a passphrase. The
+    passphrase is asked for the first time a secret is accessed, and the store stays unlocked for
+    the rest of the session. Secrets are expanded with the secret namespace, as in
+    ${secret::NAME}.
+
+    The value given to `secret set` is read from the terminal without being echoed, or from the
+    first line of the standard input when it is not a terminal, so that it never appears in the
+    command line or in the history.
+
+OPTIONS
+    list: print the names of the secrets (default)
+    set: store the secret NAME, replacing any previous value
+    get: print the value of the secret NAME
+    rm: remove the secret NAME"
+)]
+pub fn secret(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let secrets = shell.secrets_mut();
+    let result = match (args.get(1).map(|arg| arg.as_str()), args.get(2..).unwrap_or_default()) {
+        (Some("list"), []) | (None, _) => secrets.names().map(|names| {
+            for name in names {
+                println!("{}", name);
+            }
+        }),
+        (Some("set"), [name]) => match read_value(name) {
+            Ok(value) => secrets.set(name, &value),
+            Err(err) => Err(err.into()),
+        },
+        (Some("get"), [name]) => secrets.get(name).map(|value| println!("{}", value)),
+        (Some("rm"), [name]) => secrets.remove(name),
+        _ => {
+            return Status::bad_argument(
+                "secret: expected `set NAME`, `get NAME`, `rm NAME` or `list`",
+            )
+        }
+    };
+    match result {
+        Ok(()) => Status::SUCCESS,
+        Err(err) => Status::error(format!("ion: secret: {}", err)),
+    }
+}
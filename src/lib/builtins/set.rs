@@ -1,7 +1,7 @@
 use super::Status;
 use crate as ion_shell;
 use crate::{
-    shell::{variables::Value, Shell},
+    shell::{parse_duration, variables::Value, Shell},
     types,
 };
 use builtins_proc::builtin;
@@ -17,7 +17,7 @@ enum PositionalArgs {
     desc = "Set or unset values of shell options and positional parameters.",
     man = "
 SYNOPSIS
-    set [ --help ] [-e | +e] [-p | +p] [- | --] [STRING]...
+    set [ --help ] [-e | +e] [-p | +p] [-o OPTION | +o OPTION] [- | --] [STRING]...
 
 DESCRIPTION
     Shell options may be set using the '-' character, and unset using the '+' character.
@@ -30,6 +30,42 @@ OPTIONS
         It can be combined with the option -e to let a script fail 
         if an errors occures in a pipe
         
+    -o pipefail
+        The same as -p. When several commands of the pipe fail, the code of the last of them is
+        returned. The code of each command of the last pipe is kept in @PIPESTATUS either way.
+
+    -o errexit-on-parse
+        Exit immediately on the first syntax error. By default, the statement that failed to
+        parse is reported and execution resumes with the next statement, unless it opens a
+        block or a branch of one.
+
+    -o collapse-errors
+        Print the errors which repeat inside of a loop only once. Once the loop is done, each
+        of them is followed by the number of times it repeated.
+
+    -o bigmath
+        Evaluate the arithmetic expansions with integers and decimals of any size, which never
+        overflow nor get rounded, except for the quotients without an end which are truncated
+        after 32 decimal places. Only the operators + - * / % ** and the parentheses are then
+        supported.
+
+    -o nullglob
+        Expand the globs which match no file to nothing. By default, such a glob is kept as it
+        is, so that the command sees the pattern itself.
+
+    -o failglob
+        Fail with an expansion error on the globs which match no file. It takes precedence over
+        nullglob.
+
+    -o huponexit
+        Send SIGHUP to the background jobs still in the job table when an interactive shell
+        exits. The jobs removed with disown, or marked with disown -h, are left running.
+
+    -o statement-timeout=DURATION
+        Kill the commands of any pipeline still running after DURATION, in seconds or with
+        the suffix m, h or d, which fails with an error naming the pipeline. A duration of 0,
+        or +o statement-timeout, removes the limit.
+
     --  Following arguments will be set as positional arguments in the shell.
         If no argument are supplied, arguments will be unset.
 
@@ -59,6 +95,43 @@ pub fn set(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
             "+e" => shell.opts_mut().err_exit = false,
             "-p" => shell.opts_mut().pipe_fail = true,
             "+p" => shell.opts_mut().pipe_fail = false,
+            "-o" | "+o" => {
+                let enable = arg.starts_with('-');
+                match args_iter.next().map(types::Str::as_str) {
+                    Some("errexit-on-parse") => shell.opts_mut().err_exit_on_parse = enable,
+                    Some("collapse-errors") => shell.opts_mut().collapse_errors = enable,
+                    Some("pipefail") => shell.opts_mut().pipe_fail = enable,
+                    Some("bigmath") => shell.opts_mut().big_math = enable,
+                    Some("nullglob") => shell.opts_mut().null_glob = enable,
+                    Some("failglob") => shell.opts_mut().fail_glob = enable,
+                    Some("huponexit") => shell.opts_mut().hup_on_exit = enable,
+                    Some("statement-timeout") if !enable => {
+                        shell.opts_mut().statement_timeout = None
+                    }
+                    Some(option) if enable && option.starts_with("statement-timeout=") => {
+                        let duration = &option["statement-timeout=".len()..];
+                        match parse_duration(duration) {
+                            Some(timeout) => {
+                                shell.opts_mut().statement_timeout =
+                                    Some(timeout).filter(|timeout| !timeout.is_zero())
+                            }
+                            None => {
+                                return Status::bad_argument(format!(
+                                    "set: invalid statement timeout '{}'",
+                                    duration
+                                ))
+                            }
+                        }
+                    }
+                    Some(option) => {
+                        return Status::bad_argument(format!(
+                            "set: option '{}' is not recognized",
+                            option
+                        ))
+                    }
+                    None => return Status::bad_argument("set: -o requires an option name"),
+                }
+            }
             _ => {
                 return Status::bad_argument(format!(
                     "set: argument '{}' is not recognized. Try adding `--` before it to pass it \
@@ -1,32 +1,139 @@
 use super::Status;
 use crate as ion_shell;
-use crate::{shell::Shell, types};
+use crate::{
+    shell::{Shell, Value, Variables},
+    types,
+};
 use builtins_proc::builtin;
-use std::fs::File;
+use std::{fs::File, io, path::Path, rc::Rc};
+
+/// The libraries of functions bundled with the shell, sourced or imported as `ion:NAME`
+const LIBRARIES: &[(&str, &str)] = &[("structured", include_str!("libraries/structured.ion"))];
+
+/// The commands of the bundled library named by a path of the form `ion:NAME`
+fn library(path: &str) -> Option<&'static str> {
+    let name = path.strip_prefix("ion:")?;
+    LIBRARIES.iter().find(|&&(library, _)| library == name).map(|&(_, commands)| commands)
+}
 
 #[builtin(
     desc = "evaluates given file",
     man = "
 SYNOPSIS
-    source FILEPATH
+    source FILEPATH [ARGUMENTS...]
 
 DESCRIPTION
     Evaluates the commands in a specified file in the current shell. All changes in shell
-    variables will affect the current shell because of this."
+    variables will affect the current shell because of this.
+
+    Given ARGUMENTS, the file reads them from @args, whose first element is FILEPATH, as a
+    script does. The @args of the shell are restored once the file ran.
+
+    A return statement in the file stops it, and the status of source is the one of the last
+    command the file ran, or the integer given to return.
+
+    A FILEPATH of the form ion:NAME is a library bundled with the shell:
+
+    ion:structured
+        Wrappers of ps and df returning their output as maps, as @ps_map() and @df_map(FIELD)."
 )]
 pub fn source(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
-    match args.get(1) {
-        Some(argument) => {
-            if let Ok(file) = File::open(argument.as_str()) {
-                if let Err(why) = shell.execute_command(file) {
-                    Status::error(format!("ion: {}", why))
-                } else {
-                    Status::SUCCESS
-                }
-            } else {
-                Status::error(format!("ion: failed to open {}\n", argument))
-            }
+    let argument = match args.get(1) {
+        Some(argument) => argument,
+        None => return Status::error("an argument is required for source"),
+    };
+    let result = if let Some(commands) = library(argument.as_str()) {
+        with_args(shell, &args[1..], |shell| {
+            shell.read_source(argument, |shell| shell.execute_command(commands.as_bytes()))
+        })
+    } else if let Ok(commands) = shell.sourced_commands(Path::new(argument.as_str())) {
+        with_args(shell, &args[1..], |shell| {
+            shell.read_source(argument, |shell| shell.execute_commands(commands))
+        })
+    } else {
+        return Status::error(format!("ion: failed to open {}\n", argument));
+    };
+    result.unwrap_or_else(|why| Status::error(format!("ion: {}", why)))
+}
+
+/// Run the sourced file with the arguments given after its path as `@args`, if there are any,
+/// restoring the previous ones afterwards
+fn with_args<'a, T>(
+    shell: &mut Shell<'a>,
+    args: &[types::Str],
+    run: impl FnOnce(&mut Shell<'a>) -> T,
+) -> T {
+    if args.len() < 2 {
+        return run(shell);
+    }
+    let previous = shell.variables().get("args").cloned();
+    let args = args.iter().map(|arg| Value::Str(arg.clone())).collect();
+    shell.variables_mut().set("args", Value::Array(args));
+    let result = run(shell);
+    match previous {
+        Some(previous) => shell.variables_mut().set("args", previous),
+        None => {
+            shell.variables_mut().remove("args");
         }
-        None => Status::error("an argument is required for source"),
     }
+    result
+}
+
+#[builtin(
+    desc = "evaluates given file as a module",
+    man = "
+SYNOPSIS
+    import FILEPATH as NAME
+
+DESCRIPTION
+    Evaluates the commands in a specified file, and defines the functions and variables it
+    creates under the NAME:: namespace rather than under their own names, so the libraries
+    imported do not overwrite each other's definitions nor the ones of the shell.
+
+    The functions of the module call each other, and read the variables of the module, by
+    their own names. Assigning to a variable of the module from one of its functions only
+    changes it until the function returns.
+
+    As with source, a FILEPATH of the form ion:NAME is a library bundled with the shell.
+
+EXAMPLES
+    import ./lib/strings.ion as str
+    str::pad_left 8 $name
+    echo $str::separator"
+)]
+pub fn import(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (path, module) = match args {
+        [_, path, as_, module] if as_ == "as" => (path, module),
+        _ => return Status::bad_argument("ion: import: usage: import FILEPATH as NAME"),
+    };
+    if !Variables::is_valid_name(module) {
+        return Status::bad_argument(format!("ion: import: invalid module name '{}'", module));
+    }
+    let file: Box<dyn io::Read> = match library(path.as_str()) {
+        Some(commands) => Box::new(commands.as_bytes()),
+        None => match File::open(path.as_str()) {
+            Ok(file) => Box::new(file),
+            Err(why) => {
+                return Status::error(format!("ion: import: failed to open {}: {}", path, why))
+            }
+        },
+    };
+
+    // The module runs in its own namespace, so its definitions do not update the variables of
+    // the shell which have the same names
+    shell.variables_mut().new_scope(true);
+    let result = shell.read_source(path, |shell| shell.execute_command(file));
+    let definitions = shell.variables_mut().take_scope();
+    if let Err(why) = result {
+        return Status::error(format!("ion: import: {}: {}", path, why));
+    }
+    for (name, value) in definitions {
+        let name = format!("{}::{}", module, name);
+        let value = match value {
+            Value::Function(function) => Value::Function(Rc::new(function.with_name(&name))),
+            value => value,
+        };
+        shell.variables_mut().set(&name, value);
+    }
+    Status::SUCCESS
 }
@@ -1,8 +1,10 @@
 use super::Status;
 use crate as ion_shell;
-use crate::{types, Shell};
+use crate::{shell::Value, types, Shell};
 use builtins_proc::builtin;
+use regex::Regex;
 use std::{
+    cmp::Ordering,
     fs,
     os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt},
     path::Path,
@@ -40,6 +42,20 @@ OPTIONS
     STRING != STRING
         the strings are not equal
 
+    STRING =~ REGEX
+        the regular expression matches the STRING. The match and the groups it captured are
+        stored in the array MATCHES, the whole match first, and a group which captured nothing
+        is an empty string. MATCHES is emptied when the expression does not match. Unless ion
+        is built with the unicode feature, the Perl classes such as \\d and \\w are not
+        supported: write [0-9] or [A-Za-z0-9_] instead.
+
+    --version-compare VERSION OPERATOR VERSION
+        the versions compare as the integer OPERATOR, one of -eq, -ge, -gt, -le, -lt and -ne,
+        says. The versions are ordered as in semantic versioning: their numbers separated by
+        dots are compared one at a time, a missing number counting as 0, then a version with a
+        pre-release, as in 1.2.0-rc.1, comes before the same version without one. A leading v
+        and the build metadata after a + are ignored.
+
     INTEGER -eq INTEGER
         the integers are equal
 
@@ -113,14 +129,30 @@ EXAMPLES
     Test if 10 is greater than 5:
         test 10 -gt 5 && echo "10 is greater than 5" || echo "10 is not greater than 5"
 
+    Test if a string is a date, and print its year:
+        test $date =~ '^([0-9]{4})-[0-9]{2}-[0-9]{2}$' && echo @MATCHES[1]
+
+    Test if the installed version is recent enough:
+        test --version-compare $version -ge 1.10.0 && echo "up to date"
+
     Test if the user is running a 64-bit OS (POSIX environment only):
         test $(getconf LONG_BIT) = 64 && echo "64-bit OS" || echo "32-bit OS"
 
 AUTHOR
     Written by Michael Murphy."#
 )]
-pub fn test(args: &[types::Str], _: &mut Shell<'_>) -> Status {
-    match evaluate_arguments(&args[1..]) {
+pub fn test(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let result = match &args[1..] {
+        [string, operator, pattern] if operator.as_str() == "=~" => regex_match(string, pattern)
+            .map(|matches| {
+                let matched = matches.is_some();
+                let matches = matches.unwrap_or_default().into_iter().map(Value::Str).collect();
+                shell.variables_mut().set("MATCHES", Value::Array(matches));
+                matched
+            }),
+        _ => evaluate_arguments(&args[1..]),
+    };
+    match result {
         Ok(true) => Status::TRUE,
         Ok(false) => Status::FALSE,
         Err(why) => Status::error(why),
@@ -129,6 +161,21 @@ pub fn test(args: &[types::Str], _: &mut Shell<'_>) -> Status {
 
 fn evaluate_arguments(arguments: &[types::Str]) -> Result<bool, types::Str> {
     match arguments.first() {
+        Some(s) if s.as_str() == "--version-compare" => match &arguments[1..] {
+            [first, operator, second] => {
+                let ordering = compare_versions(first, second)?;
+                match operator.as_str() {
+                    "-eq" => Ok(ordering == Ordering::Equal),
+                    "-ge" => Ok(ordering != Ordering::Less),
+                    "-gt" => Ok(ordering == Ordering::Greater),
+                    "-le" => Ok(ordering != Ordering::Greater),
+                    "-lt" => Ok(ordering == Ordering::Less),
+                    "-ne" => Ok(ordering != Ordering::Equal),
+                    _ => Err(format!("test: unknown condition: {:?}", operator).into()),
+                }
+            }
+            _ => Err("test: usage: --version-compare VERSION OPERATOR VERSION".into()),
+        },
         Some(s) if s.starts_with('-') && s[1..].starts_with(char::is_alphabetic) => {
             // Access the second character in the flag string: this will be type of the
             // flag. If no flag was given, return `SUCCESS`
@@ -183,6 +230,67 @@ fn evaluate_expression(first: &str, operator: &str, second: &str) -> Result<bool
     }
 }
 
+/// The match of the regular expression in the string, followed by the groups it captured, or
+/// `None` if it does not match
+fn regex_match(string: &str, pattern: &str) -> Result<Option<Vec<types::Str>>, types::Str> {
+    let regex = Regex::new(pattern)
+        .map_err(|why| format!("test: invalid regular expression {:?}: {}", pattern, why))?;
+    Ok(regex.captures(string).map(|captures| {
+        captures
+            .iter()
+            .map(|group| group.map_or_else(types::Str::new, |group| group.as_str().into()))
+            .collect()
+    }))
+}
+
+/// Splits a version into its numbers and its pre-release identifiers
+fn parse_version(version: &str) -> Result<(Vec<u64>, Vec<&str>), types::Str> {
+    let invalid = || types::Str::from(format!("test: invalid version: {:?}", version));
+    let stripped = version.strip_prefix('v').unwrap_or(version);
+    let stripped = stripped.split('+').next().unwrap_or_default();
+    let (release, pre_release) = match stripped.split_once('-') {
+        Some((release, pre_release)) => (release, pre_release.split('.').collect()),
+        None => (stripped, Vec::new()),
+    };
+    let numbers = release
+        .split('.')
+        .map(|number| number.parse().map_err(|_| invalid()))
+        .collect::<Result<_, _>>()?;
+    Ok((numbers, pre_release))
+}
+
+/// Orders the versions as semantic versioning does
+fn compare_versions(first: &str, second: &str) -> Result<Ordering, types::Str> {
+    let (first_numbers, first_pre) = parse_version(first)?;
+    let (second_numbers, second_pre) = parse_version(second)?;
+    let length = first_numbers.len().max(second_numbers.len());
+    let number = |numbers: &[u64], index| numbers.get(index).copied().unwrap_or(0);
+    let ordering = (0..length)
+        .map(|index| number(&first_numbers, index).cmp(&number(&second_numbers, index)))
+        .find(|&ordering| ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal);
+    if ordering != Ordering::Equal {
+        return Ok(ordering);
+    }
+    Ok(match (first_pre.is_empty(), second_pre.is_empty()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        // Numeric identifiers are compared as numbers, and come before the alphanumeric ones
+        (false, false) => first_pre
+            .iter()
+            .zip(&second_pre)
+            .map(|(left, right)| match (left.parse::<u64>(), right.parse::<u64>()) {
+                (Ok(left), Ok(right)) => left.cmp(&right),
+                (Ok(_), Err(_)) => Ordering::Less,
+                (Err(_), Ok(_)) => Ordering::Greater,
+                (Err(_), Err(_)) => left.cmp(right),
+            })
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or_else(|| first_pre.len().cmp(&second_pre.len())),
+    })
+}
+
 /// Exits SUCCESS if both files have the same device and inode numbers
 fn files_have_same_device_and_inode_numbers(first: &str, second: &str) -> bool {
     // Obtain the device and inode of the first file or return FAILED
@@ -445,3 +553,30 @@ fn test_file_size_is_greater_than_zero() {
     assert_eq!(file_size_is_greater_than_zero("testing/file_with_text"), true);
     assert_eq!(file_size_is_greater_than_zero("testing/empty_file"), false);
 }
+
+#[test]
+fn test_regex_match() {
+    assert_eq!(
+        regex_match("2024-02-28", r"^([0-9]+)-([0-9]+)(x)?"),
+        Ok(Some(vec!["2024-02".into(), "2024".into(), "02".into(), "".into()]))
+    );
+    assert_eq!(regex_match("ion", "^a"), Ok(None));
+    assert!(regex_match("ion", "(").is_err());
+}
+
+#[test]
+fn test_version_compare() {
+    let compare = |first: &str, operator: &str, second: &str| {
+        let args: Vec<types::Str> =
+            vec!["--version-compare".into(), first.into(), operator.into(), second.into()];
+        evaluate_arguments(&args)
+    };
+    assert_eq!(compare("1.10.0", "-gt", "1.9.3"), Ok(true));
+    assert_eq!(compare("v1.2", "-eq", "1.2.0+build.5"), Ok(true));
+    assert_eq!(compare("1.0.0-rc.1", "-lt", "1.0.0"), Ok(true));
+    assert_eq!(compare("1.0.0-alpha", "-lt", "1.0.0-alpha.1"), Ok(true));
+    assert_eq!(compare("1.0.0-alpha.beta", "-gt", "1.0.0-alpha.10"), Ok(true));
+    assert_eq!(compare("1.0.0-rc.2", "-lt", "1.0.0-rc.10"), Ok(true));
+    assert!(compare("1.x", "-lt", "1.0").is_err());
+    assert!(compare("1.0", "<", "1.1").is_err());
+}
@@ -0,0 +1,63 @@
+use super::Status;
+use crate as ion_shell;
+use crate::{
+    shell::{Shell, Trap},
+    types,
+};
+use builtins_proc::builtin;
+
+#[builtin(
+    desc = "run commands on signals, on exit and on failures",
+    man = "
+SYNOPSIS
+    trap [COMMAND | - SIGNAL...]
+
+DESCRIPTION
+    Sets COMMAND to run when the shell receives one of the SIGNALs, rather than being interrupted
+    by it. The signals are handled between statements, and the command leaves the exit status
+    unchanged. An empty COMMAND ignores the signals, and - restores their default behavior.
+    Without arguments, the trapped commands are listed.
+
+SIGNALS
+    HUP, INT, TERM
+        the signals of these names, with or without the SIG prefix, or their numbers
+    EXIT, 0
+        the exit of the shell
+    ERR
+        the failure of a pipeline, outside of the conditions of if and while
+
+EXAMPLES
+    trap 'rm -f $tmp' EXIT
+    trap 'echo interrupted' INT TERM"
+)]
+pub fn trap(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    let (command, names) = match args {
+        [_] => {
+            let mut traps: Vec<_> = shell
+                .traps()
+                .iter()
+                .map(|(trap, command)| format!("trap '{}' {}", command, trap))
+                .collect();
+            traps.sort();
+            for trap in traps {
+                println!("{}", trap);
+            }
+            return Status::SUCCESS;
+        }
+        [_, command, names @ ..] if !names.is_empty() => (command, names),
+        _ => return Status::error("ion: trap: a command and signals are required"),
+    };
+
+    let traps = match names.iter().map(|name| name.parse::<Trap>()).collect::<Result<Vec<_>, _>>() {
+        Ok(traps) => traps,
+        Err(why) => return Status::error(format!("ion: trap: {}", why)),
+    };
+    for trap in traps {
+        if &**command == "-" {
+            shell.traps_mut().remove(&trap);
+        } else {
+            shell.traps_mut().insert(trap, command.clone());
+        }
+    }
+    Status::SUCCESS
+}
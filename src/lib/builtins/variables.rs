@@ -4,7 +4,10 @@ use std::io::{self, Write};
 
 use super::Status;
 use crate as ion_shell;
-use crate::{shell::variables::Variables, types, Shell};
+use crate::{
+    shell::{variables::Variables, Value},
+    types, Shell,
+};
 use builtins_proc::builtin;
 
 fn print_list(vars: &Variables) {
@@ -73,14 +76,31 @@ fn parse_alias(args: &str) -> Binding {
     desc = "View, set or unset aliases",
     man = "
 SYNOPSIS
-    alias [ALIAS_NAME]
+    alias [-g] [ALIAS_NAME[=VALUE]]
 
 DESCRIPTION
     The alias command will define an alias for another command, 
     and thus may be used as a command itself. 
-    If no argument is given then all defined aliases and their commands are shown."
+    If no argument is given then all defined aliases and their commands are shown.
+
+    The arguments following an alias are appended to its value. When the value ends with a
+    space, the first of these arguments is expanded as well if it is an alias.
+
+OPTIONS
+    -g  Define, show or list the global aliases. These replace the unquoted words which name
+        them in any position of a command, rather than only the command itself, before the
+        command is parsed. Escaping or quoting the word prevents its expansion.
+
+EXAMPLES
+    alias gco='git checkout'
+    alias -g L='| less'
+    gco main
+    cat log L"
 )]
 pub fn alias(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    if args.get(1).map_or(false, |arg| arg.as_str() == "-g") {
+        return global_alias(&args[2..], shell);
+    }
     match parse_alias(&args[1..].join(" ")) {
         Binding::InvalidKey(key) => {
             return Status::error(format!("ion: alias name, '{}', is invalid", key));
@@ -100,14 +120,42 @@ pub fn alias(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     Status::SUCCESS
 }
 
+/// Define, show or list the global aliases, which `alias -g` sets
+fn global_alias(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
+    match parse_alias(&args.join(" ")) {
+        Binding::InvalidKey(key) => {
+            return Status::error(format!("ion: alias name, '{}', is invalid", key));
+        }
+        Binding::KeyValue(key, value) => {
+            shell.global_aliases_mut().insert(key, value);
+        }
+        Binding::ListEntries => {
+            let stdout = io::stdout();
+            let stdout = &mut stdout.lock();
+            for (key, value) in shell.global_aliases() {
+                writeln!(stdout, "{} = {}", key, value).unwrap();
+            }
+        }
+        Binding::KeyOnly(key) => {
+            if let Some(alias) = shell.global_aliases().get(&key) {
+                println!("alias -g {}='{}'", key, alias);
+            } else {
+                return Status::error(format!("ion: alias '{}' not found", key));
+            }
+        }
+    }
+    Status::SUCCESS
+}
+
 #[builtin(
     desc = "removes an alias from a shell",
     man = "
 SYNOPSIS
-    unalias ALIAS_NAME
+    unalias ALIAS_NAME...
 
 DESCRIPTION
-    Dropping an alias will erase it from the shell.
+    Dropping an alias will erase it from the shell. Both the aliases of commands and the global
+    aliases set with alias -g are removed, while the variables and functions are left as they are.
 "
 )]
 pub fn unalias(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
@@ -115,7 +163,12 @@ pub fn unalias(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
         return Status::error("ion: you must specify an alias name".to_string());
     }
     for alias in args.iter().skip(1) {
-        if shell.variables_mut().remove(alias.as_ref()).is_none() {
+        let removed = if let Some(Value::Alias(_)) = shell.variables().get(alias) {
+            shell.variables_mut().remove(alias.as_ref()).is_some()
+        } else {
+            shell.global_aliases_mut().remove(alias).is_some()
+        };
+        if !removed {
             return Status::error(format!("ion: undefined alias: {}", alias));
         }
     }
@@ -140,6 +193,9 @@ pub fn drop(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
     }
 
     for variable in args.iter().skip(1) {
+        if shell.is_constant(variable) {
+            return Status::error(format!("ion: cannot drop the constant {}", variable));
+        }
         if shell.variables_mut().remove(variable.as_ref()).is_none() {
             return Status::error(format!("ion: undefined variable: {}", variable));
         }
@@ -151,7 +207,10 @@ pub fn drop(args: &[types::Str], shell: &mut Shell<'_>) -> Status {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::expansion::Expander;
+    use crate::{
+        expansion::Expander, parser::lexers::assignments::Operator,
+        shell::flow_control::LocalAction,
+    };
 
     fn vec_string(args: &[&str]) -> Vec<types::Str> { args.iter().map(|s| (*s).into()).collect() }
 
@@ -164,6 +223,15 @@ mod test {
         assert!(shell.expand_string("$FOO").is_err());
     }
 
+    #[test]
+    fn drop_fails_with_constant() {
+        let mut shell = Shell::default();
+        shell.local(&LocalAction::Const("FOO".into(), Operator::Equal, "BAR".into()));
+        let return_status = builtin_drop(&vec_string(&["drop", "FOO"]), &mut shell);
+        assert!(return_status.is_failure());
+        assert_eq!(shell.expand_string("$FOO").unwrap().join(""), "BAR");
+    }
+
     #[test]
     fn drop_fails_with_no_arguments() {
         let mut shell = Shell::default();
@@ -0,0 +1,168 @@
+//! The operators of braced variables which strip or replace the parts of their values matching a
+//! glob pattern, as in `${path%.*}` or `${name/-/_}`.
+use glob::Pattern;
+
+/// An operator following the name of a braced variable
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Affix<'a> {
+    /// `#` and `##`: remove the shortest, or the longest, prefix matching the pattern
+    Prefix { pattern: &'a str, longest: bool },
+    /// `%` and `%%`: remove the shortest, or the longest, suffix matching the pattern
+    Suffix { pattern: &'a str, longest: bool },
+    /// `/` and `//`: replace the first, or every, longest match of the pattern
+    Replace { pattern: &'a str, replacement: &'a str, all: bool },
+}
+
+impl<'a> Affix<'a> {
+    /// Split the text of a braced variable into the name of the variable, and the operator
+    /// following it if there is one
+    pub(crate) fn parse(text: &'a str) -> (&'a str, Option<Self>) {
+        let end = match text.find(|c| matches!(c, '#' | '%' | '/')) {
+            Some(end)
+                if end > 0 && text[..end].chars().all(|c| c.is_alphanumeric() || c == '_') =>
+            {
+                end
+            }
+            _ => return (text, None),
+        };
+        let (name, operator) = text.split_at(end);
+        let affix = if let Some(pattern) = operator.strip_prefix("##") {
+            Affix::Prefix { pattern, longest: true }
+        } else if let Some(pattern) = operator.strip_prefix('#') {
+            Affix::Prefix { pattern, longest: false }
+        } else if let Some(pattern) = operator.strip_prefix("%%") {
+            Affix::Suffix { pattern, longest: true }
+        } else if let Some(pattern) = operator.strip_prefix('%') {
+            Affix::Suffix { pattern, longest: false }
+        } else {
+            let (operator, all) = match operator.strip_prefix("//") {
+                Some(operator) => (operator, true),
+                None => (&operator[1..], false),
+            };
+            let (pattern, replacement) = operator.split_once('/').unwrap_or((operator, ""));
+            Affix::Replace { pattern, replacement, all }
+        };
+        (name, Some(affix))
+    }
+
+    /// The pattern matched by the operator
+    pub(crate) const fn pattern(&self) -> &'a str {
+        match *self {
+            Affix::Prefix { pattern, .. }
+            | Affix::Suffix { pattern, .. }
+            | Affix::Replace { pattern, .. } => pattern,
+        }
+    }
+
+    /// The text replacing the matches of the pattern, which is empty unless the operator replaces
+    pub(crate) const fn replacement(&self) -> &'a str {
+        match *self {
+            Affix::Replace { replacement, .. } => replacement,
+            _ => "",
+        }
+    }
+
+    /// Apply the operator to the value, with its pattern compiled and its replacement expanded
+    pub(crate) fn apply(&self, value: &str, pattern: &Pattern, replacement: &str) -> String {
+        let boundaries = || value.char_indices().map(|(index, _)| index).chain(Some(value.len()));
+        match *self {
+            Affix::Prefix { longest, .. } => {
+                let matching = |&end: &usize| pattern.matches(&value[..end]);
+                let end = if longest {
+                    boundaries().filter(matching).last()
+                } else {
+                    boundaries().find(matching)
+                };
+                end.map_or(value, |end| &value[end..]).into()
+            }
+            Affix::Suffix { longest, .. } => {
+                let matching = |&start: &usize| pattern.matches(&value[start..]);
+                let start = if longest {
+                    boundaries().find(matching)
+                } else {
+                    boundaries().filter(matching).last()
+                };
+                start.map_or(value, |start| &value[..start]).into()
+            }
+            Affix::Replace { all, .. } => {
+                let mut replaced = String::with_capacity(value.len());
+                let mut rest = value;
+                while let Some((start, end)) = longest_match(rest, pattern) {
+                    replaced.push_str(&rest[..start]);
+                    replaced.push_str(replacement);
+                    // An empty match replaces nothing, and the search resumes after a character
+                    let skipped = if end == start {
+                        rest[end..].chars().next().map_or(0, char::len_utf8)
+                    } else {
+                        0
+                    };
+                    replaced.push_str(&rest[end..end + skipped]);
+                    rest = &rest[end + skipped..];
+                    if !all || rest.is_empty() {
+                        break;
+                    }
+                }
+                replaced.push_str(rest);
+                replaced
+            }
+        }
+    }
+}
+
+/// The bounds of the first longest part of the value matching the pattern
+fn longest_match(value: &str, pattern: &Pattern) -> Option<(usize, usize)> {
+    let boundaries = || value.char_indices().map(|(index, _)| index).chain(Some(value.len()));
+    boundaries().find_map(|start| {
+        boundaries()
+            .filter(|&end| end >= start && pattern.matches(&value[start..end]))
+            .last()
+            .map(|end| (start, end))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, value: &str) -> String {
+        let (_, affix) = Affix::parse(text);
+        let affix = affix.unwrap();
+        affix.apply(value, &Pattern::new(affix.pattern()).unwrap(), affix.replacement())
+    }
+
+    #[test]
+    fn affix_parsing() {
+        assert_eq!(Affix::parse("name"), ("name", None));
+        assert_eq!(Affix::parse("c::red"), ("c::red", None));
+        assert_eq!(
+            Affix::parse("path##*/"),
+            ("path", Some(Affix::Prefix { pattern: "*/", longest: true }))
+        );
+        assert_eq!(
+            Affix::parse("name//-/_"),
+            ("name", Some(Affix::Replace { pattern: "-", replacement: "_", all: true }))
+        );
+        assert_eq!(
+            Affix::parse("name/-"),
+            ("name", Some(Affix::Replace { pattern: "-", replacement: "", all: false }))
+        );
+    }
+
+    #[test]
+    fn affix_stripping() {
+        let path = "/usr/src/ion.tar.gz";
+        assert_eq!(apply("path#*/", path), "usr/src/ion.tar.gz");
+        assert_eq!(apply("path##*/", path), "ion.tar.gz");
+        assert_eq!(apply("path%.*", path), "/usr/src/ion.tar");
+        assert_eq!(apply("path%%.*", path), "/usr/src/ion");
+        assert_eq!(apply("path%.zip", path), path);
+    }
+
+    #[test]
+    fn affix_replacing() {
+        assert_eq!(apply("name/-/_", "a-b-c"), "a_b-c");
+        assert_eq!(apply("name//-/_", "a-b-c"), "a_b_c");
+        assert_eq!(apply("name//[0-9]*/#", "v12.3"), "v#");
+        assert_eq!(apply("name//é/e", "été"), "ete");
+    }
+}
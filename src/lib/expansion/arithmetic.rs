@@ -0,0 +1,175 @@
+//! The parts of arithmetic expansions which are handled by the shell rather than by the `calc`
+//! crate: in-place assignments, which write back to shell variables, and ternary conditionals.
+use std::cmp::Ordering;
+
+/// The operators which may prefix `=` in an in-place assignment, longest first
+const ASSIGNMENT_OPERATORS: [&str; 11] = ["**", "<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^"];
+
+/// The comparison operators allowed in the condition of a ternary, longest first
+const COMPARISON_OPERATORS: [&str; 6] = ["==", "!=", "<=", ">=", "<", ">"];
+
+/// An assignment to a variable in an arithmetic expansion, such as `i += 1`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Assignment<'a> {
+    /// The variable to write back to
+    pub name:       &'a str,
+    /// The operator applied to the current value and the expression, if any
+    pub operator:   Option<&'a str>,
+    /// The expression on the right hand side
+    pub expression: &'a str,
+}
+
+/// Parse the expression as an assignment, if it is one
+pub fn assignment(input: &str) -> Option<Assignment<'_>> {
+    let input = input.trim();
+    let name_len = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or_else(|| input.len());
+    let name = &input[..name_len];
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let rest = input[name_len..].trim_start();
+    let operator = ASSIGNMENT_OPERATORS
+        .iter()
+        .copied()
+        .find(|op| rest.starts_with(op) && rest[op.len()..].starts_with('='))
+        .unwrap_or("");
+    let expression = rest[operator.len()..].strip_prefix('=')?;
+    // `==` is a comparison, not an assignment
+    if expression.starts_with('=') {
+        return None;
+    }
+
+    let operator = if operator.is_empty() { None } else { Some(operator) };
+    Some(Assignment { name, operator, expression: expression.trim() })
+}
+
+/// Split a `condition ? then : otherwise` expression into its parts. Ternaries are right
+/// associative, so `a ? b : c ? d : e` is split as `a`, `b` and `c ? d : e`.
+pub fn ternary(input: &str) -> Option<(&str, &str, &str)> {
+    let question = find_top_level(input, |bytes, i| bytes[i] == b'?')?;
+    let branches = &input[question + 1..];
+    let mut nested = 0;
+    let colon = find_top_level(branches, |bytes, i| match bytes[i] {
+        b'?' => {
+            nested += 1;
+            false
+        }
+        b':' if nested == 0 => true,
+        b':' => {
+            nested -= 1;
+            false
+        }
+        _ => false,
+    })?;
+    Some((input[..question].trim(), branches[..colon].trim(), branches[colon + 1..].trim()))
+}
+
+/// Split a comparison into its operands and operator
+pub fn comparison(input: &str) -> Option<(&str, &str, &str)> {
+    let operator_at = |bytes: &[u8], i: usize| {
+        let rest = &bytes[i..];
+        // Shifts are not comparisons
+        if rest.starts_with(b"<<")
+            || rest.starts_with(b">>")
+            || (i > 0 && (bytes[i - 1] == b'<' || bytes[i - 1] == b'>'))
+        {
+            return None;
+        }
+        COMPARISON_OPERATORS.iter().copied().find(|op| rest.starts_with(op.as_bytes()))
+    };
+    let i = find_top_level(input, |bytes, i| operator_at(bytes, i).is_some())?;
+    let operator = operator_at(input.as_bytes(), i)?;
+    Some((input[..i].trim(), operator, input[i + operator.len()..].trim()))
+}
+
+/// Compare two evaluated operands with a comparison operator
+pub fn compare(left: f64, operator: &str, right: f64) -> bool {
+    match operator {
+        "==" => (left - right).abs() < std::f64::EPSILON,
+        "!=" => (left - right).abs() >= std::f64::EPSILON,
+        "<=" => left <= right,
+        ">=" => left >= right,
+        "<" => left < right,
+        _ => left > right,
+    }
+}
+
+/// Whether the ordering of two operands satisfies a comparison operator
+pub fn holds(ordering: Ordering, operator: &str) -> bool {
+    match operator {
+        "==" => ordering == Ordering::Equal,
+        "!=" => ordering != Ordering::Equal,
+        "<=" => ordering != Ordering::Greater,
+        ">=" => ordering != Ordering::Less,
+        "<" => ordering == Ordering::Less,
+        _ => ordering == Ordering::Greater,
+    }
+}
+
+/// Find the first byte outside of parentheses which satisfies the predicate
+fn find_top_level<F: FnMut(&[u8], usize) -> bool>(input: &str, mut predicate: F) -> Option<usize> {
+    let bytes = input.as_bytes();
+    let mut level = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' => level += 1,
+            b')' => level -= 1,
+            _ if level == 0 && predicate(bytes, i) => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments() {
+        assert_eq!(
+            assignment("i += 1"),
+            Some(Assignment { name: "i", operator: Some("+"), expression: "1" })
+        );
+        assert_eq!(
+            assignment("x**=2 * y"),
+            Some(Assignment { name: "x", operator: Some("**"), expression: "2 * y" })
+        );
+        assert_eq!(
+            assignment("flags <<= 2"),
+            Some(Assignment { name: "flags", operator: Some("<<"), expression: "2" })
+        );
+        assert_eq!(
+            assignment("total = a ? b : c"),
+            Some(Assignment { name: "total", operator: None, expression: "a ? b : c" })
+        );
+        assert_eq!(assignment("a == b"), None);
+        assert_eq!(assignment("a <= b"), None);
+        assert_eq!(assignment("1 + 2"), None);
+        assert_eq!(assignment("2a = 3"), None);
+    }
+
+    #[test]
+    fn ternaries() {
+        assert_eq!(ternary("a > b ? a : b"), Some(("a > b", "a", "b")));
+        assert_eq!(ternary("a ? b : c ? d : e"), Some(("a", "b", "c ? d : e")));
+        assert_eq!(ternary("a ? b ? c : d : e"), Some(("a", "b ? c : d", "e")));
+        assert_eq!(ternary("(a ? b : c) + 1"), None);
+        assert_eq!(ternary("a + b"), None);
+    }
+
+    #[test]
+    fn comparisons() {
+        assert_eq!(comparison("a + 1 >= b"), Some(("a + 1", ">=", "b")));
+        assert_eq!(comparison("1 << 2 < 5"), Some(("1 << 2", "<", "5")));
+        assert_eq!(comparison("a >> 1"), None);
+        assert!(compare(1.0, "<", 2.0));
+        assert!(compare(2.0, "==", 2.0));
+        assert!(!compare(2.0, "!=", 2.0));
+        assert!(holds(Ordering::Less, "<="));
+        assert!(!holds(Ordering::Equal, ">"));
+    }
+}
@@ -0,0 +1,411 @@
+//! Arbitrary precision arithmetic, which evaluates the arithmetic expansions in place of the `calc`
+//! crate while the `bigmath` option is set. Numbers are decimals of any size, so that integers
+//! never overflow and decimals are never rounded, except for the quotients which do not end, that
+//! are truncated after `DIVISION_SCALE` decimal places.
+//!
+//! Only the operators on numbers are supported: `+`, `-`, `*`, `/`, `%`, `**`, `²`, `³` and
+//! parentheses.
+use std::{cmp::Ordering, fmt, iter::Peekable, str::Chars};
+
+/// The number of decimal places kept by a division
+const DIVISION_SCALE: usize = 32;
+
+/// The largest exponent taken by a power, beyond which computing it could take forever
+const MAX_EXPONENT: u64 = 10_000;
+
+/// A decimal number: its digits, the least significant first, scaled down by `scale` decimal
+/// places. Numbers are kept without any zero beyond their most significant digit or their least
+/// significant decimal, so that each has a single representation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BigDecimal {
+    negative: bool,
+    digits:   Vec<u8>,
+    scale:    usize,
+}
+
+/// Remove the zeros beyond the most significant digit
+fn trim(mut digits: Vec<u8>) -> Vec<u8> {
+    while digits.last() == Some(&0) {
+        digits.pop();
+    }
+    digits
+}
+
+fn compare_digits(left: &[u8], right: &[u8]) -> Ordering {
+    left.len().cmp(&right.len()).then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut sum = Vec::with_capacity(left.len().max(right.len()) + 1);
+    let mut carry = 0;
+    for i in 0..left.len().max(right.len()) {
+        let digit = left.get(i).unwrap_or(&0) + right.get(i).unwrap_or(&0) + carry;
+        sum.push(digit % 10);
+        carry = digit / 10;
+    }
+    sum.push(carry);
+    trim(sum)
+}
+
+/// Subtract the right digits from the left ones, which must be the greatest
+fn sub_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut difference = Vec::with_capacity(left.len());
+    let mut borrow = 0;
+    for (i, &digit) in left.iter().enumerate() {
+        let subtracted = right.get(i).unwrap_or(&0) + borrow;
+        if digit >= subtracted {
+            difference.push(digit - subtracted);
+            borrow = 0;
+        } else {
+            difference.push(digit + 10 - subtracted);
+            borrow = 1;
+        }
+    }
+    trim(difference)
+}
+
+fn mul_digits(left: &[u8], right: &[u8]) -> Vec<u8> {
+    if left.is_empty() || right.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![0u32; left.len() + right.len()];
+    for (i, &l) in left.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &r) in right.iter().enumerate() {
+            let digit = product[i + j] + u32::from(l) * u32::from(r) + carry;
+            product[i + j] = digit % 10;
+            carry = digit / 10;
+        }
+        product[i + right.len()] += carry;
+    }
+    trim(product.into_iter().map(|digit| digit as u8).collect())
+}
+
+/// The quotient and the remainder of the division of the left digits by the right ones, which
+/// must not be zero
+fn div_digits(left: &[u8], right: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let mut quotient = vec![0; left.len()];
+    let mut remainder = Vec::new();
+    for (i, &digit) in left.iter().enumerate().rev() {
+        remainder.insert(0, digit);
+        remainder = trim(remainder);
+        while compare_digits(&remainder, right) != Ordering::Less {
+            remainder = sub_digits(&remainder, right);
+            quotient[i] += 1;
+        }
+    }
+    (trim(quotient), remainder)
+}
+
+/// Multiply the digits by a power of ten
+fn shift(digits: &[u8], places: usize) -> Vec<u8> {
+    if digits.is_empty() {
+        return Vec::new();
+    }
+    let mut shifted = vec![0; places];
+    shifted.extend_from_slice(digits);
+    shifted
+}
+
+impl BigDecimal {
+    fn new(negative: bool, digits: Vec<u8>, mut scale: usize) -> Self {
+        let mut digits = trim(digits);
+        let zeros = digits.iter().take(scale).take_while(|&&digit| digit == 0).count();
+        digits.drain(..zeros);
+        scale -= zeros;
+        if digits.is_empty() {
+            Self::default()
+        } else {
+            Self { negative, digits, scale }
+        }
+    }
+
+    /// Whether the number is zero
+    pub fn is_zero(&self) -> bool { self.digits.is_empty() }
+
+    /// Whether the number has no decimals
+    pub fn is_integer(&self) -> bool { self.scale == 0 }
+
+    /// The digits of both numbers, with as many decimal places, and the number of these places
+    fn aligned(&self, other: &Self) -> (Vec<u8>, Vec<u8>, usize) {
+        let scale = self.scale.max(other.scale);
+        (shift(&self.digits, scale - self.scale), shift(&other.digits, scale - other.scale), scale)
+    }
+
+    fn neg(mut self) -> Self {
+        self.negative = !self.negative && !self.is_zero();
+        self
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let (left, right, scale) = self.aligned(other);
+        if self.negative == other.negative {
+            Self::new(self.negative, add_digits(&left, &right), scale)
+        } else if compare_digits(&left, &right) == Ordering::Less {
+            Self::new(other.negative, sub_digits(&right, &left), scale)
+        } else {
+            Self::new(self.negative, sub_digits(&left, &right), scale)
+        }
+    }
+
+    fn sub(&self, other: &Self) -> Self { self.add(&other.clone().neg()) }
+
+    fn mul(&self, other: &Self) -> Self {
+        Self::new(
+            self.negative != other.negative,
+            mul_digits(&self.digits, &other.digits),
+            self.scale + other.scale,
+        )
+    }
+
+    fn div(&self, other: &Self) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err("division by zero".into());
+        }
+        let dividend = shift(&self.digits, DIVISION_SCALE + other.scale);
+        let (quotient, _) = div_digits(&dividend, &other.digits);
+        Ok(Self::new(self.negative != other.negative, quotient, self.scale + DIVISION_SCALE))
+    }
+
+    /// The remainder of the division truncated toward zero, which has the sign of the dividend
+    fn rem(&self, other: &Self) -> Result<Self, String> {
+        if other.is_zero() {
+            return Err("division by zero".into());
+        }
+        let (left, right, scale) = self.aligned(other);
+        let (_, remainder) = div_digits(&left, &right);
+        Ok(Self::new(self.negative, remainder, scale))
+    }
+
+    fn pow(&self, exponent: &Self) -> Result<Self, String> {
+        if !exponent.is_integer() {
+            return Err(format!("the exponent {} is not an integer", exponent));
+        }
+        let mut remaining = exponent
+            .digits
+            .iter()
+            .rev()
+            .try_fold(0u64, |power, &digit| {
+                power.checked_mul(10).map(|power| power + u64::from(digit))
+            })
+            .filter(|&power| power <= MAX_EXPONENT)
+            .ok_or_else(|| format!("the exponent {} is too large", exponent))?;
+        let mut power = Self::new(false, vec![1], 0);
+        let mut base = self.clone();
+        while remaining > 0 {
+            if remaining % 2 == 1 {
+                power = power.mul(&base);
+            }
+            base = base.mul(&base);
+            remaining /= 2;
+        }
+        if exponent.negative {
+            Self::new(false, vec![1], 0).div(&power)
+        } else {
+            Ok(power)
+        }
+    }
+}
+
+impl std::str::FromStr for BigDecimal {
+    type Err = String;
+
+    /// Parse a decimal number, as `-12.5`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (negative, unsigned) = match text.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (integer, decimals) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+        let valid = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+        if integer.len() + decimals.len() == 0 || !valid(integer) || !valid(decimals) {
+            return Err(format!("'{}' is not a number", text));
+        }
+        let digits = integer.bytes().chain(decimals.bytes()).rev().map(|byte| byte - b'0');
+        Ok(Self::new(negative, digits.collect(), decimals.len()))
+    }
+}
+
+impl PartialOrd for BigDecimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for BigDecimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (left, right, _) = self.aligned(other);
+        match (self.negative, other.negative) {
+            (false, false) => compare_digits(&left, &right),
+            (true, true) => compare_digits(&right, &left),
+            (negative, _) => {
+                if negative {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for BigDecimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        let mut digits: Vec<u8> = self.digits.clone();
+        // Zeros before the decimal point and the decimals
+        digits.resize(digits.len().max(self.scale + 1), 0);
+        let text: String = digits.iter().rev().map(|&digit| char::from(b'0' + digit)).collect();
+        let (integer, decimals) = text.split_at(text.len() - self.scale);
+        if self.negative {
+            write!(f, "-")?;
+        }
+        if decimals.is_empty() {
+            write!(f, "{}", integer)
+        } else {
+            write!(f, "{}.{}", integer, decimals)
+        }
+    }
+}
+
+/// A recursive descent parser evaluating the expression as it reads it
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) { while self.chars.next_if(|c| c.is_whitespace()).is_some() {} }
+
+    /// The next character which is not whitespace, if it is the one expected, which is consumed
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&expected).is_some()
+    }
+
+    fn expression(&mut self) -> Result<BigDecimal, String> {
+        let mut value = self.term()?;
+        loop {
+            if self.eat('+') {
+                value = value.add(&self.term()?);
+            } else if self.eat('-') {
+                value = value.sub(&self.term()?);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<BigDecimal, String> {
+        let mut value = self.power()?;
+        loop {
+            if self.eat('*') {
+                value = value.mul(&self.power()?);
+            } else if self.eat('/') {
+                value = value.div(&self.power()?)?;
+            } else if self.eat('%') {
+                value = value.rem(&self.power()?)?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// A power, which is right-associative
+    fn power(&mut self) -> Result<BigDecimal, String> {
+        let base = self.unary()?;
+        self.skip_whitespace();
+        let mut lookahead = self.chars.clone();
+        if lookahead.next() == Some('*') && lookahead.next() == Some('*') {
+            self.chars = lookahead;
+            base.pow(&self.power()?)
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn unary(&mut self) -> Result<BigDecimal, String> {
+        if self.eat('-') {
+            Ok(self.unary()?.neg())
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.postfix()
+        }
+    }
+
+    /// A number followed by any of the square and cube signs
+    fn postfix(&mut self) -> Result<BigDecimal, String> {
+        let mut value = self.primary()?;
+        loop {
+            if self.eat('²') {
+                value = value.mul(&value);
+            } else if self.eat('³') {
+                value = value.mul(&value).mul(&value);
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<BigDecimal, String> {
+        if self.eat('(') {
+            let value = self.expression()?;
+            return if self.eat(')') { Ok(value) } else { Err("missing ')'".into()) };
+        }
+        let mut number = String::new();
+        while let Some(c) = self.chars.next_if(|&c| c.is_ascii_digit() || c == '.') {
+            number.push(c);
+        }
+        match self.chars.peek() {
+            _ if !number.is_empty() => number.parse(),
+            Some(c) => Err(format!("'{}' is not supported by bigmath", c)),
+            None => Err("missing operand".into()),
+        }
+    }
+}
+
+/// Evaluate the arithmetic expression, whose variables were substituted
+pub fn eval(expression: &str) -> Result<String, String> {
+    let mut parser = Parser { chars: expression.chars().peekable() };
+    let value = parser.expression()?;
+    parser.skip_whitespace();
+    match parser.chars.peek() {
+        Some(c) => Err(format!("'{}' is not supported by bigmath", c)),
+        None => Ok(value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn integers() {
+        assert_eq!(eval("9223372036854775807 + 1").unwrap(), "9223372036854775808");
+        assert_eq!(eval("2 ** 100").unwrap(), "1267650600228229401496703205376");
+        assert_eq!(eval("-(3 - 10) * 4 % 5").unwrap(), "3");
+        assert_eq!(eval("2 ** 3 ** 2").unwrap(), "512");
+        assert_eq!(eval("-7 % 3").unwrap(), "-1");
+        assert_eq!(eval("12²").unwrap(), "144");
+    }
+
+    #[test]
+    fn decimals() {
+        assert_eq!(eval("0.1 + 0.2").unwrap(), "0.3");
+        assert_eq!(eval("1.50 * 2").unwrap(), "3");
+        assert_eq!(eval("1 / 4").unwrap(), "0.25");
+        assert_eq!(eval("-1 / 3").unwrap(), "-0.33333333333333333333333333333333");
+        assert_eq!(eval("2 ** -2").unwrap(), "0.25");
+        assert_eq!(eval("5.5 % 2").unwrap(), "1.5");
+        assert!("-0.5".parse::<BigDecimal>().unwrap() < "0.25".parse().unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("1 & 2").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("2 ** 0.5").is_err());
+        assert!(eval("1..2").is_err());
+    }
+}
@@ -0,0 +1,301 @@
+//! Globs with the extended patterns `?(a|b)`, `*(a|b)`, `+(a|b)` and `!(a|b)`, which the glob
+//! crate does not support. The other globs are left to it.
+use std::{fs, path::Path};
+
+/// How often the alternatives of a group may be repeated
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Repeat {
+    /// `?(...)`: zero or one time
+    Optional,
+    /// `*(...)`: any number of times
+    Any,
+    /// `+(...)`: at least once
+    Some,
+    /// `!(...)`: anything but one of the alternatives
+    Not,
+}
+
+/// A part of the pattern of a path component
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Char(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[a-z]` or `[!a-z]`, with the ranges of characters
+    Class {
+        negated: bool,
+        ranges:  Vec<(char, char)>,
+    },
+    Group(Repeat, Vec<Vec<Token>>),
+}
+
+/// Whether the pattern has an extended group, and should be expanded by this module
+pub(crate) fn is_extended(pattern: &str) -> bool {
+    let mut escaped = false;
+    let mut previous = '\0';
+    for character in pattern.chars() {
+        if escaped {
+            escaped = false;
+            previous = '\0';
+            continue;
+        }
+        if character == '(' && matches!(previous, '?' | '*' | '+' | '!') {
+            return true;
+        }
+        escaped = character == '\\';
+        previous = character;
+    }
+    false
+}
+
+/// The paths matching the pattern, sorted in each directory. `**` matches the directory and all of
+/// its subdirectories.
+pub(crate) fn glob(pattern: &str) -> Vec<String> {
+    let mut paths = vec![if pattern.starts_with('/') { "/".to_string() } else { String::new() }];
+    let components: Vec<&str> = pattern.split('/').collect();
+    for (index, &component) in components.iter().enumerate() {
+        let last = index + 1 == components.len();
+        paths = match component {
+            // A trailing slash only keeps the directories
+            "" if last && index > 0 => paths
+                .into_iter()
+                .filter(|path| Path::new(path).is_dir())
+                .map(|path| if path.ends_with('/') { path } else { path + "/" })
+                .collect(),
+            "" => paths,
+            "**" => paths.into_iter().flat_map(directories).collect(),
+            _ => match parse(component) {
+                Some(tokens) if tokens.iter().any(|token| !matches!(token, Token::Char(_))) => {
+                    paths.into_iter().flat_map(|path| matching(path, &tokens)).collect()
+                }
+                _ => paths
+                    .into_iter()
+                    .map(|path| join(&path, &unescape(component)))
+                    .filter(|path| fs::symlink_metadata(path).is_ok())
+                    .collect(),
+            },
+        };
+    }
+    paths.retain(|path| !path.is_empty());
+    paths
+}
+
+/// Append a file name to a path, which is empty for the current directory
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.into()
+    } else if path.ends_with('/') {
+        format!("{}{}", path, name)
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
+/// The sorted names of the entries of a directory, or of the current directory for an empty path
+fn entries(path: &str) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(if path.is_empty() { "." } else { path })
+        .map(|entries| {
+            entries.filter_map(|entry| entry.ok()?.file_name().into_string().ok()).collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+/// The entries of the directory whose name matches the pattern
+fn matching(path: String, tokens: &[Token]) -> Vec<String> {
+    entries(&path)
+        .into_iter()
+        .filter(|name| matches(tokens, &name.chars().collect::<Vec<_>>()))
+        .map(|name| join(&path, &name))
+        .collect()
+}
+
+/// The directory, followed by all of its subdirectories
+fn directories(path: String) -> Vec<String> {
+    let mut found = Vec::new();
+    for name in entries(&path) {
+        let child = join(&path, &name);
+        if fs::symlink_metadata(&child).map_or(false, |metadata| metadata.is_dir()) {
+            found.extend(directories(child));
+        }
+    }
+    found.insert(0, path);
+    found
+}
+
+fn unescape(component: &str) -> String {
+    let mut unescaped = String::with_capacity(component.len());
+    let mut chars = component.chars();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => unescaped.extend(chars.next()),
+            _ => unescaped.push(character),
+        }
+    }
+    unescaped
+}
+
+/// Parse the pattern of a path component, or return `None` if a group is not closed
+fn parse(component: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = component.chars().collect();
+    let mut position = 0;
+    let alternatives = sequence(&chars, &mut position, false)?;
+    if position < chars.len() {
+        // An unmatched closing parenthesis
+        return None;
+    }
+    alternatives.into_iter().next()
+}
+
+/// Parse the alternatives of a group, up to its closing parenthesis, or the whole component
+fn sequence(chars: &[char], position: &mut usize, group: bool) -> Option<Vec<Vec<Token>>> {
+    let mut alternatives = vec![Vec::new()];
+    while let Some(&character) = chars.get(*position) {
+        *position += 1;
+        let repeat = match character {
+            '?' => Some(Repeat::Optional),
+            '*' => Some(Repeat::Any),
+            '+' => Some(Repeat::Some),
+            '!' => Some(Repeat::Not),
+            _ => None,
+        };
+        let token = match (repeat, chars.get(*position)) {
+            (Some(repeat), Some('(')) => {
+                *position += 1;
+                Token::Group(repeat, sequence(chars, position, true)?)
+            }
+            _ => match character {
+                '\\' => {
+                    *position += 1;
+                    Token::Char(*chars.get(*position - 1)?)
+                }
+                '?' => Token::AnyChar,
+                '*' => Token::AnyString,
+                '[' => class(chars, position).unwrap_or(Token::Char('[')),
+                '|' if group => {
+                    alternatives.push(Vec::new());
+                    continue;
+                }
+                ')' if group => return Some(alternatives),
+                ')' => {
+                    *position -= 1;
+                    return Some(alternatives);
+                }
+                _ => Token::Char(character),
+            },
+        };
+        alternatives.last_mut()?.push(token);
+    }
+    if group {
+        None
+    } else {
+        Some(alternatives)
+    }
+}
+
+/// Parse a character class following its opening bracket, leaving the position unchanged if it
+/// is not closed
+fn class(chars: &[char], position: &mut usize) -> Option<Token> {
+    let mut index = *position;
+    let negated = matches!(chars.get(index), Some('!') | Some('^'));
+    if negated {
+        index += 1;
+    }
+    let mut ranges = Vec::new();
+    let start = index;
+    loop {
+        let first = *chars.get(index)?;
+        if first == ']' && index > start {
+            break;
+        }
+        match (chars.get(index + 1), chars.get(index + 2)) {
+            (Some('-'), Some(&last)) if last != ']' => {
+                ranges.push((first, last));
+                index += 3;
+            }
+            _ => {
+                ranges.push((first, first));
+                index += 1;
+            }
+        }
+    }
+    *position = index + 1;
+    Some(Token::Class { negated, ranges })
+}
+
+/// Whether the whole text matches the tokens
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => return text.is_empty(),
+    };
+    match token {
+        Token::Char(character) => text.first() == Some(character) && matches(rest, &text[1..]),
+        Token::AnyChar => !text.is_empty() && matches(rest, &text[1..]),
+        Token::Class { negated, ranges } => match text.first() {
+            Some(&character) => {
+                let inside =
+                    ranges.iter().any(|&(first, last)| first <= character && character <= last);
+                inside != *negated && matches(rest, &text[1..])
+            }
+            None => false,
+        },
+        Token::AnyString => (0..=text.len()).any(|end| matches(rest, &text[end..])),
+        Token::Group(repeat, alternatives) => (0..=text.len()).any(|end| {
+            let part = &text[..end];
+            let accepted = match repeat {
+                Repeat::Optional => part.is_empty() || one_of(alternatives, part),
+                Repeat::Any => repeated(alternatives, part),
+                Repeat::Some if part.is_empty() => one_of(alternatives, part),
+                Repeat::Some => repeated(alternatives, part),
+                Repeat::Not => !one_of(alternatives, part),
+            };
+            accepted && matches(rest, &text[end..])
+        }),
+    }
+}
+
+fn one_of(alternatives: &[Vec<Token>], text: &[char]) -> bool {
+    alternatives.iter().any(|alternative| matches(alternative, text))
+}
+
+/// Whether the text is a sequence of parts matching one of the alternatives
+fn repeated(alternatives: &[Vec<Token>], text: &[char]) -> bool {
+    text.is_empty()
+        || (1..=text.len())
+            .any(|end| one_of(alternatives, &text[..end]) && repeated(alternatives, &text[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matching(pattern: &str, name: &str) -> bool {
+        matches(&parse(pattern).unwrap(), &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn extended_detection() {
+        assert!(is_extended("!(*.txt)"));
+        assert!(is_extended("src/+(a|b).rs"));
+        assert!(!is_extended("*.txt"));
+        assert!(!is_extended("\\!(a)"));
+    }
+
+    #[test]
+    fn extended_matching() {
+        assert!(matching("!(*.txt)", "main.rs"));
+        assert!(!matching("!(*.txt)", "notes.txt"));
+        assert!(matching("*.?(tar.)gz", "ion.tar.gz"));
+        assert!(matching("*.?(tar.)gz", "ion.gz"));
+        assert!(matching("+(ab|c).rs", "abcab.rs"));
+        assert!(!matching("+(ab|c).rs", ".rs"));
+        assert!(matching("*(ab|c).rs", ".rs"));
+        assert!(matching("[!a-c]?(x|y)", "dy"));
+        assert!(!matching("[!a-c]?(x|y)", "by"));
+        assert_eq!(parse("+(a|b"), None);
+    }
+}
@@ -1,15 +1,34 @@
 use super::{
     super::{is_expression, words::Select, Error, Expander, ExpanderInternal, Index},
+    quoted,
     strings::unescape,
-    MethodError, Pattern,
+    MethodError, Pattern, StringMethod,
 };
 use crate::{
     expansion::is_array_expression,
+    parser::lexers::ArgumentSplitter,
     types::{self, Args},
 };
-use std::char;
+use std::{char, cmp::Ordering, collections::HashSet};
 use unicode_segmentation::UnicodeSegmentation;
 
+/// Sort the values by their keys, as numbers when they all are some, and as text otherwise. The
+/// values with equal keys keep their order.
+fn sort_keyed(mut keyed: Vec<(types::Str, types::Str)>) -> Args {
+    let numbers: Option<Vec<f64>> = keyed.iter().map(|(key, _)| key.parse::<f64>().ok()).collect();
+    match numbers {
+        Some(numbers) => {
+            let mut numbered: Vec<_> = numbers.into_iter().zip(keyed).collect();
+            numbered.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+            numbered.into_iter().map(|(_, (_, value))| value).collect()
+        }
+        None => {
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            keyed.into_iter().map(|(_, value)| value).collect()
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ArrayMethod<'a> {
     method:    &'a str,
@@ -93,6 +112,143 @@ impl<'a> ArrayMethod<'a> {
         }
     }
 
+    fn sort<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let array = self.resolve_array(expand_func)?;
+        let sorted = sort_keyed(array.into_iter().map(|value| (value.clone(), value)).collect());
+        expand_func.slice_array(sorted.into_iter(), &self.selection)
+    }
+
+    /// Sort by the value of the string method, or function, given as argument for each element
+    fn sort_by<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let method = match self.pattern {
+            Pattern::StringPattern(pattern) => expand_func.expand_string(pattern)?.join(" "),
+            Pattern::Whitespace => {
+                return Err(MethodError::WrongArgument(
+                    "sort_by",
+                    "requires the name of a method as an argument",
+                )
+                .into())
+            }
+        };
+        let array = self.resolve_array(expand_func)?;
+        let mut keyed = Vec::with_capacity(array.len());
+        for value in array {
+            let mut key = types::Str::new();
+            let variable = quoted(&value);
+            let key_method = StringMethod {
+                method:    &method,
+                variable:  &variable,
+                pattern:   " ",
+                selection: None,
+            };
+            key_method.handle(&mut key, expand_func)?;
+            keyed.push((key, value));
+        }
+        expand_func.slice_array(sort_keyed(keyed).into_iter(), &self.selection)
+    }
+
+    /// The name of the function given as argument to a higher-order method
+    fn function<E: Expander>(
+        &self,
+        method: &'static str,
+        expand_func: &mut E,
+    ) -> Result<types::Str, Error<E::Error>> {
+        match self.pattern {
+            Pattern::StringPattern(pattern) => {
+                Ok(expand_func.expand_string(pattern)?.join(" ").into())
+            }
+            Pattern::Whitespace => {
+                Err(MethodError::WrongArgument(method, "requires the name of a function").into())
+            }
+        }
+    }
+
+    /// The values returned by the function called with each element
+    fn map<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let function = self.function("map", expand_func)?;
+        let mut mapped = Args::new();
+        for value in self.resolve_array(expand_func)? {
+            match expand_func.call(&function, &[quoted(&value).as_str()]) {
+                Some(values) => mapped.extend(values?),
+                None => {
+                    return Err(MethodError::UnknownFunction("map", function.to_string()).into())
+                }
+            }
+        }
+        expand_func.slice_array(mapped.into_iter(), &self.selection)
+    }
+
+    /// The elements for which the function succeeds
+    fn filter<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let function = self.function("filter", expand_func)?;
+        let mut kept = Args::new();
+        for value in self.resolve_array(expand_func)? {
+            match expand_func.call(&function, &[quoted(&value).as_str()]) {
+                Some(values) => {
+                    values?;
+                    if expand_func.succeeded() {
+                        kept.push(value);
+                    }
+                }
+                None => {
+                    return Err(MethodError::UnknownFunction("filter", function.to_string()).into())
+                }
+            }
+        }
+        expand_func.slice_array(kept.into_iter(), &self.selection)
+    }
+
+    fn unique<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let mut seen = HashSet::new();
+        let array = self.resolve_array(expand_func)?;
+        let unique = array.into_iter().filter(|value| seen.insert(value.clone()));
+        expand_func.slice_array(unique, &self.selection)
+    }
+
+    /// The elements of both arrays in turn, up to the end of the shortest
+    fn zip<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let other = match self.pattern {
+            Pattern::StringPattern(pattern) => Self::resolve_arg_array(expand_func, pattern)?,
+            Pattern::Whitespace => {
+                return Err(MethodError::WrongArgument("zip", "requires a second array").into())
+            }
+        };
+        let array = self.resolve_array(expand_func)?;
+        let zipped = array.into_iter().zip(other).flat_map(|(first, second)| vec![first, second]);
+        expand_func.slice_array(zipped, &self.selection)
+    }
+
+    /// The elements grouped by the number given, each group being joined by spaces
+    fn chunks<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let size = match self.pattern {
+            Pattern::StringPattern(pattern) => {
+                expand_func.expand_string(pattern)?.join(" ").parse::<usize>().ok()
+            }
+            Pattern::Whitespace => None,
+        };
+        let size = match size {
+            Some(size) if size > 0 => size,
+            _ => {
+                return Err(MethodError::WrongArgument(
+                    "chunks",
+                    "requires a valid positive number as an argument",
+                )
+                .into())
+            }
+        };
+        let array = self.resolve_array(expand_func)?;
+        let chunks = array.chunks(size).map(|chunk| types::Str::from(chunk.join(" ")));
+        expand_func.slice_array(chunks, &self.selection)
+    }
+
+    /// The words of each element, as the elements of a single array
+    fn flatten<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let array = self.resolve_array(expand_func)?;
+        let words: Vec<types::Str> =
+            array.iter().flat_map(|value| value.split_whitespace().map(types::Str::from)).collect();
+        expand_func.slice_array(words.into_iter(), &self.selection)
+    }
+
     fn split<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
         let variable = self.resolve_var(expand_func)?;
         match self.pattern {
@@ -108,6 +264,90 @@ impl<'a> ArrayMethod<'a> {
         }
     }
 
+    /// The fields of each line, split by the pattern or by whitespace, as a single array
+    fn fields<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        let variable = self.resolve_var(expand_func)?;
+        match self.pattern {
+            Pattern::Whitespace => {
+                let data = variable.lines().flat_map(str::split_whitespace);
+                expand_func.slice_array(data, &self.selection)
+            }
+            Pattern::StringPattern(pattern) => {
+                let escape = unescape(&expand_func.expand_string(pattern)?.join(" "));
+                let data = variable.lines().flat_map(|line| line.split(escape.as_str()));
+                expand_func.slice_array(data, &self.selection)
+            }
+        }
+    }
+
+    /// The records of a CSV or TSV document, each as a JSON array of its fields, or as a JSON
+    /// object keyed by the fields of the header with the `header` argument
+    #[cfg(feature = "json")]
+    fn records<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        use super::documents::{from_array, from_map};
+        let document = super::read_document(expand_func, self.variable)?;
+        let mut delimiter = if self.method == "tsv" { '\t' } else { ',' };
+        let mut header = false;
+        if let Pattern::StringPattern(pattern) = self.pattern {
+            for argument in expand_func.expand_string(pattern)? {
+                if argument == "header" {
+                    header = true;
+                    continue;
+                }
+                let argument = unescape(&argument);
+                let mut chars = argument.chars();
+                delimiter = match (chars.next(), chars.next()) {
+                    (Some(delimiter), None) => delimiter,
+                    _ => {
+                        return Err(MethodError::WrongArgument(
+                            "csv",
+                            "the delimiter must be a single character",
+                        )
+                        .into())
+                    }
+                };
+            }
+        }
+        let mut records = super::delimited::records(&document, delimiter)
+            .into_iter()
+            .map(|record| record.into_iter().map(types::Str::from).collect::<Vec<_>>());
+        let rows: Vec<String> = if header {
+            let keys = records.next().unwrap_or_default();
+            records.map(|record| from_map(&keys, &record)).collect()
+        } else {
+            records.map(|record| from_array(&record)).collect()
+        };
+        expand_func.slice_array(rows.into_iter(), &self.selection)
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn records<E: Expander>(&self, _: &mut E) -> Result<Args, Error<E::Error>> {
+        Err(MethodError::MissingFeature("json").into())
+    }
+
+    #[cfg(feature = "json")]
+    fn document<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
+        use super::documents::{self, Format};
+        let format = Format::of_method(self.method).unwrap();
+        let document = super::read_document(expand_func, self.variable)?;
+        let path = match self.pattern {
+            Pattern::StringPattern(pattern) => expand_func.expand_string(pattern)?.join(" "),
+            Pattern::Whitespace => String::new(),
+        };
+        let value = documents::lookup(format, &document, &path)?;
+        expand_func.slice_array(documents::elements(&value).into_iter(), &self.selection)
+    }
+
+    #[cfg(not(feature = "json"))]
+    fn document<E: Expander>(&self, _: &mut E) -> Result<Args, Error<E::Error>> {
+        let feature = match self.method {
+            "toml" => "toml",
+            "yaml" => "yaml",
+            _ => "json",
+        };
+        Err(MethodError::MissingFeature(feature).into())
+    }
+
     fn subst<E: Expander>(&self, expand_func: &mut E) -> Result<Args, Error<E::Error>> {
         let variable = self.resolve_var(expand_func)?;
 
@@ -191,15 +431,28 @@ impl<'a> ArrayMethod<'a> {
         match self.method {
             "bytes" => self.bytes(expand_func),
             "chars" => self.chars(expand_func),
+            "chunks" => self.chunks(expand_func),
+            "csv" | "tsv" => self.records(expand_func),
+            "fields" => self.fields(expand_func),
+            "filter" => self.filter(expand_func),
+            "flatten" => self.flatten(expand_func),
             "graphemes" => self.graphemes(expand_func),
+            "json" | "toml" | "yaml" => self.document(expand_func),
             "keys" => self.map_keys(expand_func).map_err(Error::from),
             "lines" => self.lines(expand_func),
+            "map" => self.map(expand_func),
             "reverse" => self.reverse(expand_func),
+            "sort" => self.sort(expand_func),
+            "sort_by" => self.sort_by(expand_func),
             "split_at" => self.split_at(expand_func),
             "split" => self.split(expand_func),
+            "unique" => self.unique(expand_func),
             "values" => self.map_values(expand_func).map_err(Error::from),
             "subst" => self.subst(expand_func),
-            _ => Err(MethodError::InvalidArrayMethod(self.method.to_string()).into()),
+            "zip" => self.zip(expand_func),
+            _ => expand_func.call(self.method, &self.arguments()).unwrap_or_else(|| {
+                Err(MethodError::InvalidArrayMethod(self.method.to_string()).into())
+            }),
         }
     }
 
@@ -213,9 +466,24 @@ impl<'a> ArrayMethod<'a> {
                 current.push_str(&self.split(expand_func)?.join(" "));
                 Ok(())
             }
-            _ => Err(MethodError::InvalidArrayMethod(self.method.to_string()).into()),
+            _ => match expand_func.call(self.method, &self.arguments()) {
+                Some(values) => {
+                    current.push_str(&values?.join(" "));
+                    Ok(())
+                }
+                None => Err(MethodError::InvalidArrayMethod(self.method.to_string()).into()),
+            },
         }
     }
+
+    /// The unexpanded arguments of the method, when it is a function called as an expression
+    fn arguments(&self) -> Vec<&'a str> {
+        let mut arguments: Vec<_> = ArgumentSplitter::new(self.variable).collect();
+        if let Pattern::StringPattern(pattern) = self.pattern {
+            arguments.extend(ArgumentSplitter::new(pattern));
+        }
+        arguments
+    }
 }
 
 #[cfg(test)]
@@ -359,6 +627,57 @@ mod test {
         assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["c", "b", "a"]);
     }
 
+    #[test]
+    fn test_sort() {
+        let method = ArrayMethod::new("sort", "[b c a b]", Pattern::Whitespace, None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["a", "b", "b", "c"]);
+        // Numbers are sorted by their value
+        let method = ArrayMethod::new("sort", "[10 9 -1.5]", Pattern::Whitespace, None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["-1.5", "9", "10"]);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let method = ArrayMethod::new("sort_by", "[ccc a bb]", Pattern::StringPattern("len"), None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["a", "bb", "ccc"]);
+        let method = ArrayMethod::new("sort_by", "@ARRAY", Pattern::Whitespace, None);
+        assert!(method.handle_as_array(&mut DummyExpander).is_err());
+    }
+
+    #[test]
+    fn test_map_and_filter() {
+        assert_eq!(quoted("it's $HOME"), "\"it's \\$HOME\"");
+        let method = ArrayMethod::new("map", "@ARRAY", Pattern::StringPattern("double"), None);
+        assert!(matches!(
+            method.handle_as_array(&mut DummyExpander),
+            Err(Error::MethodError(MethodError::UnknownFunction("map", _)))
+        ));
+        let method = ArrayMethod::new("filter", "@ARRAY", Pattern::Whitespace, None);
+        assert!(method.handle_as_array(&mut DummyExpander).is_err());
+    }
+
+    #[test]
+    fn test_unique() {
+        let method = ArrayMethod::new("unique", "[b a b c a]", Pattern::Whitespace, None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["b", "a", "c"]);
+    }
+
+    #[test]
+    fn test_zip() {
+        let method = ArrayMethod::new("zip", "@ARRAY", Pattern::StringPattern("[1 2]"), None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["a", "1", "b", "2"]);
+    }
+
+    #[test]
+    fn test_chunks_and_flatten() {
+        let method = ArrayMethod::new("chunks", "[1 2 3 4 5]", Pattern::StringPattern("2"), None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["1 2", "3 4", "5"]);
+        let method = ArrayMethod::new("chunks", "@ARRAY", Pattern::StringPattern("0"), None);
+        assert!(method.handle_as_array(&mut DummyExpander).is_err());
+        let method = ArrayMethod::new("flatten", "[\"1 2\" 3]", Pattern::Whitespace, None);
+        assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), args!["1", "2", "3"]);
+    }
+
     #[test]
     fn test_subst_variable_over_default() {
         let method = ArrayMethod::new("subst", "@ARRAY", Pattern::StringPattern("[2, 3]"), None);
@@ -0,0 +1,61 @@
+//! The `@csv()` and `@tsv()` methods, which read the records of a document whose fields are
+//! separated by commas or by tabs, such as a spreadsheet export.
+use std::mem;
+
+/// The records of the document, whose fields are separated by the delimiter. A field may be quoted
+/// to hold the delimiter, line breaks or quotes, which are then doubled, as in `"say ""hi"""`.
+/// Empty lines are skipped.
+pub(super) fn records(document: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = document.chars().peekable();
+    while let Some(character) = chars.next() {
+        if quoted {
+            match character {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(character),
+            }
+            continue;
+        }
+        match character {
+            '"' => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                if record.is_empty() && field.is_empty() {
+                    continue;
+                }
+                record.push(mem::take(&mut field));
+                records.push(mem::take(&mut record));
+            }
+            _ if character == delimiter => record.push(mem::take(&mut field)),
+            _ => field.push(character),
+        }
+    }
+    if !record.is_empty() || !field.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_records() {
+        let document = "name,quote\r\nion,\"say \"\"hi\"\", then\nbye\"\n\nbash,\n";
+        assert_eq!(
+            records(document, ','),
+            vec![vec!["name", "quote"], vec!["ion", "say \"hi\", then\nbye"], vec!["bash", ""]]
+        );
+        assert_eq!(records("a\tb,c", '\t'), vec![vec!["a", "b,c"]]);
+        assert_eq!(records("", ','), Vec::<Vec<String>>::new());
+    }
+}
@@ -0,0 +1,190 @@
+//! The `json`, `toml` and `yaml` methods, which read a value out of a structured document, such as
+//! the output of a command or a configuration file. The TOML and YAML documents are converted to
+//! JSON values, so that the three formats are read the same way.
+use super::MethodError;
+use crate::types;
+use serde_json::{Map, Value};
+
+/// The format of a document read by a method
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The format read by the method, if it reads documents
+    pub(super) fn of_method(method: &str) -> Option<Self> {
+        match method {
+            "json" | "json_get" => Some(Self::Json),
+            "toml" | "toml_get" => Some(Self::Toml),
+            "yaml" | "yaml_get" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    /// The name of the format, which is also the one of the feature providing it
+    pub(super) const fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+            Self::Yaml => "yaml",
+        }
+    }
+
+    fn parse(self, document: &str) -> Result<Value, MethodError> {
+        let invalid = |why: String| MethodError::InvalidDocument(self.name(), why);
+        match self {
+            Self::Json => serde_json::from_str(document).map_err(|why| invalid(why.to_string())),
+            #[cfg(feature = "toml")]
+            Self::Toml => toml::from_str::<toml::Value>(document)
+                .map_err(|why| invalid(why.to_string()))
+                .and_then(|value| {
+                    serde_json::to_value(value).map_err(|why| invalid(why.to_string()))
+                }),
+            #[cfg(feature = "yaml")]
+            Self::Yaml => serde_yaml::from_str::<serde_yaml::Value>(document)
+                .map_err(|why| invalid(why.to_string()))
+                .and_then(|value| {
+                    serde_json::to_value(value).map_err(|why| invalid(why.to_string()))
+                }),
+            #[allow(unreachable_patterns)]
+            _ => Err(MethodError::MissingFeature(self.name())),
+        }
+    }
+}
+
+/// The value at the path in the document, or null if there is none. The path is made of the keys
+/// of the objects and the indices of the arrays leading to the value, separated by dots, as in
+/// `tags.0`, or written as in jq, as in `.tags[0]`. Negative indices count from the end of the
+/// arrays, and an empty path is the whole document.
+pub(super) fn lookup(format: Format, document: &str, path: &str) -> Result<Value, MethodError> {
+    let mut value = format.parse(document)?;
+    let keys = path.split(|c| matches!(c, '.' | '[' | ']')).filter(|key| !key.is_empty());
+    for key in keys.map(|key| key.trim_matches('"')) {
+        value = match value {
+            Value::Object(mut object) => object.remove(key).unwrap_or(Value::Null),
+            Value::Array(mut array) => {
+                let index = match key.parse::<isize>() {
+                    Ok(index) if index < 0 => array.len().checked_sub(index.unsigned_abs()),
+                    Ok(index) => Some(index as usize),
+                    Err(_) => None,
+                };
+                match index {
+                    Some(index) if index < array.len() => array.swap_remove(index),
+                    _ => Value::Null,
+                }
+            }
+            _ => Value::Null,
+        };
+    }
+    Ok(value)
+}
+
+/// A string as a JSON string
+pub(super) fn from_string(value: &str) -> String { Value::String(value.into()).to_string() }
+
+/// An array of strings as a JSON array
+pub(super) fn from_array(values: &[types::Str]) -> String {
+    Value::Array(values.iter().map(|value| Value::String(value.to_string())).collect()).to_string()
+}
+
+/// The keys and values of a map as a JSON object
+pub(super) fn from_map(keys: &[types::Str], values: &[types::Str]) -> String {
+    let object: Map<String, Value> = keys
+        .iter()
+        .zip(values)
+        .map(|(key, value)| (key.to_string(), Value::String(value.to_string())))
+        .collect();
+    Value::Object(object).to_string()
+}
+
+/// The key under which the TOML dates are serialized
+const TOML_DATETIME: &str = "$__toml_private_datetime";
+
+/// The value as a string: the text of a string, nothing for null, and the JSON of the others
+pub(super) fn scalar(value: &Value) -> String {
+    match value {
+        // The dates of TOML documents are serialized as objects holding their text
+        Value::Object(object) if object.len() == 1 && object.contains_key(TOML_DATETIME) => {
+            scalar(&object[TOML_DATETIME])
+        }
+        Value::String(string) => string.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
+
+/// The value as an array: the elements of an array, the keys and values of an object one after
+/// the other as for a map, nothing for null, or else the value alone
+pub(super) fn elements(value: &Value) -> Vec<String> {
+    match value {
+        Value::Array(array) => array.iter().map(scalar).collect(),
+        Value::Object(object) => {
+            object.iter().flat_map(|(key, value)| vec![key.clone(), scalar(value)]).collect()
+        }
+        Value::Null => Vec::new(),
+        value => vec![scalar(value)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"{"name": "ion", "tags": ["shell", "rust"], "version": {"major": 1}}"#;
+
+    #[test]
+    fn json_lookup() {
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, "name").unwrap()), "ion");
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, "tags.1").unwrap()), "rust");
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, ".tags[-2]").unwrap()), "shell");
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, ".version[\"major\"]").unwrap()), "1");
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, "version").unwrap()), r#"{"major":1}"#);
+        assert_eq!(scalar(&lookup(Format::Json, DOCUMENT, "tags.2").unwrap()), "");
+        assert_eq!(
+            elements(&lookup(Format::Json, DOCUMENT, "tags").unwrap()),
+            vec!["shell", "rust"]
+        );
+        assert_eq!(
+            elements(&lookup(Format::Json, DOCUMENT, "version").unwrap()),
+            vec!["major", "1"]
+        );
+        assert_eq!(
+            elements(&lookup(Format::Json, DOCUMENT, "missing").unwrap()),
+            Vec::<String>::new()
+        );
+        assert!(lookup(Format::Json, "{", "").is_err());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn toml_lookup() {
+        let document =
+            "[package]\nname = \"ion\"\nauthors = [\"a\", \"b\"]\nreleased = 2019-01-01\n";
+        assert_eq!(scalar(&lookup(Format::Toml, document, "package.name").unwrap()), "ion");
+        assert_eq!(scalar(&lookup(Format::Toml, document, "package.authors[-1]").unwrap()), "b");
+        assert_eq!(
+            scalar(&lookup(Format::Toml, document, "package.released").unwrap()),
+            "2019-01-01"
+        );
+        assert!(lookup(Format::Toml, "name =", "").is_err());
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn yaml_lookup() {
+        let document = "servers:\n  - host: a\n    port: 80\n  - host: b\n";
+        assert_eq!(scalar(&lookup(Format::Yaml, document, "servers.0.port").unwrap()), "80");
+        assert_eq!(scalar(&lookup(Format::Yaml, document, ".servers[1].host").unwrap()), "b");
+        assert!(lookup(Format::Yaml, "servers: [", "").is_err());
+    }
+
+    #[test]
+    fn json_serialization() {
+        assert_eq!(from_string("say \"hi\"\n"), r#""say \"hi\"\n""#);
+        assert_eq!(from_array(&["a".into(), "1".into()]), r#"["a","1"]"#);
+        assert_eq!(from_map(&["k".into()], &["v".into()]), r#"{"k":"v"}"#);
+    }
+}
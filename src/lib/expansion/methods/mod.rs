@@ -1,11 +1,16 @@
 mod arrays;
+#[cfg(feature = "json")]
+mod delimited;
+#[cfg(feature = "json")]
+mod documents;
 mod strings;
 
 use self::strings::unescape;
 pub use self::{arrays::ArrayMethod, strings::StringMethod};
 
-use super::Expander;
+use super::{is_expression, Error as ExpansionError, Expander};
 use crate::{parser::lexers::ArgumentSplitter, types};
+use std::{fs, path::Path};
 use thiserror::Error;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -19,7 +24,7 @@ pub struct MethodArgs<'a, 'b, E: Expander> {
     args:                       &'a str,
     expand:                     &'b mut E,
     /// If true then the third argument may be an empty string.
-    /// Currently used for method replace, replacen and regex_replace
+    /// Currently used for method replace, replacen, replace_n and regex_replace
     /// Need to use this ad hoc approach because several other integration tests
     /// fail if empty string arguments are allowed always.
     is_empty_third_arg_allowed: bool,
@@ -43,10 +48,64 @@ pub enum MethodError {
     /// A wrong argument was given to the method (extra, missing, or wrong type)
     #[error("{0}: {1}")]
     WrongArgument(&'static str, &'static str),
+    /// The function given to a higher-order method does not exist
+    #[error("{0}: '{1}' is not a function")]
+    UnknownFunction(&'static str, String),
 
     /// An invalid regex was provided. This is specific to the `matches` method
     #[error("regex_replace: error in regular expression '{0}': {1}")]
     InvalidRegex(String, #[source] regex::Error),
+    /// The value given to the `json`, `toml` or `yaml` methods is not a valid document
+    #[error("{0}: invalid document: {1}")]
+    InvalidDocument(&'static str, String),
+    /// The file given to a method reading documents could not be read
+    #[error("could not read the document '{0}': {1}")]
+    UnreadableDocument(String, String),
+    /// The expression given to the `math` method could not be evaluated
+    #[error("math: {0}")]
+    InvalidMath(String),
+    /// The value given to the `date` method is not a time
+    #[error("date: invalid time '{0}'")]
+    InvalidTime(String),
+    /// The method is part of an optional feature this build of the shell does not have
+    #[error("{0}: ion was built without the {0} feature")]
+    MissingFeature(&'static str),
+}
+
+/// The document given to a method reading one: the value of the variable or of the expression, or
+/// else the contents of the file at this path, as in `$toml(Cargo.toml package.name)`
+fn read_document<E: Expander>(
+    expand: &mut E,
+    variable: &str,
+) -> super::Result<types::Str, E::Error> {
+    match expand.string(variable) {
+        Ok(value) => Ok(value),
+        Err(ExpansionError::VarNotFound(_)) if is_expression(variable) => {
+            Ok(expand.expand_string(variable)?.join(" ").into())
+        }
+        Err(ExpansionError::VarNotFound(_)) if Path::new(variable).is_file() => {
+            fs::read_to_string(variable).map(types::Str::from).map_err(|why| {
+                MethodError::UnreadableDocument(variable.into(), why.to_string()).into()
+            })
+        }
+        Err(why) => Err(why),
+    }
+}
+
+/// The value as a literal string expression, to pass it to a method or a function
+pub(crate) fn quoted(value: &str) -> String {
+    if !value.contains('\'') {
+        return format!("'{}'", value);
+    }
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        if "\\\"$@".contains(c) {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
 }
 
 impl<'a, 'b, E: 'b + Expander> MethodArgs<'a, 'b, E> {
@@ -1,7 +1,8 @@
-use super::{super::Select, MethodArgs, MethodError};
+use super::{super::Select, quoted, MethodArgs, MethodError};
 use crate::{
     assignments::is_array,
     expansion::{is_expression, Error, Expander, ExpanderInternal, Result},
+    parser::lexers::ArgumentSplitter,
     types,
 };
 use regex::Regex;
@@ -76,6 +77,21 @@ fn escape(input: &str) -> String {
     output
 }
 
+/// The words of the input with their first letter in uppercase, and the others in lowercase
+fn title_case(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut word_start = true;
+    for c in input.chars() {
+        if word_start {
+            output.extend(c.to_uppercase());
+        } else {
+            output.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+    output
+}
+
 /// Represents a method that operates on and returns a string
 #[derive(Debug, PartialEq, Clone)]
 pub struct StringMethod<'a> {
@@ -152,11 +168,46 @@ impl<'a> StringMethod<'a> {
             "extension" => path_eval!(extension),
             "filename" => path_eval!(file_stem),
             "parent" => path_eval!(parent),
-            "to_lowercase" => string_case!(to_lowercase),
-            "to_uppercase" => string_case!(to_uppercase),
+            "to_lowercase" | "lower" => string_case!(to_lowercase),
+            "to_uppercase" | "upper" => string_case!(to_uppercase),
+            "title_case" => output.push_str(&title_case(&get_var!())),
             "trim" => output.push_str(get_var!().trim()),
             "trim_end" => output.push_str(get_var!().trim_end()),
             "trim_start" => output.push_str(get_var!().trim_start()),
+            "trim_chars" => {
+                let chars = MethodArgs::new(self.pattern, expand).join(" ")?;
+                output.push_str(get_var!().trim_matches(|c| chars.contains(c)));
+            }
+            "pad_left" | "pad_right" => {
+                let (width, fill) = {
+                    let mut args = MethodArgs::new(self.pattern, expand).array();
+                    (args.next(), args.next())
+                };
+                let width = match width.and_then(|width| width.parse::<usize>().ok()) {
+                    Some(width) => width,
+                    None => {
+                        let method =
+                            if self.method == "pad_left" { "pad_left" } else { "pad_right" };
+                        return Err(MethodError::WrongArgument(
+                            method,
+                            "first argument isn't a valid positive integer",
+                        )
+                        .into());
+                    }
+                };
+                let fill = fill.and_then(|fill| fill.chars().next()).unwrap_or(' ');
+                let value = get_var!();
+                let len = UnicodeSegmentation::graphemes(value.as_str(), true).count();
+                let padding: String =
+                    std::iter::repeat(fill).take(width.saturating_sub(len)).collect();
+                if self.method == "pad_left" {
+                    output.push_str(&padding);
+                    output.push_str(&value);
+                } else {
+                    output.push_str(&value);
+                    output.push_str(&padding);
+                }
+            }
             "repeat" => match MethodArgs::new(self.pattern, expand).join(" ")?.parse::<usize>() {
                 Ok(repeat) => output.push_str(&get_var!().repeat(repeat)),
                 Err(_) => {
@@ -213,6 +264,45 @@ impl<'a> StringMethod<'a> {
                     }
                 }
             }
+            "replace_n" => {
+                let params = {
+                    let mut args = MethodArgs::new(self.pattern, expand);
+                    args.allow_third_args_empty();
+                    let mut args = args.array();
+                    (args.next(), args.next(), args.next())
+                };
+                match params {
+                    (Some(replace), Some(with), Some(nth)) => {
+                        let nth = match nth.parse::<usize>() {
+                            Ok(nth) if nth > 0 => nth,
+                            _ => {
+                                return Err(MethodError::WrongArgument(
+                                    "replace_n",
+                                    "third argument isn't a valid positive integer",
+                                )
+                                .into())
+                            }
+                        };
+                        let value = get_var!();
+                        // Only the nth occurrence is replaced, counting from 1
+                        match value.match_indices(replace.as_str()).nth(nth - 1) {
+                            Some((start, _)) => {
+                                output.push_str(&value[..start]);
+                                output.push_str(&with);
+                                output.push_str(&value[start + replace.len()..]);
+                            }
+                            None => output.push_str(&value),
+                        }
+                    }
+                    _ => {
+                        return Err(MethodError::WrongArgument(
+                            "replace_n",
+                            "three arguments required",
+                        )
+                        .into())
+                    }
+                }
+            }
             "regex_replace" => {
                 let params = {
                     let mut args = MethodArgs::new(self.pattern, expand);
@@ -298,6 +388,52 @@ impl<'a> StringMethod<'a> {
                 };
                 output.push_str(&out.map_or(-1, |i| i as isize).to_string());
             }
+            "index_of" => {
+                let value = MethodArgs::new(self.pattern, expand).join(" ")?;
+                let array = match expand.array(variable, &Select::All) {
+                    Ok(array) => array,
+                    Err(Error::VarNotFound(_)) if is_expression(variable) => {
+                        expand.expand_string(variable)?
+                    }
+                    Err(why) => return Err(why),
+                };
+                let index = array.iter().position(|element| *element == value);
+                output.push_str(&index.map_or(-1, |i| i as isize).to_string());
+            }
+            "reduce" => {
+                let mut args = MethodArgs::new(self.pattern, expand).array().collect::<Vec<_>>();
+                if args.len() != 2 {
+                    return Err(MethodError::WrongArgument(
+                        "reduce",
+                        "requires a function and an initial value",
+                    )
+                    .into());
+                }
+                let mut value = args.pop().unwrap_or_default();
+                let function = args.pop().unwrap_or_default();
+                let array = match expand.array(variable, &Select::All) {
+                    Ok(array) => array,
+                    Err(Error::VarNotFound(_)) if is_expression(variable) => {
+                        expand.expand_string(variable)?
+                    }
+                    Err(why) => return Err(why),
+                };
+                for element in array {
+                    let args = [quoted(&value), quoted(&element)];
+                    let args = [args[0].as_str(), args[1].as_str()];
+                    value = match expand.call(&function, &args) {
+                        Some(values) => values?.join(" ").into(),
+                        None => {
+                            return Err(MethodError::UnknownFunction(
+                                "reduce",
+                                function.to_string(),
+                            )
+                            .into())
+                        }
+                    };
+                }
+                expand.slice(output, value, &self.selection)?;
+            }
             "unescape" => {
                 let out = match expand.string(variable) {
                     Ok(value) => value,
@@ -349,12 +485,96 @@ impl<'a> StringMethod<'a> {
                     output.push_str(&first_str)
                 };
             }
-            _ => {
-                return Err(Error::from(MethodError::InvalidScalarMethod(self.method.to_string())))
+            "math" => {
+                use crate::builtins::math::{calculate, parse_precision, with_precision, Rounding};
+                let value = calculate(&get_var!()).map_err(MethodError::InvalidMath)?;
+                let precision = MethodArgs::new(self.pattern, expand).join(" ")?;
+                if precision.is_empty() {
+                    output.push_str(&value);
+                } else {
+                    let precision = parse_precision(&precision).ok_or(
+                        MethodError::WrongArgument("math", "the precision is not from 0 to 17"),
+                    )?;
+                    let number =
+                        value.parse::<f64>().map_err(|_| MethodError::InvalidMath(value))?;
+                    output.push_str(&with_precision(number, precision, Rounding::Nearest));
+                }
+            }
+            "date" => {
+                use crate::builtins::datetime::{format_time, parse_time, ISO_8601};
+                let time = match expand.string(variable) {
+                    Ok(value) => value,
+                    Err(Error::VarNotFound(_)) if is_expression(variable) => {
+                        expand.expand_string(variable)?.join(" ").into()
+                    }
+                    Err(Error::VarNotFound(_)) if variable == "now" => "now".into(),
+                    Err(why) => return Err(why),
+                };
+                let time = parse_time(&time).ok_or(MethodError::InvalidTime(time.to_string()))?;
+                let format = MethodArgs::new(self.pattern, expand).join(" ")?;
+                let format = if format.is_empty() { ISO_8601 } else { format.as_str() };
+                output.push_str(&format_time(time, format));
+            }
+            #[cfg(feature = "json")]
+            "json" | "json_get" | "toml" | "toml_get" | "yaml" | "yaml_get" => {
+                use super::documents::{self, Format};
+                let format = Format::of_method(self.method).unwrap();
+                let path = MethodArgs::new(self.pattern, expand).join(" ")?;
+                let document = super::read_document(expand, variable)?;
+                let value = documents::lookup(format, &document, &path)?;
+                expand.slice(output, documents::scalar(&value), &self.selection)?;
             }
+            #[cfg(feature = "json")]
+            "to_json" => {
+                use super::documents::{from_array, from_map, from_string};
+                let json = match expand.map_keys(variable) {
+                    Ok(keys) => from_map(&keys, &expand.map_values(variable)?),
+                    Err(Error::NotAMap(_)) => match expand.array(variable, &Select::All) {
+                        Ok(array) => from_array(&array),
+                        Err(Error::ScalarAsArray(_)) => from_string(&get_var!()),
+                        Err(why) => return Err(why),
+                    },
+                    Err(Error::VarNotFound(_))
+                        if crate::expansion::is_array_expression(variable) =>
+                    {
+                        from_array(&expand.expand_string(variable)?)
+                    }
+                    Err(Error::VarNotFound(_)) if is_expression(variable) => {
+                        from_string(&expand.expand_string(variable)?.join(" "))
+                    }
+                    Err(why) => return Err(why),
+                };
+                output.push_str(&json);
+            }
+            #[cfg(not(feature = "json"))]
+            "json" | "json_get" | "to_json" => {
+                return Err(MethodError::MissingFeature("json").into())
+            }
+            #[cfg(not(feature = "json"))]
+            "toml" | "toml_get" => return Err(MethodError::MissingFeature("toml").into()),
+            #[cfg(not(feature = "json"))]
+            "yaml" | "yaml_get" => return Err(MethodError::MissingFeature("yaml").into()),
+            _ => match expand.call(self.method, &self.arguments()) {
+                Some(values) => output.push_str(&values?.join(" ")),
+                None => {
+                    return Err(Error::from(MethodError::InvalidScalarMethod(
+                        self.method.to_string(),
+                    )))
+                }
+            },
         }
         Ok(())
     }
+
+    /// The unexpanded arguments of the method, when it is a function called as an expression
+    fn arguments(&self) -> Vec<&'a str> {
+        let mut arguments: Vec<_> = ArgumentSplitter::new(self.variable).collect();
+        // The pattern is a single space when only one argument was given
+        if self.pattern != " " {
+            arguments.extend(ArgumentSplitter::new(self.pattern));
+        }
+        arguments
+    }
 }
 
 #[cfg(test)]
@@ -606,6 +826,69 @@ mod test {
         method.handle(&mut output, &mut DummyExpander).unwrap();
     }
 
+    #[test]
+    fn test_replace_n() {
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "replace_n",
+            variable:  "\"one one one\"",
+            pattern:   "[\"one\" \"two\" 2]",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "one two one");
+    }
+
+    #[test]
+    fn test_pad() {
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "pad_left",
+            variable:  "$A",
+            pattern:   "[4 0]",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "0001");
+
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "pad_right",
+            variable:  "$pkmn2",
+            pattern:   "[9 .]",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "Poke\u{0301}mon..");
+    }
+
+    #[test]
+    fn test_trim_chars() {
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "trim_chars",
+            variable:  "\"--name__\"",
+            pattern:   "\"-_\"",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "name");
+    }
+
+    #[test]
+    fn test_case_folding() {
+        assert_eq!(title_case("hello WORLD  élan"), "Hello World  Élan");
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "upper",
+            variable:  "\"straße\"",
+            pattern:   "",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "STRASSE");
+    }
+
     #[test]
     fn test_regex_replace_succeeding() {
         let mut output = types::Str::new();
@@ -645,6 +928,29 @@ mod test {
         assert_eq!(&*output, "FOO BAR");
     }
 
+    #[test]
+    fn test_index_of() {
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "index_of",
+            variable:  "@ARRAY",
+            pattern:   "c",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "2");
+
+        let mut output = types::Str::new();
+        let method = StringMethod {
+            method:    "index_of",
+            variable:  "[a b]",
+            pattern:   "c",
+            selection: None,
+        };
+        method.handle(&mut output, &mut DummyExpander).unwrap();
+        assert_eq!(&*output, "-1");
+    }
+
     #[test]
     fn test_join_with_array() {
         let mut output = types::Str::new();
@@ -1,12 +1,17 @@
 // TODO: Handle Runtime Errors
+mod affixes;
+mod arithmetic;
+mod bigmath;
 mod braces;
+mod extglob;
 mod loops;
 mod methods;
 /// Expand pipelines
 pub mod pipelines;
 mod words;
 
-use self::braces::BraceToken;
+pub(crate) use self::methods::quoted;
+use self::{affixes::Affix, braces::BraceToken};
 pub use self::{
     loops::ForValueExpression,
     methods::MethodError,
@@ -15,10 +20,11 @@ pub use self::{
 use crate::{
     parser::lexers::assignments::TypeError,
     ranges::{parse_range, Index, Range},
+    shell::secrets::SecretError,
     types::{self, Args},
 };
 use auto_enums::auto_enum;
-use glob::glob;
+use glob::{glob, Pattern, PatternError};
 use itertools::Itertools;
 use std::{
     borrow::Cow,
@@ -76,6 +82,9 @@ pub enum Error<T: fmt::Debug + error::Error + fmt::Display + 'static> {
     /// Variable is not defined
     #[error("Variable \"{0}\" does not exist")]
     VarNotFound(String),
+    /// The secret could not be read from the secret store
+    #[error("could not read secret: {0}")]
+    Secret(#[source] SecretError),
 
     /// Failed to fetch the user home directory
     #[error("Could not fetch the user home directory")]
@@ -103,6 +112,18 @@ pub enum Error<T: fmt::Debug + error::Error + fmt::Display + 'static> {
     /// Mixed types between maps and scalar/array value
     #[error("variable '{0}' is not a map-like value")]
     NotAMap(String),
+
+    /// The pattern given to strip or replace a part of a variable is not a valid glob
+    #[error("invalid pattern '{0}': {1}")]
+    InvalidPattern(String, #[source] PatternError),
+
+    /// A glob matched no file while the failglob option is set
+    #[error("no match found for the pattern '{0}'")]
+    NoGlobMatch(String),
+
+    /// The expander does not provide an expansion
+    #[error("{0} is not supported here")]
+    Unsupported(&'static str),
 }
 
 impl<T: fmt::Display + fmt::Debug + error::Error> From<TypeError> for Error<T> {
@@ -146,10 +167,30 @@ pub trait Expander: Sized {
         _command: &str,
         _set_cmd_duration: bool,
     ) -> Result<types::Str, Self::Error>;
+    /// Run a command connected to a pipe, returning the path of the pipe. The command writes
+    /// to the pipe if it is an input of the expanded command, and reads from it otherwise.
+    fn substitute(&mut self, _command: &str, _input: bool) -> Result<types::Str, Self::Error> {
+        Err(Error::Unsupported("process substitution"))
+    }
     /// Iterating upon key-value maps.
     fn map_keys(&self, _name: &str) -> Result<Args, Self::Error>;
     /// Iterating upon key-value maps.
     fn map_values(&self, _name: &str) -> Result<Args, Self::Error>;
+    /// Assign a string variable, for assignments within arithmetic expansions.
+    fn set_string(&mut self, _name: &str, _value: &str) -> Result<(), Self::Error> {
+        Err(Error::Unsupported("assignment"))
+    }
+    /// Call a function as an expression (ex: `$name(args)`) with its unexpanded arguments,
+    /// returning the value it returned, or `None` if there is no function of this name.
+    fn call(&mut self, _name: &str, _args: &[&str]) -> Option<Result<Args, Self::Error>> { None }
+    /// Whether the last function called as an expression succeeded
+    fn succeeded(&self) -> bool { true }
+    /// Whether the arithmetic expansions are evaluated with arbitrary precision
+    fn big_math(&self) -> bool { false }
+    /// Whether a glob matching no file expands to nothing, rather than to itself
+    fn null_glob(&self) -> bool { false }
+    /// Whether a glob matching no file is an error
+    fn fail_glob(&self) -> bool { false }
     /// Get a string that exists in the shell.
     fn get_string(&mut self, value: &str) -> Result<types::Str, Self::Error> {
         Ok(self.expand_string(value)?.join(" ").into())
@@ -188,10 +229,15 @@ trait ExpanderInternal: Expander {
         &mut self,
         current: &mut types::Str,
         command: &str,
+        raw: bool,
         selection: &Option<&'a str>,
     ) -> Result<(), Self::Error> {
         let result = self.command(command, true)?;
-        self.slice(current, result.trim_end_matches('\n'), selection)
+        self.slice(
+            current,
+            if raw { result.as_str() } else { result.trim_end_matches('\n') },
+            selection,
+        )
     }
 
     fn expand_brace(
@@ -279,10 +325,10 @@ trait ExpanderInternal: Expander {
         for element in elements {
             expanded.extend(self.expand_string(element)?);
         }
-        if let Some((start, length)) = range.bounds(expanded.len()) {
-            Ok(expanded.into_iter().skip(start).take(length).collect())
-        } else {
+        if range.starts_past(expanded.len()) {
             Err(Error::InvalidRange { length: elements.len(), range })
+        } else {
+            Ok(range.indices(expanded.len()).map(|index| expanded[index].clone()).collect())
         }
     }
 
@@ -330,11 +376,10 @@ trait ExpanderInternal: Expander {
                     }
                 }
                 Select::Range(range) => {
-                    let graphemes = UnicodeSegmentation::graphemes(expanded.as_ref(), true);
-                    if let Some((start, length)) = range.bounds(graphemes.clone().count()) {
-                        graphemes.skip(start).take(length).for_each(|str| {
-                            output.push_str(str.as_ref());
-                        });
+                    let graphemes: Vec<&str> =
+                        UnicodeSegmentation::graphemes(expanded.as_ref(), true).collect();
+                    for index in range.indices(graphemes.len()) {
+                        output.push_str(graphemes[index]);
                     }
                 }
                 Select::Key(_) => (),
@@ -345,6 +390,29 @@ trait ExpanderInternal: Expander {
         Ok(())
     }
 
+    /// The value of a variable, stripped or replaced by the operator following its name, as in
+    /// `${path%.*}`
+    fn variable(&mut self, text: &str) -> Result<types::Str, Self::Error> {
+        let (name, affix) = match Affix::parse(text) {
+            (name, Some(affix)) => (name, affix),
+            (name, None) => return self.string(name),
+        };
+        let value = self.string(name)?;
+        // The pattern and the replacement may refer to variables, as in `${path#$HOME/}`
+        let mut expand = |text: &str| -> Result<types::Str, Self::Error> {
+            if text.contains('$') {
+                Ok(self.expand_string_no_glob(text)?.join(" ").into())
+            } else {
+                Ok(text.into())
+            }
+        };
+        let pattern = expand(affix.pattern())?;
+        let replacement = expand(affix.replacement())?;
+        let pattern = Pattern::new(&pattern)
+            .map_err(|why| Error::InvalidPattern(pattern.to_string(), why))?;
+        Ok(affix.apply(&value, &pattern, &replacement).into())
+    }
+
     fn expand_string_no_glob(&mut self, original: &str) -> Result<Args, Self::Error> {
         let mut token_buffer = Vec::new();
         let mut contains_brace = false;
@@ -414,7 +482,7 @@ trait ExpanderInternal: Expander {
             }
             WordToken::ArrayProcess(command, quoted, ref index) => {
                 crate::IonPool::string(|output| {
-                    self.expand_process(output, command, &None)?;
+                    self.expand_process(output, command, false, &None)?;
 
                     if quoted {
                         Ok(args!(format!(
@@ -450,13 +518,17 @@ trait ExpanderInternal: Expander {
                 self.expand(&mut output, &mut expanded_words, text.as_ref(), do_glob, tilde)?
             }
             WordToken::Whitespace(text) => output.push_str(text),
-            WordToken::Process(command, ref index) => {
-                self.expand_process(&mut output, command, index)?
+            WordToken::Process(command, raw, ref index) => {
+                self.expand_process(&mut output, command, raw, index)?
             }
             WordToken::Variable(text, ref index) => {
-                self.slice(&mut output, self.string(text)?, index)?;
+                let value = self.variable(text)?;
+                self.slice(&mut output, value, index)?;
             }
             WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s),
+            WordToken::Substitution(command, input) => {
+                output.push_str(&self.substitute(command, input)?)
+            }
             _ => unreachable!(),
         }
 
@@ -501,23 +573,37 @@ trait ExpanderInternal: Expander {
         let expanded: types::Str = if tilde { self.tilde(&concat)? } else { concat };
 
         if do_glob {
-            match glob_for_os(&expanded) {
-                Ok(var) => {
-                    let prev_size = expanded_words.len();
-                    expanded_words
-                        .extend(var.filter_map(|path| path.ok()?.to_str().map(Into::into)));
-                    if expanded_words.len() == prev_size {
-                        expanded_words.push(expanded);
-                    }
-                }
-                Err(_) => expanded_words.push(expanded),
-            }
+            self.glob(expanded_words, expanded)?;
         } else {
             output.push_str(prepare_path_for_os(&expanded).as_ref());
         }
         Ok(())
     }
 
+    /// Add the paths matching the glob to the words. A glob matching no file is added as it is,
+    /// unless the nullglob or failglob options are set.
+    fn glob(&self, words: &mut Args, pattern: types::Str) -> Result<(), Self::Error> {
+        let paths: Vec<String> = if extglob::is_extended(&pattern) {
+            extglob::glob(&prepare_path_for_os(&pattern))
+        } else {
+            match glob_for_os(&pattern) {
+                Ok(paths) => paths.filter_map(|path| path.ok()?.to_str().map(Into::into)).collect(),
+                Err(_) => {
+                    words.push(prepare_path_for_os(&pattern).as_ref().into());
+                    return Ok(());
+                }
+            }
+        };
+        if !paths.is_empty() {
+            words.extend(paths.into_iter().map(Into::into));
+        } else if self.fail_glob() {
+            return Err(Error::NoGlobMatch(pattern.to_string()));
+        } else if !self.null_glob() {
+            words.push(pattern);
+        }
+        Ok(())
+    }
+
     fn expand_tokens(
         &mut self,
         token_buffer: &[WordToken<'_>],
@@ -568,9 +654,11 @@ trait ExpanderInternal: Expander {
                     let _ =
                         write!(&mut output, "{}", self.array(array, &index)?.iter().format(" "));
                 }
-                WordToken::ArrayProcess(command, _, ref index)
-                | WordToken::Process(command, ref index) => {
-                    self.expand_process(&mut output, command, index)?;
+                WordToken::ArrayProcess(command, _, ref index) => {
+                    self.expand_process(&mut output, command, false, index)?;
+                }
+                WordToken::Process(command, raw, ref index) => {
+                    self.expand_process(&mut output, command, raw, index)?;
                 }
                 WordToken::ArrayMethod(ref method, _) => {
                     method.handle(&mut output, self)?;
@@ -594,9 +682,13 @@ trait ExpanderInternal: Expander {
                     output.push_str(text);
                 }
                 WordToken::Variable(text, ref index) => {
-                    self.slice(&mut output, self.string(text)?, index)?;
+                    let value = self.variable(text)?;
+                    self.slice(&mut output, value, index)?;
                 }
                 WordToken::Arithmetic(s) => self.expand_arithmetic(&mut output, s),
+                WordToken::Substitution(command, input) => {
+                    output.push_str(&self.substitute(command, input)?);
+                }
             }
         }
 
@@ -615,24 +707,15 @@ trait ExpanderInternal: Expander {
                 expanded_words.extend(braces::expand(tokens, &vector_of_arrays));
             }
 
-            Ok(expanded_words.into_iter().fold(Args::new(), |mut array, word| {
+            let mut array = Args::new();
+            for word in expanded_words {
                 if word.find('*').is_some() {
-                    if let Ok(paths) = glob_for_os(&word) {
-                        array.extend(paths.map(|path| {
-                            if let Ok(path_buf) = path {
-                                (*path_buf.to_string_lossy()).into()
-                            } else {
-                                "".into()
-                            }
-                        }))
-                    } else {
-                        array.push(prepare_path_for_os(&word).as_ref().into());
-                    }
+                    self.glob(&mut array, word)?;
                 } else {
                     array.push(prepare_path_for_os(&word).as_ref().into());
                 }
-                array
-            }))
+            }
+            Ok(array)
         } else {
             if !output.is_empty() {
                 expanded_words.insert(0, output);
@@ -646,7 +729,45 @@ trait ExpanderInternal: Expander {
     /// x * 5 + y => 22
     /// ```
     /// if `x=5` and `y=7`
-    fn expand_arithmetic(&self, output: &mut types::Str, input: &str) {
+    fn expand_arithmetic(&mut self, output: &mut types::Str, input: &str) {
+        output.push_str(&self.arithmetic(input).unwrap_or_else(|why| why));
+    }
+
+    /// Evaluate an arithmetic expression. Assignments and ternaries are resolved here, and the
+    /// remaining arithmetic is handed to `calc`.
+    fn arithmetic(&mut self, input: &str) -> std::result::Result<String, String> {
+        if let Some(assignment) = arithmetic::assignment(input) {
+            let value = self.arithmetic(assignment.expression)?;
+            let value = match assignment.operator {
+                Some(op) => self.calculate(&format!("{} {} ({})", assignment.name, op, value))?,
+                None => value,
+            };
+            self.set_string(assignment.name, &value).map_err(|why| why.to_string())?;
+            Ok(value)
+        } else if let Some((condition, then, otherwise)) = arithmetic::ternary(input) {
+            let condition = match arithmetic::comparison(condition) {
+                Some((left, op, right)) if self.big_math() => {
+                    let left = self.arithmetic(left).and_then(big_number)?;
+                    let ordering = left.cmp(&self.arithmetic(right).and_then(big_number)?);
+                    arithmetic::holds(ordering, op)
+                }
+                Some((left, op, right)) => {
+                    let left = self.arithmetic(left).and_then(number)?;
+                    arithmetic::compare(left, op, self.arithmetic(right).and_then(number)?)
+                }
+                None if self.big_math() => {
+                    !self.arithmetic(condition).and_then(big_number)?.is_zero()
+                }
+                None => self.arithmetic(condition).and_then(number)? != 0.0,
+            };
+            self.arithmetic(if condition { then } else { otherwise })
+        } else {
+            self.calculate(input)
+        }
+    }
+
+    /// Substitute the variables of the expression and evaluate it with `calc`
+    fn calculate(&self, input: &str) -> std::result::Result<String, String> {
         crate::IonPool::string(|intermediate| {
             crate::IonPool::string(|varbuf| {
                 let flush = |var: &mut types::Str, out: &mut types::Str| {
@@ -672,15 +793,26 @@ trait ExpanderInternal: Expander {
 
                 flush(varbuf, intermediate);
 
-                output.push_str(&match calc::eval(intermediate) {
-                    Ok(s) => s.to_string(),
-                    Err(e) => e.to_string(),
-                });
-            });
-        });
+                if self.big_math() {
+                    bigmath::eval(intermediate)
+                } else {
+                    calc::eval(intermediate)
+                        .map(|value| value.to_string())
+                        .map_err(|e| e.to_string())
+                }
+            })
+        })
     }
 }
 
+/// Parse the result of an arithmetic expression as a number
+fn number(value: String) -> std::result::Result<f64, String> {
+    value.parse().map_err(|_| format!("'{}' is not a number", value))
+}
+
+/// Parse the result of an arithmetic expression as a number of arbitrary precision
+fn big_number(value: String) -> std::result::Result<bigmath::BigDecimal, String> { value.parse() }
+
 /// Resolveds glob pattern like '*'. On redox os it resolves the prefix **file:/** as schemes for
 /// files.
 fn glob_for_os(pattern: &str) -> core::result::Result<glob::Paths, glob::PatternError> {
@@ -763,11 +895,23 @@ pub(crate) mod test {
         fn tilde(&self, input: &str) -> Result<types::Str, Self::Error> { Ok(input.into()) }
 
         fn map_keys<'a>(&'a self, name: &str) -> Result<Args, Self::Error> {
-            Err(Error::VarNotFound(name.into()))
+            match name {
+                "MAP" => Ok(args!["one", "two"]),
+                _ => Err(Error::VarNotFound(name.into())),
+            }
         }
 
         fn map_values<'a>(&'a self, name: &str) -> Result<Args, Self::Error> {
-            Err(Error::VarNotFound(name.into()))
+            match name {
+                "MAP" => Ok(args!["1", "2"]),
+                _ => Err(Error::VarNotFound(name.into())),
+            }
+        }
+
+        fn set_string(&mut self, _name: &str, _value: &str) -> Result<(), Self::Error> { Ok(()) }
+
+        fn substitute(&mut self, cmd: &str, _input: bool) -> Result<types::Str, Self::Error> {
+            Ok(cmd.into())
         }
     }
 
@@ -776,13 +920,23 @@ pub(crate) mod test {
         let mut output = types::Str::new();
 
         let line = " Mary   had\ta little  \n\t lamb😉😉\t";
-        DummyExpander.expand_process(&mut output, line, &None).unwrap();
+        DummyExpander.expand_process(&mut output, line, false, &None).unwrap();
         assert_eq!(output.as_str(), line);
 
         output.clear();
         let line = "foo not bar😉😉\n\n";
-        DummyExpander.expand_process(&mut output, line, &None).unwrap();
+        DummyExpander.expand_process(&mut output, line, false, &None).unwrap();
         assert_eq!(output.as_str(), "foo not bar😉😉");
+
+        output.clear();
+        DummyExpander.expand_process(&mut output, line, true, &None).unwrap();
+        assert_eq!(output.as_str(), line);
+    }
+
+    #[test]
+    fn expand_substitutions() {
+        let expanded = DummyExpander.expand_string("diff <(ls $B) >(cat)").unwrap();
+        assert_eq!(args!["diff", "ls $B", "cat"], expanded);
     }
 
     #[test]
@@ -875,6 +1029,12 @@ pub(crate) mod test {
         let line = "$((3 * 10 - 27))";
         let expected = args!["3"];
         assert_eq!(expected, DummyExpander.expand_string(line).unwrap());
+        let line = "$((A > 0 ? 1 << 4 : 2 ** 3))";
+        let expected = args!["16"];
+        assert_eq!(expected, DummyExpander.expand_string(line).unwrap());
+        let line = "$((A += 2))";
+        let expected = args!["3"];
+        assert_eq!(expected, DummyExpander.expand_string(line).unwrap());
     }
 
     #[test]
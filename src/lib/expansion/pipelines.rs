@@ -4,9 +4,10 @@ use crate::{
     types,
 };
 use itertools::Itertools;
-use std::fmt;
+use std::{fmt, os::unix::io::RawFd};
 
 /// What to redirect to the next command
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum RedirectFrom {
     /// Stdout (`|`)
@@ -19,26 +20,70 @@ pub enum RedirectFrom {
     None,
 }
 
+impl RedirectFrom {
+    /// Whether the redirection covers the standard stream of the given file descriptor
+    pub fn includes(self, fd: RawFd) -> bool {
+        matches!(
+            (self, fd),
+            (RedirectFrom::Both, 1..=2) | (RedirectFrom::Stdout, 1) | (RedirectFrom::Stderr, 2)
+        )
+    }
+}
+
 /// An output redirection for a command
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct Redirection {
     /// What to redirect
     pub from:   RedirectFrom,
     /// Where to redirect
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_str"))]
     pub file:   types::Str,
     /// Should the file be overridden
     pub append: bool,
 }
 
+/// What a numbered file descriptor of a command is redirected to
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub enum Descriptor {
+    /// A file opened for reading (`3< file`)
+    Input(#[cfg_attr(feature = "serde", serde(with = "crate::types::serde_str"))] types::Str),
+    /// A file opened for writing (`3> file`), or appended to (`3>> file`)
+    Output {
+        /// The file to write to
+        #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_str"))]
+        file:   types::Str,
+        /// Should the file be appended to rather than overridden
+        append: bool,
+    },
+    /// A copy of another file descriptor (`2>&1`)
+    Duplicate(RawFd),
+    /// Nothing, the file descriptor is closed (`2>&-`)
+    Close,
+}
+
+/// A redirection of a numbered file descriptor for a command. These are applied in order, after
+/// the redirections of the standard streams and the pipes.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Debug, PartialEq, Clone)]
+pub struct FdRedirection {
+    /// The file descriptor to redirect
+    pub fd: RawFd,
+    /// Where to redirect it
+    pub to: Descriptor,
+}
+
 /// Represents input that a process could initially receive from `stdin`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Input {
     /// A file; the contents of said file will be written to the `stdin` of a
     /// process
-    File(types::Str),
+    File(#[cfg_attr(feature = "serde", serde(with = "crate::types::serde_str"))] types::Str),
     /// A string literal that is written to the `stdin` of a process.
     /// If there is a second string, that second string is the EOF phrase for the heredoc.
-    HereString(types::Str),
+    HereString(#[cfg_attr(feature = "serde", serde(with = "crate::types::serde_str"))] types::Str),
 }
 
 impl<'a> fmt::Display for RedirectFrom {
@@ -82,8 +127,22 @@ impl<'a> fmt::Display for Redirection {
     }
 }
 
+impl fmt::Display for FdRedirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to {
+            Descriptor::Input(ref file) => write!(f, "{}< {}", self.fd, file),
+            Descriptor::Output { ref file, append } => {
+                write!(f, "{}>{} {}", self.fd, if append { ">" } else { "" }, file)
+            }
+            Descriptor::Duplicate(other) => write!(f, "{}>&{}", self.fd, other),
+            Descriptor::Close => write!(f, "{}>&-", self.fd),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 /// Where should the pipeline be run
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PipeType {
     /// In the foreground
     Normal,
@@ -101,6 +160,7 @@ impl Default for PipeType {
 /// A pipeline
 ///
 /// Ex: `cat <<< input > output | cat &| cat &`
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pipeline<T> {
     /// The individual commands
     pub items: Vec<PipeItem<T>>,
@@ -112,14 +172,17 @@ pub struct Pipeline<T> {
 ///
 /// For example `cat <<< input > output` is a pipeitem, with its own redirections, but representing
 /// a single executable to run
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Debug, PartialEq, Clone)]
 pub struct PipeItem<T> {
     /// The command to spawn
-    pub job:     T,
+    pub job:         T,
     /// Where to send output
-    pub outputs: Vec<Redirection>,
+    pub outputs:     Vec<Redirection>,
     /// A list of inputs
-    pub inputs:  Vec<Input>,
+    pub inputs:      Vec<Input>,
+    /// Redirections of numbered file descriptors
+    pub descriptors: Vec<FdRedirection>,
 }
 
 impl<'a> PipeItem<RefinedJob<'a>> {
@@ -156,12 +219,32 @@ impl<'a> PipeItem<Job> {
             })
             .collect::<Result<_, _>>()?;
 
-        Ok(PipeItem { job, outputs, inputs })
+        let descriptors = self
+            .descriptors
+            .iter()
+            .map(|redirection| {
+                let to = match redirection.to {
+                    Descriptor::Input(ref file) => shell.get_string(file).map(Descriptor::Input),
+                    Descriptor::Output { ref file, append } => {
+                        shell.get_string(file).map(|file| Descriptor::Output { file, append })
+                    }
+                    ref to => Ok(to.clone()),
+                };
+                to.map(|to| FdRedirection { fd: redirection.fd, to })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(PipeItem { job, outputs, inputs, descriptors })
     }
 
     /// Create a new pipeitem with the given job and redirections
-    pub const fn new(job: Job, outputs: Vec<Redirection>, inputs: Vec<Input>) -> Self {
-        Self { job, outputs, inputs }
+    pub const fn new(
+        job: Job,
+        outputs: Vec<Redirection>,
+        inputs: Vec<Input>,
+        descriptors: Vec<FdRedirection>,
+    ) -> Self {
+        Self { job, outputs, inputs, descriptors }
     }
 }
 
@@ -174,6 +257,9 @@ impl<'a> fmt::Display for PipeItem<RefinedJob<'a>> {
         for output in &self.outputs {
             write!(f, " {}", output)?;
         }
+        for descriptor in &self.descriptors {
+            write!(f, " {}", descriptor)?;
+        }
         write!(
             f,
             "{}",
@@ -193,6 +279,7 @@ impl<'a> Pipeline<RefinedJob<'a>> {
         self.items.len() > 1
             || self.items.iter().any(|it| !it.outputs.is_empty())
             || self.items.iter().any(|it| !it.inputs.is_empty())
+            || self.items.iter().any(|it| !it.descriptors.is_empty())
             || self.pipe != PipeType::Normal
     }
 }
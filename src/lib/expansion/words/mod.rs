@@ -56,7 +56,11 @@ fn index_until_unescaped_character(input: &str, characters: &[u8]) -> (usize, u8
         } else if byte == b'\\' {
             backslash = true;
         } else {
-            if !glob_character_found && [b'?', b'*'].contains(&byte) {
+            // The extended patterns +(...) and !(...) glob as well as ?(...) and *(...)
+            if !glob_character_found
+                && ([b'?', b'*'].contains(&byte)
+                    || byte == b'(' && matches!(prev_character, b'+' | b'!'))
+            {
                 glob_character_found = true;
             }
 
@@ -108,14 +112,17 @@ pub enum WordToken<'a> {
     ArrayVariable(&'a str, bool, Option<&'a str>),
     /// A process that should expand to an array
     ArrayProcess(&'a str, bool, Option<&'a str>),
-    /// A process that expands to a scalar value
-    Process(&'a str, Option<&'a str>),
+    /// A process that expands to a scalar value, keeping its trailing newlines if the flag is
+    /// set
+    Process(&'a str, bool, Option<&'a str>),
     /// A method on a scalar value
     StringMethod(StringMethod<'a>),
     /// A method on a array value
     ArrayMethod(ArrayMethod<'a>, bool),
     /// An arithmetic expression
     Arithmetic(&'a str),
+    /// A process substitution, which is an input of the command if the flag is set
+    Substitution(&'a str, bool),
 }
 
 /// Iterate over the terminal tokens of the parsed text
@@ -124,6 +131,7 @@ pub struct WordIterator<'a> {
     data:    &'a str,
     read:    usize,
     quotes:  Quotes,
+    triple:  bool,
     backsl:  bool,
     do_glob: bool,
 }
@@ -334,7 +342,7 @@ impl<'a> WordIterator<'a> {
     }
 
     /// Contains the logic for parsing subshell syntax.
-    fn process<I>(&mut self, iterator: &mut I) -> WordToken<'a>
+    fn process<I>(&mut self, iterator: &mut I, raw: bool) -> WordToken<'a>
     where
         I: Iterator<Item = u8>,
     {
@@ -370,9 +378,9 @@ impl<'a> WordIterator<'a> {
                         self.read += 1;
                         return if let Some(&b'[') = self.data.as_bytes().get(self.read) {
                             let _ = iterator.next();
-                            WordToken::Process(output, Some(self.read_selection(iterator)))
+                            WordToken::Process(output, raw, Some(self.read_selection(iterator)))
                         } else {
-                            WordToken::Process(output, None)
+                            WordToken::Process(output, raw, None)
                         };
                     } else {
                         level -= 1;
@@ -443,6 +451,10 @@ impl<'a> WordIterator<'a> {
                 b'(' => {
                     let method = &self.data[start..self.read];
                     self.read += 1;
+                    // `$raw(command)` is a process whose output is kept as is
+                    if method == "raw" {
+                        return self.process(&mut iterator, true);
+                    }
                     start = self.read;
                     let mut iterator = self.trim_left_spaces(iterator);
                     let mut depth = 0;
@@ -720,7 +732,7 @@ impl<'a> WordIterator<'a> {
 
     /// Creates a new iterator with a given expander
     pub const fn new(data: &'a str, do_glob: bool) -> WordIterator<'a> {
-        WordIterator { data, backsl: false, read: 0, quotes: Quotes::None, do_glob }
+        WordIterator { data, backsl: false, read: 0, quotes: Quotes::None, triple: false, do_glob }
     }
 }
 
@@ -743,6 +755,22 @@ impl<'a> Iterator for WordIterator<'a> {
             match character {
                 b'\'' => {
                     match self.quotes {
+                        // Triple single quotes are raw, and may contain single quotes
+                        Quotes::None if self.data[self.read..].starts_with("'''") => {
+                            start += 3;
+                            self.read += 3;
+                            let idx =
+                                self.data[start..].find("'''").unwrap_or(self.data.len() - start);
+                            self.read += idx;
+                            let ret = Some(WordToken::Normal(
+                                self.data[start..self.read].into(),
+                                glob,
+                                tilde,
+                            ));
+                            // An unterminated string ends with the input
+                            self.read = (self.read + 3).min(self.data.len());
+                            return ret;
+                        }
                         Quotes::None => {
                             start += 1;
                             self.read += 1;
@@ -776,7 +804,21 @@ impl<'a> Iterator for WordIterator<'a> {
                     }
                 }
                 b'"' => {
+                    let triple_quote = self.data[self.read..].starts_with("\"\"\"");
                     match self.quotes {
+                        Quotes::None if triple_quote => {
+                            start += 3;
+                            self.read += 3;
+                            self.quotes = Quotes::Double;
+                            self.triple = true;
+                            let _ = iterator.nth(1);
+                            if self.data[self.read..].starts_with("\"\"\"") {
+                                self.read += 3;
+                                self.quotes = Quotes::None;
+                                self.triple = false;
+                                return Some(WordToken::Normal("".into(), glob, tilde));
+                            }
+                        }
                         Quotes::None => {
                             start += 1;
                             self.read += 1;
@@ -789,6 +831,22 @@ impl<'a> Iterator for WordIterator<'a> {
                                 return Some(WordToken::Normal("".into(), glob, tilde));
                             }
                         }
+                        Quotes::Double if self.triple && triple_quote => {
+                            start += 3;
+                            self.read += 3;
+                            self.quotes = Quotes::None;
+                            self.triple = false;
+                            let _ = iterator.nth(1);
+                        }
+                        // A lone double quote inside triple quotes is literal
+                        Quotes::Double if self.triple => {
+                            self.read += 1;
+                            return Some(WordToken::Normal(
+                                self.data[start..self.read].into(),
+                                glob,
+                                tilde,
+                            ));
+                        }
                         Quotes::Double => {
                             start += 1;
                             self.read += 1;
@@ -800,6 +858,16 @@ impl<'a> Iterator for WordIterator<'a> {
                         }
                     }
                 }
+                b'<' | b'>' if self.quotes == Quotes::None && iterator.peek() == Some(&b'(') => {
+                    let _ = iterator.next();
+                    self.read += 2;
+                    return match self.process(&mut iterator, false) {
+                        WordToken::Process(command, ..) => {
+                            Some(WordToken::Substitution(command, character == b'<'))
+                        }
+                        token => Some(token),
+                    };
+                }
                 b'$' => {
                     match self.quotes {
                         Quotes::None | Quotes::Double => {
@@ -814,7 +882,7 @@ impl<'a> Iterator for WordIterator<'a> {
                                         self.read += 1;
                                         return Some(self.arithmetic_expression(&mut iterator));
                                     } else {
-                                        return Some(self.process(&mut iterator));
+                                        return Some(self.process(&mut iterator, false));
                                     }
                                 }
                                 Some(b'{') => {
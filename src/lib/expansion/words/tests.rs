@@ -86,7 +86,11 @@ fn array_process_within_string_process() {
         &[
             WordToken::Normal("echo".into(), false, false),
             WordToken::Whitespace(" "),
-            WordToken::Process("let free=[@(free -h)]; echo @free[6]@free[8]/@free[7]", None),
+            WordToken::Process(
+                "let free=[@(free -h)]; echo @free[6]@free[8]/@free[7]",
+                false,
+                None,
+            ),
         ],
     )
 }
@@ -127,9 +131,27 @@ fn nested_processes() {
     let expected = &[
         WordToken::Normal("echo".into(), false, false),
         WordToken::Whitespace(" "),
-        WordToken::Process("echo $(echo one)", None),
+        WordToken::Process("echo $(echo one)", false, None),
         WordToken::Whitespace(" "),
-        WordToken::Process("echo one $(echo two) three", None),
+        WordToken::Process("echo one $(echo two) three", false, None),
+    ];
+    compare(input, expected);
+}
+
+#[test]
+fn raw_processes() {
+    let input = "echo $raw(printf '%s\\n' $(echo one))[..2] $len(x)";
+    let expected = &[
+        WordToken::Normal("echo".into(), false, false),
+        WordToken::Whitespace(" "),
+        WordToken::Process("printf '%s\\n' $(echo one)", true, Some("..2")),
+        WordToken::Whitespace(" "),
+        WordToken::StringMethod(StringMethod {
+            method:    "len",
+            variable:  "x",
+            pattern:   " ",
+            selection: None,
+        }),
     ];
     compare(input, expected);
 }
@@ -140,7 +162,7 @@ fn words_process_with_quotes() {
     let expected = &[
         WordToken::Normal("echo".into(), false, false),
         WordToken::Whitespace(" "),
-        WordToken::Process("git branch | rg '[*]' | awk '{print $2}'", None),
+        WordToken::Process("git branch | rg '[*]' | awk '{print $2}'", false, None),
     ];
     compare(input, expected);
 
@@ -148,9 +170,26 @@ fn words_process_with_quotes() {
     let expected = &[
         WordToken::Normal("echo".into(), false, false),
         WordToken::Whitespace(" "),
-        WordToken::Process("git branch | rg \"[*]\" | awk '{print $2}'", None),
+        WordToken::Process("git branch | rg \"[*]\" | awk '{print $2}'", false, None),
+    ];
+    compare(input, expected);
+}
+
+#[test]
+fn triple_quotes() {
+    let input = "'''it's $raw\n''' \"\"\"say \"$A\"\n\"\"\"";
+    let expected = &[
+        WordToken::Normal("it's $raw\n".into(), false, false),
+        WordToken::Whitespace(" "),
+        WordToken::Normal("say".into(), false, false),
+        WordToken::Whitespace(" "),
+        WordToken::Normal("\"".into(), false, false),
+        WordToken::Variable("A", None),
+        WordToken::Normal("\"".into(), false, false),
+        WordToken::Normal("\n".into(), false, false),
     ];
     compare(input, expected);
+    compare("'''unterminated", &[WordToken::Normal("unterminated".into(), false, false)]);
 }
 
 #[test]
@@ -168,9 +207,9 @@ fn test_words() {
         WordToken::Whitespace(" "),
         WordToken::Normal("~".into(), false, true),
         WordToken::Whitespace(" "),
-        WordToken::Process("echo foo", None),
+        WordToken::Process("echo foo", false, None),
         WordToken::Whitespace(" "),
-        WordToken::Process("seq 1 100", None),
+        WordToken::Process("seq 1 100", false, None),
     ];
     compare(input, expected);
 }
@@ -248,3 +287,16 @@ fn array_methods() {
     let expected = args!["111"];
     assert_eq!(method.handle_as_array(&mut DummyExpander).unwrap(), expected);
 }
+
+#[test]
+fn test_substitutions() {
+    let input = "diff <(sort $(cat a)) >(tee \"b\")";
+    let expected = &[
+        WordToken::Normal("diff".into(), false, false),
+        WordToken::Whitespace(" "),
+        WordToken::Substitution("sort $(cat a)", true),
+        WordToken::Whitespace(" "),
+        WordToken::Substitution("tee \"b\"", false),
+    ];
+    compare(input, expected);
+}
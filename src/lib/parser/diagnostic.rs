@@ -0,0 +1,259 @@
+use super::{pipelines::PipelineParsingError, statement::Error};
+use std::{fmt, ops::Range};
+use thiserror::Error;
+
+/// How severe a diagnostic is
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Severity {
+    /// The statement could not be parsed
+    Error,
+    /// The statement was parsed, but is likely not what was intended
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A parse error together with the information needed to report it to the user: a stable error
+/// code, the offending line, the span of the offending token and an optional suggestion.
+///
+/// ```
+/// use ion_shell::parser::{Diagnostic, Error};
+///
+/// let diagnostic = Diagnostic::new(Error::InvalidCharacter('(', 6), "echo (foo)");
+/// assert_eq!(diagnostic.code(), "E0002");
+/// assert_eq!(
+///     diagnostic.render(),
+///     "error[E0002]: syntax error: '(' at position 6 is out of place\n    echo (foo)\n         ^"
+/// );
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Error)]
+#[error("{error}")]
+pub struct Diagnostic {
+    severity:   Severity,
+    #[source]
+    error:      Error,
+    line:       Option<String>,
+    span:       Option<Range<usize>>,
+    suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic for an error found while parsing the given line
+    #[must_use]
+    pub fn new(error: Error, line: &str) -> Self {
+        let span = match error {
+            // Positions are one-based in the splitter
+            Error::InvalidCharacter(c, pos) if pos > 0 && line.get(pos - 1..).is_some() => {
+                Some(pos - 1..pos - 1 + c.len_utf8())
+            }
+            _ => None,
+        };
+        let suggestion = suggest(&error, line.trim());
+        Self { severity: Severity::Error, error, line: Some(line.into()), span, suggestion }
+    }
+
+    /// The stable code identifying the kind of error
+    #[must_use]
+    pub fn code(&self) -> &'static str { code(&self.error) }
+
+    /// Narrow the diagnostic to the statement found at the span of the line: the suggestion is
+    /// made for the statement, which is pointed at unless the offending token is known
+    #[must_use]
+    pub fn within(mut self, span: Range<usize>) -> Self {
+        if let Some(statement) = self.line.as_deref().and_then(|line| line.get(span.clone())) {
+            self.suggestion = suggest(&self.error, statement.trim());
+            self.span = self.span.or(Some(span));
+        }
+        self
+    }
+
+    /// The severity of the diagnostic
+    #[must_use]
+    pub const fn severity(&self) -> Severity { self.severity }
+
+    /// The underlying parse error
+    #[must_use]
+    pub const fn error(&self) -> &Error { &self.error }
+
+    /// The line in which the error was found, if known
+    #[must_use]
+    pub fn line(&self) -> Option<&str> { self.line.as_deref() }
+
+    /// The byte range of the offending token in the line, if known
+    #[must_use]
+    pub fn span(&self) -> Option<Range<usize>> { self.span.clone() }
+
+    /// A "did you mean" hint for the user, if any
+    #[must_use]
+    pub fn suggestion(&self) -> Option<&str> { self.suggestion.as_deref() }
+
+    /// Render the diagnostic for a terminal, with carets under the offending token
+    #[must_use]
+    pub fn render(&self) -> String {
+        let mut out = format!("{}[{}]: {}", self.severity, self.code(), self.error);
+        if let Some(ref line) = self.line {
+            // Only the first line of a multi-line statement is shown
+            let first = line.lines().next().unwrap_or_default();
+            out.push_str("\n    ");
+            out.push_str(first);
+            if let Some(span) = self.span.as_ref().filter(|span| span.end <= first.len()) {
+                let offset = first[..span.start].chars().count();
+                let width = first[span.clone()].chars().count().max(1);
+                out.push_str("\n    ");
+                out.extend(std::iter::repeat(' ').take(offset));
+                out.extend(std::iter::repeat('^').take(width));
+            }
+        }
+        if let Some(ref suggestion) = self.suggestion {
+            out.push_str("\n    = help: ");
+            out.push_str(suggestion);
+        }
+        out
+    }
+}
+
+impl From<Error> for Diagnostic {
+    fn from(error: Error) -> Self {
+        Self { severity: Severity::Error, error, line: None, span: None, suggestion: None }
+    }
+}
+
+fn code(error: &Error) -> &'static str {
+    match error {
+        Error::IllegalCommandName(_) => "E0001",
+        Error::InvalidCharacter(..) => "E0002",
+        Error::UnterminatedSubshell => "E0003",
+        Error::UnterminatedBracedVar => "E0004",
+        Error::UnterminatedSquareBracket => "E0005",
+        Error::UnterminatedSingleQuotes => "E0006",
+        Error::UnterminatedDoubleQuotes => "E0007",
+        Error::UnterminatedBrace => "E0008",
+        Error::UnterminatedMethod => "E0009",
+        Error::UnterminatedArithmetic => "E0010",
+        Error::ExpectedCommandButFound(_) => "E0011",
+        Error::IncompleteFlowControl => "E0012",
+        Error::NoKeySupplied => "E0013",
+        Error::NoOperatorSupplied => "E0014",
+        Error::NoValueSupplied => "E0015",
+        Error::NoInKeyword => "E0016",
+        Error::Case(_) => "E0017",
+        Error::InvalidFunctionName(_) => "E0018",
+        Error::InvalidFunctionArgument(_) => "E0019",
+        Error::Pipeline(PipelineParsingError::HeredocsDeprecated) => "E0020",
+        Error::Pipeline(_) => "E0021",
+        Error::InvalidCoprocessName(_) => "E0022",
+        Error::InvalidLabel(_) => "E0023",
+        Error::InvalidCatchVariable(_) => "E0024",
+        Error::InvalidDeferred(_) => "E0025",
+        Error::InvalidSelectVariable(_) => "E0026",
+    }
+}
+
+fn suggest(error: &Error, line: &str) -> Option<String> {
+    let keyword = line.split_whitespace().next().unwrap_or_default();
+    match error {
+        Error::UnterminatedSubshell => Some("add a closing `)`".into()),
+        Error::UnterminatedBracedVar | Error::UnterminatedBrace => Some("add a closing `}`".into()),
+        Error::UnterminatedSquareBracket => Some("add a closing `]`".into()),
+        Error::UnterminatedSingleQuotes => Some("add a closing `'`".into()),
+        Error::UnterminatedDoubleQuotes => Some("add a closing `\"`".into()),
+        Error::UnterminatedArithmetic => Some("close the expression with `))`".into()),
+        Error::IncompleteFlowControl => match keyword {
+            "for" => Some("did you mean `for VARIABLE in VALUES`?".into()),
+            "match" => Some("did you mean `match VALUE`?".into()),
+            "case" => Some("did you mean `case PATTERN`?".into()),
+            "coproc" => Some("did you mean `coproc NAME`?".into()),
+            "defer" => Some("did you mean `defer COMMAND`?".into()),
+            "yield" => Some("did you mean `yield VALUES`?".into()),
+            _ => None,
+        },
+        Error::NoOperatorSupplied if ["let", "local", "global"].contains(&keyword) => {
+            Some(format!("did you mean `{} = VALUE`?", line))
+        }
+        Error::NoInKeyword => {
+            let typo = line.split_whitespace().skip(2).find(|word| is_typo_of(word, "in"));
+            Some(match typo {
+                Some(typo) => format!("did you mean `in` instead of `{}`?", typo),
+                None => "the loop variables must be followed by `in VALUES`".into(),
+            })
+        }
+        Error::InvalidFunctionName(name) | Error::InvalidCoprocessName(name) => {
+            let name: String =
+                name.chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect();
+            Some(format!("did you mean `{} {}`?", keyword, name))
+        }
+        Error::Pipeline(PipelineParsingError::HeredocsDeprecated) => {
+            Some("use a herestring instead: `cat <<< \"text\"`".into())
+        }
+        _ => None,
+    }
+}
+
+/// Whether `word` is a single edit away from `expected`
+fn is_typo_of(word: &str, expected: &str) -> bool {
+    let (word, expected): (Vec<char>, Vec<char>) =
+        (word.chars().collect(), expected.chars().collect());
+    match word.len() as isize - expected.len() as isize {
+        0 => {
+            let diffs: Vec<usize> = (0..word.len()).filter(|&i| word[i] != expected[i]).collect();
+            // A single substitution, or two swapped characters
+            diffs.len() == 1
+                || (diffs.len() == 2
+                    && diffs[1] == diffs[0] + 1
+                    && word[diffs[0]] == expected[diffs[1]]
+                    && word[diffs[1]] == expected[diffs[0]])
+        }
+        1 => (0..word.len()).any(|i| word[..i].iter().chain(&word[i + 1..]).eq(expected.iter())),
+        -1 => is_typo_of(&expected.iter().collect::<String>(), &word.iter().collect::<String>()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caret_under_token() {
+        let diagnostic = Diagnostic::new(Error::InvalidCharacter(')', 6), "echo a) b");
+        assert_eq!(diagnostic.span(), Some(5..6));
+        assert!(diagnostic.render().ends_with("\n    echo a) b\n         ^"));
+    }
+
+    #[test]
+    fn suggestions() {
+        let diagnostic = Diagnostic::new(Error::NoInKeyword, "for x ni 1..3");
+        assert_eq!(diagnostic.suggestion(), Some("did you mean `in` instead of `ni`?"));
+        let diagnostic = Diagnostic::new(Error::InvalidFunctionName("a-b".into()), "fn a-b");
+        assert_eq!(diagnostic.suggestion(), Some("did you mean `fn a_b`?"));
+        let diagnostic = Diagnostic::new(Error::NoOperatorSupplied, "let x");
+        assert_eq!(diagnostic.suggestion(), Some("did you mean `let x = VALUE`?"));
+    }
+
+    #[test]
+    fn statement_within_line() {
+        let diagnostic = Diagnostic::new(Error::NoInKeyword, "echo a; for x ni 1..3").within(8..21);
+        assert_eq!(diagnostic.span(), Some(8..21));
+        assert_eq!(diagnostic.suggestion(), Some("did you mean `in` instead of `ni`?"));
+        assert!(diagnostic
+            .render()
+            .contains("\n    echo a; for x ni 1..3\n            ^^^^^^^^^^^^^\n"));
+    }
+
+    #[test]
+    fn typos() {
+        assert!(is_typo_of("ni", "in"));
+        assert!(is_typo_of("on", "in"));
+        assert!(is_typo_of("i", "in"));
+        assert!(is_typo_of("inn", "in"));
+        assert!(!is_typo_of("in", "in"));
+        assert!(!is_typo_of("out", "in"));
+    }
+}
@@ -1,3 +1,4 @@
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -98,7 +99,100 @@ impl Levels {
     }
 }
 
-/// An efficient `Iterator` structure for splitting arguments
+/// How an argument is quoted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Quoting {
+    /// No quotes (ex: `$HOME/src`)
+    None,
+    /// Wholly between single quotes (ex: `'a b'`)
+    Single,
+    /// Wholly between double quotes (ex: `"a $b"`)
+    Double,
+    /// Wholly between triple single quotes (ex: `'''it's raw'''`)
+    TripleSingle,
+    /// Wholly between triple double quotes (ex: `"""say "hi" """`)
+    TripleDouble,
+    /// Quoted in part only, or with several quoted parts (ex: `--name="a b"`)
+    Mixed,
+}
+
+impl Quoting {
+    /// How the argument is quoted
+    pub fn of(argument: &str) -> Self {
+        let wholly = |quote: &str, escapes: bool| {
+            argument.len() >= 2 * quote.len()
+                && argument.starts_with(quote)
+                && closing_quote(&argument[quote.len()..], quote, escapes)
+                    == Some(argument.len() - 2 * quote.len())
+        };
+        if wholly("'''", false) {
+            Self::TripleSingle
+        } else if wholly("\"\"\"", false) {
+            Self::TripleDouble
+        } else if wholly("'", true) {
+            Self::Single
+        } else if wholly("\"", true) {
+            Self::Double
+        } else if argument.contains(|c| c == '\'' || c == '"') {
+            Self::Mixed
+        } else {
+            Self::None
+        }
+    }
+}
+
+/// The position of the first closing quote in the text following an opening quote
+fn closing_quote(text: &str, quote: &str, escapes: bool) -> Option<usize> {
+    let mut characters = text.char_indices();
+    while let Some((index, character)) = characters.next() {
+        if escapes && character == '\\' {
+            let _ = characters.next();
+        } else if text[index..].starts_with(quote) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// An argument split by an `ArgumentSplitter`, with its location in the split text
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Argument<'a> {
+    /// The text of the argument, unexpanded and with its quotes
+    pub text:    &'a str,
+    /// The range of bytes of the argument in the split text
+    pub span:    Range<usize>,
+    /// How the argument is quoted
+    pub quoting: Quoting,
+}
+
+impl<'a> Argument<'a> {
+    /// Locate an argument within the text it was split from
+    fn within(data: &'a str, text: &'a str) -> Self {
+        let start = text.as_ptr() as usize - data.as_ptr() as usize;
+        Self { text, span: start..start + text.len(), quoting: Quoting::of(text) }
+    }
+}
+
+/// An efficient `Iterator` structure for splitting arguments.
+///
+/// The text is split on the spaces which are outside of quotes, processes (`$(..)` and
+/// `@(..)`), methods (`$method(..)`), array literals (`[..]`) and braces (`{..}`). A backslash
+/// escapes the next character, including a space. The arguments are borrowed from the text as
+/// they were written: they are neither expanded nor unquoted.
+///
+/// This is how ion splits the arguments of commands, so completion engines and other tools can
+/// tokenize command lines the same way. `spanned` also gives where each argument is in the
+/// text, and how it is quoted.
+///
+/// ```
+/// use ion_shell::parser::lexers::{ArgumentSplitter, Quoting};
+///
+/// let words: Vec<_> = ArgumentSplitter::new("echo 'a b' @split($x ', ')").collect();
+/// assert_eq!(words, ["echo", "'a b'", "@split($x ', ')"]);
+///
+/// let last = ArgumentSplitter::new("echo 'a b'").spanned().last().unwrap();
+/// assert_eq!((last.span, last.quoting), (5..10, Quoting::Single));
+/// ```
 #[derive(Debug)]
 pub struct ArgumentSplitter<'a> {
     data:   &'a str,
@@ -106,6 +200,7 @@ pub struct ArgumentSplitter<'a> {
     read:   usize,
     comm:   Comm,
     quotes: bool,
+    triple: bool,
     variab: bool,
     array:  bool,
     method: bool,
@@ -119,12 +214,19 @@ impl<'a> ArgumentSplitter<'a> {
             read: 0,
             comm: Comm::None,
             quotes: false,
+            triple: false,
             variab: false,
             array: false,
             method: false,
         }
     }
 
+    /// Also give the span and quoting of each argument
+    pub fn spanned(self) -> impl Iterator<Item = Argument<'a>> {
+        let data = self.data;
+        self.map(move |text| Argument::within(data, text))
+    }
+
     fn scan_singlequotes<B: Iterator<Item = u8>>(&mut self, bytes: &mut B) {
         while let Some(character) = bytes.next() {
             match character {
@@ -139,6 +241,20 @@ impl<'a> ArgumentSplitter<'a> {
             self.read += 1;
         }
     }
+
+    fn scan_triple_singlequotes<B: Iterator<Item = u8>>(&mut self, bytes: &mut B) {
+        // Skip the remaining opening quotes
+        let _ = bytes.nth(1);
+        self.read += 3;
+        let mut closing = 0;
+        for character in bytes {
+            self.read += 1;
+            closing = if character == b'\'' { closing + 1 } else { 0 };
+            if closing == 3 {
+                break;
+            }
+        }
+    }
 }
 
 impl<'a> Iterator for ArgumentSplitter<'a> {
@@ -199,14 +315,26 @@ impl<'a> Iterator for ArgumentSplitter<'a> {
                     let _ = levels.down(Field::Proc);
                 }
 
-                // Toggle double quote rules.
+                // Toggle double quote rules. Inside triple quotes, only triple quotes count.
                 b'"' => {
-                    self.quotes ^= true;
+                    if self.data[self.read..].starts_with("\"\"\"") && (!self.quotes || self.triple)
+                    {
+                        let _ = bytes.nth(1);
+                        self.read += 2;
+                        self.quotes ^= true;
+                        self.triple = self.quotes;
+                    } else if !self.triple {
+                        self.quotes ^= true;
+                    }
                 }
                 // Loop through characters until single quote rules are completed.
                 b'\'' if !self.quotes => {
-                    self.scan_singlequotes(&mut bytes);
-                    self.read += 2;
+                    if self.data[self.read..].starts_with("'''") {
+                        self.scan_triple_singlequotes(&mut bytes);
+                    } else {
+                        self.scan_singlequotes(&mut bytes);
+                        self.read += 2;
+                    }
                     continue;
                 }
                 // Break from the loop once a root-level space is found.
@@ -271,6 +399,40 @@ mod tests {
         compare(input, expected);
     }
 
+    #[test]
+    fn triple_quotes() {
+        let input = "echo '''it's a 'raw' string''' \"\"\"say \"hi\" $(echo there)\"\"\" end";
+        let expected = vec![
+            "echo",
+            "'''it's a 'raw' string'''",
+            "\"\"\"say \"hi\" $(echo there)\"\"\"",
+            "end",
+        ];
+        compare(input, expected);
+    }
+
+    #[test]
+    fn spans_and_quoting() {
+        let input = r#"echo  'a b' "c"'d' """e "f" """ '''g'''  $(h i)"#;
+        let arguments = ArgumentSplitter::new(input)
+            .spanned()
+            .map(|argument| (&input[argument.span], argument.quoting))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            arguments,
+            vec![
+                ("echo", Quoting::None),
+                ("'a b'", Quoting::Single),
+                (r#""c"'d'"#, Quoting::Mixed),
+                (r#""""e "f" """"#, Quoting::TripleDouble),
+                ("'''g'''", Quoting::TripleSingle),
+                ("$(h i)", Quoting::None),
+            ]
+        );
+        assert_eq!(Quoting::of(r"'a\'b'"), Quoting::Single);
+        assert_eq!(Quoting::of("'a'b'"), Quoting::Mixed);
+    }
+
     #[test]
     fn capture_empty_quotes() {
         let input = "'abc' 'a' ''";
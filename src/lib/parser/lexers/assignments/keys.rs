@@ -102,14 +102,18 @@ impl<'a> KeyIterator<'a> {
         }
     }
 
-    // Parameters are values that follow the semicolon (':').
+    // Parameters are values that follow the semicolon (':'). The choices of an enumeration
+    // (ex: `{a, b}`) may be separated by spaces.
     fn parse_parameter(&mut self, name: &'a str) -> Result<Key<'a>, TypeError> {
         let mut start = self.read;
+        let mut braces = 0;
         for byte in self.data.bytes().skip(self.read) {
             self.read += 1;
             match byte {
+                b'{' => braces += 1,
+                b'}' => braces -= 1,
                 b' ' if start + 1 == self.read => start += 1,
-                b' ' => return Key::parse(name, self.data[start..self.read].trim()),
+                b' ' if braces == 0 => return Key::parse(name, self.data[start..self.read].trim()),
                 _ => (),
             }
         }
@@ -249,4 +253,42 @@ mod tests {
         );
         assert_eq!(parser.next().unwrap(), Err(TypeError::Invalid("a".into())));
     }
+
+    #[test]
+    fn enum_key_parsing() {
+        let mut parser = KeyIterator::new("env:{dev, staging, prod} mode:{fast,slow} x:{a,}");
+        let choices = |choices: &[&str]| {
+            Primitive::Enum(choices.iter().map(|&choice| choice.into()).collect())
+        };
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key { name: "env", kind: choices(&["dev", "staging", "prod"]) })
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key { name: "mode", kind: choices(&["fast", "slow"]) })
+        );
+        assert_eq!(parser.next().unwrap(), Err(TypeError::Invalid("{a,}".into())));
+        assert_eq!(choices(&["dev", "prod"]).to_string(), "{dev, prod}");
+    }
+
+    #[test]
+    fn optional_key_parsing() {
+        let mut parser = KeyIterator::new("a:int? b:[str?] c:{x, y}? d:int??");
+        let optional = |kind| Primitive::Optional(Box::new(kind));
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key { name: "a", kind: optional(Primitive::Integer) })
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key { name: "b", kind: Primitive::Array(Box::new(optional(Primitive::Str))) })
+        );
+        assert_eq!(
+            parser.next().unwrap(),
+            Ok(Key { name: "c", kind: optional(Primitive::Enum(vec!["x".into(), "y".into()])) })
+        );
+        assert_eq!(parser.next().unwrap(), Err(TypeError::Invalid("int??".into())));
+        assert_eq!(optional(Primitive::Integer).to_string(), "int?");
+    }
 }
@@ -11,6 +11,20 @@ pub enum Primitive {
     Integer,
     /// A floating-point value
     Float,
+    /// A path, whose leading tilde is expanded, and which is made absolute and resolved if it is
+    /// canonical (ex: `~/src`)
+    Path {
+        /// Whether the path is resolved to the file it names, which must exist
+        canonical: bool,
+    },
+    /// A duration, normalized to a number of seconds (ex: `5m30s`)
+    Duration,
+    /// A size, normalized to a number of bytes (ex: `10GiB`)
+    Size,
+    /// One of the listed strings (ex: `{dev, staging, prod}`)
+    Enum(Vec<String>),
+    /// A value of the type, or nothing (ex: `int?`)
+    Optional(Box<Self>),
     /// Arrays
     Array(Box<Self>),
     /// A hash map
@@ -28,6 +42,23 @@ impl Primitive {
             "bool" => Some(Self::Boolean),
             "int" => Some(Self::Integer),
             "float" => Some(Self::Float),
+            "path" => Some(Self::Path { canonical: false }),
+            "realpath" => Some(Self::Path { canonical: true }),
+            "duration" => Some(Self::Duration),
+            "size" => Some(Self::Size),
+            _ if data.ends_with('?') => match Self::parse(&data[..data.len() - 1])? {
+                Self::Optional(_) => None,
+                kind => Some(Self::Optional(Box::new(kind))),
+            },
+            _ if data.starts_with('{') && data.ends_with('}') => {
+                let choices: Vec<String> =
+                    data[1..data.len() - 1].split(',').map(|choice| choice.trim().into()).collect();
+                if choices.iter().any(String::is_empty) {
+                    None
+                } else {
+                    Some(Self::Enum(choices))
+                }
+            }
             _ => {
                 let open_bracket = data.find('[')?;
                 let close_bracket = data.rfind(']')?;
@@ -54,6 +85,12 @@ impl Display for Primitive {
             Self::Boolean => write!(f, "bool"),
             Self::Float => write!(f, "float"),
             Self::Integer => write!(f, "int"),
+            Self::Path { canonical: false } => write!(f, "path"),
+            Self::Path { canonical: true } => write!(f, "realpath"),
+            Self::Duration => write!(f, "duration"),
+            Self::Size => write!(f, "size"),
+            Self::Enum(ref choices) => write!(f, "{{{}}}", choices.join(", ")),
+            Self::Optional(ref kind) => write!(f, "{}?", kind),
             Self::Array(ref kind) => write!(f, "[{}]", kind),
             Self::HashMap(ref kind) => match **kind {
                 Self::Str => write!(f, "hmap[]"),
@@ -4,6 +4,7 @@
 //! The `StatementSplitter` than takes the data and produces statements, with the help of
 //! `parse_and_validate`
 
+mod diagnostic;
 /// The terminal tokens associated with the parsing process
 pub mod lexers;
 /// Parse the pipelines to a Pipeline struct
@@ -12,7 +13,9 @@ mod statement;
 mod terminator;
 
 pub use self::{
-    statement::{parse_and_validate, Error, StatementSplitter},
+    diagnostic::{Diagnostic, Severity},
+    lexers::{Argument, ArgumentSplitter, Quoting},
+    statement::{opens_block, parse_and_validate, Error, StatementSplitter},
     terminator::Terminator,
 };
 
@@ -1,8 +1,10 @@
-use std::iter::Peekable;
+use std::{iter::Peekable, os::unix::io::RawFd};
 use thiserror::Error;
 
 use crate::{
-    expansion::pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
+    expansion::pipelines::{
+        Descriptor, FdRedirection, Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection,
+    },
     parser::lexers::arguments::{Field, Levels, LevelsError},
     shell::Job,
     types::*,
@@ -26,6 +28,9 @@ pub enum PipelineParsingError {
     /// No file was provided after the input redirection
     #[error("expected file argument after redirection for input")]
     NoRedirectionArg,
+    /// The target of a file descriptor duplication is not a file descriptor
+    #[error("expected a file descriptor or '-' after '>&', found '{0}'")]
+    InvalidDescriptor(String),
 
     // quotes
     /// Unterminated double quotes
@@ -52,6 +57,7 @@ trait AddItem<'a> {
         args: Args,
         outputs: Vec<Redirection>,
         inputs: Vec<Input>,
+        descriptors: Vec<FdRedirection>,
     );
 }
 
@@ -62,13 +68,71 @@ impl<'a> AddItem<'a> for Pipeline<Job> {
         args: Args,
         outputs: Vec<Redirection>,
         inputs: Vec<Input>,
+        descriptors: Vec<FdRedirection>,
     ) {
         if !args.is_empty() {
-            self.items.push(PipeItem::new(Job::new(args, redirection), outputs, inputs));
+            self.items.push(PipeItem::new(
+                Job::new(args, redirection),
+                outputs,
+                inputs,
+                descriptors,
+            ));
         }
     }
 }
 
+/// The standard stream which a file descriptor refers to
+fn stream_of(fd: RawFd) -> RedirectFrom {
+    match fd {
+        1 => RedirectFrom::Stdout,
+        2 => RedirectFrom::Stderr,
+        _ => RedirectFrom::None,
+    }
+}
+
+/// Add a file descriptor redirection. Redirections of the standard streams to files are kept
+/// with the other redirections of the standard streams.
+///
+/// As redirections apply from left to right, a copy of stdout or stderr which was already
+/// redirected to files, as in `> file 2>&1`, is sent to the same files. A copy which is kept as a
+/// descriptor redirection therefore always refers to the stream before its redirections.
+fn push_descriptor(
+    fd: RawFd,
+    to: Descriptor,
+    outputs: &mut Vec<Redirection>,
+    inputs: &mut Vec<Input>,
+    descriptors: &mut Vec<FdRedirection>,
+) {
+    match (fd, to) {
+        (0, Descriptor::Input(file)) => inputs.push(Input::File(file)),
+        (1, Descriptor::Output { file, append }) => {
+            outputs.push(Redirection { from: RedirectFrom::Stdout, file, append })
+        }
+        (2, Descriptor::Output { file, append }) => {
+            outputs.push(Redirection { from: RedirectFrom::Stderr, file, append })
+        }
+        (fd @ 1..=2, to) if to != Descriptor::Duplicate(fd) => {
+            // The earlier redirections of the stream are replaced
+            outputs.retain(|output| output.from != stream_of(fd));
+            for output in outputs.iter_mut().filter(|output| output.from == RedirectFrom::Both) {
+                output.from = stream_of(3 - fd);
+            }
+            descriptors.retain(|redirection| redirection.fd != fd);
+            match to {
+                Descriptor::Duplicate(other @ 1..=2)
+                    if outputs.iter().any(|output| output.from.includes(other)) =>
+                {
+                    for output in outputs.iter_mut() {
+                        output.from = RedirectFrom::Both;
+                    }
+                }
+                to => descriptors.push(FdRedirection { fd, to }),
+            }
+        }
+        (fd, to) => descriptors.push(FdRedirection { fd, to }),
+    }
+}
+
 /// Collect pipelines in the input
 #[derive(Debug, Clone)]
 pub struct Collector<'a> {
@@ -112,12 +176,62 @@ impl<'a> Collector<'a> {
             .map(|file| outputs.push(Redirection { from, file: file.into(), append }))
     }
 
+    /// Parse what a file descriptor is redirected to, following its `<` or `>` operator
+    fn descriptor<I>(
+        &self,
+        operator: u8,
+        bytes: &mut Peekable<I>,
+    ) -> Result<Descriptor, PipelineParsingError>
+    where
+        I: Iterator<Item = (usize, u8)>,
+    {
+        let missing = if operator == b'<' {
+            PipelineParsingError::NoRedirectionArg
+        } else {
+            PipelineParsingError::NoRedirection
+        };
+        let append = operator == b'>' && matches!(bytes.peek(), Some(&(_, b'>')));
+        if append {
+            bytes.next();
+        }
+        if let Some(&(_, b'&')) = bytes.peek() {
+            bytes.next();
+            return match self.arg(bytes)? {
+                Some("-") => Ok(Descriptor::Close),
+                Some(fd) => fd
+                    .parse()
+                    .map(Descriptor::Duplicate)
+                    .map_err(|_| PipelineParsingError::InvalidDescriptor(fd.into())),
+                None => Err(missing),
+            };
+        }
+        match self.arg(bytes)? {
+            Some(file) if operator == b'<' => Ok(Descriptor::Input(file.into())),
+            Some(file) => Ok(Descriptor::Output { file: file.into(), append }),
+            None => Err(missing),
+        }
+    }
+
+    /// The file descriptor and the number of its digits, if the argument starting at the index
+    /// is the number of a redirected file descriptor, such as `2` in `2>&1`
+    fn fd_prefix(&self, start: usize) -> Option<(RawFd, usize)> {
+        let digits = self.data[start..].bytes().take_while(u8::is_ascii_digit).count();
+        match (self.peek(start + digits), self.peek(start + digits + 1)) {
+            (Some(b'<'), Some(b'(')) | (Some(b'>'), Some(b'(')) | (Some(b'<'), Some(b'<')) => None,
+            (Some(b'<'), _) | (Some(b'>'), _) => {
+                Some((self.data[start..start + digits].parse().ok()?, digits))
+            }
+            _ => None,
+        }
+    }
+
     fn parse(&self) -> Result<Pipeline<Job>, PipelineParsingError> {
         let mut bytes = self.data.bytes().enumerate().peekable();
         let mut args = Args::with_capacity(ARG_DEFAULT_SIZE);
         let mut pipeline = Pipeline::new();
         let mut outputs: Vec<Redirection> = Vec::new();
         let mut inputs: Vec<Input> = Vec::new();
+        let mut descriptors: Vec<FdRedirection> = Vec::new();
 
         while let Some(&(i, b)) = bytes.peek() {
             // Determine what production rule we are using based on the first character
@@ -142,6 +256,7 @@ impl<'a> Collector<'a> {
                                 std::mem::replace(&mut args, Args::with_capacity(ARG_DEFAULT_SIZE)),
                                 std::mem::take(&mut outputs),
                                 std::mem::take(&mut inputs),
+                                std::mem::take(&mut descriptors),
                             );
                         }
                         Some(&(_, b'!')) => {
@@ -176,6 +291,7 @@ impl<'a> Collector<'a> {
                                 std::mem::replace(&mut args, Args::with_capacity(ARG_DEFAULT_SIZE)),
                                 std::mem::take(&mut outputs),
                                 std::mem::take(&mut inputs),
+                                std::mem::take(&mut descriptors),
                             );
                         }
                         Some(_) | None => self.push_arg(&mut args, &mut bytes)?,
@@ -183,16 +299,29 @@ impl<'a> Collector<'a> {
                 }
                 b'|' => {
                     bytes.next();
+                    // `|&` pipes both stdout and stderr, as `&|` does
+                    let from = if let Some(&(_, b'&')) = bytes.peek() {
+                        bytes.next();
+                        RedirectFrom::Both
+                    } else {
+                        RedirectFrom::Stdout
+                    };
                     pipeline.add_item(
-                        RedirectFrom::Stdout,
+                        from,
                         std::mem::replace(&mut args, Args::with_capacity(ARG_DEFAULT_SIZE)),
                         std::mem::take(&mut outputs),
                         std::mem::take(&mut inputs),
+                        std::mem::take(&mut descriptors),
                     );
                 }
+                // Process substitutions are arguments rather than redirections
+                b'<' | b'>' if self.peek(i + 1) == Some(b'(') => {
+                    self.push_arg(&mut args, &mut bytes)?
+                }
                 b'>' => {
                     bytes.next();
-                    self.push_redir_to_output(RedirectFrom::Stdout, &mut outputs, &mut bytes)?;
+                    let to = self.descriptor(b'>', &mut bytes)?;
+                    push_descriptor(1, to, &mut outputs, &mut inputs, &mut descriptors);
                 }
                 b'<' => {
                     bytes.next();
@@ -210,23 +339,31 @@ impl<'a> Collector<'a> {
                         } else {
                             return Err(PipelineParsingError::HeredocsDeprecated);
                         }
-                    } else if let Some(file) = self.arg(&mut bytes)? {
-                        // Otherwise interpret it as stdin redirection
-                        inputs.push(Input::File(file.into()));
                     } else {
-                        return Err(PipelineParsingError::NoRedirectionArg);
+                        // Otherwise interpret it as stdin redirection
+                        let to = self.descriptor(b'<', &mut bytes)?;
+                        push_descriptor(0, to, &mut outputs, &mut inputs, &mut descriptors);
                     }
                 }
                 // Skip over whitespace between jobs
                 b' ' | b'\t' => {
                     bytes.next();
                 }
-                // Assume that the next character starts an argument and parse that argument
-                _ => self.push_arg(&mut args, &mut bytes)?,
+                // Assume that the next character starts an argument and parse that argument,
+                // unless it is the number of a redirected file descriptor
+                _ => match self.fd_prefix(i) {
+                    Some((fd, digits)) => {
+                        // Consume the digits and the operator
+                        bytes.nth(digits);
+                        let to = self.descriptor(self.data.as_bytes()[i + digits], &mut bytes)?;
+                        push_descriptor(fd, to, &mut outputs, &mut inputs, &mut descriptors);
+                    }
+                    None => self.push_arg(&mut args, &mut bytes)?,
+                },
             }
         }
 
-        pipeline.add_item(RedirectFrom::None, args, outputs, inputs);
+        pipeline.add_item(RedirectFrom::None, args, outputs, inputs, descriptors);
         Ok(pipeline)
     }
 
@@ -315,6 +452,10 @@ impl<'a> Collector<'a> {
                 // Evaluate a quoted string but do not return it
                 // We pass in i, the index of a quote, but start a character later. This ensures
                 // the production rules will produce strings with the quotes intact
+                b'"' | b'\'' if self.is_triple_quote(i) => {
+                    bytes.nth(2);
+                    self.triple_quoted(bytes, i, b)?;
+                }
                 b'"' => {
                     bytes.next();
                     self.double_quoted(bytes, i)?;
@@ -329,6 +470,9 @@ impl<'a> Collector<'a> {
                     bytes.next();
                     bytes.next();
                 }
+                b'<' | b'>' if start == Some(i) && self.peek(i + 1) == Some(b'(') => {
+                    bytes.next();
+                }
                 // If we see a byte from the follow set, we've definitely reached the end of
                 // the arguments
                 b'&' | b'|' | b'<' | b'>' | b' ' | b'\t' if levels.are_rooted() => {
@@ -395,6 +539,45 @@ impl<'a> Collector<'a> {
         Err(PipelineParsingError::UnterminatedSingleQuote)
     }
 
+    fn triple_quoted<I>(
+        &self,
+        bytes: &mut Peekable<I>,
+        start: usize,
+        quote: u8,
+    ) -> Result<&'a str, PipelineParsingError>
+    where
+        I: Iterator<Item = (usize, u8)>,
+    {
+        let mut closing = 0;
+        while let Some((i, b)) = bytes.next() {
+            match b {
+                // Escapes are only recognized in the interpolated variant
+                b'\\' if quote == b'"' => {
+                    bytes.next();
+                    closing = 0;
+                }
+                // We return an inclusive range to keep the quote type intact
+                _ if b == quote => {
+                    closing += 1;
+                    if closing == 3 {
+                        return Ok(&self.data[start..=i]);
+                    }
+                }
+                _ => closing = 0,
+            }
+        }
+        if quote == b'"' {
+            Err(PipelineParsingError::UnterminatedDoubleQuote)
+        } else {
+            Err(PipelineParsingError::UnterminatedSingleQuote)
+        }
+    }
+
+    fn is_triple_quote(&self, index: usize) -> bool {
+        let data = self.data.as_bytes();
+        data.get(index..index + 3).map_or(false, |quotes| quotes.iter().all(|&b| b == data[index]))
+    }
+
     const fn peek(&self, index: usize) -> Option<u8> {
         if index < self.data.len() {
             Some(self.data.as_bytes()[index])
@@ -415,7 +598,10 @@ impl<'a> Collector<'a> {
 mod tests {
     use crate::{
         parser::{
-            pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
+            pipelines::{
+                Collector, Descriptor, FdRedirection, Input, PipeItem, PipeType, Pipeline,
+                RedirectFrom, Redirection,
+            },
             statement::parse,
         },
         shell::{flow_control::Statement, Job, Shell},
@@ -443,6 +629,32 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let pipeline =
+            super::Collector::run("cat < in 3>> log 2>&1 | grep 'a b' ^> err &| tee <<< text &")
+                .unwrap();
+        let json = serde_json::to_string(&pipeline).unwrap();
+        assert!(json.contains(r#"["grep","'a b'"]"#));
+        assert_eq!(serde_json::from_str::<Pipeline<Job>>(&json).unwrap(), pipeline);
+    }
+
+    #[test]
+    fn stderr_pipes() {
+        if let Statement::Pipeline(pipeline) = parse("make |& less ^| grep error &| cat").unwrap() {
+            let items = pipeline.items;
+            assert_eq!(4, items.len());
+            assert_eq!(RedirectFrom::Both, items[0].job.redirection);
+            assert_eq!("less", &items[1].job.args[0]);
+            assert_eq!(RedirectFrom::Stderr, items[1].job.redirection);
+            assert_eq!(RedirectFrom::Both, items[2].job.redirection);
+            assert_eq!(RedirectFrom::None, items[3].job.redirection);
+        } else {
+            panic!();
+        }
+    }
+
     #[test]
     fn braces() {
         if let Statement::Pipeline(pipeline) = parse("echo {a b} {a {b c}}").unwrap() {
@@ -613,6 +825,91 @@ mod tests {
         }
     }
 
+    #[test]
+    fn triple_quoting() {
+        if let Statement::Pipeline(pipeline) =
+            parse("echo '''a 'b' > c''' \"\"\"d \"e\" | f\"\"\" > out").unwrap()
+        {
+            let items = pipeline.items;
+            assert_eq!(3, items[0].job.args.len());
+            assert_eq!("'''a 'b' > c'''", &items[0].job.args[1]);
+            assert_eq!("\"\"\"d \"e\" | f\"\"\"", &items[0].job.args[2]);
+            assert_eq!(1, items[0].outputs.len());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn process_substitution() {
+        if let Statement::Pipeline(pipeline) =
+            parse("diff <(sort a | uniq) <(sort b) > out < in").unwrap()
+        {
+            let items = pipeline.items;
+            assert_eq!(1, items.len());
+            assert_eq!(3, items[0].job.args.len());
+            assert_eq!("<(sort a | uniq)", &items[0].job.args[1]);
+            assert_eq!("<(sort b)", &items[0].job.args[2]);
+            assert_eq!(1, items[0].outputs.len());
+            assert_eq!(1, items[0].inputs.len());
+        } else {
+            panic!()
+        }
+    }
+
+    #[test]
+    fn descriptor_redirection() {
+        if let Statement::Pipeline(pipeline) =
+            parse("cmd a2> out 2> err 3< in 4>> log 2>&1 >&2 5<&- 0< file").unwrap()
+        {
+            let item = &pipeline.items[0];
+            assert_eq!(args!["cmd", "a2"], item.job.args);
+            assert_eq!(vec![Input::File("file".into())], item.inputs);
+            assert_eq!(
+                vec![Redirection {
+                    from:   RedirectFrom::Both,
+                    file:   "out".into(),
+                    append: false,
+                }],
+                item.outputs
+            );
+            assert_eq!(
+                vec![
+                    FdRedirection { fd: 3, to: Descriptor::Input("in".into()) },
+                    FdRedirection {
+                        fd: 4,
+                        to: Descriptor::Output { file: "log".into(), append: true },
+                    },
+                    FdRedirection { fd: 5, to: Descriptor::Close },
+                ],
+                item.descriptors
+            );
+        } else {
+            panic!()
+        }
+        if let Statement::Pipeline(pipeline) = parse("cmd 2>&1 > out | grep").unwrap() {
+            let item = &pipeline.items[0];
+            assert_eq!(
+                vec![Redirection {
+                    from:   RedirectFrom::Stdout,
+                    file:   "out".into(),
+                    append: false,
+                }],
+                item.outputs
+            );
+            assert_eq!(
+                vec![FdRedirection { fd: 2, to: Descriptor::Duplicate(1) }],
+                item.descriptors
+            );
+        } else {
+            panic!()
+        }
+        assert_eq!(
+            super::Collector::new("echo >&x").parse(),
+            Err(super::PipelineParsingError::InvalidDescriptor("x".into()))
+        );
+    }
+
     #[test]
     fn double_quoting_contains_single() {
         if let Statement::Pipeline(pipeline) = parse("echo \"Hello 'Rusty' World\"").unwrap() {
@@ -797,17 +1094,19 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(args!["cat"], RedirectFrom::Stdout),
-                    inputs:  vec![
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::Stdout),
+                    inputs:      vec![
                         Input::File("file1".into()),
                         Input::HereString("\"herestring\"".into()),
                     ],
-                    outputs: Vec::new(),
+                    outputs:     Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(args!["tr", "'x'", "'y'"], RedirectFrom::None),
-                    inputs:  Vec::new(),
-                    outputs: vec![
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["tr", "'x'", "'y'"], RedirectFrom::None),
+                    inputs:      Vec::new(),
+                    outputs:     vec![
                         Redirection {
                             from:   RedirectFrom::Stderr,
                             file:   "err".into(),
@@ -839,19 +1138,22 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job:     Job::new(args!["cat"], RedirectFrom::Stdout),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::Stdout),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(args!["echo", "hello"], RedirectFrom::Stdout),
-                    inputs:  Vec::new(),
-                    outputs: Vec::new(),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["echo", "hello"], RedirectFrom::Stdout),
+                    inputs:      Vec::new(),
+                    outputs:     Vec::new(),
                 },
                 PipeItem {
-                    job:     Job::new(args!["cat"], RedirectFrom::None),
-                    inputs:  vec![Input::File("stuff".into())],
-                    outputs: vec![Redirection {
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::None),
+                    inputs:      vec![Input::File("stuff".into())],
+                    outputs:     vec![Redirection {
                         from:   RedirectFrom::Stderr,
                         file:   "other".into(),
                         append: true,
@@ -871,19 +1173,22 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job: Job::new(args!["cat"], RedirectFrom::Stdout),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::Stdout),
 
                     inputs:  Vec::new(),
                     outputs: Vec::new(),
                 },
                 PipeItem {
-                    job: Job::new(args!["echo", "hello"], RedirectFrom::Stdout),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["echo", "hello"], RedirectFrom::Stdout),
 
                     inputs:  Vec::new(),
                     outputs: Vec::new(),
                 },
                 PipeItem {
-                    job: Job::new(args!["cat"], RedirectFrom::None),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::None),
 
                     inputs:  vec![Input::File("stuff".into())],
                     outputs: vec![Redirection {
@@ -940,7 +1245,8 @@ mod tests {
         let input = "math <<< $(cat math.txt)";
         let expected = Pipeline {
             items: vec![PipeItem {
-                job: Job::new(args!["math"], RedirectFrom::None),
+                descriptors: Vec::new(),
+                job:         Job::new(args!["math"], RedirectFrom::None),
 
                 inputs:  vec![Input::HereString("$(cat math.txt)".into())],
                 outputs: vec![],
@@ -958,13 +1264,15 @@ mod tests {
         let expected = Pipeline {
             items: vec![
                 PipeItem {
-                    job: Job::new(args!["cat"], RedirectFrom::Stdout),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["cat"], RedirectFrom::Stdout),
 
                     inputs:  Vec::new(),
                     outputs: Vec::new(),
                 },
                 PipeItem {
-                    job: Job::new(args!["tr", "'o'", "'x'"], RedirectFrom::None),
+                    descriptors: Vec::new(),
+                    job:         Job::new(args!["tr", "'o'", "'x'"], RedirectFrom::None),
 
                     inputs:  vec![Input::HereString("$VAR".into())],
                     outputs: vec![Redirection {
@@ -1000,7 +1308,8 @@ mod tests {
         let input = "echo zardoz >> foo\\'bar";
         let expected = Pipeline {
             items: vec![PipeItem {
-                job: Job::new(args!["echo", "zardoz"], RedirectFrom::None),
+                descriptors: Vec::new(),
+                job:         Job::new(args!["echo", "zardoz"], RedirectFrom::None),
 
                 inputs:  Vec::new(),
                 outputs: vec![Redirection {
@@ -12,6 +12,8 @@ pub enum Error {
     ExtraBind(String),
     #[error("extra variable, '{0}', was given to case")]
     ExtraVar(String),
+    #[error("no pattern was given after '|'")]
+    NoPattern,
 }
 
 impl FromStr for Case {
@@ -22,8 +24,11 @@ impl FromStr for Case {
             return Ok(Self::default());
         }
         let mut splitter = ArgumentSplitter::new(data);
-        // let argument = splitter.next().ok_or(CaseError::Empty)?;
-        let mut argument = None;
+        let mut values = Vec::new();
+        let regex = data.starts_with("~ ");
+        if regex {
+            splitter.next();
+        }
         let mut binding = None;
         let mut conditional = None;
         loop {
@@ -31,49 +36,32 @@ impl FromStr for Case {
                 Some("@") => {
                     binding = Some(splitter.next().ok_or(Error::NoBindVariable)?);
                     match splitter.next() {
-                        Some("if") => {
-                            // Joining by folding is more efficient than collecting into Vec and
-                            // then joining
-                            let mut string =
-                                splitter.fold(String::with_capacity(5), |mut state, element| {
-                                    state.push_str(element);
-                                    state.push(' ');
-                                    state
-                                });
-                            string.pop(); // Pop out the unneeded ' ' character
-                            if string.is_empty() {
-                                return Err(Error::NoConditional);
-                            }
-                            conditional = Some(string);
-                        }
+                        Some("if") => conditional = Some(guard(splitter)?),
                         Some(value) => return Err(Error::ExtraBind(value.into())),
                         None => (),
                     }
                 }
-                Some("if") => {
-                    // Joining by folding is more efficient than collecting into Vec and then
-                    // joining
-                    let mut string =
-                        splitter.fold(String::with_capacity(5), |mut state, element| {
-                            state.push_str(element);
-                            state.push(' ');
-                            state
-                        });
-                    string.pop(); // Pop out the unneeded ' ' character
-                    if string.is_empty() {
-                        return Err(Error::NoConditional);
-                    }
-                    conditional = Some(string);
+                Some("if") => conditional = Some(guard(splitter)?),
+                Some("|") if !values.is_empty() => {
+                    values.push(splitter.next().ok_or(Error::NoPattern)?);
+                    continue;
                 }
-                Some(inner) if argument.is_none() => {
-                    argument = Some(inner);
+                Some(inner) if values.is_empty() => {
+                    values.push(inner);
                     continue;
                 }
                 Some(inner) => return Err(Error::ExtraVar(inner.into())),
                 None => (),
             }
+            // A wildcard among the patterns matches anything
+            let values = if values.contains(&"_") {
+                Vec::new()
+            } else {
+                values.into_iter().map(Into::into).collect()
+            };
             return Ok(Self {
-                value: argument.filter(|&val| val != "_").map(Into::into),
+                values,
+                regex,
                 binding: binding.map(Into::into),
                 conditional,
                 statements: Vec::new(),
@@ -82,6 +70,22 @@ impl FromStr for Case {
     }
 }
 
+/// Join the rest of the arguments into the statement guarding the case
+fn guard(splitter: ArgumentSplitter<'_>) -> Result<String, Error> {
+    // Joining by folding is more efficient than collecting into Vec and then joining
+    let mut string = splitter.fold(String::with_capacity(5), |mut state, element| {
+        state.push_str(element);
+        state.push(' ');
+        state
+    });
+    string.pop(); // Pop out the unneeded ' ' character
+    if string.is_empty() {
+        Err(Error::NoConditional)
+    } else {
+        Ok(string)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -90,7 +94,8 @@ mod tests {
     fn case_parsing() {
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec!["test".into()],
+                regex:       false,
                 binding:     Some("test".into()),
                 conditional: Some("exists".into()),
                 statements:  Vec::new(),
@@ -99,7 +104,8 @@ mod tests {
         );
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec!["test".into()],
+                regex:       false,
                 binding:     Some("test".into()),
                 conditional: None,
                 statements:  Vec::new(),
@@ -108,12 +114,35 @@ mod tests {
         );
         assert_eq!(
             Ok(Case {
-                value:       Some("test".into()),
+                values:      vec!["test".into()],
+                regex:       false,
                 binding:     None,
                 conditional: None,
                 statements:  Vec::new(),
             }),
             "test".parse::<Case>()
         );
+        assert_eq!(
+            Ok(Case {
+                values:      vec!["foo".into(), "bar".into()],
+                regex:       false,
+                binding:     None,
+                conditional: Some("test $x -gt 3".into()),
+                statements:  Vec::new(),
+            }),
+            "foo | bar if test $x -gt 3".parse::<Case>()
+        );
+        assert_eq!(
+            Ok(Case {
+                values:      vec![r"'^v(\d+)'".into()],
+                regex:       true,
+                binding:     None,
+                conditional: None,
+                statements:  Vec::new(),
+            }),
+            r"~ '^v(\d+)'".parse::<Case>()
+        );
+        assert_eq!(Ok(Case::default()), "foo | _".parse::<Case>());
+        assert_eq!(Err(Error::NoPattern), "foo |".parse::<Case>());
     }
 }
@@ -1,4 +1,4 @@
-use crate::parser::lexers::assignments::{KeyBuf, KeyIterator, TypeError};
+use crate::parser::lexers::assignments::{KeyBuf, KeyIterator, Primitive, TypeError};
 use thiserror::Error;
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Error)]
@@ -7,22 +7,42 @@ pub enum FunctionParseError {
     RepeatedArgument(String),
     #[error("{0}")]
     TypeError(#[source] TypeError),
+    #[error("a script does not return a value, but was given the return type '{0}'")]
+    ReturnTypeOfScript(String),
 }
 
 /// The arguments expression given to a function declaration goes into here, which will be
 /// converted into a tuple consisting of a `KeyIterator` iterator, which will collect type
-/// information, and an optional description of the function.
-pub fn parse_function(arg: &str) -> (KeyIterator<'_>, Option<&str>) {
+/// information, whether the last argument is variadic, the optional return type following `->`,
+/// and an optional description of the function.
+pub fn parse_function(arg: &str) -> (KeyIterator<'_>, bool, Option<&str>, Option<&str>) {
     let mut parts = arg.splitn(2, "--");
     let (args, description) = (parts.next().unwrap().trim(), parts.next().map(str::trim));
-    (KeyIterator::new(args), description)
+    let mut parts = args.splitn(2, "->");
+    let (args, return_type) = (parts.next().unwrap().trim(), parts.next().map(str::trim));
+    // A variadic argument (ex: `rest:int...`) collects the remaining arguments
+    match args.strip_suffix("...") {
+        Some(fixed) if !fixed.ends_with(char::is_whitespace) && !fixed.is_empty() => {
+            (KeyIterator::new(fixed), true, return_type, description)
+        }
+        _ => (KeyIterator::new(args), false, return_type, description),
+    }
+}
+
+/// Parse the return type of a function declaration
+pub fn parse_return_type(return_type: &str) -> Result<Primitive, FunctionParseError> {
+    Primitive::parse(return_type)
+        .ok_or_else(|| FunctionParseError::TypeError(TypeError::Invalid(return_type.into())))
 }
 
 /// All type information will be collected from the `KeyIterator` and stored into a vector. If a
 /// type or argument error is detected, then that error will be returned instead. This is required
 /// because of lifetime restrictions on `KeyIterator`, which will not live for the remainder of the
-/// declared function's lifetime.
-pub fn collect_arguments(args: KeyIterator<'_>) -> Result<Vec<KeyBuf>, FunctionParseError> {
+/// declared function's lifetime. The type of a variadic argument is an array of the declared type.
+pub fn collect_arguments(
+    args: KeyIterator<'_>,
+    variadic: bool,
+) -> Result<Vec<KeyBuf>, FunctionParseError> {
     let mut keybuf: Vec<KeyBuf> = Vec::new();
     for arg in args {
         match arg {
@@ -37,6 +57,9 @@ pub fn collect_arguments(args: KeyIterator<'_>) -> Result<Vec<KeyBuf>, FunctionP
             Err(e) => return Err(FunctionParseError::TypeError(e)),
         }
     }
+    if let (true, Some(last)) = (variadic, keybuf.last_mut()) {
+        last.kind = Primitive::Array(Box::new(last.kind.clone()));
+    }
     Ok(keybuf)
 }
 
@@ -44,13 +67,15 @@ pub fn collect_arguments(args: KeyIterator<'_>) -> Result<Vec<KeyBuf>, FunctionP
 mod tests {
     use crate::parser::{
         lexers::assignments::{KeyBuf, Primitive},
-        statement::functions::{collect_arguments, parse_function, FunctionParseError},
+        statement::functions::{
+            collect_arguments, parse_function, parse_return_type, FunctionParseError,
+        },
     };
 
     #[test]
     fn function_parsing() {
-        let (arg_iter, description) = parse_function("a:int b:bool c[] d -- description");
-        let args = collect_arguments(arg_iter);
+        let (arg_iter, _, _, description) = parse_function("a:int b:bool c[] d -- description");
+        let args = collect_arguments(arg_iter, false);
         assert_eq!(
             args,
             Ok(vec![
@@ -63,10 +88,56 @@ mod tests {
         assert_eq!(description, Some("description"))
     }
 
+    #[test]
+    fn function_map_arguments() {
+        let (arg_iter, ..) = parse_function("a:hmap[str] b:bmap[[int]]");
+        assert_eq!(
+            collect_arguments(arg_iter, false),
+            Ok(vec![
+                KeyBuf { name: "a".into(), kind: Primitive::HashMap(Box::new(Primitive::Str)) },
+                KeyBuf {
+                    name: "b".into(),
+                    kind: Primitive::BTreeMap(Box::new(Primitive::Array(Box::new(
+                        Primitive::Integer
+                    )))),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn function_variadic_argument() {
+        let (arg_iter, variadic, _, description) = parse_function("sep rest:int... -- joins");
+        assert!(variadic);
+        assert_eq!(description, Some("joins"));
+        assert_eq!(
+            collect_arguments(arg_iter, variadic),
+            Ok(vec![
+                KeyBuf { name: "sep".into(), kind: Primitive::Str },
+                KeyBuf {
+                    name: "rest".into(),
+                    kind: Primitive::Array(Box::new(Primitive::Integer)),
+                },
+            ])
+        );
+        assert!(!parse_function("a b ...").1);
+    }
+
+    #[test]
+    fn function_return_type() {
+        let (arg_iter, variadic, return_type, description) =
+            parse_function("a:int b:int... -> [int] -- doubles");
+        assert_eq!((variadic, return_type, description), (true, Some("[int]"), Some("doubles")));
+        assert_eq!(collect_arguments(arg_iter, variadic).map(|args| args.len()), Ok(2));
+        assert_eq!(parse_return_type("[int]"), Ok(Primitive::Array(Box::new(Primitive::Integer))));
+        assert!(parse_return_type("integer").is_err());
+        assert_eq!(parse_function("a b").2, None);
+    }
+
     #[test]
     fn function_repeated_arg() {
-        let (arg_iter, description) = parse_function("a:bool b a[] -- failed def");
-        let args = collect_arguments(arg_iter);
+        let (arg_iter, _, _, description) = parse_function("a:bool b a[] -- failed def");
+        let args = collect_arguments(arg_iter, false);
         assert_eq!(args, Err(FunctionParseError::RepeatedArgument("a".into())));
         assert_eq!(description, Some("failed def"));
     }
@@ -4,7 +4,7 @@ mod parse;
 mod splitter;
 
 pub use self::{
-    parse::parse,
+    parse::{opens_block, parse},
     splitter::{StatementSplitter, StatementVariant},
 };
 use super::{
@@ -77,6 +77,21 @@ pub enum Error {
         Function names may only contain alphanumeric characters"
     )]
     InvalidFunctionName(String),
+    /// The provided coprocess name was invalid
+    #[error("'{0}' is not a valid coprocess name")]
+    InvalidCoprocessName(String),
+    /// The label of a loop, or the one given to break or continue, was invalid
+    #[error("'{0}' is not a valid loop label")]
+    InvalidLabel(String),
+    /// The variable given to catch was not a valid variable name
+    #[error("'{0}' is not a valid variable name for catch")]
+    InvalidCatchVariable(String),
+    /// The variable given to select was not a valid variable name
+    #[error("'{0}' is not a valid variable name for select")]
+    InvalidSelectVariable(String),
+    /// The statement given to defer does not stand on its own
+    #[error("'{0}' can not be deferred")]
+    InvalidDeferred(String),
     /// The arguments did not match the function's signature
     #[error("function argument error: {0}")]
     InvalidFunctionArgument(#[source] FunctionParseError),
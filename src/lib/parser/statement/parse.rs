@@ -1,9 +1,10 @@
 use super::{
     super::pipelines,
-    functions::{collect_arguments, parse_function},
+    functions::{collect_arguments, parse_function, parse_return_type, FunctionParseError},
     Error,
 };
 use crate::{
+    expansion::pipelines::PipeType,
     parser::lexers::{assignment_lexer, ArgumentSplitter},
     shell::{
         flow_control::{Case, ElseIf, ExportAction, IfMode, LocalAction, Statement},
@@ -13,6 +14,13 @@ use crate::{
 };
 use std::char;
 
+/// The assignment following `const`, which declares constants rather than assigning a variable
+/// named `const`
+fn constant(assignment: &str) -> Option<&str> {
+    let assignment = assignment.trim_start().strip_prefix("const ")?.trim_start();
+    Some(assignment).filter(|rest| rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
 pub fn parse(code: &str) -> super::Result {
     let cmd = code.trim();
     match cmd {
@@ -20,22 +28,51 @@ pub fn parse(code: &str) -> super::Result {
         _ if cmd.starts_with("return ") => {
             Ok(Statement::Return(Some(cmd[7..].trim_start().into())))
         }
-        "end" => Ok(Statement::End),
-        "break" => Ok(Statement::Break),
-        "continue" => Ok(Statement::Continue),
-        "for" | "match" | "case" => Err(Error::IncompleteFlowControl),
+        "end" => Ok(Statement::End(PipeType::Normal)),
+        // The block which is closed runs as a job of its own
+        _ if cmd.starts_with("end") && cmd[3..].trim_start() == "&" => {
+            Ok(Statement::End(PipeType::Background))
+        }
+        _ if cmd.starts_with("end") && cmd[3..].trim_start() == "&!" => {
+            Ok(Statement::End(PipeType::Disown))
+        }
+        "break" => Ok(Statement::Break(None)),
+        "continue" => Ok(Statement::Continue(None)),
+        _ if cmd.starts_with("break ") => Ok(Statement::Break(Some(label(&cmd[6..])?))),
+        _ if cmd.starts_with("continue ") => Ok(Statement::Continue(Some(label(&cmd[9..])?))),
+        "for" | "match" | "case" | "coproc" | "defer" | "yield" => {
+            Err(Error::IncompleteFlowControl)
+        }
+        _ if cmd.starts_with("yield ") => Ok(Statement::Yield(cmd[6..].trim_start().into())),
+        "try" => Ok(Statement::Try { statements: Vec::new(), binding: None, catch: None }),
+        "catch" => Ok(Statement::Catch(None)),
+        _ if cmd.starts_with("catch ") => {
+            let name = cmd[6..].trim();
+            if Variables::is_valid_name(name) {
+                Ok(Statement::Catch(Some(name.into())))
+            } else {
+                Err(Error::InvalidCatchVariable(name.into()))
+            }
+        }
         "let" => Ok(Statement::Let(LocalAction::List)),
-        _ if cmd.starts_with("let ") => {
+        _ if cmd.starts_with("let ") || cmd.starts_with("local ") || cmd.starts_with("global ") => {
+            let (keyword, assignment) = cmd.split_at(cmd.find(' ').unwrap());
+            let (keyword, assignment) = match constant(assignment) {
+                Some(assignment) if keyword == "let" => ("const", assignment),
+                _ => (keyword, assignment),
+            };
             // Split the let expression and ensure that the statement is valid.
-            let (keys, op, vals) = assignment_lexer(cmd[4..].trim_start());
+            let (keys, op, vals) = assignment_lexer(assignment.trim_start());
             match vals {
                 Some(vals) => {
                     // If the values exist, then the keys and operator also exists.
-                    Ok(Statement::Let(LocalAction::Assign(
-                        keys.unwrap().into(),
-                        op.unwrap(),
-                        vals.into(),
-                    )))
+                    let (keys, op, vals) = (keys.unwrap().into(), op.unwrap(), vals.into());
+                    Ok(Statement::Let(match keyword {
+                        "const" => LocalAction::Const(keys, op, vals),
+                        "local" => LocalAction::Local(keys, op, vals),
+                        "global" => LocalAction::Global(keys, op, vals),
+                        _ => LocalAction::Assign(keys, op, vals),
+                    }))
                 }
                 None if op.is_none() => Err(Error::NoOperatorSupplied),
                 _ => Err(Error::NoValueSupplied),
@@ -43,9 +80,16 @@ pub fn parse(code: &str) -> super::Result {
         }
         "export" => Ok(Statement::Export(ExportAction::List)),
         _ if cmd.starts_with("export ") => {
+            let (constant, assignment) = match constant(&cmd[7..]) {
+                Some(assignment) => (true, assignment),
+                None => (false, cmd[7..].trim_start()),
+            };
             // Split the let expression and ensure that the statement is valid.
-            let (keys, op, vals) = assignment_lexer(cmd[7..].trim_start());
+            let (keys, op, vals) = assignment_lexer(assignment);
             match (vals, keys, op) {
+                (Some(vals), Some(keys), Some(op)) if constant => {
+                    Ok(Statement::Export(ExportAction::Const(keys.into(), op, vals.into())))
+                }
                 (Some(vals), Some(keys), Some(op)) => {
                     // If the values exist, then the keys and operator also exists.
                     Ok(Statement::Export(ExportAction::Assign(keys.into(), op, vals.into())))
@@ -80,8 +124,10 @@ pub fn parse(code: &str) -> super::Result {
         _ if cmd.starts_with("while ") => {
             let pipeline = pipelines::Collector::run(cmd[6..].trim_start())?;
             Ok(Statement::While {
+                label:      None,
                 expression: vec![Statement::Pipeline(pipeline)],
                 statements: Vec::new(),
+                otherwise:  None,
             })
         }
         _ if cmd.starts_with("for ") => {
@@ -92,13 +138,43 @@ pub fn parse(code: &str) -> super::Result {
 
             match cmd {
                 Some(cmd) => Ok(Statement::For {
+                    label: None,
                     variables,
                     values: ArgumentSplitter::new(cmd.trim()).map(types::Str::from).collect(),
                     statements: Vec::new(),
+                    otherwise: None,
+                }),
+                None => Err(Error::NoInKeyword),
+            }
+        }
+        _ if cmd.starts_with("select ") => {
+            let mut parts = cmd[7..].trim_start().splitn(2, " in ");
+            let variable = parts.next().unwrap().trim();
+            match parts.next() {
+                Some(_) if !Variables::is_valid_name(variable) => {
+                    Err(Error::InvalidSelectVariable(variable.into()))
+                }
+                Some(cmd) => Ok(Statement::Select {
+                    label:      None,
+                    variable:   variable.into(),
+                    values:     ArgumentSplitter::new(cmd.trim()).map(types::Str::from).collect(),
+                    statements: Vec::new(),
                 }),
                 None => Err(Error::NoInKeyword),
             }
         }
+        _ if labelled_loop(cmd).is_some() => {
+            let (name, cmd) = labelled_loop(cmd).unwrap();
+            let name = label(name)?;
+            let mut statement = parse(cmd)?;
+            if let Statement::For { ref mut label, .. }
+            | Statement::While { ref mut label, .. }
+            | Statement::Select { ref mut label, .. } = statement
+            {
+                *label = Some(name);
+            }
+            Ok(statement)
+        }
         _ if cmd.starts_with("case ") => {
             Ok(Statement::Case(cmd[5..].trim_start().parse::<Case>()?))
         }
@@ -114,14 +190,35 @@ pub fn parse(code: &str) -> super::Result {
                 return Err(Error::InvalidFunctionName(name.into()));
             }
 
-            let (args, description) = parse_function(&cmd[pos..]);
+            let (args, variadic, return_type, description) = parse_function(&cmd[pos..]);
             Ok(Statement::Function {
                 description: description.map(types::Str::from),
-                name:        name.into(),
-                args:        collect_arguments(args)?,
-                statements:  Vec::new(),
+                name: name.into(),
+                args: collect_arguments(args, variadic)?,
+                variadic,
+                return_type: return_type.map(parse_return_type).transpose()?,
+                statements: Vec::new(),
+            })
+        }
+        // The header of a script, declaring its arguments as those of a function
+        _ if cmd.starts_with("args:") => {
+            let (args, variadic, return_type, description) = parse_function(&cmd[5..]);
+            if let Some(return_type) = return_type {
+                return Err(FunctionParseError::ReturnTypeOfScript(return_type.into()).into());
+            }
+            Ok(Statement::Args {
+                description: description.map(types::Str::from),
+                args: collect_arguments(args, variadic)?,
+                variadic,
             })
         }
+        _ if cmd.starts_with("coproc ") => {
+            let name = cmd[7..].trim_start();
+            if !Variables::is_valid_name(name) {
+                return Err(Error::InvalidCoprocessName(name.into()));
+            }
+            Ok(Statement::Coproc { name: name.into(), statements: Vec::new() })
+        }
         _ if cmd.starts_with("time ") => {
             // Ignore embedded time calls
             let mut timed = cmd[4..].trim_start();
@@ -131,6 +228,30 @@ pub fn parse(code: &str) -> super::Result {
             Ok(Statement::Time(Box::new(parse(timed)?)))
         }
         _ if cmd.eq("time") => Ok(Statement::Time(Box::new(Statement::Default))),
+        // Options are left to the timeout command, as in `timeout -s KILL 5 command`
+        _ if cmd.starts_with("timeout ") && !cmd[8..].trim_start().starts_with('-') => {
+            let timed = cmd[8..].trim_start();
+            let (duration, timed) = timed.split_once(char::is_whitespace).unwrap_or((timed, ""));
+            let timed = timed.trim_start();
+            let statement = if timed.is_empty() { Statement::Default } else { parse(timed)? };
+            Ok(Statement::Timeout(duration.into(), Box::new(statement)))
+        }
+        _ if cmd.starts_with("defer ") => {
+            let deferred = cmd[6..].trim_start();
+            match parse(deferred)? {
+                Statement::End(_)
+                | Statement::Else
+                | Statement::ElseIf(_)
+                | Statement::Case(_)
+                | Statement::Catch(_)
+                | Statement::Break(_)
+                | Statement::Continue(_)
+                | Statement::Return(_)
+                | Statement::Yield(_)
+                | Statement::Defer(_) => Err(Error::InvalidDeferred(deferred.into())),
+                statement => Ok(Statement::Defer(Box::new(statement))),
+            }
+        }
         _ if cmd.starts_with("and ") => Ok(Statement::And(Box::new(parse(cmd[3..].trim_start())?))),
         _ if cmd.eq("and") => Ok(Statement::And(Box::new(Statement::Default))),
         _ if cmd.starts_with("or ") => Ok(Statement::Or(Box::new(parse(cmd[2..].trim_start())?))),
@@ -143,11 +264,52 @@ pub fn parse(code: &str) -> super::Result {
     }
 }
 
+/// Whether the statement opens a block or a branch of one, judged by its keywords alone so that
+/// it is known of a statement which failed to parse
+pub fn opens_block(code: &str) -> bool {
+    let cmd = code.trim();
+    let cmd = labelled_loop(cmd).map_or(cmd, |(_, rest)| rest);
+    let (keyword, rest) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+    match keyword {
+        "if" | "else" | "for" | "while" | "select" | "match" | "case" | "fn" | "coproc" | "try"
+        | "catch" => true,
+        "time" | "defer" | "and" | "or" | "not" | "!" => opens_block(rest),
+        "timeout" => rest
+            .trim_start()
+            .split_once(char::is_whitespace)
+            .map_or(false, |(_, rest)| opens_block(rest)),
+        _ => false,
+    }
+}
+
+/// Split a loop from the label before it, as in `outer: for x in 1..=3`
+fn labelled_loop(cmd: &str) -> Option<(&str, &str)> {
+    let (name, rest) = cmd.split_at(cmd.find(": ")?);
+    let rest = rest[1..].trim_start();
+    if !name.contains(char::is_whitespace)
+        && ["for ", "while ", "select "].iter().any(|keyword| rest.starts_with(keyword))
+    {
+        Some((name, rest))
+    } else {
+        None
+    }
+}
+
+/// The label of a loop, or the loop targeted by `break` or `continue`
+fn label(name: &str) -> Result<types::Str, Error> {
+    let name = name.trim();
+    if Variables::is_valid_name(name) {
+        Ok(name.into())
+    } else {
+        Err(Error::InvalidLabel(name.into()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{
-        expansion::pipelines::{PipeItem, PipeType, Pipeline, RedirectFrom},
+        expansion::pipelines::{PipeItem, Pipeline, RedirectFrom},
         parser::lexers::{
             assignments::{KeyBuf, Primitive},
             Operator,
@@ -186,6 +348,31 @@ mod tests {
                 "[hello world]".to_owned()
             )),
         );
+        assert_eq!(
+            parse("let const PREFIX = /opt").unwrap(),
+            Statement::Let(LocalAction::Const(
+                "PREFIX".to_owned(),
+                Operator::Equal,
+                "/opt".to_owned()
+            )),
+        );
+        // A variable may be named const
+        assert_eq!(
+            parse("let const = 1").unwrap(),
+            Statement::Let(LocalAction::Assign(
+                "const".to_owned(),
+                Operator::Equal,
+                "1".to_owned()
+            )),
+        );
+        assert_eq!(
+            parse("export const PREFIX = /opt").unwrap(),
+            Statement::Export(ExportAction::Const(
+                "PREFIX".to_owned(),
+                Operator::Equal,
+                "/opt".to_owned()
+            )),
+        );
         // Can it handle line breaks in value ?
         assert_eq!(
             parse("let list = [hello \n world]").unwrap(),
@@ -206,6 +393,19 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parsing_local_and_global() {
+        assert_eq!(
+            parse("local count += 1").unwrap(),
+            Statement::Let(LocalAction::Local("count".into(), Operator::Add, "1".into())),
+        );
+        assert_eq!(
+            parse("global  list = [a b]").unwrap(),
+            Statement::Let(LocalAction::Global("list".into(), Operator::Equal, "[a b]".into())),
+        );
+        assert!(matches!(parse("local count"), Err(Error::NoOperatorSupplied)));
+    }
+
     #[test]
     fn parsing_let_error() {
         let actual = parse("let Foo");
@@ -227,20 +427,133 @@ mod tests {
         assert_eq!(
             parse("for x y z in 1..=10").unwrap(),
             Statement::For {
+                label:      None,
                 variables:  vec!["x", "y", "z"].into_iter().map(Into::into).collect(),
                 values:     vec!["1..=10"].into_iter().map(Into::into).collect(),
                 statements: Vec::new(),
+                otherwise:  None,
             }
         );
 
         assert_eq!(
             parse("for  x  in  {1..=10} {1..=10}").unwrap(),
             Statement::For {
+                label:      None,
                 variables:  vec!["x"].into_iter().map(Into::into).collect(),
                 values:     vec!["{1..=10}", "{1..=10}"].into_iter().map(Into::into).collect(),
                 statements: Vec::new(),
+                otherwise:  None,
+            }
+        );
+    }
+
+    #[test]
+    fn parsing_args() {
+        assert_eq!(
+            parse("args: src count:int files:str... -- Copy the files").unwrap(),
+            Statement::Args {
+                description: Some("Copy the files".into()),
+                args:        vec![
+                    KeyBuf { name: "src".into(), kind: Primitive::Str },
+                    KeyBuf { name: "count".into(), kind: Primitive::Integer },
+                    KeyBuf {
+                        name: "files".into(),
+                        kind: Primitive::Array(Box::new(Primitive::Str)),
+                    },
+                ],
+                variadic:    true,
+            }
+        );
+        assert!(matches!(
+            parse("args: x:int -> str"),
+            Err(Error::InvalidFunctionArgument(FunctionParseError::ReturnTypeOfScript(_)))
+        ));
+    }
+
+    #[test]
+    fn parsing_select() {
+        assert_eq!(
+            parse("select  choice in @options  quit").unwrap(),
+            Statement::Select {
+                label:      None,
+                variable:   "choice".into(),
+                values:     vec!["@options", "quit"].into_iter().map(Into::into).collect(),
+                statements: Vec::new(),
+            }
+        );
+        assert!(matches!(
+            parse("menu: select x in a b").unwrap(),
+            Statement::Select { label: Some(label), .. } if label.as_str() == "menu"
+        ));
+        assert!(matches!(parse("select x y in a b"), Err(Error::InvalidSelectVariable(_))));
+        assert!(matches!(parse("select x"), Err(Error::NoInKeyword)));
+    }
+
+    #[test]
+    fn parsing_try() {
+        assert_eq!(
+            parse("try").unwrap(),
+            Statement::Try { statements: Vec::new(), binding: None, catch: None }
+        );
+        assert_eq!(parse("catch").unwrap(), Statement::Catch(None));
+        assert_eq!(parse("catch  err").unwrap(), Statement::Catch(Some("err".into())));
+        assert!(matches!(parse("catch $err"), Err(Error::InvalidCatchVariable(_))));
+    }
+
+    #[test]
+    fn parsing_defer() {
+        assert_eq!(
+            parse("defer  let x = 1").unwrap(),
+            Statement::Defer(Box::new(parse("let x = 1").unwrap()))
+        );
+        assert!(matches!(parse("defer for x in 1..=3"), Ok(Statement::Defer(_))));
+        assert!(matches!(parse("defer"), Err(Error::IncompleteFlowControl)));
+        assert!(matches!(parse("defer end"), Err(Error::InvalidDeferred(_))));
+        assert!(matches!(parse("defer defer echo"), Err(Error::InvalidDeferred(_))));
+    }
+
+    #[test]
+    fn parsing_yield() {
+        assert_eq!(parse("yield  $x @rest").unwrap(), Statement::Yield("$x @rest".into()));
+        assert!(matches!(parse("yield"), Err(Error::IncompleteFlowControl)));
+        assert!(matches!(parse("defer yield 1"), Err(Error::InvalidDeferred(_))));
+    }
+
+    #[test]
+    fn parsing_timeout() {
+        assert_eq!(
+            parse("timeout 5s  grep x").unwrap(),
+            Statement::Timeout("5s".into(), Box::new(parse("grep x").unwrap()))
+        );
+        assert_eq!(
+            parse("timeout $limit").unwrap(),
+            Statement::Timeout("$limit".into(), Box::new(Statement::Default))
+        );
+        assert!(matches!(parse("timeout -s KILL 5 sleep 10"), Ok(Statement::Pipeline(_))));
+    }
+
+    #[test]
+    fn parsing_labels() {
+        assert_eq!(
+            parse("outer: for x in 1..=3").unwrap(),
+            Statement::For {
+                label:      Some("outer".into()),
+                variables:  vec!["x"].into_iter().map(Into::into).collect(),
+                values:     vec!["1..=3"].into_iter().map(Into::into).collect(),
+                statements: Vec::new(),
+                otherwise:  None,
             }
         );
+        assert!(matches!(
+            parse("lines:  while read line").unwrap(),
+            Statement::While { label: Some(label), .. } if label.as_str() == "lines"
+        ));
+        assert_eq!(parse("break outer").unwrap(), Statement::Break(Some("outer".into())));
+        assert_eq!(parse("continue").unwrap(), Statement::Continue(None));
+        assert!(matches!(parse("break a-b"), Err(Error::InvalidLabel(_))));
+        assert!(matches!(parse("a-b: for x in 1..=3"), Err(Error::InvalidLabel(_))));
+        // Only a loop takes a label
+        assert!(matches!(parse("note: echo"), Ok(Statement::Pipeline(_))));
     }
 
     #[test]
@@ -250,14 +563,15 @@ mod tests {
         let correct_parse = Statement::If {
             expression: vec![Statement::Pipeline(Pipeline {
                 items: vec![PipeItem {
-                    job:     Job::new(
+                    descriptors: Vec::new(),
+                    job:         Job::new(
                         vec!["test".into(), "1".into(), "-eq".into(), "2".into()]
                             .into_iter()
                             .collect(),
                         RedirectFrom::None,
                     ),
-                    outputs: Vec::new(),
-                    inputs:  Vec::new(),
+                    outputs:     Vec::new(),
+                    inputs:      Vec::new(),
                 }],
                 pipe:  PipeType::Normal,
             })],
@@ -293,18 +607,33 @@ mod tests {
     fn parsing_ends() {
         // Default case where spaced normally
         let parsed_if = parse("end").unwrap();
-        let correct_parse = Statement::End;
+        let correct_parse = Statement::End(PipeType::Normal);
         assert_eq!(correct_parse, parsed_if);
 
         // Trailing spaces after final value
         let parsed_if = parse("end         ").unwrap();
-        let correct_parse = Statement::End;
+        let correct_parse = Statement::End(PipeType::Normal);
         assert_eq!(correct_parse, parsed_if);
 
         // Leading spaces after final value
         let parsed_if = parse("         end").unwrap();
-        let correct_parse = Statement::End;
+        let correct_parse = Statement::End(PipeType::Normal);
         assert_eq!(correct_parse, parsed_if);
+
+        // Blocks sent to the background
+        assert_eq!(parse("end &").unwrap(), Statement::End(PipeType::Background));
+        assert_eq!(parse("end&!").unwrap(), Statement::End(PipeType::Disown));
+        assert!(matches!(parse("endless &"), Ok(Statement::Pipeline(_))));
+    }
+
+    #[test]
+    fn parsing_coprocesses() {
+        assert_eq!(
+            parse("coproc  worker ").unwrap(),
+            Statement::Coproc { name: "worker".into(), statements: Vec::new() }
+        );
+        assert_eq!(parse("coproc"), Err(Error::IncompleteFlowControl));
+        assert_eq!(parse("coproc a-b"), Err(Error::InvalidCoprocessName("a-b".into())));
     }
 
     #[test]
@@ -315,6 +644,8 @@ mod tests {
             description: None,
             name:        "bob".into(),
             args:        Vec::default(),
+            variadic:    false,
+            return_type: None,
             statements:  Vec::default(),
         };
         assert_eq!(correct_parse, parsed_if);
@@ -336,6 +667,8 @@ mod tests {
                 KeyBuf { name: "a".into(), kind: Primitive::Str },
                 KeyBuf { name: "b".into(), kind: Primitive::Str },
             ],
+            variadic:    false,
+            return_type: None,
             statements:  Vec::default(),
         };
         assert_eq!(correct_parse, parsed_if);
@@ -352,6 +685,8 @@ mod tests {
                 KeyBuf { name: "a".into(), kind: Primitive::Str },
                 KeyBuf { name: "b".into(), kind: Primitive::Str },
             ],
+            variadic:    false,
+            return_type: None,
             statements:  vec![],
         };
         assert_eq!(correct_parse, parsed_if);
@@ -360,4 +695,17 @@ mod tests {
         let parsed_if = parse("fn bob a b      --bob is a nice function").unwrap();
         assert_eq!(correct_parse, parsed_if);
     }
+
+    #[test]
+    fn block_openers() {
+        assert!(opens_block("for x"));
+        assert!(opens_block("  if echo a) b"));
+        assert!(opens_block("else if"));
+        assert!(opens_block("outer: for x"));
+        assert!(opens_block("time timeout 5s match"));
+        assert!(!opens_block("echo for x"));
+        assert!(!opens_block("end"));
+        assert!(!opens_block("timeout 5s"));
+        assert!(!opens_block("iffy"));
+    }
 }
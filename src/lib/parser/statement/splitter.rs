@@ -29,9 +29,11 @@ pub struct StatementSplitter<'a> {
     math_paren_level:     i8,
     logical:              LogicalOp,
     vbrace:               bool,
+    affix:                bool,
     variable:             bool,
     single_quotes:        bool,
     double_quotes:        bool,
+    triple_quotes:        bool,
 }
 
 impl<'a> StatementSplitter<'a> {
@@ -46,14 +48,42 @@ impl<'a> StatementSplitter<'a> {
             math_paren_level: 0,
             logical: LogicalOp::None,
             vbrace: false,
+            affix: false,
             variable: false,
             single_quotes: false,
             double_quotes: false,
+            triple_quotes: false,
         }
     }
 
+    /// The position in the data at which the next statement starts
+    pub const fn position(&self) -> usize { self.read }
+
     fn inside_quotes(&self) -> bool { return self.single_quotes || self.double_quotes }
 
+    /// The length of the run of bytes starting the data which leave the state unchanged.
+    ///
+    /// Only the closing quotes matter inside of raw triple single quotes. Inside of other quotes,
+    /// out of any variable, method or arithmetic, only the closing quote, the backslash and the
+    /// sigils do. These are looked for with memchr; elsewhere, the characters of words are
+    /// jumped over.
+    fn plain_run(&self, data: &[u8]) -> usize {
+        if self.single_quotes && self.triple_quotes {
+            memchr::memchr(b'\'', data).unwrap_or(data.len())
+        } else if self.inside_quotes()
+            && !self.variable
+            && !self.vbrace
+            && self.paren_level == 0
+            && self.math_paren_level == 0
+        {
+            let quote = if self.single_quotes { b'\'' } else { b'"' };
+            let end = memchr::memchr3(quote, b'\\', b'$', data).unwrap_or(data.len());
+            memchr::memchr(b'@', &data[..end]).unwrap_or(end)
+        } else {
+            data.iter().position(|&byte| !is_word(byte, self.vbrace)).unwrap_or(data.len())
+        }
+    }
+
     fn get_statement(&self, statement: &'a str) -> StatementVariant<'a> {
         match self.logical {
             LogicalOp::And => StatementVariant::And(statement.trim()),
@@ -63,6 +93,12 @@ impl<'a> StatementSplitter<'a> {
     }
 }
 
+/// Whether the byte is part of a word, which leaves the state of the splitter unchanged. Only
+/// the characters of variable names are inside of `${}`.
+const fn is_word(byte: u8, vbrace: bool) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_' || (byte >= 0x80 && !vbrace)
+}
+
 impl<'a> Iterator for StatementSplitter<'a> {
     type Item = Result<StatementVariant<'a>, Error>;
 
@@ -75,22 +111,51 @@ impl<'a> Iterator for StatementSplitter<'a> {
 
         bytes.peek()?;
 
+        let data = self.data.as_bytes();
         while let Some((i, character)) = bytes.next() {
+            // Runs of bytes which leave the state unchanged, like the letters of words or the
+            // text of quotes, are jumped over at once
+            let run = if skip { 0 } else { self.plain_run(&data[i..]) };
+            if run > 0 {
+                if run > 1 {
+                    let _ = bytes.nth(run - 2);
+                }
+                last = Some(data[i + run - 1]);
+                continue;
+            }
             match character {
                 _ if skip => {
                     skip = false;
                     last = None;
                     continue;
                 }
+                // Triple single quotes are raw: only the closing quotes are meaningful.
+                _ if self.single_quotes && self.triple_quotes => {
+                    if self.data[i..].starts_with("'''") {
+                        let _ = bytes.nth(1);
+                        self.single_quotes = false;
+                        self.triple_quotes = false;
+                    }
+                }
                 b'\\' => skip = true,
                 _ if self.vbrace => {
                     // We are in `${}` or `@{}` block, variable must use
                     // the following charset : [^A-Za-z0-9_:,}]
                     match character {
-                        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b':' | b',' => (),
                         b'}' => {
                             self.vbrace = false;
+                            self.affix = false;
+                        }
+                        // The pattern stripped from, or replaced in, a string variable
+                        _ if self.affix => (),
+                        b'#' | b'%' | b'/'
+                            if self.data[..i].rfind('{').map_or(false, |open| {
+                                open > 0 && data[open - 1] == b'$' && open + 1 < i
+                            }) =>
+                        {
+                            self.affix = true
                         }
+                        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b':' | b',' => (),
                         _ => {
                             if error.is_none() {
                                 error = Some(Error::InvalidCharacter(character as char, i + 1))
@@ -100,11 +165,23 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 }
                 // Toggle quotes and stop matching variables.
                 b'\'' if !self.double_quotes => {
+                    if !self.single_quotes && self.data[i..].starts_with("'''") {
+                        let _ = bytes.nth(1);
+                        self.triple_quotes = true;
+                    }
                     self.single_quotes = !self.single_quotes;
                     self.variable = false;
                 }
                 b'"' if !self.single_quotes => {
-                    self.double_quotes = !self.double_quotes;
+                    if self.data[i..].starts_with("\"\"\"")
+                        && (!self.double_quotes || self.triple_quotes)
+                    {
+                        let _ = bytes.nth(1);
+                        self.triple_quotes = !self.double_quotes;
+                        self.double_quotes = !self.double_quotes;
+                    } else if !self.triple_quotes {
+                        self.double_quotes = !self.double_quotes;
+                    }
                     self.variable = false;
                 }
                 // square brackets
@@ -114,6 +191,12 @@ impl<'a> Iterator for StatementSplitter<'a> {
                 b']' if !self.inside_quotes() => {
                     self.square_bracket_level -= 1;
                 }
+                // Process substitution
+                b'<' | b'>'
+                    if !self.inside_quotes() && self.data.as_bytes().get(i + 1) == Some(&b'(') =>
+                {
+                    self.variable = true
+                }
                 // Array expansion
                 b'@' | b'$' => self.variable = true,
                 b'{' if [Some(b'$'), Some(b'@')].contains(&last) => self.vbrace = true,
@@ -123,6 +206,12 @@ impl<'a> Iterator for StatementSplitter<'a> {
                     self.paren_level -= 1;
                 }
                 b'(' if self.variable => self.paren_level += 1,
+                // The groups of the extended globs, like !(*.txt)
+                b'(' if !self.inside_quotes()
+                    && matches!(last, Some(b'?' | b'*' | b'+' | b'!')) =>
+                {
+                    self.paren_level += 1
+                }
                 b'(' if error.is_none() && !self.inside_quotes() => {
                     error = Some(Error::InvalidCharacter(character as char, i + 1))
                 }
@@ -361,6 +450,29 @@ fn syntax_errors() {
     assert_eq!(results.len(), 1);
 }
 
+#[test]
+fn triple_quotes() {
+    let command = "echo '''it's (raw); \\'''; echo \"\"\"say \"$(echo hi)\"; \"\"\"";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("echo '''it's (raw); \\'''")));
+    assert_eq!(results[1], Ok(StatementVariant::Default("echo \"\"\"say \"$(echo hi)\"; \"\"\"")));
+    assert_eq!(results.len(), 2);
+
+    let command = "echo '''one 'two''";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Err(Error::UnterminatedSingleQuotes));
+    assert_eq!(results.len(), 1);
+}
+
+#[test]
+fn process_substitution() {
+    let command = "diff <(ls a; ls b) >(cat); echo '<(' > out";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("diff <(ls a; ls b) >(cat)")));
+    assert_eq!(results[1], Ok(StatementVariant::Default("echo '<(' > out")));
+    assert_eq!(results.len(), 2);
+}
+
 #[test]
 fn arithmetic() {
     let command = "$((3 + 3))";
@@ -456,6 +568,15 @@ fn braced_variables() {
     let results = StatementSplitter::new(command).collect::<Vec<_>>();
     assert_eq!(results[0], Ok(StatementVariant::Default(command)));
     assert_eq!(results.len(), 1);
+
+    let command = "echo ${path##*/} ${name//[-.]/_} ${file%.tar*}; ls";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default(&command[..command.len() - 4])));
+    assert_eq!(results.len(), 2);
+
+    let command = "echo @{list#a}";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Err(Error::InvalidCharacter('#', 12)));
 }
 
 #[test]
@@ -497,3 +618,40 @@ fn variants() {
     assert_eq!(results[4], Ok(StatementVariant::Default(r#"echo "Have a good day!""#)));
     assert_eq!(results.len(), 5);
 }
+
+#[test]
+fn plain_runs() {
+    let mut splitter = StatementSplitter::new("");
+    assert_eq!(splitter.plain_run(b"abc_9$x"), 5);
+    assert_eq!(splitter.plain_run("\u{e9}t\u{e9};".as_bytes()), 5);
+    splitter.vbrace = true;
+    assert_eq!(splitter.plain_run("\u{e9};".as_bytes()), 0);
+    splitter.vbrace = false;
+    splitter.single_quotes = true;
+    assert_eq!(splitter.plain_run(b"a;b (c) & d\\'"), 11);
+    assert_eq!(splitter.plain_run(b"a;b @x'"), 4);
+    splitter.variable = true;
+    assert_eq!(splitter.plain_run(b"a;b @x'"), 1);
+    splitter.variable = false;
+    splitter.triple_quotes = true;
+    assert_eq!(splitter.plain_run(b"a;b (c)'''"), 7);
+    splitter.single_quotes = false;
+    splitter.triple_quotes = false;
+    splitter.double_quotes = true;
+    assert_eq!(splitter.plain_run(b"it's; a $x\""), 8);
+
+    let command = "echo ${long_name} na\u{ef}ve_words&&echo '''a;b''' done";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(results[0], Ok(StatementVariant::Default("echo ${long_name} na\u{ef}ve_words")));
+    assert_eq!(results[1], Ok(StatementVariant::And("echo '''a;b''' done")));
+    assert_eq!(results.len(), 2);
+
+    let command = "echo 'a; (b) && c' \"d; $(echo ')'; e) \\\" f\"; echo \"@{g}\"";
+    let results = StatementSplitter::new(command).collect::<Vec<_>>();
+    assert_eq!(
+        results[0],
+        Ok(StatementVariant::Default("echo 'a; (b) && c' \"d; $(echo ')'; e) \\\" f\""))
+    );
+    assert_eq!(results[1], Ok(StatementVariant::Default("echo \"@{g}\"")));
+    assert_eq!(results.len(), 2);
+}
@@ -4,6 +4,8 @@ use std::{iter::Peekable, str};
 enum Quotes {
     Single,
     Double,
+    TripleSingle,
+    TripleDouble,
     None,
 }
 
@@ -24,6 +26,7 @@ pub struct Terminator<I: Iterator<Item = u8>> {
     array:      usize,
     skip_next:  bool,
     quotes:     Quotes,
+    quote_run:  u8,
     terminated: bool,
     and_or:     bool,
     whitespace: bool,
@@ -93,12 +96,11 @@ impl<I: Iterator<Item = u8>> Iterator for Terminator<I> {
 
         if self.skip_next {
             self.skip_next = false;
-        } else if self.quotes != Quotes::None && next != Some(b'\\') {
-            match (next, &self.quotes) {
-                (Some(b'\''), Quotes::Single) | (Some(b'"'), Quotes::Double) => {
-                    self.quotes = Quotes::None;
-                }
-                _ => (),
+        } else if self.quotes != Quotes::None
+            && (next != Some(b'\\') || self.quotes == Quotes::TripleSingle)
+        {
+            if let Some(character) = next {
+                self.handle_quoted(character);
             }
         } else if let Some(character) = next {
             next = self.handle_char(character, prev_whitespace);
@@ -140,13 +142,19 @@ impl<I: Iterator<Item = u8>> Terminator<I> {
         match character {
             b'\'' => {
                 self.quotes = Quotes::Single;
+                self.quote_run = 1;
                 Some(b'\'')
             }
             b'"' => {
                 self.quotes = Quotes::Double;
+                self.quote_run = 1;
                 Some(b'"')
             }
-            b'(' if self.inner.prev() == Some(&b'$') || self.inner.prev() == Some(&b'@') => {
+            b'(' if matches!(
+                self.inner.prev(),
+                Some(&b'$') | Some(&b'@') | Some(&b'<') | Some(&b'>')
+            ) =>
+            {
                 self.subshell += 1;
                 Some(b'(')
             }
@@ -201,6 +209,31 @@ impl<I: Iterator<Item = u8>> Terminator<I> {
         }
     }
 
+    /// Track the closing quotes of a quoted string. Two quotes directly followed by a third one
+    /// open a triple-quoted string, which only ends at the next three consecutive quotes.
+    fn handle_quoted(&mut self, character: u8) {
+        let (quote, triple) = match self.quotes {
+            Quotes::Single | Quotes::TripleSingle => (b'\'', Quotes::TripleSingle),
+            _ => (b'"', Quotes::TripleDouble),
+        };
+        if character != quote {
+            self.quote_run = 0;
+            return;
+        }
+
+        self.quote_run += 1;
+        if self.quotes == triple {
+            if self.quote_run == 3 {
+                self.quotes = Quotes::None;
+            }
+        } else if self.quote_run == 3 {
+            self.quotes = triple;
+            self.quote_run = 0;
+        } else if self.quote_run != 2 || self.inner.peek() != Some(&quote) {
+            self.quotes = Quotes::None;
+        }
+    }
+
     /// Create a new reader on the provided input
     pub fn new(inner: I) -> Self {
         Self {
@@ -208,6 +241,7 @@ impl<I: Iterator<Item = u8>> Terminator<I> {
             array:      0,
             skip_next:  false,
             quotes:     Quotes::None,
+            quote_run:  0,
             terminated: false,
             and_or:     false,
             whitespace: false,
@@ -298,6 +332,20 @@ mod testing {
         );
     }
 
+    #[test]
+    fn terminate_triple_quotes_over_several_lines() {
+        let input =
+            "let sql = '''\nSELECT 'a';\n'''\necho \"\"\"it's \"quoted\"\n\"\"\"\necho done";
+        assert_serveral_terminations(
+            input,
+            vec![
+                "let sql = '''\nSELECT 'a';\n'''",
+                "echo \"\"\"it's \"quoted\"\n\"\"\"",
+                "echo done",
+            ],
+        );
+    }
+
     fn assert_serveral_terminations(input: &str, expected: Vec<&str>) {
         let stmts =
             input.bytes().batching(|lines| Terminator::new(lines).terminate()).collect::<Vec<_>>();
@@ -64,12 +64,17 @@ impl<'b> Shell<'b> {
     /// Export a variable to the process environment given a binding
     pub fn export(&mut self, action: &ExportAction) -> Status {
         match action {
-            ExportAction::Assign(ref keys, op, ref vals) => {
+            ExportAction::Assign(ref keys, op, ref vals)
+            | ExportAction::Const(ref keys, op, ref vals) => {
                 let actions = AssignmentActions::new(keys, *op, vals);
+                let constant = matches!(action, ExportAction::Const(..));
 
                 for action in actions {
                     let err = action.map_err(|e| e.to_string()).and_then(|act| {
                         let Action(key, operator, expression) = act;
+                        if self.constants.contains(key.name) {
+                            return Err(format!("cannot assign to the constant `{}`", key.name));
+                        }
                         value_check(self, expression, &key.kind)
                             .map_err(|e| format!("{}: {}", key.name, e))
                             // TODO: handle operators here in the same way as local
@@ -90,6 +95,11 @@ impl<'b> Shell<'b> {
                                     key.name, key.kind
                                 )),
                             })
+                            .map(|()| {
+                                if constant {
+                                    self.constants.insert(key.name.into());
+                                }
+                            })
                     });
 
                     if let Err(why) = err {
@@ -123,6 +133,7 @@ impl<'b> Shell<'b> {
     pub(crate) fn calculate<'a>(
         &mut self,
         actions: AssignmentActions<'a>,
+        scoping: Scoping,
     ) -> Result<Vec<(Key<'a>, Value<Rc<Function>>)>, String> {
         let mut backup: Vec<_> = Vec::with_capacity(4);
         for action in actions {
@@ -143,6 +154,11 @@ impl<'b> Shell<'b> {
                 continue;
             }
 
+            let global = matches!(scoping, Scoping::Global | Scoping::Constant);
+            if self.assigns_constant(key.name, global) {
+                return Err(format!("cannot assign to the constant `{}`", key.name));
+            }
+
             let rhs = value_check(self, expression, &key.kind)
                 .map_err(|why| format!("{}: {}", key.name, why))?;
 
@@ -184,22 +200,49 @@ impl<'b> Shell<'b> {
                 Status::SUCCESS
             }
             LocalAction::Assign(ref keys, op, ref vals) => {
-                let actions = AssignmentActions::new(keys, *op, vals);
-                if let Err(why) = self.calculate(actions).and_then(|apply| {
-                    for (key, value) in apply {
-                        self.assign(&key, value)?
-                    }
-                    Ok(())
-                }) {
-                    Status::error(format!("ion: assignment error: {}", why))
-                } else {
-                    Status::SUCCESS
-                }
+                self.assign_all(keys, *op, vals, Scoping::Nearest)
+            }
+            LocalAction::Local(ref keys, op, ref vals) => {
+                self.assign_all(keys, *op, vals, Scoping::Function)
             }
+            LocalAction::Global(ref keys, op, ref vals) => {
+                self.assign_all(keys, *op, vals, Scoping::Global)
+            }
+            LocalAction::Const(ref keys, op, ref vals) => {
+                self.assign_all(keys, *op, vals, Scoping::Constant)
+            }
+        }
+    }
+
+    fn assign_all(&mut self, keys: &str, op: Operator, vals: &str, scoping: Scoping) -> Status {
+        let actions = AssignmentActions::new(keys, op, vals);
+        if let Err(why) = self.calculate(actions, scoping).and_then(|apply| {
+            for (key, value) in apply {
+                self.assign(&key, value, scoping)?
+            }
+            Ok(())
+        }) {
+            Status::error(format!("ion: assignment error: {}", why))
+        } else {
+            Status::SUCCESS
         }
     }
 }
 
+/// The scope in which an assignment stores its variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Scoping {
+    /// `let`: update the variable visible from the function, or else create it in the current
+    /// scope
+    Nearest,
+    /// `local`: create the variable in the scope of the function, shadowing the outer ones
+    Function,
+    /// `global`: create or update the variable in the global scope
+    Global,
+    /// `let const`: create the variable in the global scope, which cannot be assigned again
+    Constant,
+}
+
 // This should logically be a method over operator, but Value is only accessible in the main repo
 // TODO: too much allocations occur over here. We need to expand variables before they get
 // parsed
@@ -0,0 +1,52 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use thiserror::Error;
+
+/// A handle to stop a shell from another thread, such as the event loop of an embedder.
+///
+/// Clones of a token share its state: cancelling any of them cancels the shell given one with
+/// [`Shell::set_cancellation`](crate::Shell::set_cancellation).
+///
+/// ```
+/// use ion_shell::{CancellationToken, Interruption, IonError, Shell};
+///
+/// let mut shell = Shell::new();
+/// let token = CancellationToken::new();
+/// shell.set_cancellation(Some(token.clone()));
+/// token.cancel();
+/// assert!(matches!(
+///     shell.execute_command("echo unreachable".as_bytes()),
+///     Err(IonError::Interrupted(Interruption::Cancelled))
+/// ));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a token which is not cancelled yet
+    #[must_use]
+    pub fn new() -> Self { Self::default() }
+
+    /// Stop the shell at the next statement, killing the commands running in the foreground
+    pub fn cancel(&self) { self.0.store(true, Ordering::SeqCst); }
+
+    /// Make the token usable again, once the shell it cancelled has stopped
+    pub fn reset(&self) { self.0.store(false, Ordering::SeqCst); }
+
+    /// Whether the token was cancelled
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool { self.0.load(Ordering::SeqCst) }
+}
+
+/// Why the execution was stopped before its end
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interruption {
+    /// The cancellation token of the shell was cancelled
+    #[error("the execution was cancelled")]
+    Cancelled,
+    /// The deadline of the shell was reached
+    #[error("the deadline of the execution was reached")]
+    DeadlineExceeded,
+}
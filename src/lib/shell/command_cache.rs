@@ -0,0 +1,108 @@
+use std::{
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path)
+        .map_or(false, |metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// The executable files named after the command, in the order of the directories of PATH, or the
+/// command itself if it is a path
+pub(crate) fn executables(command: &str) -> Vec<PathBuf> {
+    if command.contains('/') {
+        let path = PathBuf::from(command);
+        return if is_executable(&path) { vec![path] } else { Vec::new() };
+    }
+    let paths = env::var_os("PATH").unwrap_or_else(|| "/bin".into());
+    env::split_paths(&paths)
+        .map(|path| path.join(command))
+        .filter(|executable| is_executable(executable))
+        .collect()
+}
+
+/// The paths of the programs run by the shell, so that the directories of PATH are searched once
+/// for each command rather than every time it runs. The cache is emptied when PATH changes, and
+/// a path whose file is no longer executable is searched again.
+#[derive(Debug, Default)]
+pub struct CommandCache {
+    /// The PATH the commands were searched in
+    path:     Option<OsString>,
+    /// The path of each command, and the number of times it was looked up
+    commands: HashMap<String, (PathBuf, usize)>,
+}
+
+impl CommandCache {
+    /// Empty the cache if PATH changed since the commands were searched
+    fn check_path(&mut self) {
+        let path = env::var_os("PATH");
+        if path != self.path {
+            self.commands.clear();
+            self.path = path;
+        }
+    }
+
+    /// The path of the program the command runs, or `None` if there is none, or if the command
+    /// is a path itself
+    pub fn resolve(&mut self, command: &str) -> Option<PathBuf> {
+        if command.contains('/') {
+            return None;
+        }
+        self.check_path();
+        if let Some((path, hits)) = self.commands.get_mut(command) {
+            if is_executable(path) {
+                *hits += 1;
+                return Some(path.clone());
+            }
+        }
+        let path = executables(command).into_iter().next()?;
+        self.commands.insert(command.into(), (path.clone(), 1));
+        Some(path)
+    }
+
+    /// Forget the path of the command, returning whether it was cached
+    pub fn forget(&mut self, command: &str) -> bool { self.commands.remove(command).is_some() }
+
+    /// Forget the paths of every command
+    pub fn clear(&mut self) { self.commands.clear() }
+
+    /// The cached commands, sorted by name, with their paths and the number of times they were
+    /// looked up
+    pub fn entries(&mut self) -> Vec<(&str, &Path, usize)> {
+        self.check_path();
+        let mut entries: Vec<_> = self
+            .commands
+            .iter()
+            .map(|(command, (path, hits))| (command.as_str(), path.as_path(), *hits))
+            .collect();
+        entries.sort_unstable();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_commands() {
+        let mut cache = CommandCache::default();
+        let path = cache.resolve("sh").unwrap();
+        assert_eq!(cache.resolve("sh"), Some(path.clone()));
+        assert_eq!(cache.entries(), vec![("sh", path.as_path(), 2)]);
+        assert_eq!(cache.resolve("ion-no-such-command"), None);
+        assert_eq!(cache.resolve("./sh"), None);
+        assert!(cache.forget("sh"));
+        assert!(!cache.forget("sh"));
+        assert_eq!(
+            executables("testing/executable_file"),
+            vec![PathBuf::from("testing/executable_file")]
+        );
+        assert_eq!(executables("testing/empty_file"), Vec::<PathBuf>::new());
+    }
+}
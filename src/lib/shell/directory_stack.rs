@@ -139,11 +139,22 @@ impl DirectoryStack {
     pub fn switch_to_previous_directory(&mut self) -> Result<(), DirStackError> {
         let prev = self.get_previous_dir().ok_or(DirStackError::NoPreviousDir)?;
 
-        self.popd(0);
         println!("{}", prev);
+        // Going back to the directory under the top swaps them, so that both stay on the stack
+        if self.dirs.get(1).map_or(false, |dir| *dir == Path::new(&prev)) {
+            return self.swap(1);
+        }
+        self.popd(0);
         self.change_and_push_dir(Path::new(&prev))
     }
 
+    // cd +<num> and cd -<num>
+    pub fn move_to_top(&mut self, index: usize) -> Result<(), DirStackError> {
+        let dir = self.dirs.remove(index).ok_or(DirStackError::OutOfRange { index })?;
+        self.dirs.push_front(dir);
+        self.set_current_dir_by_index(0)
+    }
+
     pub fn switch_to_home_directory(&mut self) -> Result<(), DirStackError> {
         match env::var_os("HOME") {
             Some(home) => self.change_and_push_dir(Path::new(&home)),
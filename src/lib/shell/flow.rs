@@ -1,28 +1,42 @@
 use super::{
-    flow_control::{Block, Case, ElseIf, Function, IfMode, Statement},
+    flow_control::{Block, Case, ElseIf, Function, FunctionError, IfMode, Statement},
+    generators,
     pipe_exec::PipelineError,
-    signals, Shell,
+    reporting::{self, Collapsing},
+    select, signals, Shell,
 };
 use crate::{
-    assignments::is_array,
+    assignments::{is_array, value_check},
     builtins::Status,
     expansion::{
-        pipelines::{PipeItem, Pipeline},
+        is_array_expression,
+        pipelines::{PipeItem, PipeType, Pipeline},
         Expander, ForValueExpression,
     },
-    parser::{parse_and_validate, StatementSplitter, Terminator},
-    shell::{IonError, Job, Value},
+    parser::{
+        lexers::ArgumentSplitter, opens_block, parse_and_validate, Diagnostic, StatementSplitter,
+        Terminator,
+    },
+    shell::{Interruption, IonError, Job, Trap, Value},
     types,
 };
 use itertools::Itertools;
 use nix::unistd::Pid;
-use std::{rc::Rc, time::SystemTime};
+use regex::Regex;
+use std::{
+    cell::Cell,
+    ops::Range,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime},
+};
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Condition {
-    Continue,
-    Break,
+    /// Go on with the loop with the label, or the innermost loop
+    Continue(Option<types::Str>),
+    /// Leave the loop with the label, or the innermost loop
+    Break(Option<types::Str>),
     NoOp,
     Return,
 }
@@ -61,10 +75,31 @@ pub enum BlockError {
     /// Found a continue outside a loop
     #[error("found Continue without loop body")]
     UnmatchedContinue,
+    /// A catch was found without its corresponding try block
+    #[error("found Catch without Try block")]
+    LoneCatch,
+    /// Multiple catch were found for the same try
+    #[error("Catch block already exists")]
+    MultipleCatch,
 
     /// Unclosed block
     #[error("expected end block for `{0}`")]
     UnclosedBlock(String),
+    /// The block closed by an `end &` can not run as a job
+    #[error("`{0}` can not run in the background")]
+    InvalidBackground(String),
+    /// The regular expression of a case could not be compiled
+    #[error("invalid regular expression '{0}' in case: {1}")]
+    InvalidCasePattern(String, String),
+    /// The duration of a timeout is not a number of seconds, minutes, hours or days
+    #[error("invalid duration '{0}' for timeout")]
+    InvalidTimeout(String),
+    /// The menu of a select loop could not be shown, or the choice could not be read
+    #[error("could not choose from the select menu: {0}")]
+    SelectMenu(String),
+    /// The variable of a loop is a constant
+    #[error("cannot assign to the constant `{0}`")]
+    ConstantVariable(String),
 }
 
 impl<'a> Shell<'a> {
@@ -74,14 +109,37 @@ impl<'a> Shell<'a> {
     ) -> std::result::Result<(), BlockError> {
         let block = match block.last_mut().expect("Should not insert statement if stack is empty!")
         {
-            Statement::Time(inner) => inner,
+            Statement::Time(inner) | Statement::Timeout(_, inner) | Statement::Defer(inner) => {
+                inner
+            }
             top_block => top_block,
         };
 
         match block {
             Statement::Function { ref mut statements, .. }
-            | Statement::For { ref mut statements, .. }
-            | Statement::While { ref mut statements, .. } => statements.push(statement),
+            | Statement::Select { ref mut statements, .. }
+            | Statement::Coproc { ref mut statements, .. } => statements.push(statement),
+            // The statements after an `else` run once the loop completes without a `break`
+            Statement::For { ref mut statements, ref mut otherwise, .. }
+            | Statement::While { ref mut statements, ref mut otherwise, .. } => {
+                match (statement, otherwise) {
+                    (Statement::Else, Some(_)) => return Err(BlockError::MultipleElse),
+                    (Statement::Else, otherwise) => *otherwise = Some(Vec::new()),
+                    (statement, Some(otherwise)) => otherwise.push(statement),
+                    (statement, None) => statements.push(statement),
+                }
+            }
+            Statement::Try { ref mut statements, ref mut binding, ref mut catch } => {
+                match (statement, catch) {
+                    (Statement::Catch(_), Some(_)) => return Err(BlockError::MultipleCatch),
+                    (Statement::Catch(name), catch) => {
+                        *binding = name;
+                        *catch = Some(Vec::new());
+                    }
+                    (statement, Some(catch)) => catch.push(statement),
+                    (statement, None) => statements.push(statement),
+                }
+            }
             Statement::Match { ref mut cases, .. } => {
                 if let Statement::Case(case) = statement {
                     cases.push(case)
@@ -131,6 +189,9 @@ impl<'a> Shell<'a> {
             // Push new block to stack
             Statement::For { .. }
             | Statement::While { .. }
+            | Statement::Select { .. }
+            | Statement::Try { .. }
+            | Statement::Coproc { .. }
             | Statement::Match { .. }
             | Statement::If { .. }
             | Statement::Function { .. } => {
@@ -151,18 +212,18 @@ impl<'a> Shell<'a> {
                 block.push(statement);
                 Ok(None)
             }
-            Statement::End => {
+            Statement::End(pipe) => {
                 match block.len() {
                     0 => Err(BlockError::UnmatchedEnd),
                     // Ready to return the complete block
-                    1 => Ok(block.pop()),
+                    1 => block.pop().map(|statement| in_background(statement, pipe)).transpose(),
                     // Merge back the top block into the previous one
                     _ => {
                         let last_statement = block.pop().unwrap();
                         if let Statement::Case(_) = last_statement {
                             Self::insert_into_block(block, last_statement)?;
                             // Merge last Case back and pop off Match too
-                            let match_stm = block.pop().unwrap();
+                            let match_stm = in_background(block.pop().unwrap(), pipe)?;
                             if block.is_empty() {
                                 Ok(Some(match_stm))
                             } else {
@@ -170,7 +231,7 @@ impl<'a> Shell<'a> {
                                 Ok(None)
                             }
                         } else {
-                            Self::insert_into_block(block, last_statement)?;
+                            Self::insert_into_block(block, in_background(last_statement, pipe)?)?;
                             Ok(None)
                         }
                     }
@@ -203,7 +264,7 @@ impl<'a> Shell<'a> {
                         }
                         _ => false,
                     },
-                    Statement::While { ref mut expression, ref statements } => {
+                    Statement::While { ref mut expression, ref statements, .. } => {
                         if statements.is_empty() {
                             expression.push(statement.clone());
                             true
@@ -227,14 +288,27 @@ impl<'a> Shell<'a> {
                     Ok(Some(Statement::Time(inner)))
                 }
             }
+            Statement::Timeout(duration, inner) => {
+                if inner.is_block() {
+                    block.push(Statement::Timeout(duration, inner));
+                    Ok(None)
+                } else {
+                    Ok(Some(Statement::Timeout(duration, inner)))
+                }
+            }
+            Statement::Defer(inner) if inner.is_block() => {
+                block.push(Statement::Defer(inner));
+                Ok(None)
+            }
             _ if block.is_empty() => {
                 // Filter out toplevel statements that should produce an error
                 // otherwise return the statement for immediat execution
                 match statement {
                     Statement::ElseIf(_) => Err(BlockError::LoneElseIf),
                     Statement::Else => Err(BlockError::LoneElse),
-                    Statement::Break => Err(BlockError::UnmatchedBreak),
-                    Statement::Continue => Err(BlockError::UnmatchedContinue),
+                    Statement::Break(_) => Err(BlockError::UnmatchedBreak),
+                    Statement::Continue(_) => Err(BlockError::UnmatchedContinue),
+                    Statement::Catch(_) => Err(BlockError::LoneCatch),
                     // Toplevel statement, return to execute immediately
                     _ => Ok(Some(statement)),
                 }
@@ -256,14 +330,14 @@ impl<'a> Shell<'a> {
         failure: &[Statement],
     ) -> Result {
         // Try execute success branch
-        self.execute_statements(expression)?;
+        self.execute_condition(expression)?;
         if self.previous_status.is_success() {
             return self.execute_statements(success);
         }
 
         // Try to execute else_if branches
         for ElseIf { expression, success } in else_if {
-            self.execute_statements(expression)?;
+            self.execute_condition(expression)?;
 
             if self.previous_status.is_success() {
                 return self.execute_statements(success);
@@ -277,10 +351,17 @@ impl<'a> Shell<'a> {
     /// specified in the range.
     fn execute_for(
         &mut self,
+        label: Option<&types::Str>,
         variables: &[types::Str],
         values: &[types::Str],
         statements: &[Statement],
+        otherwise: Option<&[Statement]>,
     ) -> Result {
+        if let Some(constant) =
+            variables.iter().find(|name| self.assigns_constant(name.as_str(), false))
+        {
+            return Err(BlockError::ConstantVariable(constant.to_string()).into());
+        }
         macro_rules! set_vars_then_exec {
             ($chunk:expr, $def:expr) => {
                 for (key, value) in variables.iter().zip($chunk.chain(::std::iter::repeat($def))) {
@@ -289,17 +370,29 @@ impl<'a> Shell<'a> {
                     }
                 }
 
-                match self.execute_statements(statements)? {
-                    Condition::Break => break,
-                    Condition::Return => return Ok(Condition::Return),
-                    Condition::Continue | Condition::NoOp => (),
+                if let Some(condition) =
+                    Self::after_iteration(self.execute_statements(statements)?, label)
+                {
+                    return Ok(condition);
                 }
             };
         }
 
         let default = types::Str::new();
+        let _collapsing = self.opts.collapse_errors.then(Collapsing::new);
 
-        match ForValueExpression::new(values, self)? {
+        // The values of a generator, and the lines of a streamed command, are read as the loop
+        // consumes them, as those of a range
+        let expression = match self.generator_call(values)? {
+            Some((function, args)) => {
+                ForValueExpression::Range(Box::new(self.fork_generator(&function, &args)?))
+            }
+            None => match generators::streamed_command(values) {
+                Some(command) => ForValueExpression::Range(Box::new(self.fork_stream(command)?)),
+                None => ForValueExpression::new(values, self)?,
+            },
+        };
+        match expression {
             ForValueExpression::Multiple(values) => {
                 for chunk in &values.iter().chunks(variables.len()) {
                     set_vars_then_exec!(chunk, &default);
@@ -310,7 +403,11 @@ impl<'a> Shell<'a> {
                     self.variables_mut().set(&variables[0], value);
                 }
 
-                self.execute_statements(statements)?;
+                if let Some(condition) =
+                    Self::after_iteration(self.execute_statements(statements)?, label)
+                {
+                    return Ok(condition);
+                }
             }
             ForValueExpression::Range(range) => {
                 for chunk in &range.chunks(variables.len()) {
@@ -319,29 +416,186 @@ impl<'a> Shell<'a> {
             }
         };
 
-        Ok(Condition::NoOp)
+        match otherwise {
+            Some(otherwise) => self.execute_statements(otherwise),
+            None => Ok(Condition::NoOp),
+        }
     }
 
     /// Executes all of the statements within a while block until a certain
     /// condition is met.
-    fn execute_while(&mut self, expression: &[Statement], statements: &[Statement]) -> Result {
+    fn execute_while(
+        &mut self,
+        label: Option<&types::Str>,
+        expression: &[Statement],
+        statements: &[Statement],
+        otherwise: Option<&[Statement]>,
+    ) -> Result {
+        let _collapsing = self.opts.collapse_errors.then(Collapsing::new);
         loop {
-            self.execute_statements(expression)?;
+            self.execute_condition(expression)?;
             if self.previous_status.is_failure() {
-                return Ok(Condition::NoOp);
+                return match otherwise {
+                    Some(otherwise) => self.execute_statements(otherwise),
+                    None => Ok(Condition::NoOp),
+                };
+            }
+
+            if let Some(condition) =
+                Self::after_iteration(self.execute_statements(statements)?, label)
+            {
+                return Ok(condition);
+            }
+        }
+    }
+
+    /// Run the statements with the variable set to the value chosen from a menu of the values,
+    /// until the loop is left or the input ends. The menu is prompted for with `$PS3`.
+    fn execute_select(
+        &mut self,
+        label: Option<&types::Str>,
+        variable: &str,
+        values: &[types::Str],
+        statements: &[Statement],
+    ) -> Result {
+        if self.assigns_constant(variable, false) {
+            return Err(BlockError::ConstantVariable(variable.into()).into());
+        }
+        let mut options = Vec::new();
+        for value in values {
+            options.extend(self.expand_string(value)?);
+        }
+        let _collapsing = self.opts.collapse_errors.then(Collapsing::new);
+
+        loop {
+            let prompt = self.variables.get_str("PS3").unwrap_or_else(|_| "#? ".into());
+            let index = match select::choose(&options, &prompt) {
+                Ok(Some(index)) => index,
+                Ok(None) => return Ok(Condition::NoOp),
+                Err(why) => return Err(BlockError::SelectMenu(why.to_string()).into()),
+            };
+            self.variables_mut().set(variable, options[index].clone());
+            if let Some(condition) =
+                Self::after_iteration(self.execute_statements(statements)?, label)
+            {
+                return Ok(condition);
+            }
+        }
+    }
+
+    /// Whether a loop goes on after an iteration ended with the condition, or the condition with
+    /// which it stops instead. The `break` and `continue` targeting an outer loop stop this one.
+    fn after_iteration(condition: Condition, label: Option<&types::Str>) -> Option<Condition> {
+        match condition {
+            Condition::NoOp => None,
+            Condition::Continue(target) if target.is_none() || target.as_ref() == label => None,
+            Condition::Break(target) if target.is_none() || target.as_ref() == label => {
+                Some(Condition::NoOp)
             }
+            condition => Some(condition),
+        }
+    }
 
-            // Cloning is needed so the statement can be re-iterated again if needed.
-            match self.execute_statements(statements)? {
-                Condition::Break => return Ok(Condition::NoOp),
-                Condition::Return => return Ok(Condition::Return),
-                Condition::Continue | Condition::NoOp => (),
+    /// Run the statements of a try block until one of them fails, by exiting with a failure
+    /// status or with an error, then run the catch block with the failure set in the variable
+    /// given to it. A statement followed by `&&` or `||` is only checked at the end of the chain.
+    fn execute_try(
+        &mut self,
+        statements: &[Statement],
+        binding: Option<&types::Str>,
+        catch: Option<&[Statement]>,
+    ) -> Result {
+        self.variables.new_scope(false);
+        let mut failure = None;
+        let mut condition = Ok(Condition::NoOp);
+        for (index, statement) in statements.iter().enumerate() {
+            match self.execute_statement(statement) {
+                Ok(Condition::NoOp) => {
+                    let chained = matches!(
+                        statements.get(index + 1),
+                        Some(Statement::And(_) | Statement::Or(_))
+                    );
+                    if !chained && is_command(statement) && self.previous_status.is_failure() {
+                        failure =
+                            Some((self.previous_status, command_source(statement), String::new()));
+                        break;
+                    }
+                }
+                // Cancellations are not failures of the script
+                Err(IonError::Interrupted(interruption)) => {
+                    condition = Err(interruption.into());
+                    break;
+                }
+                Err(why) => {
+                    let (status, command) = match why {
+                        IonError::PipelineExecutionError(PipelineError::CommandNotFound(
+                            ref command,
+                        )) => (Status::NO_SUCH_COMMAND, command.to_string()),
+                        _ => (Status::COULD_NOT_EXEC, command_source(statement)),
+                    };
+                    failure = Some((status, command, why.to_string()));
+                    break;
+                }
+                result => {
+                    condition = result;
+                    break;
+                }
             }
         }
+        self.variables.pop_scope();
+
+        let (status, command, message) = match failure {
+            Some(failure) => failure,
+            None => return condition,
+        };
+        self.previous_status = status;
+        self.variables.set("?", status);
+        let catch = match catch {
+            Some(catch) => catch,
+            None => return Ok(Condition::NoOp),
+        };
+        self.variables.new_scope(false);
+        if let Some(binding) = binding {
+            let mut map = types::HashMap::with_capacity(3);
+            map.insert("status".into(), Value::from(status));
+            map.insert("command".into(), Value::Str(command.as_str().into()));
+            map.insert("message".into(), Value::Str(message.as_str().into()));
+            self.variables.set(binding, Value::HashMap(map));
+        }
+        let condition = self.execute_statements(catch);
+        self.variables.pop_scope();
+        condition
+    }
+
+    /// Evaluate the value given to `return`, checking it against the return type of the function
+    /// being executed. Without a return type, an integer value is also the exit status.
+    fn return_value_of(
+        &mut self,
+        expression: &str,
+    ) -> std::result::Result<Value<Rc<Function>>, IonError> {
+        if let Some(Some(kind)) = self.return_types.last().cloned() {
+            return value_check(self, expression, &kind)
+                .map_err(|_| FunctionError::InvalidReturnType(kind, expression.into()).into());
+        }
+
+        let values = self.expand_string(expression)?;
+        let value = values.join(" ");
+        if let Ok(status) = value.parse::<i32>() {
+            self.previous_status = Status::from_exit_code(status);
+        }
+        Ok(if is_array_expression(expression) {
+            values.into_iter().map(Value::Str).collect()
+        } else {
+            Value::Str(value.into())
+        })
     }
 
     /// Executes a single statement
     pub fn execute_statement(&mut self, statement: &Statement) -> Result {
+        if let Some(interruption) = self.interruption() {
+            return Err(interruption.into());
+        }
+        self.release_err_trap(Some(statement));
         match statement {
             Statement::Let(action) => {
                 self.previous_status = self.local(action);
@@ -351,14 +605,32 @@ impl<'a> Shell<'a> {
                 self.previous_status = self.export(action);
                 self.variables.set("?", self.previous_status);
             }
-            Statement::While { expression, statements } => {
-                let condition = self.execute_while(expression, statements)?;
+            Statement::While { label, expression, statements, otherwise } => {
+                let condition = self.execute_while(
+                    label.as_ref(),
+                    expression,
+                    statements,
+                    otherwise.as_deref(),
+                )?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
+            }
+            Statement::For { label, variables, values, statements, otherwise } => {
+                let condition = self.execute_for(
+                    label.as_ref(),
+                    variables,
+                    values,
+                    statements,
+                    otherwise.as_deref(),
+                )?;
                 if condition != Condition::NoOp {
                     return Ok(condition);
                 }
             }
-            Statement::For { variables, values, statements } => {
-                let condition = self.execute_for(variables, values, statements)?;
+            Statement::Select { label, variable, values, statements } => {
+                let condition =
+                    self.execute_select(label.as_ref(), variable, values, statements)?;
                 if condition != Condition::NoOp {
                     return Ok(condition);
                 }
@@ -370,16 +642,28 @@ impl<'a> Shell<'a> {
                     return Ok(condition);
                 }
             }
-            Statement::Function { name, args, statements, description } => {
-                self.variables.set(
-                    name,
-                    Value::Function(Rc::new(Function::new(
-                        description.clone(),
-                        name.clone(),
-                        args.to_vec(),
-                        statements.to_vec(),
-                    ))),
+            Statement::Function { name, args, variadic, return_type, statements, description } => {
+                let mut function = Function::new(
+                    description.clone(),
+                    name.clone(),
+                    args.to_vec(),
+                    *variadic,
+                    return_type.clone(),
+                    statements.to_vec(),
                 );
+                // A function defined again with other arguments is overloaded rather than
+                // replaced
+                if let Some(Value::Function(previous)) = self.variables.get(name) {
+                    function = function.overloading(previous);
+                }
+                let function = Value::Function(Rc::new(function));
+                // The functions defined by a function are visible in the whole of it, and only
+                // until it returns
+                if self.return_types.is_empty() {
+                    self.variables.set(name, function);
+                } else {
+                    self.variables.set_local(name, function);
+                }
             }
             Statement::Pipeline(pipeline) => {
                 let (pipeline, statements) = expand_pipeline(self, pipeline)?;
@@ -390,6 +674,7 @@ impl<'a> Shell<'a> {
                     // history.previous_status
                     self.variables_mut().set("?", status);
                     self.previous_status = status;
+                    self.err_trap_pending = status.is_failure() && !self.in_condition;
                 }
                 if !statements.is_empty() {
                     self.execute_statements(&statements)?;
@@ -414,6 +699,12 @@ impl<'a> Shell<'a> {
                     return Ok(condition);
                 }
             }
+            Statement::Timeout(duration, box_statement) => {
+                let condition = self.execute_timeout(duration, box_statement)?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
+            }
             Statement::And(box_statement) => {
                 let condition = if self.previous_status.is_success() {
                     self.execute_statement(box_statement)?
@@ -442,8 +733,34 @@ impl<'a> Shell<'a> {
                 self.previous_status.toggle();
                 self.variables.set("?", self.previous_status);
             }
-            Statement::Break => return Ok(Condition::Break),
-            Statement::Continue => return Ok(Condition::Continue),
+            Statement::Invalid { error, .. } => {
+                reporting::report(format!("ion: {}", error.render()));
+                self.previous_status = Status::COULD_NOT_EXEC;
+                self.variables.set("?", self.previous_status);
+            }
+            Statement::Coproc { name, statements } => {
+                self.previous_status = match self.fork_coprocess(name, statements) {
+                    Ok(()) => Status::SUCCESS,
+                    Err(why) => Status::error(format!("ion: coproc {}: {}", name, why)),
+                };
+                self.variables.set("?", self.previous_status);
+            }
+            Statement::Try { statements, binding, catch } => {
+                let condition = self.execute_try(statements, binding.as_ref(), catch.as_deref())?;
+                if condition != Condition::NoOp {
+                    return Ok(condition);
+                }
+            }
+            Statement::Background { pipe, statement } => {
+                self.previous_status = match self.fork_block(statement, *pipe) {
+                    Ok(()) => Status::SUCCESS,
+                    Err(why) => Status::error(format!("ion: background block: {}", why)),
+                };
+                self.variables.set("?", self.previous_status);
+            }
+            Statement::Defer(statement) => self.defer((**statement).clone()),
+            Statement::Break(label) => return Ok(Condition::Break(label.clone())),
+            Statement::Continue(label) => return Ok(Condition::Continue(label.clone())),
             Statement::Match { expression, cases } => {
                 let condition = self.execute_match(expression, cases)?;
 
@@ -451,37 +768,145 @@ impl<'a> Shell<'a> {
                     return Ok(condition);
                 }
             }
+            Statement::Args { description, args, variadic } => {
+                // The script is named by the first of its arguments, as in its usage
+                let given: Vec<String> = match self.variables.get("args") {
+                    Some(Value::Array(given)) => given.iter().map(ToString::to_string).collect(),
+                    _ => Vec::new(),
+                };
+                let script =
+                    given.first().map_or_else(|| "ion".into(), |name| name.as_str().into());
+                let header = Function::new(
+                    description.clone(),
+                    script,
+                    args.to_vec(),
+                    *variadic,
+                    None,
+                    Vec::new(),
+                );
+                header.bind_script(self, &given)?;
+            }
+            Statement::Yield(expression) => {
+                let values = self.expand_string(expression)?;
+                self.yield_values(values);
+            }
             Statement::Return(expression) => {
-                if let Some(expression) = expression {
-                    let value = self.expand_string(expression.as_ref())?.join(" ");
-                    if let Ok(status) = value.parse::<i32>() {
-                        self.previous_status = Status::from_exit_code(status);
-                    }
-                }
+                self.return_value = match expression {
+                    Some(expression) => Some(self.return_value_of(expression)?),
+                    None => None,
+                };
                 return Ok(Condition::Return);
             }
             _ => {}
         }
-        if let Some(signal) = signals::SignalHandler.next() {
-            let _ = self.handle_signal(signal);
-            Err(IonError::from(PipelineError::Interrupted(Pid::this(), signal)))
-        } else {
-            Ok(Condition::NoOp)
+        match signals::SignalHandler.next() {
+            // A trapped signal runs its command rather than interrupting the execution
+            Some(signal) if self.run_trap(Trap::Signal(signal)) => Ok(Condition::NoOp),
+            Some(signal) => {
+                let _ = self.handle_signal(signal);
+                Err(IonError::from(PipelineError::Interrupted(Pid::this(), signal)))
+            }
+            None => Ok(Condition::NoOp),
         }
     }
 
+    /// Replace the unquoted words of the statement which name global aliases, set with
+    /// `alias -g`, by their values. The statements defining and removing aliases are left as
+    /// they are, so that they can name them.
+    fn expand_global_aliases(&self, statement: String) -> String {
+        let first = ArgumentSplitter::new(&statement).next();
+        if self.global_aliases.is_empty() || matches!(first, None | Some("alias" | "unalias")) {
+            return statement;
+        }
+        let mut expanded = String::with_capacity(statement.len());
+        let mut end = 0;
+        for word in ArgumentSplitter::new(&statement).spanned() {
+            if let Some(value) = self.global_aliases.get(word.text) {
+                expanded.push_str(&statement[end..word.span.start]);
+                expanded.push_str(value);
+                end = word.span.end;
+            }
+        }
+        if end == 0 {
+            return statement;
+        }
+        expanded.push_str(&statement[end..]);
+        expanded
+    }
+
+    /// Executes the condition of an `if` or a `while`, whose failures are not trapped by `ERR`
+    fn execute_condition(&mut self, expression: &[Statement]) -> Result {
+        let in_condition = std::mem::replace(&mut self.in_condition, true);
+        let condition = self.execute_statements(expression);
+        self.in_condition = in_condition;
+        condition
+    }
+
     /// Simply executes all supplied statements.
     pub fn execute_statements(&mut self, statements: &[Statement]) -> Result {
         self.variables.new_scope(false);
+        let condition = self.execute_each(statements);
+        self.variables.pop_scope();
+        condition
+    }
+
+    /// Executes the body of a function, then the statements it deferred, whether it succeeded
+    /// or not. The deferred statements still see the variables of the body.
+    pub(crate) fn execute_deferring(&mut self, statements: &[Statement]) -> Result {
+        self.variables.new_scope(false);
+        self.deferred.push(Vec::new());
+        let condition = self.execute_each(statements);
+        self.leave_deferring_scope();
+        self.variables.pop_scope();
+        condition
+    }
+
+    fn execute_each(&mut self, statements: &[Statement]) -> Result {
         let condition = statements
             .iter()
             .map(|statement| self.execute_statement(statement))
             .find(|condition| !matches!(condition, Ok(Condition::NoOp)))
             .unwrap_or(Ok(Condition::NoOp));
-        self.variables.pop_scope();
+        self.release_err_trap(None);
         condition
     }
 
+    /// Run the statement once the innermost function or script ends, as with `defer`
+    pub(crate) fn defer(&mut self, statement: Statement) {
+        self.deferred.last_mut().expect("the top level defers").push(statement);
+    }
+
+    /// Run the statements deferred by the innermost function or script, the latest first
+    pub(crate) fn leave_deferring_scope(&mut self) {
+        if self.deferred.len() > 1 {
+            let deferred = self.deferred.pop().unwrap();
+            self.execute_deferred(&deferred);
+        }
+    }
+
+    /// Run the statements deferred at the top level of the shell, and by the functions and
+    /// scripts being executed, as the shell exits.
+    pub fn run_deferred(&mut self) {
+        let scopes = std::mem::replace(&mut self.deferred, vec![Vec::new()]);
+        for deferred in scopes.iter().rev() {
+            self.execute_deferred(deferred);
+        }
+    }
+
+    /// The deferred statements change neither the status nor the value returned by the scope
+    /// they end
+    fn execute_deferred(&mut self, deferred: &[Statement]) {
+        let (status, value) = (self.previous_status, self.return_value.take());
+        for statement in deferred.iter().rev() {
+            if let Err(why) = self.execute_statement(statement) {
+                reporting::report(format!("ion: deferred statement: {}", why));
+            }
+        }
+        self.return_value = value;
+        self.previous_status = status;
+        self.variables.set("?", status);
+    }
+
     /// Expand an expression and run a branch based on the value of the
     /// expanded expression
     fn execute_match<T: AsRef<str>>(&mut self, expression: T, cases: &[Case]) -> Result {
@@ -496,13 +921,29 @@ impl<'a> Shell<'a> {
         let is_array = is_array(expression.as_ref());
         let value = self.expand_string(expression.as_ref())?;
         for case in cases.iter() {
-            let is_match = if let Some(v) = &case.value {
-                let v = self.expand_string(v)?;
-                // Anchor to start and end
-                let v = v.into_iter().map(|v| format!("^{}$", v));
-                RegexSet::new(v).ok().map_or(false, |regex| value.iter().all(|v| regex.is_match(v)))
-            } else {
+            let is_match = if case.values.is_empty() {
                 true
+            } else if case.regex {
+                match self.match_regex(&case.values, &value.join(" "))? {
+                    Some(captures) => {
+                        for (name, capture) in captures {
+                            self.variables_mut().set(&name, capture);
+                        }
+                        true
+                    }
+                    None => false,
+                }
+            } else {
+                let mut patterns = Vec::new();
+                for pattern in &case.values {
+                    // Anchor to start and end
+                    patterns.extend(
+                        self.expand_string(pattern)?.into_iter().map(|v| format!("^{}$", v)),
+                    );
+                }
+                RegexSet::new(patterns)
+                    .ok()
+                    .map_or(false, |regex| value.iter().all(|v| regex.is_match(v)))
             };
 
             if is_match {
@@ -555,6 +996,60 @@ impl<'a> Shell<'a> {
         Ok(Condition::NoOp)
     }
 
+    /// Execute the statement until the duration elapsed, after which the commands it runs are
+    /// killed and the status is `Status::TIMED_OUT`. The deadline of the shell, if it comes
+    /// sooner, still applies.
+    fn execute_timeout(&mut self, duration: &str, statement: &Statement) -> Result {
+        let duration = self.expand_string(duration)?.join(" ");
+        let duration =
+            parse_duration(&duration).ok_or_else(|| BlockError::InvalidTimeout(duration))?;
+        let outer = self.deadline;
+        let deadline = Instant::now() + duration;
+        self.deadline = Some(outer.map_or(deadline, |outer| outer.min(deadline)));
+        let result = self.execute_statement(statement);
+        self.deadline = outer;
+        match result {
+            // Only the timeout is handled here, rather than the deadline of the shell
+            Err(IonError::Interrupted(Interruption::DeadlineExceeded))
+                if self.interruption().is_none() =>
+            {
+                self.previous_status = Status::TIMED_OUT;
+                self.variables.set("?", self.previous_status);
+                self.err_trap_pending = !self.in_condition;
+                Ok(Condition::NoOp)
+            }
+            result => result,
+        }
+    }
+
+    /// Search the value with the regular expressions of a case, returning the groups captured by
+    /// the first one found: the numbered groups as `match_N`, and the named groups by their name
+    fn match_regex(
+        &mut self,
+        patterns: &[String],
+        value: &str,
+    ) -> std::result::Result<Option<Vec<(String, String)>>, IonError> {
+        for pattern in patterns {
+            for pattern in self.expand_string(pattern)? {
+                let regex = Regex::new(&pattern).map_err(|why| {
+                    BlockError::InvalidCasePattern(pattern.to_string(), why.to_string())
+                })?;
+                if let Some(found) = regex.captures(value) {
+                    let group = |index| found.get(index).map_or("", |group| group.as_str());
+                    let numbered =
+                        (1..found.len()).map(|index| (format!("match_{}", index), group(index)));
+                    let named = regex.capture_names().enumerate().filter_map(|(index, name)| {
+                        name.map(|name| (name.to_string(), group(index)))
+                    });
+                    return Ok(Some(
+                        numbered.chain(named).map(|(name, text)| (name, text.into())).collect(),
+                    ));
+                }
+            }
+        }
+        Ok(None)
+    }
+
     /// Receives a command and attempts to execute the contents.
     pub fn on_command(
         &mut self,
@@ -563,17 +1058,58 @@ impl<'a> Shell<'a> {
     ) -> std::result::Result<(), IonError> {
         let command_start_time = if set_cmd_duration { Some(SystemTime::now()) } else { None };
 
+        // The rest of a sourced file is skipped once it returned
+        if self.sources.last().map_or(false, |source| source.returned) {
+            return Ok(());
+        }
+        // Count the lines read, for `${status::line_number}`
+        let read = self.sources.last().map_or(0, |source| source.newlines);
+        let (newlines, ended) = (Cell::new(0), Cell::new(true));
+        let command_to_execute = command_to_execute.inspect(|&byte| {
+            newlines.set(newlines.get() + usize::from(byte == b'\n'));
+            ended.set(byte == b'\n');
+        });
         for stmt in command_to_execute.batching(|cmd| Terminator::new(cmd).terminate()) {
+            let stmt = self.expand_global_aliases(stmt);
+            if let Some(source) = self.sources.last_mut() {
+                source.newlines = read + newlines.get();
+                source.line = source.newlines + usize::from(!ended.get());
+            }
             // Go through all of the statements and build up the block stack
             // When block is done return statement for execution.
-            for statement in StatementSplitter::new(&stmt) {
-                let statement = parse_and_validate(statement?)?;
+            let mut statements = StatementSplitter::new(&stmt);
+            let mut start = statements.position();
+            while let Some(statement) = statements.next() {
+                let read = start..statements.position();
+                start = read.end;
+                let statement = match statement.and_then(parse_and_validate) {
+                    Ok(statement) => statement,
+                    Err(err) => {
+                        let span = statement_span(&stmt, read);
+                        let error = Diagnostic::new(err, &stmt).within(span.clone());
+                        // The body of a block which failed to open must not run in its place
+                        if self.opts.err_exit_on_parse || opens_block(&stmt[span.clone()]) {
+                            return Err(error.into());
+                        }
+                        // Keep the invalid statement in place and resume after it
+                        Statement::Invalid { error, source: stmt[span.clone()].into(), span }
+                    }
+                };
                 if let Some(stm) = Self::insert_statement(&mut self.flow_control, statement)? {
-                    self.execute_statement(&stm)?;
+                    if self.execute_statement(&stm)? == Condition::Return {
+                        // Only a sourced file stops, rather than the script or the prompt which
+                        // sourced it, at the root of the sources
+                        if let [_, .., source] = &mut self.sources[..] {
+                            source.returned = true;
+                            return Ok(());
+                        }
+                    }
                 }
             }
         }
 
+        self.release_err_trap(None);
+
         if let Some(start_time) = command_start_time {
             if let Ok(elapsed_time) = start_time.elapsed() {
                 self.variables_mut().set("CMD_DURATION", elapsed_time.as_secs().to_string());
@@ -584,6 +1120,79 @@ impl<'a> Shell<'a> {
     }
 }
 
+/// The range of a statement read by the splitter, without the whitespace around it and the
+/// separator which ends it
+fn statement_span(line: &str, read: Range<usize>) -> Range<usize> {
+    let text = line[read.clone()].trim_end();
+    let text = ["&&", "||", ";"]
+        .iter()
+        .find_map(|separator| text.strip_suffix(separator))
+        .unwrap_or(text)
+        .trim_end();
+    let trimmed = text.trim_start();
+    let start = read.start + text.len() - trimmed.len();
+    start..start + trimmed.len()
+}
+
+/// Wrap the block closed by an `end &` into a job of its own
+fn in_background(
+    statement: Statement,
+    pipe: PipeType,
+) -> std::result::Result<Statement, BlockError> {
+    match (pipe, &statement) {
+        (PipeType::Normal, _) => Ok(statement),
+        (_, Statement::Function { .. } | Statement::Coproc { .. } | Statement::Defer(_)) => {
+            Err(BlockError::InvalidBackground(statement.to_string()))
+        }
+        _ => Ok(Statement::Background { pipe, statement: Box::new(statement) }),
+    }
+}
+
+/// A duration as given to `timeout`: a number of seconds, or of minutes, hours or days with the
+/// suffix `m`, `h` or `d`. The suffix `s` is optional.
+pub(crate) fn parse_duration(duration: &str) -> Option<Duration> {
+    let (number, unit) = match duration.char_indices().last()? {
+        (i, 's') => (&duration[..i], 1.),
+        (i, 'm') => (&duration[..i], 60.),
+        (i, 'h') => (&duration[..i], 60. * 60.),
+        (i, 'd') => (&duration[..i], 24. * 60. * 60.),
+        _ => (duration, 1.),
+    };
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.)
+        .map(|number| Duration::from_secs_f64(number * unit))
+}
+
+/// Whether the statement runs a command, whose failure is caught by a try block
+fn is_command(statement: &Statement) -> bool {
+    match statement {
+        Statement::Pipeline(_) => true,
+        Statement::And(inner)
+        | Statement::Or(inner)
+        | Statement::Not(inner)
+        | Statement::Time(inner)
+        | Statement::Timeout(_, inner) => is_command(inner),
+        _ => false,
+    }
+}
+
+/// The commands run by a statement, as written in the script
+fn command_source(statement: &Statement) -> String {
+    match statement {
+        Statement::Pipeline(pipeline) => {
+            pipeline.items.iter().map(|item| item.job.args.iter().join(" ")).join(" | ")
+        }
+        Statement::And(inner)
+        | Statement::Or(inner)
+        | Statement::Not(inner)
+        | Statement::Time(inner)
+        | Statement::Timeout(_, inner) => command_source(inner),
+        _ => String::new(),
+    }
+}
+
 /// Expand a pipeline containing aliases. As aliases can split the pipeline by having logical
 /// operators in them, the function returns the first half of the pipeline and the rest of the
 /// statements, where the last statement has the other half of the pipeline merged.
@@ -598,7 +1207,19 @@ fn expand_pipeline<'a>(
 
     while let Some(item) = item_iter.next() {
         if let Some(Value::Alias(alias)) = shell.variables.get(&item.job.args[0]) {
-            statements = StatementSplitter::new(alias.0.as_str())
+            // An alias ending with a space also expands the alias given as its first argument
+            let mut alias = alias.0.to_string();
+            let mut args = item.job.args.iter().skip(1).peekable();
+            while alias.ends_with(' ') {
+                match args.peek().and_then(|arg| shell.variables.get(arg)) {
+                    Some(Value::Alias(next)) => {
+                        alias.push_str(&next.0);
+                        args.next();
+                    }
+                    _ => break,
+                }
+            }
+            statements = StatementSplitter::new(&alias)
                 .map(|stmt| parse_and_validate(stmt?).map_err(Into::into))
                 .collect::<std::result::Result<_, IonError>>()?;
 
@@ -611,7 +1232,7 @@ fn expand_pipeline<'a>(
                     first.inputs = item.inputs.clone();
 
                     // Add alias arguments to expanded args if there's any.
-                    first.job.args.extend(item.job.args.iter().skip(1).cloned());
+                    first.job.args.extend(args.cloned());
                 }
                 if len == 1 {
                     if let Some(last) = pline.items.last_mut() {
@@ -629,7 +1250,8 @@ fn expand_pipeline<'a>(
                     Statement::And(ref mut boxed_stm)
                     | Statement::Or(ref mut boxed_stm)
                     | Statement::Not(ref mut boxed_stm)
-                    | Statement::Time(ref mut boxed_stm) => {
+                    | Statement::Time(ref mut boxed_stm)
+                    | Statement::Timeout(_, ref mut boxed_stm) => {
                         if let Statement::Pipeline(ref mut pline) = &mut **boxed_stm {
                             // Set output of alias to be the output of last pipeline.
                             if let Some(last) = pline.items.last_mut() {
@@ -643,7 +1265,7 @@ fn expand_pipeline<'a>(
                             // Error in expansion
                             return Err(PipelineError::InvalidAlias(
                                 item.job.args[0].to_string(),
-                                alias.0.to_string(),
+                                alias,
                             )
                             .into());
                         }
@@ -662,6 +1284,8 @@ fn expand_pipeline<'a>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::shell::Interruption;
+    use std::time::{Duration, Instant};
 
     fn new_match() -> Statement {
         Statement::Match { expression: types::Str::from(""), cases: Vec::new() }
@@ -677,7 +1301,8 @@ mod tests {
     }
     fn new_case() -> Statement {
         Statement::Case(Case {
-            value:       None,
+            values:      Vec::new(),
+            regex:       false,
             binding:     None,
             conditional: None,
             statements:  Vec::new(),
@@ -705,11 +1330,11 @@ mod tests {
         assert_eq!(flow_control.len(), 3);
         assert_eq!(res, Ok(None));
 
-        let res = Shell::insert_statement(&mut flow_control, Statement::End);
+        let res = Shell::insert_statement(&mut flow_control, Statement::End(PipeType::Normal));
         assert_eq!(flow_control.len(), 2);
         assert_eq!(res, Ok(None));
 
-        let res = Shell::insert_statement(&mut flow_control, Statement::End);
+        let res = Shell::insert_statement(&mut flow_control, Statement::End(PipeType::Normal));
         assert_eq!(flow_control.len(), 0);
         if let Ok(Some(Statement::Match { ref cases, .. })) = res {
             assert_eq!(cases.len(), 2);
@@ -751,9 +1376,67 @@ mod tests {
             assert_eq!(Ok(Some(ok)), res);
         }
 
-        let errs = vec![Statement::Else, Statement::End, Statement::Break, Statement::Continue];
+        let errs = vec![
+            Statement::Else,
+            Statement::End(PipeType::Normal),
+            Statement::Break(None),
+            Statement::Continue(None),
+        ];
         for err in errs {
             assert!(Shell::insert_statement(&mut flow_control, err).is_err());
         }
     }
+
+    #[test]
+    fn background_blocks() {
+        let mut flow_control = Block::default();
+        Shell::insert_statement(&mut flow_control, new_match()).unwrap();
+        Shell::insert_statement(&mut flow_control, new_case()).unwrap();
+        let res = Shell::insert_statement(&mut flow_control, Statement::End(PipeType::Background));
+        assert!(matches!(
+            res,
+            Ok(Some(Statement::Background { pipe: PipeType::Background, ref statement }))
+                if matches!(**statement, Statement::Match { .. })
+        ));
+
+        let coproc = Statement::Coproc { name: "job".into(), statements: Vec::new() };
+        Shell::insert_statement(&mut flow_control, coproc).unwrap();
+        let res = Shell::insert_statement(&mut flow_control, Statement::End(PipeType::Disown));
+        assert!(matches!(res, Err(BlockError::InvalidBackground(_))));
+    }
+
+    #[test]
+    fn deadline_kills_foreground_commands() {
+        let mut shell = Shell::new();
+        shell.set_deadline(Some(Instant::now() + Duration::from_millis(200)));
+        let start = Instant::now();
+        let result = shell.execute_command("sleep 10; echo unreachable".as_bytes());
+        assert!(matches!(result, Err(IonError::Interrupted(Interruption::DeadlineExceeded))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn statement_timeout_kills_pipelines() {
+        let mut shell = Shell::new();
+        shell.opts_mut().statement_timeout = Some(Duration::from_millis(200));
+        let start = Instant::now();
+        let result = shell.execute_command("sleep 0; sleep 10; echo unreachable".as_bytes());
+        assert!(matches!(
+            result,
+            Err(IonError::PipelineExecutionError(PipelineError::StatementTimeout(ref name, _)))
+                if name == "sleep 10"
+        ));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn global_aliases() {
+        let mut shell = Shell::new();
+        shell.global_aliases_mut().insert("L".into(), "| less".into());
+        let expand = |statement: &str| shell.expand_global_aliases(statement.into());
+        assert_eq!(expand("cat log L"), "cat log | less");
+        assert_eq!(expand("cat L 'L' \\L \"L\""), "cat | less 'L' \\L \"L\"");
+        assert_eq!(expand("alias -g L = 'tail'"), "alias -g L = 'tail'");
+        assert_eq!(expand("unalias L"), "unalias L");
+    }
 }
@@ -1,12 +1,19 @@
 use crate::{
     assignments::*,
-    expansion::pipelines::Pipeline,
-    parser::lexers::assignments::{KeyBuf, Operator, Primitive},
-    shell::{IonError, Job, Shell},
+    expansion::{
+        pipelines::{PipeType, Pipeline},
+        Expander,
+    },
+    parser::{
+        lexers::assignments::{KeyBuf, Operator, Primitive},
+        Diagnostic,
+    },
+    shell::{flow::Condition, IonError, Job, Shell, Value},
     types,
 };
+use itertools::Itertools;
 use smallvec::SmallVec;
-use std::fmt;
+use std::{fmt, ops::Range, rc::Rc};
 use thiserror::Error;
 
 /// Represents a single branch in a match statement. For example, in the expression
@@ -28,18 +35,22 @@ use thiserror::Error;
 /// would be represented by the Case object:
 /// ```rust,ignore
 /// Case {
-///     value:      Some(value),
+///     values:     vec![value],
 ///     statements: vec![statement0, statement1, ... statementN],
 /// }
 /// ```
+/// A branch with several patterns, `case first | second`, matches when any of them does.
 /// The wildcard branch, a branch that matches any value, is represented as such:
 /// ```rust,ignore
-/// Case { value: None, ... }
+/// Case { values: Vec::new(), ... }
 /// ```
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct Case {
-    /// The value to match with
-    pub value:       Option<String>,
+    /// The patterns to match with, any of which may match. Empty for the wildcard.
+    pub values:      Vec<String>,
+    /// Whether the patterns are searched for as regular expressions, written `case ~ pattern`,
+    /// whose captures are bound to variables
+    pub regex:       bool,
     /// Set a variable with the exact result
     pub binding:     Option<String>,
     /// An additional statement to test before matching the case statement
@@ -64,6 +75,12 @@ pub enum LocalAction {
     List,
     /// Assign a value to a name
     Assign(String, Operator, String),
+    /// Assign a value to a name in the scope of the function, shadowing the outer variables
+    Local(String, Operator, String),
+    /// Assign a value to a name in the global scope
+    Global(String, Operator, String),
+    /// Assign a value to a name in the global scope, which cannot be assigned again
+    Const(String, Operator, String),
 }
 
 /// The action to perform on export
@@ -75,6 +92,8 @@ pub enum ExportAction {
     LocalExport(String),
     /// Export and update
     Assign(String, Operator, String),
+    /// Export a value which cannot be assigned again
+    Const(String, Operator, String),
 }
 
 /// The mode for the next if block
@@ -123,24 +142,75 @@ pub enum Statement {
         description: Option<types::Str>,
         /// The required arguments of the function, with their types
         args:        Vec<KeyBuf>,
+        /// Whether the last argument collects the remaining arguments
+        variadic:    bool,
+        /// The type of the value returned by the function
+        return_type: Option<Primitive>,
         /// The statements in the function
         statements:  Block,
     },
+    /// The arguments a script takes, checked against those it was given
+    Args {
+        /// The description of the script, shown with its usage
+        description: Option<types::Str>,
+        /// The arguments of the script, with their types
+        args:        Vec<KeyBuf>,
+        /// Whether the last argument collects the remaining arguments
+        variadic:    bool,
+    },
     /// for loop
     For {
+        /// The label given to the loop, for `break` and `continue` to target it
+        label:      Option<types::Str>,
         /// The bounds
         variables:  SmallVec<[types::Str; 4]>,
         /// The value to iterator for
         values:     Vec<types::Str>,
         /// The block to execute repetitively
         statements: Block,
+        /// The block to execute once the loop completes without a `break`, if an `else` was given
+        otherwise:  Option<Block>,
+    },
+    /// Loop over the choices made from a menu of values
+    Select {
+        /// The label given to the loop, for `break` and `continue` to target it
+        label:      Option<types::Str>,
+        /// The variable holding the chosen value
+        variable:   types::Str,
+        /// The values to choose from
+        values:     Vec<types::Str>,
+        /// The block to execute with each choice
+        statements: Block,
     },
     /// while
     While {
+        /// The label given to the loop, for `break` and `continue` to target it
+        label:      Option<types::Str>,
         /// The block to test
         expression: Block,
         /// The block to execute repetitively
         statements: Block,
+        /// The block to execute once the loop completes without a `break`, if an `else` was given
+        otherwise:  Option<Block>,
+    },
+    /// Run a block until one of its statements fails, then run the catch block
+    Try {
+        /// The block to run
+        statements: Block,
+        /// The variable holding the failure in the catch block
+        binding:    Option<types::Str>,
+        /// The block to run on failure, if a catch was given
+        catch:      Option<Block>,
+    },
+    /// Start of the catch block of a try, with the variable to hold the failure
+    Catch(Option<types::Str>),
+    /// Run a block in the background, with its standard input and output connected to named
+    /// pipes
+    Coproc {
+        /// The name of the coprocess, prefixing the variables holding the paths of the pipes
+        name:       types::Str,
+        /// The statements run by the coprocess
+        statements: Block,
     },
     /// Match
     Match {
@@ -151,24 +221,46 @@ pub enum Statement {
     },
     /// Else statement
     Else,
-    /// End of a block
-    End,
-    /// Exit loop
-    Break,
-    /// Next loop
-    Continue,
+    /// End of a block, which runs in the background if followed by `&`
+    End(PipeType),
+    /// Exit the loop with the label, or the innermost loop
+    Break(Option<types::Str>),
+    /// Next iteration of the loop with the label, or of the innermost loop
+    Continue(Option<types::Str>),
     /// Exit from the current function/script
     Return(Option<types::Str>),
+    /// Hand values to the loop iterating over the generator function being executed
+    Yield(types::Str),
     /// Execute a pipeline
     Pipeline(Pipeline<Job>),
+    /// Run a block as a job of its own
+    Background {
+        /// Whether the job is kept among the jobs of the shell, or disowned
+        pipe:      PipeType,
+        /// The block to run
+        statement: Box<Statement>,
+    },
     /// Time the statement
     Time(Box<Statement>),
+    /// Stop the statement once the duration elapsed, killing the commands it runs
+    Timeout(types::Str, Box<Statement>),
+    /// Run the statement once the function or script exits
+    Defer(Box<Statement>),
     /// Execute the statement if the previous command succeeded
     And(Box<Statement>),
     /// Execute the statement if the previous command failed
     Or(Box<Statement>),
     /// Succeed on failure of the inner statement
     Not(Box<Statement>),
+    /// A statement that failed to parse, kept so that execution can resume after it
+    Invalid {
+        /// The reason the statement could not be parsed
+        error:  Diagnostic,
+        /// The source of the statement
+        source: String,
+        /// The byte range of the statement in the line it was read from
+        span:   Range<usize>,
+    },
     /// An empty statement
     Default,
 }
@@ -185,19 +277,29 @@ impl fmt::Display for Statement {
                 Statement::If { .. } => "If { .. }",
                 Statement::ElseIf(_) => "ElseIf { .. }",
                 Statement::Function { .. } => "Function { .. }",
+                Statement::Args { .. } => "Args { .. }",
                 Statement::For { .. } => "For { .. }",
                 Statement::While { .. } => "While { .. }",
+                Statement::Select { .. } => "Select { .. }",
+                Statement::Try { .. } => "Try { .. }",
+                Statement::Catch(_) => "Catch",
+                Statement::Coproc { .. } => "Coproc { .. }",
                 Statement::Match { .. } => "Match { .. }",
                 Statement::Else => "Else",
-                Statement::End => "End",
-                Statement::Break => "Break",
-                Statement::Continue => "Continue",
+                Statement::End(_) => "End",
+                Statement::Background { .. } => "Background { .. }",
+                Statement::Break(_) => "Break",
+                Statement::Continue(_) => "Continue",
                 Statement::Pipeline(_) => "Pipeline { .. }",
                 Statement::Time(_) => "Time { .. }",
+                Statement::Timeout(..) => "Timeout { .. }",
+                Statement::Defer(_) => "Defer { .. }",
                 Statement::And(_) => "And { .. }",
                 Statement::Or(_) => "Or { .. }",
                 Statement::Not(_) => "Not { .. }",
                 Statement::Return(_) => "Return",
+                Statement::Yield(_) => "Yield",
+                Statement::Invalid { .. } => "Invalid { .. }",
                 Statement::Default => "Default",
             }
         )
@@ -216,6 +318,10 @@ impl Statement {
                 | Statement::Function { .. }
                 | Statement::For { .. }
                 | Statement::While { .. }
+                | Statement::Select { .. }
+                | Statement::Try { .. }
+                | Statement::Catch(_)
+                | Statement::Coproc { .. }
                 | Statement::Match { .. }
                 | Statement::Else
         )
@@ -231,18 +337,42 @@ pub struct Function {
     description: Option<types::Str>,
     name:        types::Str,
     args:        Vec<KeyBuf>,
+    variadic:    bool,
+    return_type: Option<Primitive>,
     statements:  Block,
+    overloads:   Vec<Function>,
 }
 
+/// The values of the declared arguments of a function, and the remaining arguments
+type Bound<'b> = (SmallVec<[(KeyBuf, Value<Rc<Function>>); 8]>, Vec<&'b str>);
+
 /// Error during function execution
 #[derive(Debug, PartialEq, Clone, Error)]
 pub enum FunctionError {
     /// The wrong number of arguments were supplied
     #[error("invalid number of arguments supplied")]
     InvalidArgumentCount,
+    /// The argument was given by name more than once
+    #[error("argument '{0}' given more than once")]
+    RepeatedArgument(String),
     /// The argument had an invalid type
     #[error("argument has invalid type: expected {0}, found value '{1}'")]
     InvalidArgumentType(Primitive, String),
+    /// The returned value had an invalid type
+    #[error("return value has invalid type: expected {0}, found value '{1}'")]
+    InvalidReturnType(Primitive, String),
+    /// None of the definitions of an overloaded function accepts the arguments
+    #[error("no definition of '{0}' accepts the arguments supplied")]
+    NoMatchingDefinition(String),
+    /// Several definitions of an overloaded function accept the arguments equally
+    #[error("ambiguous call to '{0}': several definitions accept the arguments supplied")]
+    AmbiguousCall(String),
+    /// The arguments given to a script do not match those declared by its header
+    #[error("{1}\n{0}")]
+    InvalidScriptArguments(String, Box<FunctionError>),
+    /// An argument of a script would assign a constant
+    #[error("cannot assign to the constant `{0}`")]
+    ConstantArgument(String),
 }
 
 impl Function {
@@ -252,26 +382,107 @@ impl Function {
         shell: &mut Shell<'a>,
         args: &[S],
     ) -> Result<(), IonError> {
-        if args.len() - 1 != self.args.len() {
-            return Err(FunctionError::InvalidArgumentCount.into());
+        let (function, (values, remainder)) = self.dispatch(shell, args)?;
+        shell.calls.push(self.name.clone());
+        let result = function.run(shell, values, remainder);
+        shell.calls.pop();
+        result
+    }
+
+    /// Select the definition of the function which accepts the arguments, preferring the one
+    /// whose arguments are the most specific when several of them do.
+    fn dispatch<'b, S: AsRef<str>>(
+        &'b self,
+        shell: &mut Shell<'_>,
+        args: &'b [S],
+    ) -> Result<(&'b Self, Bound<'b>), FunctionError> {
+        if self.overloads.is_empty() {
+            return Ok((self, self.bind(shell, args)?));
+        }
+
+        let mut accepting: SmallVec<[_; 4]> = self
+            .definitions()
+            .filter_map(|function| Some((function, function.bind(shell, args).ok()?)))
+            .collect();
+        let best = (0..accepting.len()).find(|&i| {
+            let function = accepting[i].0;
+            accepting.iter().enumerate().all(|(j, (other, _))| {
+                i == j || (function.is_more_specific(other) && !other.is_more_specific(function))
+            })
+        });
+        match best {
+            Some(best) => Ok(accepting.swap_remove(best)),
+            None if accepting.is_empty() => {
+                Err(FunctionError::NoMatchingDefinition(self.name.to_string()))
+            }
+            None => Err(FunctionError::AmbiguousCall(self.name.to_string())),
+        }
+    }
+
+    /// Bind the arguments to the declared arguments, checking their types
+    fn bind<'b, S: AsRef<str>>(
+        &self,
+        shell: &mut Shell<'_>,
+        args: &'b [S],
+    ) -> Result<Bound<'b>, FunctionError> {
+        let (fixed, variadic) = match self.args.split_last() {
+            Some((last, fixed)) if self.variadic => (fixed, Some(last)),
+            _ => (&self.args[..], None),
+        };
+
+        // The arguments given by name, as `--name value`, are bound first, and the others fill the
+        // remaining arguments in order
+        let mut bound: SmallVec<[Option<&str>; 8]> = SmallVec::from_elem(None, fixed.len());
+        let mut positional = Vec::with_capacity(args.len());
+        let mut given = args.iter().skip(1).map(AsRef::as_ref);
+        while let Some(arg) = given.next() {
+            let named = arg
+                .strip_prefix("--")
+                .and_then(|name| fixed.iter().position(|type_| type_.name == name));
+            match (named, named.and_then(|_| given.next())) {
+                (Some(index), Some(value)) => {
+                    if bound[index].replace(value).is_some() {
+                        return Err(FunctionError::RepeatedArgument(fixed[index].name.clone()));
+                    }
+                }
+                _ => positional.push(arg),
+            }
         }
+        // Optional arguments which are left out are given an empty value
+        let mut positional = positional.into_iter();
+        for (slot, type_) in bound.iter_mut().zip(fixed).filter(|(slot, _)| slot.is_none()) {
+            *slot = match positional.next() {
+                Some(arg) => Some(arg),
+                None if matches!(type_.kind, Primitive::Optional(_)) => Some(""),
+                None => return Err(FunctionError::InvalidArgumentCount),
+            };
+        }
+        let remainder: Vec<&str> = positional.collect();
 
-        let values = self
-            .args
+        let mut values = fixed
             .iter()
-            .zip(args.iter().skip(1))
+            .zip(bound.into_iter().flatten())
             .map(|(type_, value)| {
-                if let Ok(value) = value_check(shell, value.as_ref(), &type_.kind) {
+                if let Ok(value) = value_check(shell, value, &type_.kind) {
                     Ok((type_.clone(), value))
                 } else {
-                    Err(FunctionError::InvalidArgumentType(
-                        type_.kind.clone(),
-                        value.as_ref().into(),
-                    ))
+                    Err(FunctionError::InvalidArgumentType(type_.kind.clone(), value.into()))
                 }
             })
             .collect::<Result<SmallVec<[_; 8]>, _>>()?;
 
+        if let Some(type_) = variadic {
+            values.push((type_.clone(), Self::collect_variadic(shell, type_, &remainder)?));
+        }
+        Ok((values, remainder))
+    }
+
+    fn run(
+        &self,
+        shell: &mut Shell<'_>,
+        values: SmallVec<[(KeyBuf, Value<Rc<Self>>); 8]>,
+        remainder: Vec<&str>,
+    ) -> Result<(), IonError> {
         let index = shell
             .variables
             .index_scope_for_var(&self.name)
@@ -281,18 +492,178 @@ impl Function {
         let temporary: Vec<_> = shell.variables.pop_scopes(index).collect();
 
         shell.variables.new_scope(true);
+        // The functions of a module refer to the other definitions of the module by their names
+        if let Some((module, _)) = self.name.rsplit_once("::") {
+            shell.variables.enter_module(module);
+        }
 
+        // The arguments which were not bound to any of the declared ones
+        shell.variables.set(
+            "args",
+            Value::Array(remainder.into_iter().map(|arg| Value::Str(arg.into())).collect()),
+        );
         for (type_, value) in values {
             shell.variables.set(&type_.name, value);
         }
 
-        let res = shell.execute_statements(&self.statements);
+        // The value given to `return` is kept for the callers of the function as an expression
+        shell.return_types.push(self.return_type.clone());
+        let res = shell.execute_deferring(&self.statements);
+        shell.return_types.pop();
+        // Only a value returned by this function is kept, rather than one of the functions it
+        // called
+        if res.as_ref().ok() != Some(&Condition::Return) {
+            shell.return_value = None;
+        }
 
         shell.variables.pop_scope();
         shell.variables.append_scopes(temporary);
         res.map(|_| ())
     }
 
+    /// Check each of the remaining arguments against the element type of the variadic argument,
+    /// spreading array arguments into their elements.
+    fn collect_variadic<S: AsRef<str>>(
+        shell: &mut Shell<'_>,
+        type_: &KeyBuf,
+        args: &[S],
+    ) -> Result<Value<Rc<Self>>, FunctionError> {
+        let inner = match type_.kind {
+            Primitive::Array(ref inner) => inner,
+            _ => unreachable!("the type of a variadic argument is an array"),
+        };
+        let invalid =
+            |value: &str| FunctionError::InvalidArgumentType((**inner).clone(), value.into());
+
+        let mut values = Vec::with_capacity(args.len());
+        for arg in args {
+            let arg = arg.as_ref();
+            if arg.starts_with('@') || is_array(arg) {
+                let items = shell.get_array(arg).map_err(|_| invalid(arg))?;
+                for item in items.iter() {
+                    values
+                        .push(value_check(shell, item, inner).map_err(|_| invalid(item.as_str()))?);
+                }
+            } else {
+                values.push(value_check(shell, arg, inner).map_err(|_| invalid(arg))?);
+            }
+        }
+        Ok(Value::Array(values))
+    }
+
+    /// Bind the arguments given to a script to those declared by its `args:` header, in the
+    /// current scope. Unlike those of a function, no argument may remain unbound.
+    pub(crate) fn bind_script<S: AsRef<str>>(
+        &self,
+        shell: &mut Shell<'_>,
+        args: &[S],
+    ) -> Result<(), FunctionError> {
+        let (values, _) = match self.bind(shell, args) {
+            Ok((_, remainder)) if !self.variadic && !remainder.is_empty() => {
+                Err(FunctionError::InvalidArgumentCount)
+            }
+            bound => bound,
+        }
+        .map_err(|cause| FunctionError::InvalidScriptArguments(self.usage(), Box::new(cause)))?;
+        if let Some((type_, _)) =
+            values.iter().find(|(type_, _)| shell.assigns_constant(&type_.name, false))
+        {
+            return Err(FunctionError::ConstantArgument(type_.name.to_string()));
+        }
+        for (type_, value) in values {
+            shell.variables.set(&type_.name, value);
+        }
+        Ok(())
+    }
+
+    /// The usage of the function, with the name and type of each of its arguments
+    fn usage(&self) -> String {
+        let mut usage = format!("usage: {}", self.name);
+        for (index, arg) in self.args.iter().enumerate() {
+            match arg.kind {
+                Primitive::Array(ref inner) if self.variadic && index + 1 == self.args.len() => {
+                    usage.push_str(&format!(" <{}:{}>...", arg.name, inner))
+                }
+                ref kind => usage.push_str(&format!(" <{}:{}>", arg.name, kind)),
+            }
+        }
+        if let Some(ref description) = self.description {
+            usage.push('\n');
+            usage.push_str(description);
+        }
+        usage
+    }
+
+    /// The same function under another name, as when it is imported into a module
+    pub(crate) fn with_name(&self, name: &str) -> Self {
+        let overloads = self.overloads.iter().map(|function| function.with_name(name)).collect();
+        Self { name: name.into(), overloads, ..self.clone() }
+    }
+
+    /// The function defined again, keeping the previous definitions which take other arguments
+    /// as overloads
+    pub(crate) fn overloading(mut self, previous: &Self) -> Self {
+        self.overloads = previous
+            .definitions()
+            .filter(|function| !self.has_signature_of(function))
+            .map(|function| Self { overloads: Vec::new(), ..function.clone() })
+            .collect();
+        self
+    }
+
+    /// Whether the function is a generator, which yields its values rather than returning them
+    #[must_use]
+    pub fn is_generator(&self) -> bool {
+        self.definitions().any(|function| yields(&function.statements))
+    }
+
+    /// Each definition of the function, with its own arguments
+    fn definitions(&self) -> impl Iterator<Item = &Self> {
+        std::iter::once(self).chain(&self.overloads)
+    }
+
+    fn has_signature_of(&self, other: &Self) -> bool {
+        self.variadic == other.variadic
+            && self.args.len() == other.args.len()
+            && self.args.iter().zip(&other.args).all(|(arg, other)| arg.kind == other.kind)
+    }
+
+    /// Whether each argument of the function accepts no more values than the argument of the
+    /// other one at its position, and the function declares as many arguments
+    fn is_more_specific(&self, other: &Self) -> bool {
+        self.args.len() >= other.args.len()
+            && self.args.iter().zip(&other.args).all(|(arg, other)| narrows(&arg.kind, &other.kind))
+    }
+
+    /// The values listed by the type of the argument at the position, in any definition of the
+    /// function, to complete it
+    #[must_use]
+    pub fn choices(&self, index: usize) -> Vec<&str> {
+        self.definitions()
+            .filter_map(|function| {
+                let last = function.args.len().checked_sub(1)?;
+                match function.args[index.min(last)].kind {
+                    Primitive::Array(ref inner) if function.variadic && index >= last => {
+                        Some(&**inner)
+                    }
+                    ref kind if index <= last => Some(kind),
+                    _ => None,
+                }
+            })
+            .filter_map(|kind| match kind {
+                Primitive::Enum(choices) => Some(choices),
+                Primitive::Optional(inner) => match **inner {
+                    Primitive::Enum(ref choices) => Some(choices),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .flatten()
+            .map(String::as_str)
+            .unique()
+            .collect()
+    }
+
     /// Get the function's description
     #[must_use]
     pub const fn description(&self) -> Option<&types::Str> { self.description.as_ref() }
@@ -303,8 +674,60 @@ impl Function {
         description: Option<types::Str>,
         name: types::Str,
         args: Vec<KeyBuf>,
+        variadic: bool,
+        return_type: Option<Primitive>,
         statements: Vec<Statement>,
     ) -> Self {
-        Self { description, name, args, statements }
+        Self { description, name, args, variadic, return_type, statements, overloads: Vec::new() }
+    }
+}
+
+/// Whether the statements yield values, apart from those of the functions they define
+fn yields(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement {
+        Statement::Yield(_) => true,
+        Statement::If { expression, success, else_if, failure, .. } => {
+            yields(expression)
+                || yields(success)
+                || else_if
+                    .iter()
+                    .any(|else_if| yields(&else_if.expression) || yields(&else_if.success))
+                || yields(failure)
+        }
+        Statement::For { statements, otherwise, .. } => {
+            yields(statements) || otherwise.as_deref().map_or(false, yields)
+        }
+        Statement::While { expression, statements, otherwise, .. } => {
+            yields(expression) || yields(statements) || otherwise.as_deref().map_or(false, yields)
+        }
+        Statement::Select { statements, .. } => yields(statements),
+        Statement::Try { statements, catch, .. } => {
+            yields(statements) || catch.as_deref().map_or(false, yields)
+        }
+        Statement::Match { cases, .. } => cases.iter().any(|case| yields(&case.statements)),
+        Statement::Time(statement)
+        | Statement::Timeout(_, statement)
+        | Statement::And(statement)
+        | Statement::Or(statement)
+        | Statement::Not(statement) => yields(std::slice::from_ref(&**statement)),
+        _ => false,
+    })
+}
+
+/// Whether the values of a type are all values of another one
+fn narrows(kind: &Primitive, other: &Primitive) -> bool {
+    match (kind, other) {
+        (_, Primitive::Str)
+        | (Primitive::Integer, Primitive::Float)
+        | (Primitive::Size, Primitive::Integer)
+        | (Primitive::Size, Primitive::Float)
+        | (Primitive::Duration, Primitive::Float) => true,
+        (Primitive::Optional(kind), Primitive::Optional(other)) => narrows(kind, other),
+        (kind, Primitive::Optional(other)) => narrows(kind, other),
+        (Primitive::Array(kind), Primitive::Array(other)) => narrows(kind, other),
+        (Primitive::Enum(choices), Primitive::Enum(other)) => {
+            choices.iter().all(|choice| other.contains(choice))
+        }
+        _ => kind == other,
     }
 }
@@ -0,0 +1,211 @@
+//! Generator functions, which `yield` their values one at a time rather than returning them.
+//!
+//! A `for` loop iterating over a generator, as `for x in @name(args...)`, runs it in a child
+//! process which writes each value it yields to a pipe, terminated by a NUL byte. The loop reads
+//! the values as it consumes them, so that they are never all held in memory, and the generator is
+//! stopped once the loop ends. Elsewhere, a generator expanded as `@name(args...)` collects its
+//! values into an array, and one run as a command prints each of them on a line.
+//!
+//! A streamed process expansion, as `for line in $|(command)`, is read the same way: the command
+//! runs in a child process, and the loop reads its output a line at a time.
+use super::{
+    flow_control::Function, pipe_exec::create_pipe, IonError, PipelineError, Shell, Value,
+};
+use crate::{builtins::Status, expansion::Expander, types};
+use nix::{
+    sys::{
+        signal::{self, Signal},
+        wait,
+    },
+    unistd::{self, ForkResult, Pid},
+};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    mem,
+    rc::Rc,
+};
+
+/// Where the generator being executed yields its values
+pub(crate) enum Yielded {
+    /// To the loop consuming them, in the parent process
+    Pipe(File),
+    /// Into the array the generator is expanded to
+    Array(types::Args),
+}
+
+/// The values yielded by a generator, or the lines printed by a streamed command, running in a
+/// child process, read as they are consumed
+pub(crate) struct Generated {
+    values:    Option<BufReader<File>>,
+    /// The byte terminating each value
+    separator: u8,
+    child:     Pid,
+    done:      bool,
+}
+
+impl Iterator for Generated {
+    type Item = types::Str;
+
+    fn next(&mut self) -> Option<types::Str> {
+        let mut value = Vec::new();
+        match self.values.as_mut()?.read_until(self.separator, &mut value) {
+            Ok(0) | Err(_) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => {
+                if value.last() == Some(&self.separator) {
+                    value.pop();
+                }
+                Some(String::from_utf8_lossy(&value).into())
+            }
+        }
+    }
+}
+
+impl Drop for Generated {
+    fn drop(&mut self) {
+        // Closing the pipe stops a generator waiting for its values to be consumed, and one busy
+        // producing its next value is killed
+        self.values = None;
+        if !self.done {
+            let _ = signal::kill(self.child, Signal::SIGKILL);
+        }
+        let _ = wait::waitpid(self.child, None);
+    }
+}
+
+/// The command of a streamed process expansion given to a `for` loop, as `$|(command)`
+pub(crate) fn streamed_command(values: &[types::Str]) -> Option<&str> {
+    match values {
+        [value] => value.strip_prefix("$|(")?.strip_suffix(')'),
+        _ => None,
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// The generator called by the values of a `for` loop, as `@name(args...)`, with its
+    /// arguments
+    pub(crate) fn generator_call(
+        &mut self,
+        values: &[types::Str],
+    ) -> Result<Option<(Rc<Function>, Vec<types::Str>)>, IonError> {
+        let call = match values {
+            [call] => call,
+            _ => return Ok(None),
+        };
+        let (name, args) = match call
+            .strip_prefix('@')
+            .and_then(|call| call.strip_suffix(')'))
+            .and_then(|call| call.split_once('('))
+        {
+            Some(call) => call,
+            None => return Ok(None),
+        };
+        let function = match self.variables.get(name) {
+            Some(Value::Function(function)) if function.is_generator() => function.clone(),
+            _ => return Ok(None),
+        };
+        let args = std::iter::once(name.into()).chain(self.expand_string(args)?).collect();
+        Ok(Some((function, args)))
+    }
+
+    /// Run the generator in a child process, which yields its values to this one
+    pub(crate) fn fork_generator(
+        &mut self,
+        function: &Function,
+        args: &[types::Str],
+    ) -> Result<Generated, PipelineError> {
+        let (reader, writer) = create_pipe()?;
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                drop(reader);
+                self.yielded = Some(Yielded::Pipe(writer));
+                let code = match function.execute(self, args) {
+                    Ok(()) => self.previous_status,
+                    Err(why) => {
+                        eprintln!("ion: {}", why);
+                        Status::COULD_NOT_EXEC
+                    }
+                };
+                unsafe { nix::libc::_exit(code.as_os_code()) };
+            }
+            Ok(ForkResult::Parent { child }) => Ok(Generated {
+                values: Some(BufReader::new(reader)),
+                separator: 0,
+                child,
+                done: false,
+            }),
+            Err(why) => Err(PipelineError::CreateForkError(why)),
+        }
+    }
+
+    /// Run the command of a streamed process expansion in a child process, whose output is read
+    /// a line at a time
+    pub(crate) fn fork_stream(&mut self, command: &str) -> Result<Generated, PipelineError> {
+        let (reader, writer) = create_pipe()?;
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                drop(reader);
+                self.stdout(writer);
+                let code = match self.on_command(command.bytes(), false) {
+                    Ok(()) => self.previous_status,
+                    Err(why) => {
+                        eprintln!("ion: {}", why);
+                        Status::COULD_NOT_EXEC
+                    }
+                };
+                let _ = io::stdout().flush();
+                unsafe { nix::libc::_exit(code.as_os_code()) };
+            }
+            Ok(ForkResult::Parent { child }) => Ok(Generated {
+                values: Some(BufReader::new(reader)),
+                separator: b'\n',
+                child,
+                done: false,
+            }),
+            Err(why) => Err(PipelineError::CreateForkError(why)),
+        }
+    }
+
+    /// Run the generator, collecting the values it yields into an array
+    pub(crate) fn collect_generated<S: AsRef<str>>(
+        &mut self,
+        function: &Function,
+        args: &[S],
+    ) -> Result<types::Args, IonError> {
+        let outer = self.yielded.replace(Yielded::Array(types::Args::new()));
+        let result = function.execute(self, args);
+        let yielded = mem::replace(&mut self.yielded, outer);
+        result?;
+        match yielded {
+            Some(Yielded::Array(values)) => Ok(values),
+            _ => Ok(types::Args::new()),
+        }
+    }
+
+    /// Hand the values to the loop consuming them, or to the array the generator is expanded
+    /// to, or else print them
+    pub(crate) fn yield_values(&mut self, values: types::Args) {
+        match self.yielded {
+            Some(Yielded::Pipe(ref mut pipe)) => {
+                let mut bytes = Vec::new();
+                for value in &values {
+                    bytes.extend_from_slice(value.as_bytes());
+                    bytes.push(0);
+                }
+                // The loop consuming the values ended, and the generator ends with it
+                if pipe.write_all(&bytes).is_err() {
+                    unsafe { nix::libc::_exit(0) };
+                }
+            }
+            Some(Yielded::Array(ref mut array)) => array.extend(values),
+            None => {
+                for value in &values {
+                    println!("{}", value);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! The `status::` namespace, describing the context in which the shell runs the statement being
+//! expanded.
+use super::{variables::Value, Shell};
+use crate::types;
+use itertools::Itertools;
+use std::env;
+
+/// A script being read by the shell
+#[derive(Debug, Default)]
+pub(crate) struct Source {
+    /// The path of the file, unless it is read from the command line or the standard input
+    pub(crate) path:     Option<types::Str>,
+    /// The line breaks read from it so far
+    pub(crate) newlines: usize,
+    /// The line on which the last statement read ends
+    pub(crate) line:     usize,
+    /// Whether a `return` stopped the file, whose remaining statements are skipped
+    pub(crate) returned: bool,
+}
+
+impl<'a> Shell<'a> {
+    /// Read the commands of a sourced file, attributing the statements to its path. A `return`
+    /// in the file stops it, and is not checked against the return type of the function
+    /// sourcing it.
+    pub(crate) fn read_source<T>(&mut self, path: &str, read: impl FnOnce(&mut Self) -> T) -> T {
+        self.sources.push(Source { path: Some(path.into()), ..Source::default() });
+        self.return_types.push(None);
+        let result = read(self);
+        self.return_types.pop();
+        self.sources.pop();
+        result
+    }
+
+    /// The value of the variable of the `status::` namespace, if there is one of this name
+    pub(crate) fn status_variable(&self, name: &str) -> Option<types::Str> {
+        let boolean = |value: bool| Some(if value { "true" } else { "false" }.into());
+        match name {
+            "is_interactive" => boolean(self.opts.grab_tty),
+            "is_login" => boolean(env::args().next().map_or(false, |arg| arg.starts_with('-'))),
+            "pipestatus" => Some(self.pipestatus().iter().join(" ").into()),
+            "current_function" => Some(self.calls.last().cloned().unwrap_or_default()),
+            "filename" => Some(self.filename()),
+            "line_number" => {
+                Some(self.sources.last().map_or(0, |source| source.line).to_string().into())
+            }
+            _ => None,
+        }
+    }
+
+    /// The status of each command of the last pipeline
+    pub(crate) fn pipestatus(&self) -> types::Args {
+        match self.variables.get("PIPESTATUS") {
+            Some(Value::Array(statuses)) => {
+                statuses.iter().map(|status| status.to_string().into()).collect()
+            }
+            _ => types::Args::new(),
+        }
+    }
+
+    /// The path of the file being read, or `stdio` if the commands are not read from a file
+    fn filename(&self) -> types::Str {
+        if let Some(path) = self.sources.iter().rev().find_map(|source| source.path.as_ref()) {
+            return path.clone();
+        }
+        // The first argument of a script is its path, and otherwise the one of the shell
+        match self.variables.get("args") {
+            Some(Value::Array(args)) => match args.first() {
+                Some(Value::Str(path)) if env::args().next().as_deref() != Some(path.as_str()) => {
+                    path.clone()
+                }
+                _ => "stdio".into(),
+            },
+            _ => "stdio".into(),
+        }
+    }
+}
@@ -4,12 +4,14 @@ use crate::{
     expansion::{self, pipelines::RedirectFrom, Expander},
     types, Value,
 };
-use std::{fmt, fs::File, str};
+use std::{fmt, fs::File, os::unix::io::RawFd, str};
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A shell job
 pub struct Job {
     /// Job Arguments
+    #[cfg_attr(feature = "serde", serde(with = "crate::types::serde_args"))]
     pub args:        types::Args,
     /// Redirection of the job (stdout |, stderr ^|, both &|, None)
     pub redirection: RedirectFrom,
@@ -81,6 +83,19 @@ pub struct RefinedJob<'a> {
     pub var:         Variant<'a>,
     /// Redirection (stdout |, stderr ^|, both &|, None)
     pub redirection: RedirectFrom,
+    /// Numbered file descriptors to set, in order, after the standard streams
+    pub descriptors: Vec<(RawFd, Fd)>,
+}
+
+/// What a numbered file descriptor of a job is set to
+#[derive(Debug)]
+pub enum Fd {
+    /// An opened file
+    File(File),
+    /// A copy of another file descriptor
+    Duplicate(RawFd),
+    /// Nothing, the file descriptor is closed
+    Close,
 }
 
 pub enum Variant<'a> {
@@ -202,6 +217,7 @@ impl<'a> RefinedJob<'a> {
             args: types::Args::new(),
             var: Variant::Tee { items: (tee_out, tee_err) },
             redirection,
+            descriptors: Vec::new(),
         }
     }
 
@@ -214,12 +230,21 @@ impl<'a> RefinedJob<'a> {
             args: types::Args::new(),
             var: Variant::Cat { sources },
             redirection,
+            descriptors: Vec::new(),
         }
     }
 
     /// Apply function
     pub const fn function(args: types::Args, redirection: RedirectFrom) -> Self {
-        Self { stdin: None, stdout: None, stderr: None, args, var: Variant::Function, redirection }
+        Self {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            args,
+            var: Variant::Function,
+            redirection,
+            descriptors: Vec::new(),
+        }
     }
 
     /// Apply builtin
@@ -235,11 +260,20 @@ impl<'a> RefinedJob<'a> {
             args,
             var: Variant::Builtin { main },
             redirection,
+            descriptors: Vec::new(),
         }
     }
 
     /// Apply external program executed by this shell
     pub const fn external(args: types::Args, redirection: RedirectFrom) -> Self {
-        Self { stdin: None, stdout: None, stderr: None, args, var: Variant::External, redirection }
+        Self {
+            stdin: None,
+            stdout: None,
+            stderr: None,
+            args,
+            var: Variant::External,
+            redirection,
+            descriptors: Vec::new(),
+        }
     }
 }
@@ -1,57 +1,85 @@
 mod assignments;
+mod cancellation;
 mod colors;
+mod command_cache;
 mod directory_stack;
 mod flow;
 /// The various blocks
 pub mod flow_control;
+mod generators;
+mod introspection;
 mod job;
 mod pipe_exec;
+mod prefetch;
+pub(crate) mod reporting;
+/// The encrypted store of secrets
+pub mod secrets;
+mod select;
 mod shell_expand;
 mod signals;
 pub mod sys;
+mod traps;
 /// Variables for the shell
 pub mod variables;
 
 use self::{
+    assignments::Scoping,
     directory_stack::DirectoryStack,
     flow_control::{Block, Function, FunctionError, Statement},
-    pipe_exec::foreground,
+    generators::Yielded,
+    introspection::Source,
+    pipe_exec::{foreground, Coprocess, Received, Substitution},
+    prefetch::Prefetcher,
+    secrets::{self, Secrets},
     sys::NULL_PATH,
     variables::Variables,
 };
 pub use self::{
+    cancellation::{CancellationToken, Interruption},
     flow::BlockError,
     job::{Job, RefinedJob},
     pipe_exec::{
         job_control::{BackgroundEvent, BackgroundProcess},
         PipelineError,
     },
+    traps::Trap,
     variables::Value,
 };
+pub(crate) use self::{
+    command_cache::{executables, CommandCache},
+    flow::parse_duration,
+};
 use crate::{
     assignments::value_check,
-    builtins::{BuiltinMap, Status},
+    builtins::{resources::Priority, BuiltinMap, Status},
     expansion::{
         pipelines::{PipeType, Pipeline},
         Error as ExpansionError,
     },
     parser::{
         lexers::{Key, Primitive},
-        Error as ParseError,
+        Diagnostic, Error as ParseError,
     },
+    types,
 };
 use nix::{
     sys::signal::{self, SigHandler},
     unistd::Pid,
 };
+use rand::rngs::StdRng;
+use regex::Regex;
 use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
     convert::TryFrom,
     fs::File,
     mem,
     ops::{Deref, DerefMut},
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{atomic::Ordering, Arc, Mutex},
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime},
 };
 use thiserror::Error;
 
@@ -61,7 +89,7 @@ pub enum IonError {
     // Parse-time error
     /// Parsing failed
     #[error("syntax error: {0}")]
-    InvalidSyntax(#[source] ParseError),
+    InvalidSyntax(#[source] Diagnostic),
     /// Incorrect order of blocks
     #[error("block error: {0}")]
     StatementFlowError(#[source] BlockError),
@@ -76,11 +104,19 @@ pub enum IonError {
     /// Could not properly expand to a pipeline
     #[error("expansion error: {0}")]
     ExpansionError(#[source] ExpansionError<IonError>),
+    /// The execution was stopped by the cancellation token or the deadline of the shell
+    #[error("interrupted: {0}")]
+    Interrupted(#[source] Interruption),
 }
 
 impl From<ParseError> for IonError {
     #[must_use]
-    fn from(cause: ParseError) -> Self { Self::InvalidSyntax(cause) }
+    fn from(cause: ParseError) -> Self { Self::InvalidSyntax(cause.into()) }
+}
+
+impl From<Diagnostic> for IonError {
+    #[must_use]
+    fn from(cause: Diagnostic) -> Self { Self::InvalidSyntax(cause) }
 }
 
 impl From<FunctionError> for IonError {
@@ -93,6 +129,11 @@ impl From<BlockError> for IonError {
     fn from(cause: BlockError) -> Self { Self::StatementFlowError(cause) }
 }
 
+impl From<Interruption> for IonError {
+    #[must_use]
+    fn from(cause: Interruption) -> Self { Self::Interrupted(cause) }
+}
+
 impl From<PipelineError> for IonError {
     #[must_use]
     fn from(cause: PipelineError) -> Self { Self::PipelineExecutionError(cause) }
@@ -107,13 +148,27 @@ impl From<ExpansionError<Self>> for IonError {
 #[derive(Debug, Clone, Hash, Default)]
 pub struct Options {
     /// Exit from the shell on the first error.
-    pub err_exit:  bool,
+    pub err_exit:          bool,
     /// Activates the -p option, aka pipefail in bash
-    pub pipe_fail: bool,
+    pub pipe_fail:         bool,
     /// Do not execute any commands given to the shell.
-    pub no_exec:   bool,
+    pub no_exec:           bool,
     /// If set, denotes that this shell is running as a background job.
-    pub grab_tty:  bool,
+    pub grab_tty:          bool,
+    /// Abort on the first syntax error instead of reporting it and resuming execution
+    pub err_exit_on_parse: bool,
+    /// Print the errors which repeat inside of a loop once, followed by how often they repeated
+    pub collapse_errors:   bool,
+    /// Kill the commands of a pipeline still running once it elapsed, failing with an error
+    pub statement_timeout: Option<Duration>,
+    /// Evaluate the arithmetic expansions with numbers of arbitrary precision
+    pub big_math:          bool,
+    /// Expand the globs which match no file to nothing, rather than to themselves
+    pub null_glob:         bool,
+    /// Fail with an error on the globs which match no file
+    pub fail_glob:         bool,
+    /// Send SIGHUP to the background jobs left when an interactive shell exits
+    pub hup_on_exit:       bool,
 }
 
 /// The shell structure is a megastructure that manages all of the state of the shell throughout
@@ -135,6 +190,8 @@ pub struct Shell<'a> {
     previous_status:    Status,
     /// The job ID of the previous command sent to the background.
     previous_job:       usize,
+    /// The job ID of the command sent to the background before the previous one.
+    prior_job:          usize,
     /// Contains all the options relative to the shell
     opts:               Options,
     /// Contains information on all of the active background processes that are being managed
@@ -143,6 +200,58 @@ pub struct Shell<'a> {
     /// When the `fg` command is run, this will be used to communicate with the specified
     /// background process.
     foreground_signals: Arc<foreground::Signals>,
+    /// The secrets of the `secret::` namespace, unlocked on first access
+    secrets:            RefCell<Secrets>,
+    /// The generator of `random` and of the `rand::` namespace, once seeded by `random seed`
+    rng:                RefCell<Option<StdRng>>,
+    /// The paths of the programs run, searched in PATH once for each command
+    command_cache:      CommandCache,
+    /// The priority given to the programs run, by `nice` and `ionice`
+    priority:           Priority,
+    /// Whether the redirections of the builtin being run stay applied to the shell once it
+    /// returns, as asked by `exec` without a command
+    keep_redirections:  bool,
+    /// The process substitutions of the pipelines being run
+    substitutions:      Vec<Substitution>,
+    /// The process substitutions of the jobs sent to the background, until their command exits
+    job_substitutions:  Vec<Substitution>,
+    /// The coprocesses running in the background, terminated when the shell exits
+    coprocesses:        Vec<Coprocess>,
+    /// The return types of the functions being executed, the innermost last
+    return_types:       Vec<Option<Primitive>>,
+    /// The value given to the last `return` statement
+    return_value:       Option<Value<Rc<Function>>>,
+    /// Stops the execution when cancelled
+    cancellation:       Option<CancellationToken>,
+    /// Stops the execution once reached
+    deadline:           Option<Instant>,
+    /// The files sourced by the scripts being executed, read ahead on worker threads
+    prefetcher:         Prefetcher,
+    /// The statements deferred by the top level and by each function and script being
+    /// executed, the innermost last
+    deferred:           Vec<Block>,
+    /// The files locked by `lock` until the scope locking them ends
+    locks:              HashMap<PathBuf, File>,
+    /// The variables declared with `let const` or `export const`, which cannot be assigned again
+    constants:          HashSet<types::Str>,
+    /// The scripts being read, the innermost last
+    sources:            Vec<Source>,
+    /// The names of the functions being run, the innermost last
+    calls:              Vec<types::Str>,
+    /// The commands run on signals and on the events of the shell
+    traps:              HashMap<Trap, types::Str>,
+    /// The aliases set with `alias -g`, replacing the words of any position, by name
+    global_aliases:     BTreeMap<types::Str, types::Str>,
+    /// Whether a trapped command is running, during which no event is trapped
+    running_trap:       bool,
+    /// Whether the condition of an `if` or a `while` is being executed
+    in_condition:       bool,
+    /// Whether the ERR trap waits for the next statement, which may be the rest of a list
+    err_trap_pending:   bool,
+    /// Where the generator function being executed yields its values
+    yielded:            Option<Yielded>,
+    /// The value returned by the function piping into the one run by this process
+    piped:              RefCell<Received>,
 
     // Callbacks
     /// Custom callback for each command call
@@ -151,6 +260,8 @@ pub struct Shell<'a> {
     pre_command:      Option<PreCommandCallback<'a>>,
     /// Custom callback when a background event occurs
     background_event: Option<BackgroundEventCallback>,
+    /// The namespaces registered by the program embedding the shell
+    namespaces:       HashMap<types::Str, NamespaceCallback<'a>>,
 
     // Default std pipes
     stdin:  Option<File>,
@@ -164,6 +275,13 @@ pub type OnCommandCallback<'a> = Box<dyn Fn(&Shell<'_>, std::time::Duration) + '
 pub type PreCommandCallback<'a> = Box<dyn Fn(&Shell<'_>, &Pipeline<RefinedJob<'_>>) + 'a>;
 /// A callback that is executed when a background event occurs
 pub type BackgroundEventCallback = Arc<dyn Fn(usize, Pid, BackgroundEvent) + Send + Sync>;
+/// A callback looking up the variables of a registered namespace, given the name following the
+/// namespace, or returning `None` if there is no such variable
+pub type NamespaceCallback<'a> = Box<dyn Fn(&Shell<'_>, &str) -> Option<types::Str> + 'a>;
+
+/// The namespaces of the shell, which cannot be registered
+const NAMESPACES: [&str; 11] =
+    ["c", "color", "x", "hex", "env", "sys", "super", "global", "secret", "status", "rand"];
 
 impl<'a> Default for Shell<'a> {
     #[must_use]
@@ -211,13 +329,40 @@ impl<'a> Shell<'a> {
             flow_control: Block::with_capacity(5),
             directory_stack: DirectoryStack::new(),
             previous_job: !0,
+            prior_job: !0,
             previous_status: Status::SUCCESS,
             opts: Options::default(),
             background: Arc::new(Mutex::new(Vec::new())),
             foreground_signals: Arc::new(foreground::Signals::new()),
+            secrets: RefCell::new(Secrets::default()),
+            rng: RefCell::new(None),
+            command_cache: CommandCache::default(),
+            priority: Priority::default(),
+            keep_redirections: false,
+            substitutions: Vec::new(),
+            job_substitutions: Vec::new(),
+            coprocesses: Vec::new(),
+            return_types: Vec::new(),
+            return_value: None,
+            cancellation: None,
+            deadline: None,
+            prefetcher: Prefetcher::default(),
+            deferred: vec![Vec::new()],
+            locks: HashMap::new(),
+            constants: HashSet::new(),
+            sources: vec![Source::default()],
+            calls: Vec::new(),
+            traps: HashMap::new(),
+            global_aliases: BTreeMap::new(),
+            running_trap: false,
+            in_condition: false,
+            err_trap_pending: false,
+            yielded: None,
+            piped: RefCell::new(Received::Nothing),
             on_command: None,
             pre_command: None,
             background_event: None,
+            namespaces: HashMap::new(),
 
             stdin: None,
             stdout: None,
@@ -240,6 +385,32 @@ impl<'a> Shell<'a> {
         mem::replace(&mut self.stderr, stderr.into())
     }
 
+    /// Stop the execution at the next statement once the token is cancelled, killing the
+    /// commands running in the foreground. The execution then fails with
+    /// [`IonError::Interrupted`].
+    pub fn set_cancellation(&mut self, token: Option<CancellationToken>) {
+        self.cancellation = token;
+    }
+
+    /// Stop the execution at the next statement once the deadline is reached, killing the
+    /// commands running in the foreground. The execution then fails with
+    /// [`IonError::Interrupted`].
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) { self.deadline = deadline; }
+
+    /// Why the execution must stop, if the shell was cancelled or ran past its deadline
+    fn interruption(&self) -> Option<Interruption> {
+        if self.cancellation.as_ref().map_or(false, CancellationToken::is_cancelled) {
+            Some(Interruption::Cancelled)
+        } else if self.deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+            Some(Interruption::DeadlineExceeded)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the execution may be interrupted by a cancellation token or a deadline
+    const fn interruptible(&self) -> bool { self.cancellation.is_some() || self.deadline.is_some() }
+
     /// Access the directory stack
     #[must_use]
     pub const fn dir_stack(&self) -> &DirectoryStack { &self.directory_stack }
@@ -279,7 +450,35 @@ impl<'a> Shell<'a> {
     /// terminated, then an error will be returned.
     pub fn execute_command<T: std::io::Read>(&mut self, command: T) -> Result<Status, IonError> {
         self.on_command(command.bytes().filter_map(Result::ok), true)?;
+        self.end_of_script()
+    }
 
+    /// Execute a whole script. The files which it sources with a literal path are read on worker
+    /// threads while the script runs, rather than when their `source` command is reached.
+    pub fn execute_script(&mut self, script: &str) -> Result<Status, IonError> {
+        self.execute_commands(prefetch::split(script.bytes()))
+    }
+
+    /// Execute the terminated commands of a script, reading the files it sources ahead
+    pub(crate) fn execute_commands(&mut self, commands: Vec<String>) -> Result<Status, IonError> {
+        self.prefetcher.scan(&commands);
+        self.deferred.push(Vec::new());
+        let result = commands.iter().try_for_each(|command| self.on_command(command.bytes(), true));
+        self.leave_deferring_scope();
+        result?;
+        self.end_of_script()
+    }
+
+    /// The commands of a sourced file, taken from the worker which read it ahead if any
+    pub(crate) fn sourced_commands(&mut self, path: &Path) -> std::io::Result<Vec<String>> {
+        match self.prefetcher.take(path) {
+            Some(commands) => Ok(commands),
+            None => Ok(prefetch::split(std::fs::read(path)?.into_iter())),
+        }
+    }
+
+    /// Check that the script closed all of its blocks
+    fn end_of_script(&mut self) -> Result<Status, IonError> {
         if let Some(block) = self.flow_control.last().map(Statement::to_string) {
             self.previous_status = Status::from_exit_code(1);
             Err(IonError::StatementFlowError(BlockError::UnclosedBlock(block)))
@@ -290,6 +489,25 @@ impl<'a> Shell<'a> {
 
     /// Executes a pipeline and returns the final exit status of the pipeline.
     pub fn run_pipeline(&mut self, pipeline: &Pipeline<Job>) -> Result<Status, IonError> {
+        // Process substitutions expanded in this pipeline are done with once it has run, unless
+        // it was sent to the background, in which case they are kept until their command exits
+        let substitutions = self.substitutions.len();
+        let result = self.expand_and_run(pipeline);
+        let expanded = self.substitutions.drain(substitutions..);
+        if pipeline.pipe == PipeType::Normal || result.is_err() {
+            expanded.for_each(Substitution::close);
+        } else {
+            self.job_substitutions.extend(expanded);
+        }
+        self.reap_substitutions();
+        // The commands killed on an interruption report it rather than how they ended
+        match self.interruption() {
+            Some(interruption) => Err(interruption.into()),
+            None => result,
+        }
+    }
+
+    fn expand_and_run(&mut self, pipeline: &Pipeline<Job>) -> Result<Status, IonError> {
         let command_start_time = SystemTime::now();
 
         let mut pipeline = pipeline.expand(self)?;
@@ -360,6 +578,16 @@ impl<'a> Shell<'a> {
         }
     }
 
+    /// Get the job sent to the background before the last executed job
+    #[must_use]
+    pub const fn prior_job(&self) -> Option<usize> {
+        if self.prior_job == !0 {
+            None
+        } else {
+            Some(self.prior_job)
+        }
+    }
+
     /// Set the callback to call before each command
     pub fn set_background_event(&mut self, callback: Option<BackgroundEventCallback>) {
         self.background_event = callback;
@@ -390,6 +618,23 @@ impl<'a> Shell<'a> {
     /// Set the callback to call on each command
     pub fn on_command_mut(&mut self) -> &mut Option<OnCommandCallback<'a>> { &mut self.on_command }
 
+    /// Register a namespace, whose variables, as in `${name::key}`, are looked up by the callback.
+    /// Returns false, without registering it, if it is one of the namespaces of the shell.
+    ///
+    /// Only the embedding program registers namespaces: the shell does not load plugins.
+    pub fn register_namespace(&mut self, name: &str, callback: NamespaceCallback<'a>) -> bool {
+        if NAMESPACES.contains(&name) {
+            return false;
+        }
+        self.namespaces.insert(name.into(), callback);
+        true
+    }
+
+    /// Remove a registered namespace, returning its callback
+    pub fn unregister_namespace(&mut self, name: &str) -> Option<NamespaceCallback<'a>> {
+        self.namespaces.remove(name)
+    }
+
     /// Get access to the builtins
     #[must_use]
     pub const fn builtins(&self) -> &BuiltinMap<'a> { &self.builtins }
@@ -410,6 +655,24 @@ impl<'a> Shell<'a> {
     #[must_use]
     pub fn opts_mut(&mut self) -> &mut Options { &mut self.opts }
 
+    /// The commands trapped on signals and on the events of the shell
+    #[must_use]
+    pub const fn traps(&self) -> &HashMap<Trap, types::Str> { &self.traps }
+
+    /// Mutable access to the trapped commands
+    #[must_use]
+    pub fn traps_mut(&mut self) -> &mut HashMap<Trap, types::Str> { &mut self.traps }
+
+    /// The aliases set with `alias -g`, which replace the unquoted words of any position
+    pub(crate) const fn global_aliases(&self) -> &BTreeMap<types::Str, types::Str> {
+        &self.global_aliases
+    }
+
+    /// Mutable access to the aliases set with `alias -g`
+    pub(crate) fn global_aliases_mut(&mut self) -> &mut BTreeMap<types::Str, types::Str> {
+        &mut self.global_aliases
+    }
+
     /// Access to the variables
     #[must_use]
     pub const fn variables(&self) -> &Variables { &self.variables }
@@ -418,6 +681,51 @@ impl<'a> Shell<'a> {
     #[must_use]
     pub fn variables_mut(&mut self) -> &mut Variables { &mut self.variables }
 
+    /// Mask the values of the unlocked secrets, and the matches of the `REDACT_PATTERNS`
+    /// regular expressions, in text which is about to be written out
+    #[must_use]
+    pub fn redact<'b>(&self, text: &'b str) -> Cow<'b, str> {
+        let patterns = match self.variables.get("REDACT_PATTERNS") {
+            Some(Value::Array(patterns)) => patterns
+                .iter()
+                .filter_map(|pattern| Regex::new(&pattern.to_string()).ok())
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        secrets::redact(text, self.secrets.borrow().values(), &patterns)
+    }
+
+    /// Mutable access to the secret store
+    #[must_use]
+    pub fn secrets_mut(&mut self) -> &mut Secrets { self.secrets.get_mut() }
+
+    /// The generator of the random numbers, which is seeded by `random seed`
+    pub(crate) fn rng_mut(&mut self) -> &mut Option<StdRng> { self.rng.get_mut() }
+
+    /// The paths of the programs run by the shell, listed and emptied by `hash`
+    pub(crate) fn command_cache_mut(&mut self) -> &mut CommandCache { &mut self.command_cache }
+
+    /// The priority given to the programs run, changed while `nice` and `ionice` run a command
+    pub(crate) fn priority_mut(&mut self) -> &mut Priority { &mut self.priority }
+
+    /// Keep the redirections of the builtin being run applied to the shell once it returns,
+    /// rather than restoring the streams which the shell had before. Within a function, they last
+    /// until the function returns.
+    pub fn keep_redirections(&mut self) { self.keep_redirections = true }
+
+    /// Whether the variable was declared as a constant, which cannot be assigned or dropped
+    #[must_use]
+    pub fn is_constant(&self, name: &str) -> bool { self.constants.contains(name) }
+
+    /// Whether setting the variable would assign a constant. Within a function, a variable which
+    /// is not set in the global scope is a local of the function, which may shadow a constant.
+    pub(crate) fn assigns_constant(&self, name: &str, global: bool) -> bool {
+        self.constants.contains(name) && (global || self.return_types.is_empty())
+    }
+
+    /// The files locked by `lock`, which are released once closed
+    pub(crate) fn locks_mut(&mut self) -> &mut HashMap<PathBuf, File> { &mut self.locks }
+
     /// Access to the variables
     #[must_use]
     pub fn background_jobs(&self) -> impl Deref<Target = Vec<BackgroundProcess>> + '_ {
@@ -445,7 +753,12 @@ impl<'a> Shell<'a> {
     #[must_use]
     pub const fn previous_status(&self) -> Status { self.previous_status }
 
-    fn assign(&mut self, key: &Key<'_>, value: Value<Rc<Function>>) -> Result<(), String> {
+    fn assign(
+        &mut self,
+        key: &Key<'_>,
+        value: Value<Rc<Function>>,
+        scoping: Scoping,
+    ) -> Result<(), String> {
         match (&key.kind, &value) {
             (Primitive::Indexed(ref index_name, ref index_kind), Value::Str(_)) => {
                 let index = value_check(self, index_name, index_kind)
@@ -491,7 +804,20 @@ impl<'a> Shell<'a> {
             | (_, Value::Array(_))
             | (Primitive::HashMap(_), Value::HashMap(_))
             | (Primitive::BTreeMap(_), Value::BTreeMap(_)) => {
-                self.variables.set(key.name, value);
+                match scoping {
+                    Scoping::Nearest => self.variables.set(key.name, value),
+                    Scoping::Function => self.variables.set_local(key.name, value),
+                    Scoping::Global => self.variables.set_global(key.name, value),
+                    Scoping::Constant => {
+                        self.variables.set_global(key.name, value);
+                        self.constants.insert(key.name.into());
+                    }
+                }
+                Ok(())
+            }
+            // Nothing was given to an optional type, which unsets the variable in this scope only
+            (_, Value::None) => {
+                self.variables.set_current(key.name, Value::None);
                 Ok(())
             }
             _ => Ok(()),
@@ -0,0 +1,105 @@
+use super::PipelineError;
+use crate::{
+    builtins::Status,
+    shell::{flow_control::Statement, Shell},
+    types,
+};
+use mktemp::Temp;
+use nix::{
+    sys::{
+        signal::{self, SigHandler, Signal},
+        stat::Mode,
+        wait,
+    },
+    unistd::{self, ForkResult, Pid},
+};
+use std::{fs::OpenOptions, os::unix::io::AsRawFd, path::Path};
+
+/// A block running in the background, which reads its standard input from the `NAME_in` named
+/// pipe and writes its standard output to the `NAME_out` named pipe.
+pub struct Coprocess {
+    name:      types::Str,
+    directory: Option<Temp>,
+    child:     Pid,
+    /// The shell which forked the coprocess, the only process to terminate it
+    owner:     Pid,
+}
+
+impl Drop for Coprocess {
+    fn drop(&mut self) {
+        // A process forked by the shell leaves the coprocess and its pipes to the shell
+        if Pid::this() != self.owner {
+            std::mem::forget(self.directory.take());
+            return;
+        }
+        let _ = signal::kill(self.child, Signal::SIGTERM);
+        let _ = wait::waitpid(self.child, None);
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Terminate the coprocesses, and remove their pipes
+    pub fn terminate_coprocesses(&mut self) { self.coprocesses.clear(); }
+
+    /// Fork the statements of a coprocess, and set the `NAME_in` and `NAME_out` variables to the
+    /// paths of its pipes. A previous coprocess of the same name is terminated.
+    pub(crate) fn fork_coprocess(
+        &mut self,
+        name: &types::Str,
+        statements: &[Statement],
+    ) -> Result<(), PipelineError> {
+        self.coprocesses.retain(|coprocess| &coprocess.name != name);
+
+        let directory = Temp::new_dir().map_err(PipelineError::CreateFifoError)?;
+        let (input, output) = (directory.as_path().join("in"), directory.as_path().join("out"));
+        for pipe in &[&input, &output] {
+            unistd::mkfifo(*pipe, Mode::S_IRUSR | Mode::S_IWUSR)
+                .map_err(|err| PipelineError::CreateFifoError(err.into()))?;
+        }
+
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                self.opts_mut().grab_tty = false;
+                unsafe {
+                    signal::signal(Signal::SIGINT, SigHandler::SigDfl).unwrap();
+                    signal::signal(Signal::SIGHUP, SigHandler::SigDfl).unwrap();
+                    signal::signal(Signal::SIGTERM, SigHandler::SigDfl).unwrap();
+                }
+                let _ = unistd::setpgid(Pid::this(), Pid::this());
+
+                // The pipes are opened for reading and writing so that opening them never blocks,
+                // and so that the input does not reach its end after each write to it.
+                let pipe = |path: &Path| OpenOptions::new().read(true).write(true).open(path);
+                let redirected = pipe(&input).and_then(|input| {
+                    let output = pipe(&output)?;
+                    unistd::dup2(input.as_raw_fd(), nix::libc::STDIN_FILENO)?;
+                    unistd::dup2(output.as_raw_fd(), nix::libc::STDOUT_FILENO)?;
+                    Ok(())
+                });
+                let result = redirected.map_err(|err| err.to_string()).and_then(|()| {
+                    self.execute_statements(statements).map_err(|err| err.to_string())
+                });
+                let code = match result {
+                    Ok(_) => self.previous_status().as_os_code(),
+                    Err(why) => {
+                        eprintln!("ion: coproc {}: {}", name, why);
+                        Status::COULD_NOT_EXEC.as_os_code()
+                    }
+                };
+                unsafe { nix::libc::_exit(code) };
+            }
+            Ok(ForkResult::Parent { child }) => {
+                self.variables.set(&format!("{}_in", name), input.to_string_lossy().as_ref());
+                self.variables.set(&format!("{}_out", name), output.to_string_lossy().as_ref());
+                self.coprocesses.push(Coprocess {
+                    name: name.clone(),
+                    directory: Some(directory),
+                    child,
+                    owner: Pid::this(),
+                });
+                Ok(())
+            }
+            Err(why) => Err(PipelineError::CreateForkError(why)),
+        }
+    }
+}
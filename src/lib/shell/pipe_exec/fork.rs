@@ -1,8 +1,11 @@
-use super::job_control::{BackgroundProcess, ProcessState};
+use super::{
+    job_control::{BackgroundProcess, ProcessState},
+    PipelineError,
+};
 use crate::{
     builtins::Status,
-    expansion::pipelines::Pipeline,
-    shell::{RefinedJob, Shell},
+    expansion::pipelines::{PipeType, Pipeline},
+    shell::{flow_control::Statement, RefinedJob, Shell},
 };
 use nix::{
     sys::signal::{self, SigHandler, Signal},
@@ -14,6 +17,20 @@ impl<'a> Shell<'a> {
     /// Ensures that the forked child is given a unique process ID.
     fn create_process_group() { unistd::setpgid(Pid::this(), Pid::this()).unwrap(); }
 
+    /// Detach the forked child from the terminal and the signal handlers of the shell
+    fn become_job(&mut self) {
+        self.opts_mut().grab_tty = false;
+        unsafe {
+            signal::signal(Signal::SIGINT, SigHandler::SigDfl).unwrap();
+            signal::signal(Signal::SIGHUP, SigHandler::SigDfl).unwrap();
+            signal::signal(Signal::SIGTERM, SigHandler::SigDfl).unwrap();
+        }
+        unistd::close(io::stdin().as_raw_fd()).unwrap();
+
+        // This ensures that the child fork has a unique PGID.
+        Self::create_process_group();
+    }
+
     /// Forks the shell, adding the child to the parent's background list, and executing
     /// the given commands in the child fork.
     pub(super) fn fork_pipe(
@@ -23,16 +40,7 @@ impl<'a> Shell<'a> {
     ) -> Status {
         match unsafe { unistd::fork() } {
             Ok(ForkResult::Child) => {
-                self.opts_mut().grab_tty = false;
-                unsafe {
-                    signal::signal(Signal::SIGINT, SigHandler::SigDfl).unwrap();
-                    signal::signal(Signal::SIGHUP, SigHandler::SigDfl).unwrap();
-                    signal::signal(Signal::SIGTERM, SigHandler::SigDfl).unwrap();
-                }
-                unistd::close(io::stdin().as_raw_fd()).unwrap();
-
-                // This ensures that the child fork has a unique PGID.
-                Self::create_process_group();
+                self.become_job();
 
                 // After execution of it's commands, exit with the last command's status.
                 let code = self
@@ -58,4 +66,56 @@ impl<'a> Shell<'a> {
             Err(why) => Status::error(format!("ion: background fork failed: {}", why)),
         }
     }
+
+    /// Forks the shell to run a block closed by `end &` as a job, as a pipeline followed by `&`
+    /// would be.
+    pub(crate) fn fork_block(
+        &mut self,
+        block: &Statement,
+        pipe: PipeType,
+    ) -> Result<(), PipelineError> {
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                self.become_job();
+
+                let code = match self.execute_statement(block) {
+                    Ok(_) => self.previous_status,
+                    Err(err) => {
+                        eprintln!("ion: {}", err);
+                        Status::COULD_NOT_EXEC
+                    }
+                };
+                unsafe { nix::libc::_exit(code.as_os_code()) };
+            }
+            Ok(ForkResult::Parent { child }) => {
+                if pipe != PipeType::Disown {
+                    self.send_to_background(BackgroundProcess::new(
+                        child,
+                        ProcessState::Running,
+                        job_name(block),
+                    ));
+                }
+                Ok(())
+            }
+            Err(why) => Err(PipelineError::CreateForkError(why)),
+        }
+    }
+}
+
+/// How a block run as a job is listed among the jobs
+fn job_name(block: &Statement) -> String {
+    let keyword = match block {
+        Statement::Time(inner) => return format!("time {}", job_name(inner)),
+        Statement::Timeout(duration, inner) => {
+            return format!("timeout {} {}", duration, job_name(inner))
+        }
+        Statement::If { .. } => "if",
+        Statement::For { .. } => "for",
+        Statement::While { .. } => "while",
+        Statement::Select { .. } => "select",
+        Statement::Match { .. } => "match",
+        Statement::Try { .. } => "try",
+        _ => "block",
+    };
+    format!("{} ... end &", keyword)
 }
@@ -4,7 +4,7 @@ use super::{
 };
 use crate::{
     builtins::Status,
-    shell::{signals, BackgroundEventCallback, Shell},
+    shell::{signals, BackgroundEventCallback, Shell, Trap},
 };
 use nix::{
     sys::{
@@ -17,7 +17,7 @@ use std::{
     fmt,
     sync::Mutex,
     thread::{sleep, spawn},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 #[derive(Clone, Copy, Hash, Debug, PartialEq)]
@@ -25,6 +25,8 @@ use std::{
 pub enum ProcessState {
     Running,
     Stopped,
+    /// The job exited, and its status is kept until it is waited on
+    Done,
     Empty,
 }
 
@@ -33,6 +35,7 @@ impl fmt::Display for ProcessState {
         match *self {
             Self::Running => write!(f, "Running"),
             Self::Stopped => write!(f, "Stopped"),
+            Self::Done => write!(f, "Done"),
             Self::Empty => write!(f, "Empty"),
         }
     }
@@ -64,11 +67,12 @@ pub struct BackgroundProcess {
     ignore_sighup: bool,
     state:         ProcessState,
     name:          String,
+    exit_status:   i32,
 }
 
 impl BackgroundProcess {
     pub(super) const fn new(pid: Pid, state: ProcessState, name: String) -> Self {
-        Self { pid, ignore_sighup: false, state, name }
+        Self { pid, ignore_sighup: false, state, name, exit_status: 0 }
     }
 
     /// Get the pid associated with the job
@@ -79,9 +83,21 @@ impl BackgroundProcess {
     #[must_use]
     pub fn is_running(&self) -> bool { self.state == ProcessState::Running }
 
+    /// Get the command run by the job
+    #[must_use]
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Get the exit status of the job, once it exited
+    #[must_use]
+    pub const fn exit_status(&self) -> i32 { self.exit_status }
+
     /// Check if this is in fact a process
     #[must_use]
-    pub fn exists(&self) -> bool { self.state != ProcessState::Empty }
+    pub fn exists(&self) -> bool { !matches!(self.state, ProcessState::Empty | ProcessState::Done) }
+
+    /// Check if the job exited without being waited on yet
+    #[must_use]
+    pub fn is_done(&self) -> bool { self.state == ProcessState::Done }
 
     /// Stop capturing information about the process. *This action is irreversible*
     pub fn forget(&mut self) { self.state = ProcessState::Empty }
@@ -99,28 +115,6 @@ impl fmt::Display for BackgroundProcess {
     }
 }
 
-/// Incorporate the "pipefail" option for what error code is returned
-/// from a finished pipeline.
-enum PipeErrorPropagation {
-    /// Just propagate the error code of the last command.
-    LastCommand(Status),
-    /// No error returned from a command in the pipe yet.
-    NoErrorYet(Status),
-    /// one command returned a non-zero error code in the pipe.
-    /// Propagate this first found error for the whole pipe now.
-    FirstErrorFound(Status),
-}
-
-impl From<PipeErrorPropagation> for Status {
-    fn from(value: PipeErrorPropagation) -> Self {
-        match value {
-            PipeErrorPropagation::LastCommand(r)
-            | PipeErrorPropagation::FirstErrorFound(r)
-            | PipeErrorPropagation::NoErrorYet(r) => r,
-        }
-    }
-}
-
 impl<'a> Shell<'a> {
     /// If a SIGTERM is received, a SIGTERM will be sent to all background processes
     /// before the shell terminates itself.
@@ -135,7 +129,7 @@ impl<'a> Shell<'a> {
 
     fn add_to_background(&mut self, job: BackgroundProcess) -> usize {
         let mut processes = self.background_jobs_mut();
-        if let Some(id) = processes.iter().position(|x| !x.exists()) {
+        if let Some(id) = processes.iter().position(|x| x.state == ProcessState::Empty) {
             processes[id] = job;
             id
         } else {
@@ -176,9 +170,13 @@ impl<'a> Shell<'a> {
                     }
 
                     get_process!(|process| {
-                        process.forget();
+                        process.exit_status = exit_status;
                         if fg_was_grabbed {
+                            process.forget();
                             fg.reply_with(exit_status);
+                        } else if process.state != ProcessState::Empty {
+                            // Keep the status of a job which exited for `wait`, unless disowned
+                            process.state = ProcessState::Done;
                         }
                     });
 
@@ -234,6 +232,9 @@ impl<'a> Shell<'a> {
         // the previous job in the shell (in case fg/bg is executed w/ no args).
         let pid = process.pid();
         let njob = self.add_to_background(process);
+        if njob != self.previous_job {
+            self.prior_job = self.previous_job;
+        }
         self.previous_job = njob;
         if let Some(ref callback) = &self.background_event {
             callback(njob, pid, BackgroundEvent::Added);
@@ -271,44 +272,71 @@ impl<'a> Shell<'a> {
         }
     }
 
-    /// Wait for the job in foreground
-    pub fn watch_foreground(&mut self, group: Pid) -> Result<Status, PipelineError> {
+    /// Incorporate the "pipefail" option for what status is returned from a finished pipeline,
+    /// given the status of each of its commands.
+    ///
+    /// The status of the last command is returned, unless pipefail is set, in which case it is
+    /// that of the last command which failed. A command killed by SIGPIPE is not considered to
+    /// have failed, as it only stopped because a later command quit reading its output.
+    pub(crate) fn pipeline_status(&self, statuses: &[Status]) -> Status {
+        let broken = Status::from_signal(Signal::SIGPIPE as u8);
+        if self.opts().pipe_fail {
+            statuses
+                .iter()
+                .rev()
+                .find(|&&status| status.is_failure() && status != broken)
+                .copied()
+                .unwrap_or(Status::SUCCESS)
+        } else {
+            statuses.last().copied().unwrap_or(Status::SUCCESS)
+        }
+    }
+
+    /// Wait for the job in foreground, returning the status of each of its commands, given
+    /// their process IDs in the order of the pipeline. The job is killed once it runs past the
+    /// statement timeout, which fails with an error naming it.
+    pub fn watch_foreground(
+        &mut self,
+        group: Pid,
+        commands: &[Pid],
+        name: &str,
+    ) -> Result<Vec<Status>, PipelineError> {
         let mut signaled = None;
-        let mut exit_status = if self.opts().pipe_fail {
-            PipeErrorPropagation::NoErrorYet(Status::SUCCESS)
+        let mut statuses = vec![Status::SUCCESS; commands.len()];
+        let command = |pid: Pid| commands.iter().position(|&command| command == pid);
+        let timeout = self.opts().statement_timeout;
+        let expiry = timeout.map(|timeout| Instant::now() + timeout);
+        let mut timed_out = None;
+
+        // An interruptible shell polls the job, to kill it once interrupted
+        let (flags, mut killed) = if self.interruptible() || timeout.is_some() {
+            (WaitPidFlag::WUNTRACED | WaitPidFlag::WNOHANG, false)
         } else {
-            PipeErrorPropagation::LastCommand(Status::SUCCESS)
+            (WaitPidFlag::WUNTRACED, true)
         };
-
         loop {
-            match wait::waitpid(Pid::from_raw(-group.as_raw()), Some(WaitPidFlag::WUNTRACED)) {
+            match wait::waitpid(Pid::from_raw(-group.as_raw()), Some(flags)) {
                 Err(err) => match err {
                     nix::errno::Errno::ECHILD => {
-                        if let Some(signal) = signaled {
+                        if let Some(timeout) = timed_out {
+                            break Err(PipelineError::StatementTimeout(name.into(), timeout));
+                        } else if let Some(signal) = signaled {
                             break Err(signal);
                         } else {
-                            break Ok(exit_status.into());
+                            break Ok(statuses);
                         }
                     }
                     err => break Err(PipelineError::WaitPid(err)),
                 },
-                Ok(WaitStatus::Exited(_, status)) => {
-                    let status = Status::from_exit_code(status);
-                    match exit_status {
-                        PipeErrorPropagation::LastCommand(_) => {
-                            exit_status = PipeErrorPropagation::LastCommand(status.into())
-                        }
-                        PipeErrorPropagation::NoErrorYet(_) => {
-                            exit_status = if status.is_failure() {
-                                PipeErrorPropagation::FirstErrorFound(status)
-                            } else {
-                                PipeErrorPropagation::NoErrorYet(status)
-                            };
-                        }
-                        PipeErrorPropagation::FirstErrorFound(_) => {}
+                Ok(WaitStatus::Exited(pid, status)) => {
+                    if let Some(i) = command(pid) {
+                        statuses[i] = Status::from_exit_code(status);
                     }
                 }
                 Ok(WaitStatus::Signaled(pid, signal, core_dumped)) => {
+                    if let Some(i) = command(pid) {
+                        statuses[i] = Status::from_signal(signal as u8);
+                    }
                     if signal == signal::Signal::SIGPIPE {
                     } else if core_dumped {
                         signaled = Some(PipelineError::CoreDump(pid));
@@ -329,6 +357,17 @@ impl<'a> Shell<'a> {
                     ));
                     break Err(PipelineError::Interrupted(pid, signal));
                 }
+                Ok(WaitStatus::StillAlive) => {
+                    if !killed && self.interruption().is_some() {
+                        let _ = signal::killpg(group, Signal::SIGKILL);
+                        killed = true;
+                    } else if !killed && expiry.map_or(false, |expiry| Instant::now() >= expiry) {
+                        let _ = signal::killpg(group, Signal::SIGKILL);
+                        killed = true;
+                        timed_out = timeout;
+                    }
+                    sleep(Duration::from_millis(10));
+                }
                 Ok(_) => (),
             }
         }
@@ -337,14 +376,59 @@ impl<'a> Shell<'a> {
     /// Waits until all running background tasks have completed, and listens for signals in the
     /// event that a signal is sent to kill the running tasks.
     pub fn wait_for_background(&mut self) -> Result<(), PipelineError> {
-        while self.background_jobs().iter().any(BackgroundProcess::is_running) {
+        self.wait_until(None, |jobs| !jobs.iter().any(BackgroundProcess::is_running)).map(|_| ())
+    }
+
+    /// Wait for all of the given jobs to exit, or for any of them, until the deadline. Returns
+    /// the job which exited first when waiting for any of them, or else the last job given, and
+    /// `None` if the deadline was reached first.
+    pub fn wait_for_jobs(
+        &mut self,
+        njobs: &[usize],
+        any: bool,
+        deadline: Option<Instant>,
+    ) -> Result<Option<usize>, PipelineError> {
+        let exited = |jobs: &[BackgroundProcess], njob: usize| {
+            !jobs.get(njob).map_or(false, BackgroundProcess::exists)
+        };
+        let done = self.wait_until(deadline, |jobs| {
+            if any {
+                njobs.iter().any(|&njob| exited(jobs, njob))
+            } else {
+                njobs.iter().all(|&njob| exited(jobs, njob))
+            }
+        })?;
+        if !done {
+            return Ok(None);
+        }
+        let jobs = self.background_jobs();
+        Ok(if any {
+            njobs.iter().copied().find(|&njob| exited(&jobs, njob))
+        } else {
+            njobs.last().copied()
+        })
+    }
+
+    /// Wait until the jobs are done, returning false if the deadline was reached before
+    fn wait_until(
+        &mut self,
+        deadline: Option<Instant>,
+        done: impl Fn(&[BackgroundProcess]) -> bool,
+    ) -> Result<bool, PipelineError> {
+        while !done(&self.background_jobs()) {
+            if deadline.map_or(false, |deadline| Instant::now() >= deadline) {
+                return Ok(false);
+            }
             if let Some(signal) = signals::SignalHandler.find(|&s| s != Signal::SIGTSTP) {
-                self.background_send(signal).map_err(PipelineError::KillFailed)?;
-                return Err(PipelineError::Interrupted(Pid::this(), signal));
+                // A trapped signal runs its command, and the wait goes on
+                if !self.run_trap(Trap::Signal(signal)) {
+                    self.background_send(signal).map_err(PipelineError::KillFailed)?;
+                    return Err(PipelineError::Interrupted(Pid::this(), signal));
+                }
             }
             sleep(Duration::from_millis(100));
         }
-        Ok(())
+        Ok(true)
     }
 
     /// When given a process ID, that process's group will be assigned as the
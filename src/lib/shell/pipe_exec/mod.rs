@@ -5,22 +5,30 @@
 //! the background, handling pipeline and conditional operators, and
 //! std{in,out,err} redirections.
 
+mod coprocess;
 pub mod foreground;
 mod fork;
 pub mod job_control;
 mod pipes;
 pub mod streams;
+mod substitution;
+mod values;
 
-pub use self::pipes::create_pipe;
+pub(crate) use self::values::Received;
+pub use self::{
+    coprocess::Coprocess, pipes::create_pipe, substitution::Substitution, values::text_of,
+};
 use self::{job_control::ProcessState, pipes::TeePipe};
 use super::{
-    job::{RefinedJob, TeeItem, Variant},
+    job::{Fd, RefinedJob, TeeItem, Variant},
     signals::{self, SignalHandler},
     IonError, Shell, Value,
 };
 use crate::{
     builtins::Status,
-    expansion::pipelines::{Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection},
+    expansion::pipelines::{
+        Descriptor, FdRedirection, Input, PipeItem, PipeType, Pipeline, RedirectFrom, Redirection,
+    },
     types,
 };
 use nix::{
@@ -31,8 +39,12 @@ use smallvec::SmallVec;
 use std::{
     fs::{File, OpenOptions},
     io::{self, Write},
-    os::unix::process::CommandExt,
+    os::unix::{
+        io::{AsRawFd, RawFd},
+        process::CommandExt,
+    },
     process::{exit, Command, Stdio},
+    time::Duration,
 };
 use thiserror::Error;
 
@@ -44,6 +56,15 @@ pub enum RedirectError {
     #[error("failed to write herestring '{0}': {1}")]
     WriteError(String, #[source] io::Error),
 
+    /// Numbered file descriptor
+    #[error("failed to redirect file descriptor {fd} to file '{file}': {why}")]
+    Descriptor {
+        fd:   RawFd,
+        file: String,
+        #[source]
+        why:  io::Error,
+    },
+
     /// Output
     #[error("failed to redirect {redirect} to file '{file}': {why}")]
     Output {
@@ -80,6 +101,9 @@ pub enum PipelineError {
     /// Failed to create a fork
     #[error("could not fork: {0}")]
     CreateForkError(#[source] nix::Error),
+    /// Failed to create the named pipe of a process substitution
+    #[error("could not create named pipe: {0}")]
+    CreateFifoError(#[source] io::Error),
     /// Failed to terminate the jobs after a termination
     #[error("failed to terminate foreground jobs: {0}")]
     TerminateJobsError(#[source] nix::Error),
@@ -96,6 +120,9 @@ pub enum PipelineError {
     /// A subprocess had a core dump
     #[error("process ({0}) had a core dump")]
     CoreDump(Pid),
+    /// The pipeline ran past the statement timeout, and was killed
+    #[error("statement timed out after {1:?}: {0}")]
+    StatementTimeout(String, Duration),
     /// WaitPID errored
     #[error("waitpid error: {0}")]
     WaitPid(nix::Error),
@@ -156,6 +183,66 @@ impl Input {
     }
 }
 
+impl FdRedirection {
+    fn open(&self) -> Result<(RawFd, Fd), RedirectError> {
+        let (file, result) = match self.to {
+            Descriptor::Input(ref file) => (file, File::open(file.as_str())),
+            Descriptor::Output { ref file, append } => (
+                file,
+                OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .append(append)
+                    .truncate(!append)
+                    .open(file.as_str()),
+            ),
+            Descriptor::Duplicate(other) => return Ok((self.fd, Fd::Duplicate(other))),
+            Descriptor::Close => return Ok((self.fd, Fd::Close)),
+        };
+        result.map(|opened| (self.fd, Fd::File(opened))).map_err(|why| RedirectError::Descriptor {
+            fd: self.fd,
+            file: file.to_string(),
+            why,
+        })
+    }
+}
+
+/// Open the descriptor redirections of a job. A copy of stdout or stderr which was made before the
+/// stream was redirected to files, as in `2>&1 > file`, takes its place in the pipe to the next
+/// command, or else is a copy of the stream the command would have had.
+fn open_descriptors(
+    job: &mut RefinedJob<'_>,
+    outputs: &[Redirection],
+    descriptors: &[FdRedirection],
+) -> Result<Vec<(RawFd, Fd)>, RedirectError> {
+    let mut opened = Vec::with_capacity(descriptors.len());
+    for redirection in descriptors {
+        match redirection.to {
+            Descriptor::Duplicate(other @ 1..=2)
+                if outputs.iter().any(|output| output.from.includes(other)) =>
+            {
+                if (1..=2).contains(&redirection.fd) && job.redirection.includes(other) {
+                    if job.redirection != RedirectFrom::Both {
+                        job.redirection = match redirection.fd {
+                            1 => RedirectFrom::Stdout,
+                            _ => RedirectFrom::Stderr,
+                        };
+                    }
+                } else {
+                    let copy = streams::copy(other).map_err(|why| RedirectError::Descriptor {
+                        fd:   redirection.fd,
+                        file: format!("&{}", other),
+                        why:  why.into(),
+                    })?;
+                    opened.push((redirection.fd, Fd::File(copy)));
+                }
+            }
+            _ => opened.push(redirection.open()?),
+        }
+    }
+    Ok(opened)
+}
+
 fn need_tee(outs: &[Redirection], redirection: RedirectFrom) -> (bool, bool) {
     let (mut stdout_count, mut stderr_count) = match redirection {
         RedirectFrom::Both => (1, 1),
@@ -235,7 +322,8 @@ fn prepare<'a>(
     let mut new_commands =
         SmallVec::<[RefinedJob<'a>; 16]>::with_capacity(2 * pipeline.items.len());
     let mut prev_kind = RedirectFrom::None;
-    for PipeItem { mut job, outputs, inputs } in pipeline.items {
+    for PipeItem { mut job, outputs, inputs, descriptors } in pipeline.items {
+        job.descriptors = open_descriptors(&mut job, &outputs, &descriptors)?;
         let kind = job.redirection;
         match (inputs.len(), prev_kind) {
             (0, _) => {}
@@ -348,17 +436,26 @@ impl<'b> Shell<'b> {
     ///
     /// The aforementioned `RefinedJob` may be either a builtin or external command.
     /// The purpose of this function is therefore to execute both types accordingly.
-    fn exec_job(&mut self, job: &RefinedJob<'b>) -> Result<Status, IonError> {
+    fn exec_job(&mut self, job: &mut RefinedJob<'b>) -> Result<Status, IonError> {
         // Duplicate file descriptors, execute command, and redirect back.
         let (stdin_bk, stdout_bk, stderr_bk) =
             streams::duplicate().map_err(PipelineError::CreatePipeError)?;
         streams::redirect(&job.stdin, &job.stdout, &job.stderr)?;
-        let code = match job.var {
-            Variant::Builtin { main } => Ok(main(job.args(), self)),
-            Variant::Function => self.exec_function(job.command(), job.args()),
-            _ => panic!("exec job should not be able to be called on Cat or Tee jobs"),
-        };
-        streams::redirect(&stdin_bk, &Some(stdout_bk), &Some(stderr_bk))?;
+        let descriptors_bk = streams::backup(&job.descriptors);
+        let code =
+            streams::apply(&job.descriptors).map_err(Into::into).and_then(|()| match job.var {
+                Variant::Builtin { main } => Ok(main(job.args(), self)),
+                Variant::Function => self.exec_function(job.command(), job.args()),
+                _ => panic!("exec job should not be able to be called on Cat or Tee jobs"),
+            });
+        let _ = io::stdout().flush();
+        if std::mem::take(&mut self.keep_redirections) {
+            // The files opened at the number of their descriptor must outlive the job
+            streams::keep(std::mem::take(&mut job.descriptors));
+        } else {
+            streams::restore(descriptors_bk);
+            streams::redirect(&stdin_bk, &Some(stdout_bk), &Some(stderr_bk))?;
+        }
         code
     }
 
@@ -401,22 +498,49 @@ impl<'b> Shell<'b> {
         }
     }
 
+    /// Run a command given by its arguments, which are already expanded, in the foreground
+    pub fn execute_args(&mut self, args: types::Args) -> Result<Status, IonError> {
+        let job = if let Some(Value::Function(_)) = self.variables.get(&args[0]) {
+            RefinedJob::function(args, RedirectFrom::None)
+        } else if let Some(main) = self.builtins.get(&args[0]) {
+            RefinedJob::builtin(main, args, RedirectFrom::None)
+        } else {
+            RefinedJob::external(args, RedirectFrom::None)
+        };
+        let item =
+            PipeItem { job, outputs: Vec::new(), inputs: Vec::new(), descriptors: Vec::new() };
+        self.execute_pipeline(Pipeline { items: vec![item], pipe: PipeType::Normal })
+    }
+
+    /// Record the status of each command of the pipeline which last ran in `@PIPESTATUS`
+    fn set_pipe_status(&mut self, statuses: &[Status]) {
+        let statuses = statuses.iter().map(|&status| status.into()).collect();
+        self.variables.set_global("PIPESTATUS", Value::Array(statuses));
+    }
+
     /// Executes a piped job `job1 | job2 | job3`
     ///
     /// This function will panic if called with an empty slice
     fn pipe(&mut self, pipeline: Pipeline<RefinedJob<'b>>) -> Result<Status, IonError> {
+        let name = pipeline.to_string();
         let mut commands = prepare(pipeline)?.into_iter().peekable();
 
         if let Some(mut parent) = commands.next() {
             if parent.redirection == RedirectFrom::None && !parent.needs_forking() {
-                let status = self.exec_job(&parent);
+                let status = self.exec_job(&mut parent);
 
                 let _ = io::stdout().flush();
                 let _ = io::stderr().flush();
 
-                status
+                status.map(|status| {
+                    self.set_pipe_status(&[status]);
+                    self.pipeline_status(&[status])
+                })
             } else {
                 let (mut pgid, mut last_pid, mut current_pid) = (None, None, Pid::this());
+                let mut pids = Vec::new();
+                // Where the next function reads the value returned by the function piping into it
+                let mut receiver = None;
 
                 // Append jobs until all piped jobs are running
                 for mut child in commands {
@@ -465,7 +589,29 @@ impl<'b> Shell<'b> {
                         }
                     }
 
-                    spawn_proc(self, parent, &mut last_pid, &mut current_pid, &mut pgid)?;
+                    // Functions piping into functions also send the values they return
+                    let piped_values = matches!(parent.var, Variant::Function)
+                        && parent.redirection == RedirectFrom::Stdout
+                        && matches!(child.var, Variant::Function);
+                    let (next_receiver, sender) = if piped_values {
+                        let (reader, writer) = create_pipe()?;
+                        (Some(reader), Some(writer))
+                    } else {
+                        (None, None)
+                    };
+                    // The ends of the pipes read by the next commands are closed in this one,
+                    // so that it stops writing once they exit
+                    let held: Vec<RawFd> = child
+                        .stdin
+                        .iter()
+                        .chain(next_receiver.iter())
+                        .map(AsRawFd::as_raw_fd)
+                        .collect();
+
+                    let channels = Channels { receiver: receiver.take(), sender, held: &held };
+                    spawn_proc(self, parent, channels, &mut last_pid, &mut current_pid, &mut pgid)?;
+                    pids.push(current_pid);
+                    receiver = next_receiver;
 
                     last_pid = Some(current_pid);
                     parent = child;
@@ -474,7 +620,9 @@ impl<'b> Shell<'b> {
                     }
                 }
 
-                spawn_proc(self, parent, &mut last_pid, &mut current_pid, &mut pgid)?;
+                let channels = Channels { receiver, sender: None, held: &[] };
+                spawn_proc(self, parent, channels, &mut last_pid, &mut current_pid, &mut pgid)?;
+                pids.push(current_pid);
                 if self.opts.grab_tty {
                     unistd::tcsetpgrp(nix::libc::STDIN_FILENO, pgid.unwrap())
                         .map_err(PipelineError::TerminalGrabFailed)?;
@@ -484,7 +632,9 @@ impl<'b> Shell<'b> {
                 // Waits for all of the children of the assigned pgid to finish executing,
                 // returning the exit status of the last process in the queue.
                 // Watch the foreground group, dropping all commands that exit as they exit.
-                let status = self.watch_foreground(pgid.unwrap())?;
+                let statuses = self.watch_foreground(pgid.unwrap(), &pids, &name)?;
+                self.set_pipe_status(&statuses);
+                let status = self.pipeline_status(&statuses);
                 if status == Status::TERMINATED {
                     signal::killpg(pgid.unwrap(), signal::Signal::SIGTERM)
                         .map_err(PipelineError::TerminateJobsError)?;
@@ -500,17 +650,36 @@ impl<'b> Shell<'b> {
     }
 }
 
+/// The pipes of a forked command besides its standard streams
+struct Channels<'a> {
+    /// Where a function reads the value returned by the function piping into it
+    receiver: Option<File>,
+    /// Where a function sends the value it returns to the function it pipes into
+    sender:   Option<File>,
+    /// The pipes read by the next commands of the pipeline
+    held:     &'a [RawFd],
+}
+
 fn spawn_proc(
     shell: &mut Shell<'_>,
     cmd: RefinedJob<'_>,
+    channels: Channels<'_>,
     last_pid: &mut Option<Pid>,
     current_pid: &mut Pid,
     group: &mut Option<Pid>,
 ) -> Result<(), PipelineError> {
-    let RefinedJob { mut var, mut args, stdin, stdout, stderr, redirection } = cmd;
+    let RefinedJob { mut var, mut args, stdin, stdout, stderr, redirection, descriptors } = cmd;
+    let Channels { mut receiver, mut sender, held } = channels;
     let pid = match var {
         Variant::External => {
-            let mut command = Command::new(&args[0].as_str());
+            let mut command = match shell.command_cache.resolve(&args[0]) {
+                Some(path) => {
+                    let mut command = Command::new(path);
+                    command.arg0(args[0].as_str());
+                    command
+                }
+                None => Command::new(&args[0].as_str()),
+            };
             command.args(args[1..].iter().map(types::Str::as_str));
 
             command.stdin(stdin.map_or_else(Stdio::inherit, Into::into));
@@ -518,10 +687,15 @@ fn spawn_proc(
             command.stderr(stderr.map_or_else(Stdio::inherit, Into::into));
 
             let grp = *group;
+            let priority = shell.priority;
             unsafe {
                 command.pre_exec(move || {
                     let _ = unistd::setpgid(Pid::this(), grp.unwrap_or_else(Pid::this));
-                    Ok(())
+                    // The program still runs when the priority may not be given, as
+                    // `nice` and `ionice` warned
+                    let _ = priority.apply();
+                    streams::apply(&descriptors)
+                        .map_err(|why| io::Error::new(io::ErrorKind::Other, why.to_string()))
                 })
             };
             match command.spawn() {
@@ -536,20 +710,35 @@ fn spawn_proc(
             }
         }
         Variant::Builtin { main } => {
-            fork_exec_internal(stdout, stderr, stdin, *group, |_, _, _| main(&args, shell))
+            fork_exec_internal(stdout, stderr, stdin, &descriptors, held, *group, |_, _, _| {
+                main(&args, shell)
+            })
         }
-        Variant::Function => fork_exec_internal(stdout, stderr, stdin, *group, |_, _, _| {
-            shell
-                .exec_function(&args[0], &args)
-                .unwrap_or_else(|why| Status::error(format!("{}", why)))
-        }),
-        Variant::Cat { ref mut sources } => {
-            fork_exec_internal(stdout, None, stdin, *group, |_, _, mut stdin| {
-                Shell::exec_multi_in(sources, &mut stdin)
+        Variant::Function => {
+            fork_exec_internal(stdout, stderr, stdin, &descriptors, held, *group, |_, _, _| {
+                if let Some(receiver) = receiver.take() {
+                    *shell.piped.borrow_mut() = Received::Pending(receiver);
+                }
+                let status = shell
+                    .exec_function(&args[0], &args)
+                    .unwrap_or_else(|why| Status::error(format!("{}", why)));
+                if let Some(sender) = sender.take() {
+                    values::send(shell.return_value.take(), sender);
+                }
+                status
             })
         }
+        Variant::Cat { ref mut sources } => fork_exec_internal(
+            stdout,
+            None,
+            stdin,
+            &descriptors,
+            held,
+            *group,
+            |_, _, mut stdin| Shell::exec_multi_in(sources, &mut stdin),
+        ),
         Variant::Tee { ref mut items } => {
-            fork_exec_internal(stdout, stderr, stdin, *group, |_, _, _| {
+            fork_exec_internal(stdout, stderr, stdin, &descriptors, held, *group, |_, _, _| {
                 Shell::exec_multi_out(items, redirection)
             })
         }
@@ -567,6 +756,8 @@ fn fork_exec_internal<F>(
     stdout: Option<File>,
     stderr: Option<File>,
     stdin: Option<File>,
+    descriptors: &[(RawFd, Fd)],
+    held: &[RawFd],
     pgid: Option<Pid>,
     mut exec_action: F,
 ) -> Result<Pid, PipelineError>
@@ -579,11 +770,20 @@ where
                 signal::signal(signal::Signal::SIGINT, signal::SigHandler::SigIgn).unwrap();
                 signal::signal(signal::Signal::SIGHUP, signal::SigHandler::SigIgn).unwrap();
                 signal::signal(signal::Signal::SIGTERM, signal::SigHandler::SigIgn).unwrap();
+                // Stop writing to a pipe once the command reading it exits, as a program would
+                signal::signal(signal::Signal::SIGPIPE, signal::SigHandler::SigDfl).unwrap();
             }
             signals::unblock();
 
+            for &fd in held {
+                let _ = unistd::close(fd);
+            }
             unistd::setpgid(Pid::this(), pgid.unwrap_or_else(Pid::this)).unwrap();
             streams::redirect(&stdin, &stdout, &stderr).unwrap();
+            if let Err(why) = streams::apply(descriptors) {
+                eprintln!("ion: {}", why);
+                exit(Status::COULD_NOT_EXEC.as_os_code());
+            }
             let exit_status = exec_action(stdout, stderr, stdin);
             exit(exit_status.as_os_code())
         }
@@ -1,11 +1,18 @@
-use crate::PipelineError;
-use nix::unistd;
+use crate::{shell::job::Fd, PipelineError};
+use nix::{
+    fcntl::{self, FcntlArg, FdFlag},
+    unistd,
+};
 use std::{
     fs::File,
     io,
-    os::unix::io::{AsRawFd, FromRawFd},
+    os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd},
 };
 
+/// The lowest file descriptor to keep the backups of redirected file descriptors at, so that
+/// they do not take the place of a file descriptor redirected later on
+const BACKUP_FD: RawFd = 10;
+
 /// Use dup2 to replace `old` with `new` using `old`s file descriptor ID
 fn redir<F: AsRawFd>(old: &Option<File>, new: &F) -> Result<(), PipelineError> {
     if let Some(old) = old.as_ref().map(AsRawFd::as_raw_fd) {
@@ -38,3 +45,72 @@ pub fn redirect(
     redir(out, &io::stdout())?;
     redir(err, &io::stderr())
 }
+
+/// Set the numbered file descriptors of a job, in order
+pub fn apply(descriptors: &[(RawFd, Fd)]) -> Result<(), PipelineError> {
+    for &(fd, ref to) in descriptors {
+        match *to {
+            // The file already has the right number, but would be closed on exec
+            Fd::File(ref file) if file.as_raw_fd() == fd => {
+                fcntl::fcntl(fd, FcntlArg::F_SETFD(FdFlag::empty()))
+                    .map_err(PipelineError::CloneFdFailed)?;
+            }
+            Fd::File(ref file) => {
+                unistd::dup2(file.as_raw_fd(), fd).map_err(PipelineError::CloneFdFailed)?;
+            }
+            Fd::Duplicate(other) => {
+                unistd::dup2(other, fd).map_err(PipelineError::CloneFdFailed)?;
+            }
+            Fd::Close => {
+                let _ = unistd::close(fd);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Leaves the numbered file descriptors of a job open once the job is dropped, for the
+/// redirections which the shell keeps
+pub fn keep(descriptors: Vec<(RawFd, Fd)>) {
+    for (fd, to) in descriptors {
+        if let Fd::File(file) = to {
+            if file.as_raw_fd() == fd {
+                let _ = file.into_raw_fd();
+            }
+        }
+    }
+}
+
+/// Duplicates a file descriptor of the shell, for a command to which it is given as another one
+pub fn copy(fd: RawFd) -> nix::Result<File> {
+    fcntl::fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(BACKUP_FD))
+        .map(|fd| unsafe { File::from_raw_fd(fd) })
+}
+
+/// Duplicates the file descriptors which a job redirects, if they are open, so that they can be
+/// restored once the job ran within the shell.
+pub fn backup(descriptors: &[(RawFd, Fd)]) -> Vec<(RawFd, Option<File>)> {
+    descriptors
+        .iter()
+        .map(|&(fd, _)| {
+            let backup = fcntl::fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(BACKUP_FD))
+                .ok()
+                .map(|fd| unsafe { File::from_raw_fd(fd) });
+            (fd, backup)
+        })
+        .collect()
+}
+
+/// Restores the file descriptors saved with `backup`, closing those which were not open
+pub fn restore(backups: Vec<(RawFd, Option<File>)>) {
+    for (fd, backup) in backups.into_iter().rev() {
+        match backup {
+            Some(file) => {
+                let _ = unistd::dup2(file.as_raw_fd(), fd);
+            }
+            None => {
+                let _ = unistd::close(fd);
+            }
+        }
+    }
+}
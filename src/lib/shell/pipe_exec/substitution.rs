@@ -0,0 +1,136 @@
+use super::PipelineError;
+use crate::{builtins::Status, shell::Shell};
+use mktemp::Temp;
+use nix::{
+    sys::{
+        signal::{self, SigHandler, Signal},
+        stat::Mode,
+        wait::{self, WaitPidFlag, WaitStatus},
+    },
+    unistd::{self, ForkResult, Pid},
+};
+use std::{
+    fs::OpenOptions,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+/// A command of a process substitution, running in the background and connected to the
+/// command it was expanded for through a named pipe.
+pub struct Substitution {
+    directory: Option<Temp>,
+    child:     Pid,
+    input:     bool,
+    /// The shell which forked the command, the only process to reap it and remove its pipe
+    owner:     Pid,
+}
+
+impl Drop for Substitution {
+    fn drop(&mut self) {
+        // A process forked by the shell leaves the command and its pipe to the shell
+        if Pid::this() != self.owner {
+            std::mem::forget(self.directory.take());
+        }
+    }
+}
+
+impl Substitution {
+    fn fifo(directory: &Path) -> PathBuf { directory.join("fifo") }
+
+    /// The path the command was substituted with
+    pub fn path(&self) -> PathBuf {
+        Self::fifo(self.directory.as_ref().expect("the pipe is only removed on drop").as_path())
+    }
+
+    /// Unblock the command if nothing opened the other end of the pipe
+    fn unblock(&self) {
+        if Pid::this() != self.owner {
+            return;
+        }
+        let _ = OpenOptions::new()
+            .read(self.input)
+            .write(!self.input)
+            .custom_flags(nix::libc::O_NONBLOCK)
+            .open(self.path());
+    }
+
+    /// Wait for the command to exit, first unblocking it. The pipe is removed once dropped.
+    pub fn close(self) {
+        self.unblock();
+        let _ = wait::waitpid(self.child, None);
+    }
+
+    /// Whether the command exited, in which case it is reaped
+    fn exited(&self) -> bool {
+        Pid::this() == self.owner
+            && !matches!(
+                wait::waitpid(self.child, Some(WaitPidFlag::WNOHANG)),
+                Ok(WaitStatus::StillAlive)
+            )
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Remove the pipes of the process substitutions of background jobs whose command exited
+    pub(crate) fn reap_substitutions(&mut self) {
+        self.job_substitutions.retain(|substitution| !substitution.exited());
+    }
+
+    /// Remove the pipes of the process substitutions of background jobs, when the shell exits.
+    /// The commands still running are unblocked, but left to finish on their own.
+    pub fn close_substitutions(&mut self) {
+        self.reap_substitutions();
+        self.job_substitutions.drain(..).for_each(|substitution| substitution.unblock());
+    }
+
+    /// Fork a command which writes to a named pipe if it is an input of the expanded command,
+    /// and reads from it otherwise.
+    pub(crate) fn fork_substitution(
+        &mut self,
+        command: &str,
+        input: bool,
+    ) -> Result<Substitution, PipelineError> {
+        let directory = Temp::new_dir().map_err(PipelineError::CreateFifoError)?;
+        unistd::mkfifo(&Substitution::fifo(directory.as_path()), Mode::S_IRUSR | Mode::S_IWUSR)
+            .map_err(|err| PipelineError::CreateFifoError(err.into()))?;
+
+        match unsafe { unistd::fork() } {
+            Ok(ForkResult::Child) => {
+                self.opts_mut().grab_tty = false;
+                unsafe {
+                    signal::signal(Signal::SIGINT, SigHandler::SigDfl).unwrap();
+                    signal::signal(Signal::SIGHUP, SigHandler::SigDfl).unwrap();
+                    signal::signal(Signal::SIGTERM, SigHandler::SigDfl).unwrap();
+                }
+                let _ = unistd::setpgid(Pid::this(), Pid::this());
+
+                let fifo = Substitution::fifo(directory.as_path());
+                let result = OpenOptions::new()
+                    .read(!input)
+                    .write(input)
+                    .open(&fifo)
+                    .map_err(|err| err.to_string())
+                    .and_then(|pipe| {
+                        if input {
+                            self.stdout(pipe);
+                        } else {
+                            self.stdin(pipe);
+                        }
+                        self.on_command(command.bytes(), false).map_err(|err| err.to_string())
+                    });
+                let code = match result {
+                    Ok(()) => self.previous_status().as_os_code(),
+                    Err(why) => {
+                        eprintln!("ion: process substitution: {}", why);
+                        Status::COULD_NOT_EXEC.as_os_code()
+                    }
+                };
+                unsafe { nix::libc::_exit(code) };
+            }
+            Ok(ForkResult::Parent { child }) => {
+                Ok(Substitution { directory: Some(directory), child, input, owner: Pid::this() })
+            }
+            Err(why) => Err(PipelineError::CreateForkError(why)),
+        }
+    }
+}
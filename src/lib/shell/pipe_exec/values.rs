@@ -0,0 +1,188 @@
+//! Typed pipes between ion functions.
+//!
+//! Like any other command of a pipeline, an ion function which pipes its standard output into
+//! another ion function, as in `produce | transform | consume`, runs in a process of its own.
+//! The value it returns is also written to a second pipe, which is only created between two ion
+//! functions, so that arrays and maps keep their structure. The next function finds it in its
+//! `piped` variable, which is read from that pipe when it is first expanded, once the previous
+//! function has returned.
+use crate::{
+    shell::{shell_expand::map_pairs, Shell, Value},
+    types,
+};
+use nix::sys::signal::{self, SigHandler, Signal};
+use std::{
+    fs::File,
+    io::{Read, Write},
+    rc::Rc,
+};
+
+type Piped = Value<Rc<types::Function>>;
+
+/// The value returned by the ion function which pipes into the one run by this process
+pub(crate) enum Received {
+    /// No ion function pipes into this one
+    Nothing,
+    /// The pipe from which the value is read when first needed
+    Pending(File),
+    /// The value which was read
+    Value(Option<Piped>),
+}
+
+/// The text of a value returned by a function: a string, each element of an array, or the keys
+/// and values of a map, alternating as in the expansion of a map variable
+pub fn text_of(value: Option<Piped>) -> types::Args {
+    match value {
+        Some(Value::Str(value)) => args![value],
+        Some(Value::Array(array)) => array.iter().map(|value| value.to_string().into()).collect(),
+        Some(Value::HashMap(map)) => map_pairs(map.iter()),
+        Some(Value::BTreeMap(map)) => map_pairs(map.iter()),
+        _ => types::Args::new(),
+    }
+}
+
+fn encode_str(string: &str, bytes: &mut Vec<u8>) {
+    bytes.extend_from_slice(&(string.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// Write a value to the bytes sent to the next function, each string and collection being
+/// prefixed by its length. Functions and aliases are not sent.
+fn encode(value: &Piped, bytes: &mut Vec<u8>) {
+    match value {
+        Value::Str(string) => {
+            bytes.push(b's');
+            encode_str(string, bytes);
+        }
+        Value::Array(array) => {
+            bytes.push(b'a');
+            bytes.extend_from_slice(&(array.len() as u64).to_le_bytes());
+            array.iter().for_each(|value| encode(value, bytes));
+        }
+        Value::HashMap(map) => {
+            bytes.push(b'h');
+            bytes.extend_from_slice(&(map.len() as u64).to_le_bytes());
+            for (key, value) in map {
+                encode_str(key, bytes);
+                encode(value, bytes);
+            }
+        }
+        Value::BTreeMap(map) => {
+            bytes.push(b'b');
+            bytes.extend_from_slice(&(map.len() as u64).to_le_bytes());
+            for (key, value) in map {
+                encode_str(key, bytes);
+                encode(value, bytes);
+            }
+        }
+        _ => bytes.push(b'n'),
+    }
+}
+
+fn decode_len(bytes: &mut &[u8]) -> Option<usize> {
+    if bytes.len() < 8 {
+        return None;
+    }
+    let (len, rest) = bytes.split_at(8);
+    *bytes = rest;
+    let mut buffer = [0; 8];
+    buffer.copy_from_slice(len);
+    Some(u64::from_le_bytes(buffer) as usize)
+}
+
+fn decode_str(bytes: &mut &[u8]) -> Option<types::Str> {
+    let len = decode_len(bytes)?;
+    if bytes.len() < len {
+        return None;
+    }
+    let (string, rest) = bytes.split_at(len);
+    *bytes = rest;
+    std::str::from_utf8(string).ok().map(Into::into)
+}
+
+/// Read back a value written by `encode`, or nothing if the bytes were cut short
+fn decode(bytes: &mut &[u8]) -> Option<Piped> {
+    let (&tag, rest) = bytes.split_first()?;
+    *bytes = rest;
+    match tag {
+        b's' => decode_str(bytes).map(Value::Str),
+        b'a' => {
+            let len = decode_len(bytes)?;
+            (0..len).map(|_| decode(bytes)).collect::<Option<_>>().map(Value::Array)
+        }
+        b'h' => {
+            let len = decode_len(bytes)?;
+            (0..len)
+                .map(|_| Some((decode_str(bytes)?, decode(bytes)?)))
+                .collect::<Option<_>>()
+                .map(Value::HashMap)
+        }
+        b'b' => {
+            let len = decode_len(bytes)?;
+            (0..len)
+                .map(|_| Some((decode_str(bytes)?, decode(bytes)?)))
+                .collect::<Option<_>>()
+                .map(Value::BTreeMap)
+        }
+        _ => Some(Value::None),
+    }
+}
+
+/// Send the value returned by a function to the ion function it pipes into. A function which
+/// exited before reading it does not stop the sender.
+pub(super) fn send(value: Option<Piped>, mut channel: File) {
+    if let Some(value) = value {
+        let mut bytes = Vec::new();
+        encode(&value, &mut bytes);
+        unsafe {
+            let _ = signal::signal(Signal::SIGPIPE, SigHandler::SigIgn);
+        }
+        let _ = channel.write_all(&bytes);
+    }
+}
+
+impl<'b> Shell<'b> {
+    /// The value of the `piped` variable, unless a variable of that name hides it: the value
+    /// returned by the ion function piping into the one run by this process, waiting for it to
+    /// return the first time it is needed
+    pub(crate) fn piped(&self, name: &str) -> Option<Piped> {
+        if name != "piped" || self.variables.get(name).is_some() {
+            return None;
+        }
+        let mut received = self.piped.borrow_mut();
+        if let Received::Pending(channel) = &mut *received {
+            let mut bytes = Vec::new();
+            let value = match channel.read_to_end(&mut bytes) {
+                Ok(_) => decode(&mut &bytes[..]).filter(|value| *value != Value::None),
+                Err(_) => None,
+            };
+            *received = Received::Value(value);
+        }
+        match &*received {
+            Received::Value(value) => value.clone(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_values() {
+        let mut map = types::HashMap::default();
+        map.insert("key".into(), Value::Array(vec![Value::Str("1".into()), Value::Str("".into())]));
+        let values = vec![
+            Value::Str("a string".into()),
+            Value::Array(vec![Value::Str("é".into()), Value::Array(Vec::new())]),
+            Value::HashMap(map),
+        ];
+        for value in values {
+            let mut bytes = Vec::new();
+            encode(&value, &mut bytes);
+            assert_eq!(decode(&mut &bytes[..]), Some(value.clone()));
+            assert_eq!(decode(&mut &bytes[..bytes.len() - 1]), None);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Reading of the files sourced by a script on worker threads, while the script runs.
+//!
+//! Before a script is executed, the `source` commands with a literal path are looked for in it,
+//! and each of the files they name is read and split into commands on a thread of its own. The
+//! `source` builtin then takes the commands of the file rather than reading it, waiting for the
+//! worker if it is not done yet. The commands still only run when `source` is reached.
+//!
+//! Only regular files are read ahead: reading a pipe or a terminal, as with `source /dev/stdin`,
+//! would take the input of the commands which run before. The commands are split by the worker,
+//! but they are not parsed: a command is parsed once its global aliases are expanded, with the
+//! aliases defined by the time it runs.
+use crate::parser::Terminator;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    env, fs, io, iter,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+/// The commands of a file, as they were when it was read
+#[derive(Debug)]
+struct Prefetched {
+    modified: Option<SystemTime>,
+    commands: Vec<String>,
+}
+
+/// The files being read ahead of their execution, by absolute path
+#[derive(Debug, Default)]
+pub(crate) struct Prefetcher {
+    pending: HashMap<PathBuf, JoinHandle<io::Result<Prefetched>>>,
+}
+
+impl Prefetcher {
+    /// Start reading the files sourced with a literal path by the commands
+    pub(crate) fn scan<S: AsRef<str>>(&mut self, commands: &[S]) {
+        let paths = commands
+            .iter()
+            .flat_map(|command| command.as_ref().lines())
+            .filter_map(sourced_path)
+            .filter_map(absolute)
+            .filter(|path| fs::metadata(path).map_or(false, |metadata| metadata.is_file()));
+        for path in paths {
+            if !self.pending.contains_key(&path) {
+                let worker = path.clone();
+                self.pending.insert(path, thread::spawn(move || read(&worker)));
+            }
+        }
+    }
+
+    /// Take the commands of the file, if it was read ahead and did not change since
+    pub(crate) fn take(&mut self, path: &Path) -> Option<Vec<String>> {
+        let prefetched = self.pending.remove(&absolute(path)?)?.join().ok()?.ok()?;
+        let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && modified == prefetched.modified {
+            Some(prefetched.commands)
+        } else {
+            None
+        }
+    }
+}
+
+/// Read the file and split it into the commands which the shell executes one at a time. The file
+/// may have been replaced since it was scanned, so it is checked to still be a regular file.
+fn read(path: &Path) -> io::Result<Prefetched> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "not a regular file"));
+    }
+    let modified = metadata.modified().ok();
+    let script = fs::read(path)?;
+    Ok(Prefetched { modified, commands: split(script.into_iter()) })
+}
+
+/// Split a script into its terminated commands. The line breaks read along with each command are
+/// kept, those before it first, so that the lines of the script are still counted when it runs.
+pub(crate) fn split(script: impl Iterator<Item = u8>) -> Vec<String> {
+    let (newlines, ended) = (Cell::new(0), Cell::new(false));
+    let mut script = script.inspect(|&byte| {
+        newlines.set(newlines.get() + usize::from(byte == b'\n'));
+        ended.set(byte == b'\n');
+    });
+    iter::from_fn(|| {
+        let command = Terminator::new(&mut script).terminate()?;
+        let after = usize::from(ended.replace(false));
+        let before = newlines.replace(0) - after;
+        Some("\n".repeat(before) + &command + &"\n".repeat(after))
+    })
+    .collect()
+}
+
+/// The path given to a `source` command, if it is written literally
+fn sourced_path(line: &str) -> Option<&str> {
+    let mut words = line.trim().split_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("source"), Some(path), None)
+            if !path.contains(|c| "$@~'\"\\*?[]{}()".contains(c)) && !path.starts_with("ion:") =>
+        {
+            Some(path)
+        }
+        _ => None,
+    }
+}
+
+fn absolute<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    env::current_dir().ok().map(|dir| dir.join(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn literal_sources() {
+        assert_eq!(sourced_path("  source lib/strings.ion"), Some("lib/strings.ion"));
+        assert_eq!(sourced_path("source $HOME/lib.ion"), None);
+        assert_eq!(sourced_path("source lib.ion first second"), None);
+        assert_eq!(sourced_path("echo source lib.ion"), None);
+        assert_eq!(sourced_path("source ion:structured"), None);
+    }
+
+    #[test]
+    fn split_keeps_line_breaks() {
+        let commands = split("let x = 1\n\n# comment\necho $x".bytes());
+        let lines: Vec<_> = commands.iter().map(|command| command.matches('\n').count()).collect();
+        assert_eq!(lines, [1, 2]);
+        assert!(commands[0].starts_with("let x = 1"));
+        assert_eq!(commands[1].trim(), "echo $x");
+    }
+
+    #[test]
+    fn prefetched_files_are_taken_once() {
+        let dir = env::temp_dir().join(format!("ion-prefetch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let library = dir.join("library.ion");
+        let script = "let x = 1\nfn f\n    echo $x\nend\n";
+        fs::write(&library, script).unwrap();
+
+        let mut prefetcher = Prefetcher::default();
+        prefetcher.scan(&[format!("source {}", library.display())]);
+        assert_eq!(prefetcher.take(&library), Some(split(script.bytes())));
+        assert_eq!(prefetcher.take(&library), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_regular_files_are_read() {
+        let mut prefetcher = Prefetcher::default();
+        prefetcher.scan(&["source /dev/stdin", "source /dev/null", "source /"]);
+        assert!(prefetcher.pending.is_empty());
+        assert_eq!(read(Path::new("/dev/null")).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
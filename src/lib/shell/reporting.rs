@@ -0,0 +1,102 @@
+//! Printing of the runtime errors, collapsing those which repeat inside of loops.
+//!
+//! While the `collapse-errors` option is set, the first occurrence of each error raised by the
+//! body of a loop is printed as usual, and the following ones are only counted. Once the
+//! outermost loop is done, every error which repeated is summarized in a single line.
+use std::cell::RefCell;
+
+thread_local! {
+    static REPORTER: RefCell<Reporter> = RefCell::new(Reporter::default());
+}
+
+#[derive(Debug, Default)]
+struct Reporter {
+    /// How many collapsing loops are being executed
+    depth:    usize,
+    /// The errors met in the outermost loop, in order of first occurrence, with their count
+    repeated: Vec<(String, usize)>,
+}
+
+impl Reporter {
+    /// Count the error, returning it if it is to be printed
+    fn report(&mut self, message: String) -> Option<String> {
+        if self.depth == 0 {
+            return Some(message);
+        }
+        match self.repeated.iter_mut().find(|(seen, _)| *seen == message) {
+            Some((_, count)) => {
+                *count += 1;
+                None
+            }
+            None => {
+                self.repeated.push((message.clone(), 1));
+                Some(message)
+            }
+        }
+    }
+
+    /// Leave a loop, returning the summaries of the repeated errors if it was the outermost one
+    fn leave(&mut self) -> Vec<String> {
+        self.depth -= 1;
+        if self.depth != 0 {
+            return Vec::new();
+        }
+        self.repeated
+            .drain(..)
+            .filter(|&(_, count)| count > 1)
+            .map(|(message, count)| {
+                format!("ion: error repeated {} more times: {}", count - 1, message)
+            })
+            .collect()
+    }
+}
+
+/// Print an error on the standard error, unless it repeats one met earlier in the loop
+pub(crate) fn report<T: Into<String>>(message: T) {
+    if let Some(message) = REPORTER.with(|reporter| reporter.borrow_mut().report(message.into())) {
+        eprintln!("{}", message);
+    }
+}
+
+/// Collapses the repeated errors while it is alive. Loops hold one while they execute.
+#[derive(Debug)]
+pub(crate) struct Collapsing(());
+
+impl Collapsing {
+    pub(crate) fn new() -> Self {
+        REPORTER.with(|reporter| reporter.borrow_mut().depth += 1);
+        Self(())
+    }
+}
+
+impl Drop for Collapsing {
+    fn drop(&mut self) {
+        for summary in REPORTER.with(|reporter| reporter.borrow_mut().leave()) {
+            eprintln!("{}", summary);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_errors_are_summarized() {
+        let mut reporter = Reporter::default();
+        assert_eq!(reporter.report("outside".into()), Some("outside".into()));
+        assert_eq!(reporter.report("outside".into()), Some("outside".into()));
+
+        reporter.depth += 1;
+        assert_eq!(reporter.report("first".into()), Some("first".into()));
+        reporter.depth += 1;
+        assert_eq!(reporter.report("second".into()), Some("second".into()));
+        assert_eq!(reporter.report("first".into()), None);
+        assert_eq!(reporter.leave(), Vec::<String>::new());
+        assert_eq!(reporter.report("first".into()), None);
+        assert_eq!(reporter.report("once".into()), Some("once".into()));
+        assert_eq!(reporter.leave(), vec!["ion: error repeated 2 more times: first".to_string()]);
+
+        assert_eq!(reporter.report("first".into()), Some("first".into()));
+    }
+}
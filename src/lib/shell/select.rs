@@ -0,0 +1,148 @@
+//! The menu of a `select` loop, from which a value is chosen on each iteration.
+//!
+//! When the standard input and error are terminals, the options are listed on the terminal and
+//! one is picked with the arrow keys, or by typing its number, then confirmed with enter.
+//! Otherwise the numbered options are printed on the standard error, and each line read from the
+//! standard input chooses one, by its number or by its text, until one is chosen.
+use crate::types;
+use nix::unistd;
+use std::io::{self, Write};
+use termion::{clear, cursor, event::Key, input::TermRead, raw::IntoRawMode, style};
+
+/// The option given by its number, counted from 1, or by its text
+fn choice(options: &[types::Str], answer: &str) -> Option<usize> {
+    match answer.parse::<usize>() {
+        Ok(number) => number.checked_sub(1).filter(|&index| index < options.len()),
+        Err(_) => options.iter().position(|option| option == answer),
+    }
+}
+
+/// Choose one of the options, or none once the input ends or the menu is left
+pub(super) fn choose(options: &[types::Str], prompt: &str) -> io::Result<Option<usize>> {
+    if options.is_empty() {
+        Ok(None)
+    } else if atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr) {
+        pick(options, prompt)
+    } else {
+        ask(options, prompt)
+    }
+}
+
+/// Print the numbered options, then read lines until one of them is chosen
+fn ask(options: &[types::Str], prompt: &str) -> io::Result<Option<usize>> {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    loop {
+        for (number, option) in options.iter().enumerate() {
+            writeln!(stderr, "{}) {}", number + 1, option)?;
+        }
+        write!(stderr, "{}", prompt)?;
+        stderr.flush()?;
+        match read_line()? {
+            Some(answer) => {
+                if let Some(index) = choice(options, answer.trim()) {
+                    return Ok(Some(index));
+                }
+            }
+            None => {
+                writeln!(stderr)?;
+                return Ok(None);
+            }
+        }
+    }
+}
+
+/// Read a line from the standard input a byte at a time, so that the statements of the loop may
+/// read what follows it
+fn read_line() -> io::Result<Option<String>> {
+    let mut line = Vec::new();
+    loop {
+        let mut byte = [0];
+        match unistd::read(nix::libc::STDIN_FILENO, &mut byte)? {
+            0 if line.is_empty() => return Ok(None),
+            0 => break,
+            _ if byte[0] == b'\n' => break,
+            _ => line.push(byte[0]),
+        }
+    }
+    Ok(Some(String::from_utf8_lossy(&line).into_owned()))
+}
+
+/// Move a highlight through the options listed on the terminal, until one is chosen with enter,
+/// or the menu is left with escape, `q`, or the end of the input
+fn pick(options: &[types::Str], prompt: &str) -> io::Result<Option<usize>> {
+    let mut keys = termion::get_tty()?.keys();
+    let mut tty = termion::get_tty()?.into_raw_mode()?;
+    let last = options.len() - 1;
+    let mut selected = 0;
+    let mut typed = String::new();
+    write!(tty, "{}", cursor::Hide)?;
+    let chosen = loop {
+        for (number, option) in options.iter().enumerate() {
+            if number == selected {
+                write!(tty, "> {}{}) {}{}\r\n", style::Invert, number + 1, option, style::Reset)?;
+            } else {
+                write!(tty, "  {}) {}\r\n", number + 1, option)?;
+            }
+        }
+        write!(tty, "{}{}", prompt, typed)?;
+        tty.flush()?;
+
+        let key = match keys.next() {
+            Some(key) => key?,
+            None => break None,
+        };
+        write!(tty, "\r{}{}", cursor::Up(options.len() as u16), clear::AfterCursor)?;
+        match key {
+            Key::Char('\n') | Key::Char('\r') => break Some(selected),
+            Key::Esc | Key::Char('q') | Key::Ctrl('c') | Key::Ctrl('d') => break None,
+            Key::Up | Key::Char('k') | Key::BackTab => {
+                selected = selected.checked_sub(1).unwrap_or(last)
+            }
+            Key::Down | Key::Char('j') | Key::Char('\t') => {
+                selected = if selected == last { 0 } else { selected + 1 }
+            }
+            Key::Home => selected = 0,
+            Key::End => selected = last,
+            // The digits typed make up the number of the option to highlight
+            Key::Char(digit) if digit.is_ascii_digit() => {
+                typed.push(digit);
+                if choice(options, &typed).is_none() {
+                    typed = digit.to_string();
+                }
+                selected = choice(options, &typed).unwrap_or(selected);
+                continue;
+            }
+            Key::Backspace => {
+                typed.pop();
+                continue;
+            }
+            _ => (),
+        }
+        typed.clear();
+    };
+    match chosen {
+        Some(index) => write!(tty, "{}{}\r\n", prompt, options[index])?,
+        None => write!(tty, "\r\n")?,
+    }
+    write!(tty, "{}", cursor::Show)?;
+    tty.flush()?;
+    Ok(chosen)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn choices() {
+        let options: Vec<types::Str> = vec!["red".into(), "green".into(), "10".into()];
+        assert_eq!(choice(&options, "1"), Some(0));
+        assert_eq!(choice(&options, "green"), Some(1));
+        assert_eq!(choice(&options, "3"), Some(2));
+        // Numbers are those of the options rather than their text
+        assert_eq!(choice(&options, "10"), None);
+        assert_eq!(choice(&options, "0"), None);
+        assert_eq!(choice(&options, "blue"), None);
+    }
+}
@@ -1,7 +1,11 @@
 use super::{
-    pipe_exec::create_pipe, sys::NULL_PATH, variables::Value, IonError, PipelineError, Shell,
+    pipe_exec::{create_pipe, text_of},
+    sys::NULL_PATH,
+    variables::Value,
+    IonError, PipelineError, Shell,
 };
 use crate::{
+    builtins::random,
     expansion::{Error, Expander, Result, Select},
     types,
 };
@@ -12,6 +16,22 @@ use std::{env, fs::File, io::Read};
 #[cfg(not(target_os = "redox"))]
 use users::os::unix::UserExt;
 
+/// The keys and values of a map, alternating, so that `for key value in @map` iterates on its
+/// entries. Each value is kept as a single element, even if it is an array.
+pub(super) fn map_pairs<'a, T: 'a, I>(entries: I) -> types::Args
+where
+    I: Iterator<Item = (&'a types::Str, &'a Value<T>)>,
+{
+    let mut array = types::Args::new();
+    for (key, value) in entries {
+        if let Value::Str(_) | Value::Array(_) | Value::HashMap(_) | Value::BTreeMap(_) = *value {
+            array.push(key.clone());
+            array.push(value.to_string().into());
+        }
+    }
+    array
+}
+
 impl<'a, 'b> Expander for Shell<'b> {
     type Error = IonError;
 
@@ -54,22 +74,105 @@ impl<'a, 'b> Expander for Shell<'b> {
         }
     }
 
+    /// Runs the command of a process substitution in the background, connected to a named pipe
+    fn substitute(&mut self, command: &str, input: bool) -> Result<types::Str, Self::Error> {
+        let substitution = self
+            .fork_substitution(command, input)
+            .map_err(|err| Error::Subprocess(Box::new(err.into())))?;
+        let path = substitution.path().to_string_lossy().as_ref().into();
+        self.substitutions.push(substitution);
+        Ok(path)
+    }
+
     /// Expand a string variable given if its quoted / unquoted
     fn string(&self, name: &str) -> Result<types::Str, Self::Error> {
         if name == "?" {
             Ok(self.previous_status.into())
+        } else if let Some(secret) = name.strip_prefix("secret::") {
+            self.secrets.borrow_mut().get(secret).map(Into::into).map_err(Error::Secret)
+        } else if let Some(variable) = name.strip_prefix("rand::") {
+            let mut seeded = self.rng.borrow_mut();
+            let value = random::variable(&mut *random::generator(&mut seeded), variable);
+            value.ok_or_else(|| Error::UnsupportedNamespace(name.into()))
+        } else if let Some(variable) = name.strip_prefix("status::") {
+            self.status_variable(variable).ok_or_else(|| Error::UnsupportedNamespace(name.into()))
+        } else if let Some((lookup, key)) = name
+            .split_once("::")
+            .and_then(|(namespace, key)| Some((self.namespaces.get(namespace)?, key)))
+        {
+            lookup(self, key).ok_or_else(|| Error::VarNotFound(name.into()))
+        } else if let Some(Value::Str(value)) = self.piped(name) {
+            Ok(value)
         } else {
             self.variables().get_str(name).map_err(Into::into)
         }
     }
 
+    /// Assign a string variable, from an arithmetic expansion
+    fn set_string(&mut self, name: &str, value: &str) -> Result<(), Self::Error> {
+        self.variables.set(name, value);
+        Ok(())
+    }
+
+    /// Call a function, and expand the value it returned
+    fn call(&mut self, name: &str, args: &[&str]) -> Option<Result<types::Args, Self::Error>> {
+        let function = match self.variables.get(name) {
+            Some(Value::Function(function)) => function.clone(),
+            _ => return None,
+        };
+        let args: Vec<&str> = std::iter::once(name).chain(args.iter().copied()).collect();
+        if function.is_generator() {
+            return Some(
+                self.collect_generated(&function, &args)
+                    .map_err(|why| Error::Subprocess(Box::new(why))),
+            );
+        }
+        if let Err(why) = function.execute(self, &args) {
+            return Some(Err(Error::Subprocess(Box::new(why))));
+        }
+        Some(Ok(text_of(self.return_value.take())))
+    }
+
+    /// Whether the last function called succeeded
+    fn succeeded(&self) -> bool { self.previous_status.is_success() }
+
+    fn big_math(&self) -> bool { self.opts.big_math }
+
+    fn null_glob(&self) -> bool { self.opts.null_glob }
+
+    fn fail_glob(&self) -> bool { self.opts.fail_glob }
+
     /// Expand an array variable with some selection
     fn array(
         &self,
         name: &str,
         selection: &Select<types::Str>,
     ) -> Result<types::Args, Self::Error> {
-        match self.variables.get(name) {
+        // The statuses of the last pipeline are also an array of the `status::` namespace
+        let name = if name == "status::pipestatus" { "PIPESTATUS" } else { name };
+        // The directory stack is an array of the shell, and the value returned by the function
+        // piping into this one is received when first expanded, unless a variable hides them
+        let synthesized;
+        let value = match self.variables.get(name) {
+            None if name == "dirstack" => {
+                synthesized = Value::Array(
+                    self.directory_stack
+                        .dirs()
+                        .map(|dir| Value::Str(dir.to_string_lossy().as_ref().into()))
+                        .collect(),
+                );
+                Some(&synthesized)
+            }
+            None => match self.piped(name) {
+                Some(piped) => {
+                    synthesized = piped;
+                    Some(&synthesized)
+                }
+                None => None,
+            },
+            value => value,
+        };
+        match value {
             Some(Value::Array(array)) => match selection {
                 Select::All => Ok(array.iter().map(|x| format!("{}", x).into()).collect()),
                 Select::Index(ref id) => id
@@ -77,43 +180,17 @@ impl<'a, 'b> Expander for Shell<'b> {
                     .and_then(|n| array.get(n))
                     .map(|x| args![types::Str::from(format!("{}", x))])
                     .ok_or(Error::OutOfBound { length: array.len(), index: *id }),
-                Select::Range(ref range) => range
-                    .bounds(array.len())
-                    .and_then(|(start, length)| {
-                        if array.len() > start {
-                            Some(
-                                array
-                                    .iter()
-                                    .skip(start)
-                                    .take(length)
-                                    .map(|var| format!("{}", var).into())
-                                    .collect(),
-                            )
-                        } else {
-                            None
-                        }
-                    })
-                    .ok_or(Error::InvalidRange { length: array.len(), range: *range }),
+                Select::Range(ref range) if range.starts_past(array.len()) => {
+                    Err(Error::InvalidRange { length: array.len(), range: *range })
+                }
+                Select::Range(ref range) => Ok(range
+                    .indices(array.len())
+                    .map(|index| format!("{}", array[index]).into())
+                    .collect()),
                 Select::Key(_) => Err(Error::InvalidIndex(selection.clone(), "array", name.into())),
             },
             Some(Value::HashMap(hmap)) => match selection {
-                Select::All => {
-                    let mut array = types::Args::new();
-                    for (key, value) in hmap.iter() {
-                        array.push(key.clone());
-                        let f = format!("{}", value);
-                        match *value {
-                            Value::Str(_) => array.push(f.into()),
-                            Value::Array(_) | Value::HashMap(_) | Value::BTreeMap(_) => {
-                                for split in f.split_whitespace() {
-                                    array.push(split.into());
-                                }
-                            }
-                            _ => (),
-                        }
-                    }
-                    Ok(array)
-                }
+                Select::All => Ok(map_pairs(hmap.iter())),
                 Select::Key(key) => {
                     Ok(args![format!("{}", hmap.get(&*key).unwrap_or(&Value::Str("".into())))])
                 }
@@ -136,23 +213,7 @@ impl<'a, 'b> Expander for Shell<'b> {
                 }
             },
             Some(Value::BTreeMap(bmap)) => match selection {
-                Select::All => {
-                    let mut array = types::Args::new();
-                    for (key, value) in bmap.iter() {
-                        array.push(key.clone());
-                        let f = format!("{}", value);
-                        match *value {
-                            Value::Str(_) => array.push(f.into()),
-                            Value::Array(_) | Value::HashMap(_) | Value::BTreeMap(_) => {
-                                for split in f.split_whitespace() {
-                                    array.push(split.into());
-                                }
-                            }
-                            _ => (),
-                        }
-                    }
-                    Ok(array)
-                }
+                Select::All => Ok(map_pairs(bmap.iter())),
                 Select::Key(key) => {
                     Ok(args![format!("{}", bmap.get(&*key).unwrap_or(&Value::Str("".into())))])
                 }
@@ -180,7 +241,8 @@ impl<'a, 'b> Expander for Shell<'b> {
     }
 
     fn map_keys(&self, name: &str) -> Result<types::Args, Self::Error> {
-        match self.variables.get(name) {
+        let piped = self.piped(name);
+        match piped.as_ref().or_else(|| self.variables.get(name)) {
             Some(&Value::HashMap(ref map)) => {
                 Ok(map.keys().map(|x| x.to_string().into()).collect())
             }
@@ -193,7 +255,8 @@ impl<'a, 'b> Expander for Shell<'b> {
     }
 
     fn map_values(&self, name: &str) -> Result<types::Args, Self::Error> {
-        match self.variables.get(name) {
+        let piped = self.piped(name);
+        match piped.as_ref().or_else(|| self.variables.get(name)) {
             Some(&Value::HashMap(ref map)) => {
                 Ok(map.values().map(|x| x.to_string().into()).collect())
             }
@@ -271,3 +334,23 @@ impl<'a, 'b> Expander for Shell<'b> {
         .map(|home: types::Str| home + rest)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_namespace() {
+        let mut shell = Shell::default();
+        let settings = |_: &Shell<'_>, key: &str| match key {
+            "settings.theme" => Some("dark".into()),
+            _ => None,
+        };
+        assert!(shell.register_namespace("kv", Box::new(settings)));
+        assert!(!shell.register_namespace("env", Box::new(settings)));
+        assert_eq!(shell.string("kv::settings.theme").unwrap(), "dark");
+        assert!(shell.string("kv::settings.font").is_err());
+        assert!(shell.unregister_namespace("kv").is_some());
+        assert!(shell.string("kv::settings.theme").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Detection of the container, toolbox or chroot the shell runs in, exposed as
+//! `${sys::container}`.
+use std::{env, fs, os::unix::fs::MetadataExt, path::Path};
+
+/// The kind of container the shell runs in, or `None` when it runs on the host.
+///
+/// This is `toolbox`, `podman`, `docker`, the value given by the container manager in the
+/// `container` environment variable or in `/run/systemd/container` (such as `lxc` or
+/// `systemd-nspawn`), or `chroot` when the root directory differs from the one of the init
+/// process.
+#[must_use]
+pub fn detect() -> Option<String> {
+    if Path::new("/run/.toolboxenv").exists() {
+        return Some("toolbox".into());
+    }
+    if Path::new("/run/.containerenv").exists() {
+        return Some("podman".into());
+    }
+    if Path::new("/.dockerenv").exists() {
+        return Some("docker".into());
+    }
+    let manager = env::var("container")
+        .ok()
+        .or_else(|| fs::read_to_string("/run/systemd/container").ok())
+        .map(|manager| manager.trim().to_string())
+        .filter(|manager| !manager.is_empty());
+    if manager.is_some() {
+        return manager;
+    }
+    if is_chroot() {
+        return Some("chroot".into());
+    }
+    None
+}
+
+/// Whether the root directory is not the one of the init process. The root of init may not be
+/// readable without privileges, in which case a chroot is not detected.
+fn is_chroot() -> bool {
+    match (fs::metadata("/"), fs::metadata("/proc/1/root/")) {
+        (Ok(root), Ok(init)) => root.dev() != init.dev() || root.ino() != init.ino(),
+        _ => false,
+    }
+}
@@ -1,4 +1,7 @@
-//! System specific shell variables for NULL_PATH
+//! System specific shell variables for NULL_PATH, and introspection of the environment of the
+//! shell
+
+pub mod container;
 
 #[cfg(target_os = "redox")]
 /// NULL_PATH on Redox OS
@@ -0,0 +1,114 @@
+//! The commands run when the shell receives a signal, when it exits, and when a pipeline fails,
+//! as set by the `trap` builtin.
+//!
+//! The signals are only handled between statements, so the command trapped on a signal runs once
+//! the statement being executed is done, and the execution resumes after it rather than being
+//! interrupted.
+use super::{flow_control::Statement, reporting, Shell};
+use nix::sys::signal::Signal;
+use std::{fmt, mem, str::FromStr};
+
+/// An event on which the shell runs a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Trap {
+    /// The shell exits
+    Exit,
+    /// The shell received the signal, which is one of `SIGHUP`, `SIGINT` and `SIGTERM`
+    Signal(Signal),
+    /// A pipeline failed, outside of the conditions of `if` and `while`
+    Err,
+}
+
+impl FromStr for Trap {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        let upper = name.to_ascii_uppercase();
+        match upper.strip_prefix("SIG").unwrap_or(&upper) {
+            "EXIT" | "0" => Ok(Self::Exit),
+            "ERR" => Ok(Self::Err),
+            "HUP" | "1" => Ok(Self::Signal(Signal::SIGHUP)),
+            "INT" | "2" => Ok(Self::Signal(Signal::SIGINT)),
+            "TERM" | "15" => Ok(Self::Signal(Signal::SIGTERM)),
+            _ => Err(format!("cannot trap '{}': only EXIT, ERR, HUP, INT and TERM can be", name)),
+        }
+    }
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exit => write!(f, "EXIT"),
+            Self::Signal(signal) => write!(f, "{}", &signal.as_str()[3..]),
+            Self::Err => write!(f, "ERR"),
+        }
+    }
+}
+
+impl<'a> Shell<'a> {
+    /// Run the command trapped on the event, returning whether there is one. The command leaves
+    /// the exit status unchanged, and the events met while it runs are not trapped. The command
+    /// trapped on `EXIT` only runs once.
+    pub fn run_trap(&mut self, trap: Trap) -> bool {
+        if self.running_trap {
+            return false;
+        }
+        let command = match trap {
+            Trap::Exit => self.traps.remove(&trap),
+            _ => self.traps.get(&trap).cloned(),
+        };
+        let command = match command {
+            Some(command) => command,
+            None => return false,
+        };
+
+        self.running_trap = true;
+        let status = self.previous_status;
+        let blocks = mem::take(&mut self.flow_control);
+        let result = self.on_command(command.bytes(), false).and_then(|_| self.end_of_script());
+        if let Err(why) = result {
+            reporting::report(format!("ion: trap {}: {}", trap, why));
+        }
+        self.flow_control = blocks;
+        self.previous_status = status;
+        self.variables.set("?", status);
+        self.running_trap = false;
+        true
+    }
+
+    /// Run the ERR trap held back after a pipeline failed, unless the next statement is the rest
+    /// of an `&&` or `||` list, as only the failure of the last pipeline of a list is trapped
+    pub(crate) fn release_err_trap(&mut self, next: Option<&Statement>) {
+        if mem::take(&mut self.err_trap_pending)
+            && !matches!(next, Some(Statement::And(_)) | Some(Statement::Or(_)))
+        {
+            self.run_trap(Trap::Err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trap_names() {
+        assert_eq!("INT".parse(), Ok(Trap::Signal(Signal::SIGINT)));
+        assert_eq!("sigterm".parse(), Ok(Trap::Signal(Signal::SIGTERM)));
+        assert_eq!("1".parse(), Ok(Trap::Signal(Signal::SIGHUP)));
+        assert_eq!("0".parse(), Ok(Trap::Exit));
+        assert_eq!("ERR".parse(), Ok(Trap::Err));
+        assert!("USR1".parse::<Trap>().is_err());
+        assert_eq!(Trap::Signal(Signal::SIGINT).to_string(), "INT");
+    }
+
+    #[test]
+    fn traps_keep_the_status() {
+        let mut shell = Shell::new();
+        shell.traps_mut().insert(Trap::Err, "let failures = 1".into());
+        shell.execute_command("false".as_bytes()).unwrap();
+        assert_eq!(shell.previous_status(), crate::builtins::Status::FALSE);
+        assert!(shell.variables().get("failures").is_some());
+        assert!(!shell.run_trap(Trap::Exit));
+    }
+}
@@ -1,4 +1,4 @@
-use super::{colors::Colors, flow_control::Function};
+use super::{colors::Colors, flow_control::Function, secrets, sys::container};
 use crate::{
     expansion,
     shell::IonError,
@@ -67,6 +67,11 @@ impl Variables {
         })
     }
 
+    /// Get all the definitions: the variables, aliases and functions, the innermost last
+    pub fn definitions(&self) -> impl Iterator<Item = (&types::Str, &Value<Rc<Function>>)> {
+        self.0.scopes().flat_map(|map| map.iter())
+    }
+
     /// Get all the array values
     pub fn arrays(&self) -> impl Iterator<Item = (&types::Str, &types::Array<Rc<Function>>)> {
         self.0.scopes().rev().flat_map(|map| {
@@ -87,6 +92,32 @@ impl Variables {
     /// Exit the current scope
     pub fn pop_scope(&mut self) { self.0.pop_scope() }
 
+    /// Exit the current scope, returning the definitions it held
+    pub(crate) fn take_scope(&mut self) -> Vec<(types::Str, Value<Rc<Function>>)> {
+        let definitions =
+            self.0.scopes_mut().next().map(|scope| scope.drain().collect()).unwrap_or_default();
+        self.0.pop_scope();
+        definitions
+    }
+
+    /// Define the members of a module, named `module::name`, under their own names in the
+    /// current scope
+    pub(crate) fn enter_module(&mut self, module: &str) {
+        let members: Vec<_> = self
+            .0
+            .scopes()
+            .rev()
+            .flat_map(|scope| scope.iter())
+            .filter_map(|(name, value)| {
+                let member = name.strip_prefix(module)?.strip_prefix("::")?;
+                Some((types::Str::from(member), value.clone()))
+            })
+            .collect();
+        for (name, value) in members {
+            self.0.set(name, value);
+        }
+    }
+
     pub(crate) fn pop_scopes(
         &mut self,
         index: usize,
@@ -115,6 +146,21 @@ impl Variables {
         }
     }
 
+    /// Set a variable to a value in the current scope, shadowing the variables of the same name
+    /// in the outer scopes until it is popped
+    pub(crate) fn set_current<T: Into<Value<Rc<Function>>>>(&mut self, name: &str, value: T) {
+        self.0.set(name, value.into());
+    }
+
+    /// Set a variable to a value in the scope of the current function, or in the top scope
+    /// outside of functions. The variables of the same name in the function are removed, so it is
+    /// not shadowed by the ones of the blocks it runs in, and it is dropped when the function
+    /// returns.
+    pub fn set_local<T: Into<Value<Rc<Function>>>>(&mut self, name: &str, value: T) {
+        while self.0.remove_variable(name).is_some() {}
+        self.0.set_namespace(name, value.into());
+    }
+
     /// Set a variable to a value in the top scope.
     /// If a variable already exists in any scope, it is updated and is put in the global scope.
     pub fn set_global<T: Into<Value<Rc<Function>>>>(&mut self, name: &str, value: T) {
@@ -205,16 +251,23 @@ impl Variables {
                 Ok((c as char).to_string().into())
             }
             Some(("env", variable)) => Ok(env::var(variable).unwrap_or_default().into()),
+            Some(("sys", "container")) => Ok(container::detect().unwrap_or_default().into()),
             Some(("super", _)) | Some(("global", _)) | None => {
                 // Otherwise, it's just a simple variable name.
                 match self.get(name) {
                     Some(Value::Str(val)) => Ok(val.clone()),
+                    // An optional argument which was not given hides the environment variable
+                    Some(Value::None) => Err(Error::VarNotFound(name.into())),
                     _ => {
                         env::var(name).map(Into::into).map_err(|_| Error::VarNotFound(name.into()))
                     }
                 }
             }
-            Some((..)) => Err(Error::UnsupportedNamespace(name.into())),
+            // The variables of imported modules
+            Some((..)) => match self.get(name) {
+                Some(Value::Str(val)) => Ok(val.clone()),
+                _ => Err(Error::UnsupportedNamespace(name.into())),
+            },
         }
     }
 
@@ -272,6 +325,13 @@ impl Default for Variables {
         map.set("EUID", Value::Str(geteuid().to_string().into()));
 
         map.set("CDPATH", Array::new());
+        map.set(
+            "REDACT_PATTERNS",
+            secrets::DEFAULT_REDACT_PATTERNS
+                .iter()
+                .map(|&pattern| pattern.into())
+                .collect::<Array>(),
+        );
 
         // Initialize the HOST variable
         let mut host_name = [0_u8; 512];
@@ -328,6 +388,15 @@ pub(crate) mod tests {
         fn map_values(&self, name: &str) -> Result<types::Args, Self::Error> {
             Err(expansion::Error::VarNotFound(name.into()))
         }
+
+        fn set_string(&mut self, name: &str, value: &str) -> Result<(), Self::Error> {
+            self.0.set(name, value);
+            Ok(())
+        }
+
+        fn substitute(&mut self, cmd: &str, _input: bool) -> Result<types::Str, Self::Error> {
+            Ok(cmd.into())
+        }
     }
 
     #[test]
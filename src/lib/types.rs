@@ -4,6 +4,42 @@ pub use types_rs::{array, types::*};
 pub use crate::shell::flow_control::Function;
 /// A owned version of a set of arguments for spawning a command
 pub type Args = SmallVec<[small::String; 4]>;
+
+/// Serialization of the strings of the parsed commands, which are not serde types themselves
+#[cfg(feature = "serde")]
+pub(crate) mod serde_str {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        value: &small::String,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(value.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<small::String, D::Error> {
+        String::deserialize(deserializer).map(Into::into)
+    }
+}
+
+/// Serialization of the arguments of the parsed commands, as a sequence of strings
+#[cfg(feature = "serde")]
+pub(crate) mod serde_args {
+    use super::Args;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(args: &Args, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(args.iter().map(small::String::as_str))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Args, D::Error> {
+        Vec::<String>::deserialize(deserializer)
+            .map(|args| args.into_iter().map(Into::into).collect())
+    }
+}
+
 /// Construct a new Array containing the given arguments
 ///
 /// `array!` acts like the standard library's `vec!` macro, and can be thought
@@ -1,6 +1,6 @@
-use self::binary::{builtins, InteractiveShell};
+use self::binary::{builtins, doctor, migrate, print_error, tutor, InteractiveShell};
 use atty::Stream;
-use ion_shell::{BackgroundEvent, BuiltinMap, IonError, PipelineError, Shell, Value};
+use ion_shell::{BuiltinMap, IonError, PipelineError, Shell, Trap, Value};
 use liner::KeyBindings;
 use nix::{
     sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal},
@@ -9,8 +9,8 @@ use nix::{
 use std::{
     fs,
     io::{stdin, BufReader},
+    path::Path,
     process,
-    sync::Arc,
 };
 
 use crate::binary::MAN_ION;
@@ -193,6 +193,17 @@ fn main() {
         println!("{}", version());
         return;
     }
+    // Subcommands are only recognized when no script of the same name exists
+    if let (None, Some(subcommand)) = (&command_line_args.command, command_line_args.args.get(0)) {
+        if !Path::new(subcommand).exists() {
+            match subcommand.as_str() {
+                "doctor" if command_line_args.args.len() == 1 => process::exit(doctor::run()),
+                "migrate" => process::exit(migrate::run(&command_line_args.args[1..])),
+                "tutor" => process::exit(tutor::run(&command_line_args.args[1..])),
+                _ => (),
+            }
+        }
+    }
     if command_line_args.command.is_some() && !command_line_args.args.is_empty() {
         eprintln!("either execute command or file(s)");
         process::exit(1);
@@ -205,6 +216,8 @@ fn main() {
         .add("exec", &builtins::builtin_exec, "Replace the shell with the given command.")
         .add("exit", &builtins::builtin_exit, "Exits the current session")
         .add("suspend", &builtins::builtin_suspend, "Suspends the shell with a SIGTSTOP signal");
+    #[cfg(feature = "http")]
+    builtins.with_network();
 
     let stdin_is_a_tty = atty::is(Stream::Stdin);
     let mut shell = Shell::with_builtins(builtins);
@@ -215,18 +228,6 @@ fn main() {
         }
     }
 
-    shell.set_background_event(Some(Arc::new(|njob, pid, kind| match kind {
-        BackgroundEvent::Added => eprintln!("ion: bg [{}] {}", njob, pid),
-        BackgroundEvent::Stopped => eprintln!("ion: ([{}] {}) Stopped", njob, pid),
-        BackgroundEvent::Resumed => eprintln!("ion: ([{}] {}) Running", njob, pid),
-        BackgroundEvent::Exited(status) => {
-            eprintln!("ion: ([{}] {}) exited with {}", njob, pid, status)
-        }
-        BackgroundEvent::Errored(error) => {
-            eprintln!("ion: ([{}] {}) errored: {}", njob, pid, error)
-        }
-    })));
-
     shell.opts_mut().no_exec = command_line_args.no_execute;
     shell.opts_mut().grab_tty = stdin_is_a_tty;
     if command_line_args.print_commands {
@@ -272,13 +273,18 @@ fn main() {
         let mut reader = BufReader::new(stdin());
         loop {
             if let Err(err) = shell.execute_command(&mut reader) {
-                eprintln!("ion: {}", err);
+                print_error(&err);
             }
         }
     } else {
         shell.execute_command(BufReader::new(stdin()))
     }
     .and_then(|_| shell.wait_for_background().map_err(Into::into));
+    // The statements deferred by the script also run when it failed or was interrupted
+    shell.run_deferred();
+    shell.run_trap(Trap::Exit);
+    shell.terminate_coprocesses();
+    shell.close_substitutions();
     if let Err(IonError::PipelineExecutionError(PipelineError::Interrupted(_, signal))) = err {
         // When the job was aborted because of an interrupt signal, abort with this same signal
         let action = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
@@ -286,7 +292,7 @@ fn main() {
         let _ = nix::sys::signal::raise(signal);
     }
     if let Err(why) = err {
-        eprintln!("ion: {}", why);
+        print_error(&why);
         process::exit(1);
     }
     process::exit(shell.previous_status().as_os_code());
@@ -0,0 +1,63 @@
+mod pty;
+
+use pty::{touch, Pty, Screen};
+
+const INITRC: &str = "let PROMPT = \"test> \"\n";
+
+#[test]
+fn prompt_and_output() {
+    let mut shell = Pty::spawn(INITRC);
+    shell.wait_for("test>");
+    shell.send("echo hello\r");
+    let frame = shell.wait_for("hello\ntest>");
+    assert_eq!(frame, "test> echo hello\nhello\ntest>");
+}
+
+#[test]
+fn prompt_function() {
+    let mut shell = Pty::spawn("let mood = calm\nfn PROMPT\n    echo -n \"[$mood] \"\nend\n");
+    shell.wait_for("[calm]");
+    shell.send("let mood = glad\r");
+    assert_eq!(shell.wait_for("\n[glad]"), "[calm] let mood = glad\n[glad]");
+}
+
+#[test]
+fn file_completion() {
+    let mut shell = Pty::spawn(INITRC);
+    touch(&shell.home(), "alpha/file.txt");
+    shell.wait_for("test>");
+    shell.send("ls alp\t");
+    shell.wait_for("test> ls alpha/");
+    shell.send("\t");
+    shell.wait_for("test> ls alpha/file.txt");
+}
+
+#[test]
+fn history_recall() {
+    let mut shell = Pty::spawn(INITRC);
+    shell.wait_for("test>");
+    shell.send("echo first\r");
+    shell.wait_for("first\ntest>");
+    // The up arrow brings back the previous command
+    shell.send("\x1b[A");
+    shell.wait_for("first\ntest> echo first");
+}
+
+#[test]
+fn screen_rendering() {
+    let mut screen = Screen::new(3, 10);
+    assert_eq!(screen.feed(b"abc\x1b[31mdef\x1b[0m\r\nline\x1b[2D\x1b[Kx"), "");
+    assert_eq!(screen.frame(), "abcdef\nlix");
+    assert_eq!(screen.cell(0, 3).style, "31");
+    assert_eq!(screen.cell(0, 2).style, "");
+    // Queries of the cursor position are answered
+    assert_eq!(screen.feed(b"\x1b[6n"), "\x1b[2;4R");
+    // Sequences split across reads are kept until they are complete
+    screen.feed(b"\x1b[");
+    screen.feed(b"1;1H\xc3\xa9\xc3");
+    screen.feed(b"\xa9");
+    assert_eq!(screen.line(0), "\u{e9}\u{e9}cdef");
+    // Lines scroll up past the bottom of the screen
+    screen.feed(b"\x1b[3;1H\nlast");
+    assert_eq!(screen.frame(), "lix\n\nlast");
+}
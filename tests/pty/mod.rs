@@ -0,0 +1,370 @@
+//! Run the interactive shell on a pseudo-terminal, with scripted keystrokes, and render its
+//! output to a screen on which tests assert.
+//!
+//! The shell gets a temporary home and configuration directories, an initrc given by the test,
+//! and a terminal of a fixed size. Nothing is timed: the output is read until the screen shows
+//! what the test waits for, or until the shell stops writing to the terminal.
+#![allow(dead_code)]
+use nix::{
+    libc,
+    poll::{poll, PollFd, PollFlags},
+    pty::{openpty, Winsize},
+    unistd::{self, close, dup, setsid},
+};
+use std::{
+    fs::{self, File},
+    io,
+    os::unix::{
+        io::{FromRawFd, RawFd},
+        process::CommandExt,
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+pub const ROWS: usize = 24;
+pub const COLUMNS: usize = 80;
+
+/// How long to wait for the shell before failing the test
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the shell must stay silent to be considered done with drawing
+const QUIET: Duration = Duration::from_millis(200);
+
+/// The interactive shell running on a pseudo-terminal
+pub struct Pty {
+    master: RawFd,
+    child:  Child,
+    screen: Screen,
+    home:   mktemp::Temp,
+}
+
+impl Pty {
+    /// Start the shell in a new home directory, with the given initrc
+    pub fn spawn(initrc: &str) -> Self {
+        let home = mktemp::Temp::new_dir().unwrap();
+        let config = home.as_path().join(".config/ion");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join("initrc"), initrc).unwrap();
+
+        let size = Winsize {
+            ws_row:    ROWS as u16,
+            ws_col:    COLUMNS as u16,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let pty = openpty(Some(&size), None).unwrap();
+        let stdio = || unsafe { Stdio::from(File::from_raw_fd(dup(pty.slave).unwrap())) };
+        let mut command = Command::new(env!("CARGO_BIN_EXE_ion"));
+        command
+            .env_clear()
+            .env("HOME", home.as_path())
+            .env("XDG_CONFIG_HOME", home.as_path().join(".config"))
+            .env("XDG_DATA_HOME", home.as_path().join(".local/share"))
+            .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+            .env("TERM", "xterm-256color")
+            .env("LANG", "en_US.UTF-8")
+            .current_dir(home.as_path())
+            .stdin(stdio())
+            .stdout(stdio())
+            .stderr(stdio());
+        // The terminal becomes the controlling terminal of the shell, as in a terminal emulator
+        unsafe {
+            command.pre_exec(|| {
+                setsid().map_err(|why| io::Error::from_raw_os_error(why as i32))?;
+                if libc::ioctl(0, libc::TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+        close(pty.slave).unwrap();
+
+        Self { master: pty.master, child, screen: Screen::new(ROWS, COLUMNS), home }
+    }
+
+    /// The home directory of the shell, which is also its working directory
+    pub fn home(&self) -> PathBuf { self.home.as_path().to_path_buf() }
+
+    /// Type keys, as the bytes a terminal sends for them (`\r` for enter, `\t` for tab)
+    pub fn send(&mut self, keys: &str) {
+        let mut keys = keys.as_bytes();
+        while !keys.is_empty() {
+            let written = unistd::write(self.master, keys).unwrap();
+            keys = &keys[written..];
+        }
+    }
+
+    /// Read the output until the screen contains the text, and return the screen
+    pub fn wait_for(&mut self, text: &str) -> String {
+        self.wait_until(|screen| screen.contains(text))
+    }
+
+    /// Read the output until the screen satisfies the predicate, and return the screen
+    pub fn wait_until<F: Fn(&str) -> bool>(&mut self, predicate: F) -> String {
+        let start = Instant::now();
+        loop {
+            let frame = self.screen.frame();
+            if predicate(&frame) {
+                return frame;
+            }
+            if start.elapsed() > TIMEOUT {
+                panic!("timed out waiting for the shell, the screen was:\n{}", frame);
+            }
+            self.read(QUIET);
+        }
+    }
+
+    /// Read the output until the shell stops writing, and return the screen
+    pub fn settle(&mut self) -> String {
+        let start = Instant::now();
+        while self.read(QUIET) && start.elapsed() < TIMEOUT {}
+        self.screen.frame()
+    }
+
+    /// The rendered screen
+    pub const fn screen(&self) -> &Screen { &self.screen }
+
+    /// Read the available output within the delay, returning whether there was some
+    fn read(&mut self, delay: Duration) -> bool {
+        let mut fds = [PollFd::new(self.master, PollFlags::POLLIN)];
+        match poll(&mut fds, delay.as_millis() as i32) {
+            Ok(0) | Err(_) => return false,
+            Ok(_) => (),
+        }
+        let mut buffer = [0; 4096];
+        match unistd::read(self.master, &mut buffer) {
+            Ok(0) | Err(_) => false,
+            Ok(read) => {
+                let replies = self.screen.feed(&buffer[..read]);
+                self.send(&replies);
+                true
+            }
+        }
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = close(self.master);
+    }
+}
+
+/// A minimal terminal emulator, which renders the characters written to the terminal and keeps
+/// the style of each cell. It understands the control sequences used by the line editor.
+pub struct Screen {
+    cells:   Vec<Vec<Cell>>,
+    row:     usize,
+    column:  usize,
+    style:   String,
+    pending: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    pub character: Option<char>,
+    /// The parameters of the last SGR sequence (`\x1b[...m`) before the character was written
+    pub style:     String,
+}
+
+impl Screen {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Self {
+            cells:   vec![vec![Cell::default(); columns]; rows],
+            row:     0,
+            column:  0,
+            style:   String::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// The text of the screen, without the trailing blanks of the lines nor the trailing blank
+    /// lines
+    pub fn frame(&self) -> String {
+        let lines: Vec<String> = self.cells.iter().map(|line| Self::text(line)).collect();
+        let used = lines.iter().rposition(|line| !line.is_empty()).map_or(0, |last| last + 1);
+        lines[..used].join("\n")
+    }
+
+    /// The text of a line of the screen
+    pub fn line(&self, row: usize) -> String { Self::text(&self.cells[row]) }
+
+    /// The cell of the screen at the position
+    pub fn cell(&self, row: usize, column: usize) -> &Cell { &self.cells[row][column] }
+
+    /// The position of the cursor, as the row and column
+    pub const fn cursor(&self) -> (usize, usize) { (self.row, self.column) }
+
+    /// Render the output, and return the replies the terminal sends to the queries it contains
+    pub fn feed(&mut self, output: &[u8]) -> String {
+        self.pending.extend_from_slice(output);
+        let pending = std::mem::take(&mut self.pending);
+        let mut replies = String::new();
+        let mut index = 0;
+        while index < pending.len() {
+            let consumed = match pending[index] {
+                0x1b => self.escape(&pending[index..], &mut replies),
+                b'\r' => {
+                    self.column = 0;
+                    Some(1)
+                }
+                b'\n' => {
+                    self.line_feed();
+                    Some(1)
+                }
+                0x08 => {
+                    self.column = self.column.saturating_sub(1);
+                    Some(1)
+                }
+                b'\t' => {
+                    self.column = (self.column / 8 + 1) * 8;
+                    Some(1)
+                }
+                byte if byte < 0x20 || byte == 0x7f => Some(1),
+                _ => self.character(&pending[index..]),
+            };
+            match consumed {
+                Some(consumed) => index += consumed,
+                // The rest of the sequence has not been written yet
+                None => {
+                    self.pending = pending[index..].to_vec();
+                    break;
+                }
+            }
+        }
+        replies
+    }
+
+    fn text(line: &[Cell]) -> String {
+        let text: String = line.iter().map(|cell| cell.character.unwrap_or(' ')).collect();
+        text.trim_end().to_string()
+    }
+
+    fn line_feed(&mut self) {
+        if self.row + 1 == self.cells.len() {
+            self.cells.remove(0);
+            self.cells.push(vec![Cell::default(); self.cells[0].len()]);
+        } else {
+            self.row += 1;
+        }
+    }
+
+    /// Write the UTF-8 character at the start of the bytes
+    fn character(&mut self, bytes: &[u8]) -> Option<usize> {
+        let length = match bytes[0] {
+            0xf0..=0xff => 4,
+            0xe0..=0xef => 3,
+            0xc0..=0xdf => 2,
+            _ => 1,
+        };
+        let character = std::str::from_utf8(bytes.get(..length)?)
+            .ok()
+            .and_then(|text| text.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        let columns = self.cells[0].len();
+        if self.column >= columns {
+            self.column = 0;
+            self.line_feed();
+        }
+        self.cells[self.row][self.column] =
+            Cell { character: Some(character), style: self.style.clone() };
+        self.column += 1;
+        Some(length)
+    }
+
+    /// Apply the escape sequence at the start of the bytes
+    fn escape(&mut self, bytes: &[u8], replies: &mut String) -> Option<usize> {
+        match *bytes.get(1)? {
+            b'[' => {
+                let end = bytes[2..].iter().position(|byte| (0x40..=0x7e).contains(byte))? + 2;
+                let parameters = std::str::from_utf8(&bytes[2..end]).unwrap_or_default();
+                self.control(parameters, bytes[end], replies);
+                Some(end + 1)
+            }
+            // Operating system commands, such as the title, end with a bell or `ESC \`
+            b']' => {
+                let end = bytes.iter().position(|&byte| byte == 0x07).map(|end| end + 1).or_else(
+                    || bytes.windows(2).position(|window| window == b"\x1b\\").map(|end| end + 2),
+                )?;
+                Some(end)
+            }
+            b'(' | b')' => bytes.get(2).map(|_| 3),
+            _ => Some(2),
+        }
+    }
+
+    /// Apply a control sequence (`ESC [ parameters final`)
+    fn control(&mut self, parameters: &str, command: u8, replies: &mut String) {
+        // Private modes, such as the bracketed paste, do not change the rendering
+        if parameters.starts_with('?') {
+            return;
+        }
+        let numbers: Vec<usize> =
+            parameters.split(';').map(|number| number.parse().unwrap_or(0)).collect();
+        let first = |default: usize| numbers.get(0).copied().filter(|&n| n != 0).unwrap_or(default);
+        let (rows, columns) = (self.cells.len(), self.cells[0].len());
+        match command {
+            b'A' => self.row = self.row.saturating_sub(first(1)),
+            b'B' => self.row = (self.row + first(1)).min(rows - 1),
+            b'C' => self.column = (self.column + first(1)).min(columns - 1),
+            b'D' => self.column = self.column.saturating_sub(first(1)),
+            b'G' => self.column = (first(1) - 1).min(columns - 1),
+            b'H' | b'f' => {
+                self.row = (first(1) - 1).min(rows - 1);
+                self.column =
+                    (numbers.get(1).copied().filter(|&n| n != 0).unwrap_or(1) - 1).min(columns - 1);
+            }
+            b'J' => {
+                let (row, column) = (self.row, self.column);
+                match numbers[0] {
+                    0 => {
+                        self.clear(row, column..columns);
+                        (row + 1..rows).for_each(|row| self.clear(row, 0..columns));
+                    }
+                    1 => {
+                        (0..row).for_each(|row| self.clear(row, 0..columns));
+                        self.clear(row, 0..(column + 1).min(columns));
+                    }
+                    _ => (0..rows).for_each(|row| self.clear(row, 0..columns)),
+                }
+            }
+            b'K' => {
+                let (row, column) = (self.row, self.column.min(columns));
+                match numbers[0] {
+                    0 => self.clear(row, column..columns),
+                    1 => self.clear(row, 0..(column + 1).min(columns)),
+                    _ => self.clear(row, 0..columns),
+                }
+            }
+            b'm' => {
+                self.style = if parameters.is_empty() || parameters == "0" {
+                    String::new()
+                } else {
+                    parameters.to_string()
+                }
+            }
+            // Report the position of the cursor
+            b'n' if numbers[0] == 6 => {
+                replies.push_str(&format!("\x1b[{};{}R", self.row + 1, self.column + 1))
+            }
+            _ => (),
+        }
+    }
+
+    fn clear(&mut self, row: usize, columns: std::ops::Range<usize>) {
+        for cell in &mut self.cells[row][columns] {
+            *cell = Cell::default();
+        }
+    }
+}
+
+/// Create a file in the directory, and the directories leading to it
+pub fn touch(directory: &Path, path: &str) {
+    let path = directory.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, "").unwrap();
+}